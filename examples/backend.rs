@@ -0,0 +1,87 @@
+// Adds an output format for a made-up vendor boot container, with a small
+// header followed by the loaded sections, and converts a riscv64 kernel to
+// it:
+//
+//     cargo run --example backend
+use std::io::Write;
+
+use p9aout2elf::backend::{self, Backend};
+use p9aout2elf::layout::{ElfImage, SH_FLAG_ALLOC};
+use p9aout2elf::{ConvertOptions, ElfSectionType, Error, MachineArch, aout_to_elf};
+
+const ENTRY: u64 = 0x8020_0000;
+
+// magic, entry point, load address and size of the payload, little endian
+struct BootContainer;
+
+impl Backend for BootContainer {
+    fn name(&self) -> &'static str {
+        "vbc"
+    }
+
+    fn extension(&self) -> &'static str {
+        ".vbc"
+    }
+
+    fn write(&self, image: &ElfImage, w: &mut dyn Write) -> Result<(), Error> {
+        let loaded: Vec<_> = image
+            .sections
+            .iter()
+            .filter(|s| s.flags & SH_FLAG_ALLOC != 0)
+            .filter(|s| !matches!(s.section_type, ElfSectionType::NoBits))
+            .collect();
+        let Some(first) = loaded.first() else {
+            return Err(Error::Invalid("nothing to load".to_string()));
+        };
+        // sections are contiguous in the a.out, save for alignment
+        let mut payload = vec![];
+        for s in &loaded {
+            payload.resize((s.addr - first.addr) as usize, 0);
+            payload.extend_from_slice(&s.data);
+        }
+        w.write_all(b"VBC1")?;
+        w.write_all(&image.entry.to_le_bytes())?;
+        w.write_all(&first.addr.to_le_bytes())?;
+        w.write_all(&(payload.len() as u64).to_le_bytes())?;
+        w.write_all(&payload)?;
+        Ok(())
+    }
+}
+
+static BOOT_CONTAINER: BootContainer = BootContainer;
+
+// a.out with the expanded header of 64-bit targets, without symbols
+fn kernel() -> Vec<u8> {
+    let (text, data) = ([0x13; 0x40], [1; 0x10]);
+    let mut d = vec![];
+    let header = [
+        MachineArch::Riscv64.magic(),
+        text.len() as u32,
+        data.len() as u32,
+        0x100,
+        0,
+        ENTRY as u32,
+        0,
+        0,
+    ];
+    for v in header {
+        d.extend_from_slice(&v.to_be_bytes());
+    }
+    d.extend_from_slice(&ENTRY.to_be_bytes());
+    d.extend_from_slice(&text);
+    d.extend_from_slice(&data);
+    d
+}
+
+fn main() -> Result<(), Error> {
+    backend::register(&BOOT_CONTAINER)?;
+    println!("backends: {:?}", backend::names());
+
+    let opts = ConvertOptions {
+        backend: Some("vbc"),
+        ..Default::default()
+    };
+    let out = aout_to_elf(&kernel(), &opts)?;
+    println!("{} bytes of {}", out.len(), BOOT_CONTAINER.extension());
+    Ok(())
+}
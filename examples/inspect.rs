@@ -0,0 +1,74 @@
+// Builds a small riscv64 kernel in memory, lists its symbols, plans the
+// ELF layout and converts it, without touching the file system:
+//
+//     cargo run --example inspect
+use p9aout2elf::aout::AoutFile;
+use p9aout2elf::{ConvertOptions, MachineArch, Profile, aout_to_elf, aout_to_image};
+
+const ENTRY: u64 = 0x8020_0000;
+
+// a.out with the expanded header of 64-bit targets and 8-byte symbol values
+fn kernel() -> Vec<u8> {
+    let text = [0x13; 0x40];
+    let data = [0; 0x10];
+    let mut syms = vec![];
+    for (value, t, name) in [(ENTRY, b'T', "main"), (ENTRY + 0x1000, b'D', "conf")] {
+        syms.extend_from_slice(&u64::to_be_bytes(value));
+        syms.push(t | 0x80);
+        syms.extend_from_slice(name.as_bytes());
+        syms.push(0);
+    }
+    let mut d = vec![];
+    let header = [
+        MachineArch::Riscv64.magic(),
+        text.len() as u32,
+        data.len() as u32,
+        0x100,
+        syms.len() as u32,
+        ENTRY as u32,
+        0,
+        0,
+    ];
+    for v in header {
+        d.extend_from_slice(&v.to_be_bytes());
+    }
+    d.extend_from_slice(&ENTRY.to_be_bytes());
+    d.extend_from_slice(&text);
+    d.extend_from_slice(&data);
+    d.extend_from_slice(&syms);
+    d
+}
+
+fn main() -> Result<(), p9aout2elf::Error> {
+    let d = kernel();
+
+    let f = AoutFile::parse(&d)?;
+    let entry = f.entry64().unwrap_or_default();
+    println!("{} a.out, entry {entry:#x}", f.arch);
+    let st = f.symbol_table().unwrap_or_default();
+    for s in p9aout2elf::parse_aout_symbols(&st, false) {
+        println!("  {:16x} {:?} {}", s.value(), s.get_type(), s.name);
+    }
+
+    for profile in [Profile::Kernel, Profile::NineBoot] {
+        let opts = ConvertOptions {
+            profile,
+            ..Default::default()
+        };
+        let image = aout_to_image(&d, &opts)?;
+        let layout = image.layout()?;
+        println!("{profile:?}: {} bytes", layout.size);
+        for (s, p) in image.sections.iter().zip(&layout.sections) {
+            println!(
+                "  {:18} addr {:#x} offset {:#x} size {:#x}",
+                s.name,
+                s.addr,
+                p.offset,
+                s.size()
+            );
+        }
+        let elf = aout_to_elf(&d, &opts)?;
+        assert_eq!(elf.len() as u64, layout.size);
+    }
+    Ok(())
+}
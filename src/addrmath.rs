@@ -0,0 +1,59 @@
+// Address and size arithmetic for laying out images.
+//
+// a.out values are 32 bits wide, so they are widened to u64 before adding
+// them up. Results that may still exceed u64, e.g. aligning addresses near
+// the top, are checked, and range ends are compared in u128.
+
+/// Page size assumed by the Plan 9 loaders.
+pub const PAGE_SIZE: u64 = 4096;
+
+/// Rounds v up to a multiple of align, where 0 and 1 mean no alignment;
+/// None if the result does not fit.
+pub fn align_up(v: u64, align: u64) -> Option<u64> {
+    if align <= 1 {
+        return Some(v);
+    }
+    v.div_ceil(align).checked_mul(align)
+}
+
+/// Rounds v down to a multiple of align, where 0 and 1 mean no alignment.
+pub fn align_down(v: u64, align: u64) -> u64 {
+    if align <= 1 { v } else { v - v % align }
+}
+
+/// Whether a and b are congruent modulo align, as ELF requires of the file
+/// offset and virtual address of a LOAD segment.
+pub fn is_congruent(a: u64, b: u64, align: u64) -> bool {
+    align <= 1 || a % align == b % align
+}
+
+/// Padding to add to offset for it to become congruent to addr modulo
+/// align, less than align.
+pub fn congruence_padding(offset: u64, addr: u64, align: u64) -> u64 {
+    if align <= 1 {
+        return 0;
+    }
+    let (a, o) = (addr % align, offset % align);
+    if a >= o { a - o } else { align - (o - a) }
+}
+
+/// Whether [a, a + a_len) and [b, b + b_len) share an address; empty
+/// ranges share none.
+pub fn overlaps(a: u64, a_len: u64, b: u64, b_len: u64) -> bool {
+    let end = |start: u64, len: u64| start as u128 + len as u128;
+    a_len > 0 && b_len > 0 && (a as u128) < end(b, b_len) && (b as u128) < end(a, a_len)
+}
+
+/// Whether [inner, inner + inner_len) lies within [outer, outer + outer_len).
+pub fn contains(outer: u64, outer_len: u64, inner: u64, inner_len: u64) -> bool {
+    let end = |start: u64, len: u64| start as u128 + len as u128;
+    outer <= inner && end(inner, inner_len) <= end(outer, outer_len)
+}
+
+/// Address of the data of a Plan 9 image, at the end of the text, which
+/// starts at the entry point, rounded up to a page of the given size (see
+/// TargetProfile::data_rounding), usually PAGE_SIZE.
+pub fn data_address(entry: u32, text_size: u32, rounding: u64) -> u64 {
+    // cannot overflow, both are 32 bits wide
+    (entry as u64 + text_size as u64).div_ceil(rounding) * rounding
+}
@@ -0,0 +1,332 @@
+// A parsed Plan 9 a.out file and the regions it consists of.
+//
+// The regions are in a fixed order:
+// - header
+// - pad, i.e., the 64-bit entry point of the expanded header, empty for
+//   32-bit targets
+// - text (code)
+// - data
+// - symbol table
+// - pc/sp offset table
+// - pc/line number table
+// - trailer, anything after the declared regions
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::ops::Range;
+
+use zerocopy::byteorder::big_endian::U32;
+use zerocopy::{FromBytes, IntoBytes};
+
+use crate::{AOUT_HEADER_SIZE, Aout, AoutSymbol, AoutSymbolType, Error, MachineArch, name_size};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RegionKind {
+    Header,
+    Pad,
+    Text,
+    Data,
+    Symtab,
+    SpOff,
+    PcLine,
+    Trailer,
+}
+
+impl Display for RegionKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let n = match self {
+            RegionKind::Header => "header",
+            RegionKind::Pad => "pad",
+            RegionKind::Text => "text",
+            RegionKind::Data => "data",
+            RegionKind::Symtab => "symtab",
+            RegionKind::SpOff => "pc/sp",
+            RegionKind::PcLine => "pc/line",
+            RegionKind::Trailer => "trailer",
+        };
+        f.pad(n)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct AoutRegion {
+    pub kind: RegionKind,
+    pub offset: usize,
+    pub len: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct AoutFile<'a> {
+    pub header: Aout,
+    pub arch: MachineArch,
+    data: &'a [u8],
+}
+
+impl<'a> AoutFile<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        Self::parse_as(data, None)
+    }
+
+    /// Parses with the given architecture instead of the one the magic
+    /// tells, e.g. for a mangled magic.
+    pub fn parse_as(data: &'a [u8], arch: Option<MachineArch>) -> Result<Self, Error> {
+        let Ok((header, _)) = Aout::read_from_prefix(data) else {
+            return Err(Error::Truncated("a.out header"));
+        };
+        let arch = arch.unwrap_or_else(|| MachineArch::from_magic(header.magic));
+        if arch == MachineArch::Unknown {
+            return Err(Error::BadMagic(header.magic));
+        }
+        Ok(Self { header, arch, data })
+    }
+
+    /// All regions as declared by the header, even if the file is cut
+    /// short; the trailer is only present if there are bytes left.
+    pub fn regions(&self) -> Vec<AoutRegion> {
+        let h = &self.header;
+        let sizes = [
+            (RegionKind::Header, AOUT_HEADER_SIZE),
+            (RegionKind::Pad, self.arch.pad_size()),
+            (RegionKind::Text, u32::from(h.text_size) as usize),
+            (RegionKind::Data, u32::from(h.data_size) as usize),
+            (RegionKind::Symtab, u32::from(h.symbol_table_size) as usize),
+            (RegionKind::SpOff, u32::from(h.sp_size) as usize),
+            (RegionKind::PcLine, u32::from(h.pc_size) as usize),
+        ];
+        let mut regions = vec![];
+        let mut offset = 0;
+        for (kind, len) in sizes {
+            regions.push(AoutRegion { kind, offset, len });
+            offset += len;
+        }
+        if offset < self.data.len() {
+            regions.push(AoutRegion {
+                kind: RegionKind::Trailer,
+                offset,
+                len: self.data.len() - offset,
+            });
+        }
+        regions
+    }
+
+    pub fn region(&self, kind: RegionKind) -> Option<AoutRegion> {
+        self.regions().into_iter().find(|r| r.kind == kind)
+    }
+
+    /// Contents of a region, if present and within the file.
+    pub fn bytes(&self, kind: RegionKind) -> Option<&'a [u8]> {
+        let r = self.region(kind)?;
+        self.data.get(r.offset..r.offset + r.len)
+    }
+
+    /// Entry point from the expanded header, for 64-bit targets.
+    pub fn entry64(&self) -> Option<u64> {
+        let b = self.bytes(RegionKind::Pad).filter(|b| b.len() == 8)?;
+        Some(u64::from_be_bytes(b.try_into().unwrap()))
+    }
+
+    /// The symbol table as parse_aout_symbols reads it, see wide_symbols.
+    pub fn symbol_table(&self) -> Option<Cow<'a, [u8]>> {
+        Some(wide_symbols(self.arch, self.bytes(RegionKind::Symtab)?))
+    }
+}
+
+/// Symbol table entries with 8-byte values, as on 64-bit targets. On 32-bit
+/// targets, values have 4 bytes and are widened, up to the first entry that
+/// cannot be read; the rest is kept as is, for check_aout_symbols to report.
+pub fn wide_symbols(arch: MachineArch, st: &[u8]) -> Cow<'_, [u8]> {
+    if arch.expanded_header() {
+        return Cow::Borrowed(st);
+    }
+    // value and type
+    const NARROW_HEADER_SIZE: usize = 5;
+    let mut wide = Vec::with_capacity(st.len() * 2);
+    let mut offset = 0;
+    while offset < st.len() {
+        let e = &st[offset..];
+        let sym_type = e.get(NARROW_HEADER_SIZE - 1).copied().unwrap_or_default();
+        let name = e.get(NARROW_HEADER_SIZE..).unwrap_or(&[]);
+        let Some(n) = name_size(sym_type, name) else {
+            break;
+        };
+        let len = NARROW_HEADER_SIZE + n;
+        wide.extend_from_slice(&[0; 4]);
+        wide.extend_from_slice(&e[..len]);
+        offset += len;
+    }
+    wide.extend_from_slice(&st[offset..]);
+    Cow::Owned(wide)
+}
+
+impl AoutFile<'_> {
+    /// The file as 9front's strip(1) writes it: the header with zeroed symbol
+    /// and pc table sizes, followed by text and data. Everything after data
+    /// is dropped.
+    pub fn stripped(&self) -> Result<Vec<u8>, Error> {
+        let mut h = self.header;
+        let sizes = [h.symbol_table_size, h.sp_size, h.pc_size];
+        if sizes.iter().all(|s| u32::from(*s) == 0) {
+            return Err(Error::Invalid("already stripped".to_string()));
+        }
+        h.symbol_table_size = U32::ZERO;
+        h.sp_size = U32::ZERO;
+        h.pc_size = U32::ZERO;
+
+        let data = self.region(RegionKind::Data).unwrap();
+        let Some(body) = self.data.get(AOUT_HEADER_SIZE..data.offset + data.len) else {
+            return Err(Error::Truncated("data"));
+        };
+        let mut d = h.as_bytes().to_vec();
+        d.extend_from_slice(body);
+        Ok(d)
+    }
+
+    /// Virtual address of a file offset within text or data.
+    pub fn vaddr(&self, offset: usize) -> Option<u64> {
+        let entry = u32::from(self.header.entry_point) as u64;
+        let ts = u32::from(self.header.text_size);
+        let rounding = self.arch.default_profile().data_rounding;
+        let data_addr = crate::addrmath::data_address(entry as u32, ts, rounding);
+        let r = self
+            .regions()
+            .into_iter()
+            .find(|r| r.offset <= offset && offset < r.offset + r.len)?;
+        let delta = (offset - r.offset) as u64;
+        match r.kind {
+            RegionKind::Text => Some(entry + delta),
+            RegionKind::Data => Some(data_addr + delta),
+            _ => None,
+        }
+    }
+}
+
+/// File name components from the f symbols, which z and Z symbols refer to
+/// by index, i.e., the f symbol's value.
+pub struct SourceFiles<'a> {
+    components: HashMap<u16, &'a str>,
+}
+
+impl<'a> SourceFiles<'a> {
+    pub fn new(syms: &[AoutSymbol<'a>]) -> Self {
+        let components = syms
+            .iter()
+            .filter(|s| s.get_type() == AoutSymbolType::SourceFileNameComp)
+            .map(|s| (u32::from(s.header.value) as u16, s.name))
+            .collect();
+        Self { components }
+    }
+
+    /// Path of a z or Z symbol, with its components joined by slashes as
+    /// libmach does; unknown indices show as ?.
+    pub fn path(&self, sym: &AoutSymbol) -> String {
+        let mut path = String::new();
+        for i in sym.file_indices() {
+            if !path.is_empty() && !path.ends_with('/') {
+                path.push('/');
+            }
+            path.push_str(self.components.get(&i).unwrap_or(&"?"));
+        }
+        path
+    }
+}
+
+/// A text symbol with the frame symbols that follow it.
+pub struct Function<'a> {
+    pub name: &'a str,
+    /// T or L, not t or l
+    pub external: bool,
+    /// up to the next text symbol
+    pub range: Range<u64>,
+    /// the value of .frame (m), the frame size including the return address
+    pub frame_size: Option<u32>,
+    /// parameters (p) by their offset above the frame
+    pub params: Vec<(&'a str, u32)>,
+    /// automatic variables (a) by their offset below the frame
+    pub autos: Vec<(&'a str, u32)>,
+}
+
+/// Text symbols of one source file with its include history, i.e., the z
+/// symbols in front of them in the table.
+pub struct SourceUnit<'a> {
+    /// line and path of each z symbol, see pcline::file_line
+    pub history: Vec<(u32, String)>,
+    pub funcs: Vec<Function<'a>>,
+}
+
+/// Groups the text symbols by source file. Those before any z symbol are
+/// in a unit without history.
+pub fn source_units<'a>(syms: &[AoutSymbol<'a>], text_end: u64) -> Vec<SourceUnit<'a>> {
+    let files = SourceFiles::new(syms);
+    let mut units: Vec<SourceUnit> = vec![];
+    let mut funcs: Vec<(Function, usize)> = vec![];
+    let mut in_history = false;
+    // whether a/p/m symbols belong to the last function
+    let mut in_frame = false;
+    for s in syms {
+        let t = s.get_type();
+        let value: u32 = s.header.value.into();
+        match t {
+            AoutSymbolType::SourceFileName => {
+                if !in_history {
+                    units.push(SourceUnit {
+                        history: vec![],
+                        funcs: vec![],
+                    });
+                    in_history = true;
+                }
+                let h = &mut units.last_mut().unwrap().history;
+                h.push((value, files.path(s)));
+                in_frame = false;
+            }
+            AoutSymbolType::SourceFileOffset => in_frame = false,
+            AoutSymbolType::AutoVariable
+            | AoutSymbolType::FunctionParam
+            | AoutSymbolType::FrameSymbol
+                if in_frame =>
+            {
+                let (f, _) = funcs.last_mut().unwrap();
+                match t {
+                    AoutSymbolType::AutoVariable => f.autos.push((s.name, value)),
+                    AoutSymbolType::FunctionParam => f.params.push((s.name, value)),
+                    _ => f.frame_size = Some(value),
+                }
+            }
+            _ => {
+                in_history = false;
+                in_frame = t.is_text();
+                if t.is_text() {
+                    if units.is_empty() {
+                        units.push(SourceUnit {
+                            history: vec![],
+                            funcs: vec![],
+                        });
+                    }
+                    let f = Function {
+                        name: s.name,
+                        external: matches!(
+                            t,
+                            AoutSymbolType::TextSegment | AoutSymbolType::LeafFunction
+                        ),
+                        range: value as u64..value as u64,
+                        frame_size: None,
+                        params: vec![],
+                        autos: vec![],
+                    };
+                    funcs.push((f, units.len() - 1));
+                }
+            }
+        }
+    }
+    funcs.sort_by_key(|(f, _)| f.range.start);
+    let ends: Vec<u64> = funcs
+        .iter()
+        .skip(1)
+        .map(|(f, _)| f.range.start)
+        .chain([text_end])
+        .collect();
+    for ((mut f, u), end) in funcs.into_iter().zip(ends) {
+        f.range.end = end.max(f.range.start);
+        units[u].funcs.push(f);
+    }
+    units
+}
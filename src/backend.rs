@@ -0,0 +1,54 @@
+// Output formats added from outside the crate, e.g. vendor boot containers,
+// written from the intermediate model like the built-in ones.
+//
+// Backends are registered once, typically at the start of main, and then
+// selected by name with ConvertOptions::backend instead of format:
+//
+//     static RAW: Raw = Raw;
+//     backend::register(&RAW)?;
+//     let opts = ConvertOptions { backend: Some("raw"), ..Default::default() };
+//
+// See examples/backend.rs for a complete one.
+use std::io::Write;
+use std::sync::Mutex;
+
+use clap::ValueEnum;
+
+use crate::layout::ElfImage;
+use crate::{Error, OutputFormat};
+
+/// Writer of an output format for the intermediate model.
+pub trait Backend: Send + Sync {
+    /// name to select it by, distinct from the built-in formats
+    fn name(&self) -> &'static str;
+    /// appended to the input file name for the output, with the dot
+    fn extension(&self) -> &'static str;
+    fn write(&self, image: &ElfImage, w: &mut dyn Write) -> Result<(), Error>;
+}
+
+static BACKENDS: Mutex<Vec<&'static dyn Backend>> = Mutex::new(vec![]);
+
+/// Adds a backend, failing if its name is taken already.
+pub fn register(backend: &'static dyn Backend) -> Result<(), Error> {
+    let name = backend.name();
+    if OutputFormat::from_str(name, true).is_ok() {
+        return Err(Error::Invalid(format!("{name} is a built-in format")));
+    }
+    let mut backends = BACKENDS.lock().unwrap();
+    if backends.iter().any(|b| b.name() == name) {
+        return Err(Error::Invalid(format!("{name} is registered already")));
+    }
+    backends.push(backend);
+    Ok(())
+}
+
+/// The registered backend of the given name.
+pub fn find(name: &str) -> Option<&'static dyn Backend> {
+    let backends = BACKENDS.lock().unwrap();
+    backends.iter().find(|b| b.name() == name).copied()
+}
+
+/// Names of the registered backends, in the order of registration.
+pub fn names() -> Vec<&'static str> {
+    BACKENDS.lock().unwrap().iter().map(|b| b.name()).collect()
+}
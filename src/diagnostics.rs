@@ -0,0 +1,87 @@
+// Structured diagnostics for CI, written with --diagnostics whether or not
+// the command succeeds.
+//
+// Warnings and errors are recorded by a logger wrapping the usual one, so
+// they are the same messages as on stderr.
+use std::sync::Mutex;
+
+use log::{Level, Log, Metadata, Record};
+use p9aout2elf::timings;
+use serde::Serialize;
+
+static MESSAGES: Mutex<Vec<(Level, String)>> = Mutex::new(vec![]);
+
+struct Recorder(env_logger::Logger);
+
+impl Log for Recorder {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.0.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if record.level() <= Level::Warn {
+            let m = (record.level(), record.args().to_string());
+            MESSAGES.lock().unwrap().push(m);
+        }
+        self.0.log(record);
+    }
+
+    fn flush(&self) {
+        self.0.flush();
+    }
+}
+
+/// Installs the given logger, recording warnings and errors.
+pub fn init(logger: env_logger::Logger) {
+    log::set_max_level(logger.filter());
+    log::set_boxed_logger(Box::new(Recorder(logger))).expect("logger already set");
+}
+
+#[derive(Serialize)]
+struct Stage {
+    name: &'static str,
+    ms: f64,
+    peak_bytes: usize,
+}
+
+#[derive(Serialize)]
+struct Diagnostics {
+    schema_version: u32,
+    name: &'static str,
+    version: &'static str,
+    args: Vec<String>,
+    success: bool,
+    // the error that ended the command
+    error: Option<String>,
+    errors: Vec<String>,
+    warnings: Vec<String>,
+    timings: Vec<Stage>,
+}
+
+pub fn write(file_name: &str, error: Option<String>) -> std::io::Result<()> {
+    let messages = MESSAGES.lock().unwrap();
+    let of = |l: Level| {
+        let m = messages.iter().filter(|(ml, _)| *ml == l);
+        m.map(|(_, m)| m.clone()).collect()
+    };
+    let d = Diagnostics {
+        schema_version: crate::SCHEMA_VERSION,
+        name: env!("CARGO_PKG_NAME"),
+        version: env!("CARGO_PKG_VERSION"),
+        args: std::env::args().collect(),
+        success: error.is_none(),
+        error,
+        errors: of(Level::Error),
+        warnings: of(Level::Warn),
+        timings: timings::stages()
+            .into_iter()
+            .map(|(name, time, peak_bytes)| Stage {
+                name,
+                ms: time.as_secs_f64() * 1000.0,
+                peak_bytes,
+            })
+            .collect(),
+    };
+    let json = serde_json::to_string_pretty(&d).map_err(std::io::Error::other)?;
+    std::fs::write(file_name, json + "\n")
+}
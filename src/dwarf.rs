@@ -0,0 +1,682 @@
+// DWARF debugging information made from the Plan 9 symbol and pc tables.
+//
+// Sections are written in the byte order of the target, in the 32-bit DWARF
+// format, with addresses as wide as those of the ELF class.
+//
+// See <https://dwarfstd.org/doc/DWARF4.pdf>
+use std::ops::Range;
+
+use crate::aout::{Function, SourceUnit};
+use crate::pcline;
+use crate::{DebugCompression, MachineArch};
+
+/// Bytes in the target's byte order.
+pub struct Writer {
+    pub data: Vec<u8>,
+    big_endian: bool,
+    address_size: u8,
+}
+
+impl Writer {
+    pub fn new(big_endian: bool, is_64bit: bool) -> Self {
+        Self {
+            data: vec![],
+            big_endian,
+            address_size: if is_64bit { 8 } else { 4 },
+        }
+    }
+
+    pub fn offset(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn u8(&mut self, v: u8) {
+        self.data.push(v);
+    }
+
+    pub fn u16(&mut self, v: u16) {
+        let b = if self.big_endian {
+            v.to_be_bytes()
+        } else {
+            v.to_le_bytes()
+        };
+        self.data.extend_from_slice(&b);
+    }
+
+    pub fn u32(&mut self, v: u32) {
+        let b = if self.big_endian {
+            v.to_be_bytes()
+        } else {
+            v.to_le_bytes()
+        };
+        self.data.extend_from_slice(&b);
+    }
+
+    pub fn u64(&mut self, v: u64) {
+        let b = if self.big_endian {
+            v.to_be_bytes()
+        } else {
+            v.to_le_bytes()
+        };
+        self.data.extend_from_slice(&b);
+    }
+
+    pub fn address(&mut self, v: u64) {
+        match self.address_size {
+            8 => self.u64(v),
+            _ => self.u32(v as u32),
+        }
+    }
+
+    pub fn uleb(&mut self, mut v: u64) {
+        loop {
+            let b = (v & 0x7f) as u8;
+            v >>= 7;
+            if v == 0 {
+                self.u8(b);
+                return;
+            }
+            self.u8(b | 0x80);
+        }
+    }
+
+    pub fn sleb(&mut self, mut v: i64) {
+        loop {
+            let b = (v & 0x7f) as u8;
+            v >>= 7;
+            let done = (v == 0 && b & 0x40 == 0) || (v == -1 && b & 0x40 != 0);
+            if done {
+                self.u8(b);
+                return;
+            }
+            self.u8(b | 0x80);
+        }
+    }
+
+    pub fn string(&mut self, s: &str) {
+        self.data.extend_from_slice(s.as_bytes());
+        self.u8(0);
+    }
+
+    /// Placeholder for a unit length, to be set by end_length.
+    pub fn start_length(&mut self) -> usize {
+        let at = self.offset();
+        self.u32(0);
+        at
+    }
+
+    /// Sets the length at the given offset to what follows it.
+    pub fn end_length(&mut self, at: usize) {
+        let len = (self.offset() - at - 4) as u32;
+        let b = if self.big_endian {
+            len.to_be_bytes()
+        } else {
+            len.to_le_bytes()
+        };
+        self.data[at..at + 4].copy_from_slice(&b);
+    }
+}
+
+// call frame instructions
+const DW_CFA_ADVANCE_LOC: u8 = 0x40;
+const DW_CFA_OFFSET: u8 = 0x80;
+const DW_CFA_NOP: u8 = 0x00;
+const DW_CFA_ADVANCE_LOC1: u8 = 0x02;
+const DW_CFA_ADVANCE_LOC2: u8 = 0x03;
+const DW_CFA_ADVANCE_LOC4: u8 = 0x04;
+const DW_CFA_OFFSET_EXTENDED: u8 = 0x05;
+const DW_CFA_SAME_VALUE: u8 = 0x08;
+const DW_CFA_DEF_CFA: u8 = 0x0c;
+const DW_CFA_DEF_CFA_OFFSET: u8 = 0x0e;
+
+// DWARF numbers of the stack pointer and the return address register, and
+// the size of return addresses that calls push, 0 for link registers.
+struct FrameRegisters {
+    sp: u64,
+    ra: u64,
+    pushed: i64,
+}
+
+fn frame_registers(arch: MachineArch) -> Option<FrameRegisters> {
+    let (sp, ra, pushed) = match arch {
+        MachineArch::Amd64 => (7, 16, 8),
+        MachineArch::I386 => (4, 8, 4),
+        MachineArch::Riscv64 => (2, 1, 0),
+        MachineArch::Arm => (13, 14, 0),
+        MachineArch::Arm64 => (31, 30, 0),
+        MachineArch::Mips | MachineArch::Mips4000 | MachineArch::Spim | MachineArch::Spim4000 => {
+            (29, 31, 0)
+        }
+        MachineArch::Power | MachineArch::Power64 => (1, 65, 0),
+        _ => return None,
+    };
+    Some(FrameRegisters { sp, ra, pushed })
+}
+
+// The rules for a stack offset: the CFA is the stack pointer before the
+// call. Plan 9 saves the link register at 0(SP) in the prologue, so it is
+// only in memory once the function has a frame.
+fn frame_rules(w: &mut Writer, regs: &FrameRegisters, sp_offset: i64) {
+    w.u8(DW_CFA_DEF_CFA_OFFSET);
+    w.uleb((regs.pushed + sp_offset) as u64);
+    if regs.pushed != 0 {
+        return;
+    }
+    if sp_offset == 0 {
+        w.u8(DW_CFA_SAME_VALUE);
+        w.uleb(regs.ra);
+    } else if regs.ra < 0x40 {
+        w.u8(DW_CFA_OFFSET | regs.ra as u8);
+        w.uleb(sp_offset as u64);
+    } else {
+        w.u8(DW_CFA_OFFSET_EXTENDED);
+        w.uleb(regs.ra);
+        w.uleb(sp_offset as u64);
+    }
+}
+
+fn advance(w: &mut Writer, delta: u64) {
+    match delta {
+        0..0x40 => w.u8(DW_CFA_ADVANCE_LOC | delta as u8),
+        0x40..0x100 => {
+            w.u8(DW_CFA_ADVANCE_LOC1);
+            w.u8(delta as u8);
+        }
+        0x100..0x1_0000 => {
+            w.u8(DW_CFA_ADVANCE_LOC2);
+            w.u16(delta as u16);
+        }
+        _ => {
+            w.u8(DW_CFA_ADVANCE_LOC4);
+            w.u32(delta as u32);
+        }
+    }
+}
+
+fn pad(w: &mut Writer, start: usize) {
+    while !(w.offset() - start).is_multiple_of(w.address_size as usize) {
+        w.u8(DW_CFA_NOP);
+    }
+}
+
+/// .debug_frame with one FDE per function, given as a.out address ranges,
+/// from the stack offsets of the pc/sp table. Addresses are rebased by
+/// base. None if the architecture is not supported.
+pub fn debug_frame(
+    arch: MachineArch,
+    is_64bit: bool,
+    big_endian: bool,
+    funcs: &[Range<u64>],
+    sp_offsets: &[(u64, i64)],
+    base: u64,
+) -> Option<Vec<u8>> {
+    let regs = frame_registers(arch)?;
+    let quantum = arch.pc_quantum();
+    let mut w = Writer::new(big_endian, is_64bit);
+
+    // CIE, version 1 as in .debug_frame of DWARF 2
+    let cie = w.start_length();
+    w.u32(0xffff_ffff);
+    w.u8(1);
+    w.string("");
+    w.uleb(quantum);
+    // offsets are given as is, and below the CFA
+    w.sleb(-1);
+    w.u8(regs.ra as u8);
+    w.u8(DW_CFA_DEF_CFA);
+    w.uleb(regs.sp);
+    w.uleb(regs.pushed as u64);
+    if regs.pushed != 0 {
+        // the return address stays where the call put it
+        w.u8(DW_CFA_OFFSET | regs.ra as u8);
+        w.uleb(regs.pushed as u64);
+    } else {
+        w.u8(DW_CFA_SAME_VALUE);
+        w.uleb(regs.ra);
+    }
+    pad(&mut w, cie);
+    w.end_length(cie);
+
+    // the offset applies from each pc on
+    let offset_at = |pc: u64| {
+        let i = sp_offsets.partition_point(|(p, _)| *p < pc);
+        sp_offsets.get(i).map_or(0, |(_, v)| *v)
+    };
+    for f in funcs.iter().filter(|f| !f.is_empty()) {
+        let fde = w.start_length();
+        w.u32(cie as u32);
+        w.address(base + f.start);
+        w.address(f.end - f.start);
+
+        let mut loc = f.start;
+        let mut current = offset_at(f.start);
+        if current > 0 {
+            frame_rules(&mut w, &regs, current);
+        }
+        let first = sp_offsets.partition_point(|(p, _)| *p <= f.start);
+        for &(pc, v) in sp_offsets[first..].iter().take_while(|(p, _)| *p < f.end) {
+            // a damaged table must not make the CFA lie above the frame
+            if v == current || v < 0 {
+                continue;
+            }
+            advance(&mut w, (pc - loc) / quantum);
+            frame_rules(&mut w, &regs, v);
+            loc = pc;
+            current = v;
+        }
+        pad(&mut w, fde);
+        w.end_length(fde);
+    }
+    Some(w.data)
+}
+
+/// A source file with its functions and the lines its code stems from,
+/// addresses rebased.
+pub struct CompileUnit<'a> {
+    pub name: String,
+    /// paths of the file and those it includes, numbered from 1
+    pub files: Vec<String>,
+    pub low_pc: u64,
+    pub high_pc: u64,
+    pub sequences: Vec<Sequence>,
+    pub functions: &'a [Function<'a>],
+    /// what the a.out addresses of the functions are rebased by
+    pub base: u64,
+}
+
+/// Contiguous code with rows of address, file and line.
+pub struct Sequence {
+    pub range: Range<u64>,
+    pub rows: Vec<(u64, u64, u64)>,
+}
+
+/// Compile units for the source units that have a history, with the lines
+/// of the pc/line table as decoded by pcline::lines, if any.
+pub fn compile_units<'a>(
+    units: &'a [SourceUnit<'a>],
+    lines: &[(u64, i64)],
+    base: u64,
+) -> Vec<CompileUnit<'a>> {
+    let line_at = |pc: u64| {
+        let i = lines.partition_point(|(p, _)| *p < pc);
+        lines.get(i).map(|(_, l)| *l)
+    };
+    let mut cus = vec![];
+    for u in units {
+        let Some((_, name)) = u.history.first() else {
+            continue;
+        };
+        let mut ranges: Vec<Range<u64>> = vec![];
+        for r in u.funcs.iter().map(|f| &f.range).filter(|r| !r.is_empty()) {
+            match ranges.last_mut() {
+                Some(last) if last.end == r.start => last.end = r.end,
+                _ => ranges.push(r.clone()),
+            }
+        }
+        let (Some(first), Some(last)) = (ranges.first(), ranges.last()) else {
+            continue;
+        };
+        let (low_pc, high_pc) = (base + first.start, base + last.end);
+
+        let mut files: Vec<String> = vec![];
+        let mut sequences = vec![];
+        for r in ranges {
+            let mut rows = vec![];
+            let mut previous = None;
+            let at = lines.partition_point(|(p, _)| *p <= r.start);
+            let changes = lines[at..].iter().take_while(|(p, _)| *p < r.end);
+            let starts = line_at(r.start).map(|l| (r.start, l));
+            for (pc, aline) in starts.into_iter().chain(changes.copied()) {
+                let Some((path, line)) = pcline::file_line(&u.history, aline) else {
+                    continue;
+                };
+                let file = match files.iter().position(|f| f == path) {
+                    Some(i) => i + 1,
+                    None => {
+                        files.push(path.to_string());
+                        files.len()
+                    }
+                } as u64;
+                let row = (base + pc, file, line.max(0) as u64);
+                if previous != Some((row.1, row.2)) {
+                    rows.push(row);
+                    previous = Some((row.1, row.2));
+                }
+            }
+            if !rows.is_empty() {
+                sequences.push(Sequence {
+                    range: base + r.start..base + r.end,
+                    rows,
+                });
+            }
+        }
+        cus.push(CompileUnit {
+            name: name.clone(),
+            files,
+            low_pc,
+            high_pc,
+            sequences,
+            functions: &u.funcs,
+            base,
+        });
+    }
+    cus
+}
+
+// line number program
+const DW_LNS_COPY: u8 = 0x01;
+const DW_LNS_ADVANCE_PC: u8 = 0x02;
+const DW_LNS_ADVANCE_LINE: u8 = 0x03;
+const DW_LNS_SET_FILE: u8 = 0x04;
+const DW_LNE_END_SEQUENCE: u8 = 0x01;
+const DW_LNE_SET_ADDRESS: u8 = 0x02;
+const LINE_BASE: i64 = -5;
+const LINE_RANGE: u8 = 14;
+const OPCODE_BASE: u8 = 13;
+const STANDARD_OPCODE_LENGTHS: [u8; 12] = [0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1];
+
+/// .debug_line with a version 4 line program per compile unit, and the
+/// offset of each.
+pub fn debug_line(cus: &[CompileUnit], is_64bit: bool, big_endian: bool) -> (Vec<u8>, Vec<u32>) {
+    let mut w = Writer::new(big_endian, is_64bit);
+    let mut offsets = vec![];
+    for cu in cus {
+        offsets.push(w.offset() as u32);
+        let unit = w.start_length();
+        w.u16(4);
+        let header = w.start_length();
+        // minimum instruction length, maximum operations per instruction,
+        // default is_stmt
+        w.u8(1);
+        w.u8(1);
+        w.u8(1);
+        w.u8(LINE_BASE as u8);
+        w.u8(LINE_RANGE);
+        w.u8(OPCODE_BASE);
+        w.data.extend_from_slice(&STANDARD_OPCODE_LENGTHS);
+        // no include directories, paths are as given
+        w.u8(0);
+        for f in &cu.files {
+            w.string(f);
+            w.uleb(0);
+            w.uleb(0);
+            w.uleb(0);
+        }
+        w.u8(0);
+        w.end_length(header);
+
+        for Sequence { range, rows } in &cu.sequences {
+            w.u8(0);
+            w.uleb(1 + w.address_size as u64);
+            w.u8(DW_LNE_SET_ADDRESS);
+            w.address(range.start);
+            let (mut address, mut file, mut line) = (range.start, 1, 1);
+            for &(a, f, l) in rows {
+                if f != file {
+                    w.u8(DW_LNS_SET_FILE);
+                    w.uleb(f);
+                    file = f;
+                }
+                row(&mut w, a - address, l as i64 - line as i64);
+                (address, line) = (a, l);
+            }
+            if range.end > address {
+                w.u8(DW_LNS_ADVANCE_PC);
+                w.uleb(range.end - address);
+            }
+            w.u8(0);
+            w.uleb(1);
+            w.u8(DW_LNE_END_SEQUENCE);
+        }
+        w.end_length(unit);
+    }
+    (w.data, offsets)
+}
+
+// Appends a row, with a special opcode where the deltas fit one.
+fn row(w: &mut Writer, address_delta: u64, line_delta: i64) {
+    let line_adjust = line_delta - LINE_BASE;
+    if (0..LINE_RANGE as i64).contains(&line_adjust) {
+        let op = line_adjust as u64 + LINE_RANGE as u64 * address_delta + OPCODE_BASE as u64;
+        if op <= 0xff {
+            w.u8(op as u8);
+            return;
+        }
+    }
+    if address_delta != 0 {
+        w.u8(DW_LNS_ADVANCE_PC);
+        w.uleb(address_delta);
+    }
+    if line_delta != 0 {
+        w.u8(DW_LNS_ADVANCE_LINE);
+        w.sleb(line_delta);
+    }
+    w.u8(DW_LNS_COPY);
+}
+
+// debugging information entries
+const DW_TAG_FORMAL_PARAMETER: u64 = 0x05;
+const DW_TAG_COMPILE_UNIT: u64 = 0x11;
+const DW_TAG_VARIABLE: u64 = 0x34;
+const DW_TAG_SUBPROGRAM: u64 = 0x2e;
+const DW_CHILDREN_NO: u8 = 0;
+const DW_CHILDREN_YES: u8 = 1;
+const DW_AT_LOCATION: u64 = 0x02;
+const DW_AT_NAME: u64 = 0x03;
+const DW_AT_STMT_LIST: u64 = 0x10;
+const DW_AT_LOW_PC: u64 = 0x11;
+const DW_AT_HIGH_PC: u64 = 0x12;
+const DW_AT_LANGUAGE: u64 = 0x13;
+const DW_AT_EXTERNAL: u64 = 0x3f;
+const DW_AT_FRAME_BASE: u64 = 0x40;
+const DW_FORM_ADDR: u64 = 0x01;
+const DW_FORM_DATA2: u64 = 0x05;
+const DW_FORM_STRING: u64 = 0x08;
+const DW_FORM_FLAG: u64 = 0x0c;
+const DW_FORM_SEC_OFFSET: u64 = 0x17;
+const DW_FORM_EXPRLOC: u64 = 0x18;
+const DW_LANG_C89: u16 = 0x0001;
+const DW_OP_BREG0: u8 = 0x70;
+const DW_OP_FBREG: u8 = 0x91;
+const DW_OP_CALL_FRAME_CFA: u8 = 0x9c;
+
+// abbreviation codes, in the order of ABBREVIATIONS
+const ABBREV_COMPILE_UNIT: u64 = 1;
+const ABBREV_SUBPROGRAM: u64 = 2;
+const ABBREV_SUBPROGRAM_NO_FRAME: u64 = 3;
+const ABBREV_PARAMETER: u64 = 4;
+const ABBREV_VARIABLE: u64 = 5;
+
+type Abbreviation = (u64, u8, &'static [(u64, u64)]);
+
+const ABBREVIATIONS: [Abbreviation; 5] = [
+    (
+        DW_TAG_COMPILE_UNIT,
+        DW_CHILDREN_YES,
+        &[
+            (DW_AT_NAME, DW_FORM_STRING),
+            (DW_AT_LANGUAGE, DW_FORM_DATA2),
+            (DW_AT_STMT_LIST, DW_FORM_SEC_OFFSET),
+            (DW_AT_LOW_PC, DW_FORM_ADDR),
+            (DW_AT_HIGH_PC, DW_FORM_ADDR),
+        ],
+    ),
+    (
+        DW_TAG_SUBPROGRAM,
+        DW_CHILDREN_YES,
+        &[
+            (DW_AT_NAME, DW_FORM_STRING),
+            (DW_AT_EXTERNAL, DW_FORM_FLAG),
+            (DW_AT_LOW_PC, DW_FORM_ADDR),
+            (DW_AT_HIGH_PC, DW_FORM_ADDR),
+            (DW_AT_FRAME_BASE, DW_FORM_EXPRLOC),
+        ],
+    ),
+    (
+        DW_TAG_SUBPROGRAM,
+        DW_CHILDREN_NO,
+        &[
+            (DW_AT_NAME, DW_FORM_STRING),
+            (DW_AT_EXTERNAL, DW_FORM_FLAG),
+            (DW_AT_LOW_PC, DW_FORM_ADDR),
+            (DW_AT_HIGH_PC, DW_FORM_ADDR),
+        ],
+    ),
+    (
+        DW_TAG_FORMAL_PARAMETER,
+        DW_CHILDREN_NO,
+        &[
+            (DW_AT_NAME, DW_FORM_STRING),
+            (DW_AT_LOCATION, DW_FORM_EXPRLOC),
+        ],
+    ),
+    (
+        DW_TAG_VARIABLE,
+        DW_CHILDREN_NO,
+        &[
+            (DW_AT_NAME, DW_FORM_STRING),
+            (DW_AT_LOCATION, DW_FORM_EXPRLOC),
+        ],
+    ),
+];
+
+// The frame base is what libmach calls the frame pointer: parameters are
+// above and automatic variables below it. It is the CFA, or, without call
+// frame information, the stack pointer plus the frame size, which holds
+// once the prologue has run.
+fn frame_base(arch: MachineArch, cfi: bool, frame_size: Option<u32>) -> Option<Vec<u8>> {
+    if cfi {
+        return Some(vec![DW_OP_CALL_FRAME_CFA]);
+    }
+    let regs = frame_registers(arch)?;
+    let mut e = Writer::new(false, false);
+    e.u8(DW_OP_BREG0 + regs.sp as u8);
+    e.sleb(frame_size? as i64);
+    Some(e.data)
+}
+
+fn exprloc(w: &mut Writer, e: &[u8]) {
+    w.uleb(e.len() as u64);
+    w.data.extend_from_slice(e);
+}
+
+fn frame_offset(w: &mut Writer, offset: i64) {
+    let mut e = Writer::new(false, false);
+    e.u8(DW_OP_FBREG);
+    e.sleb(offset);
+    exprloc(w, &e.data);
+}
+
+/// .debug_info and .debug_abbrev with an entry per compile unit, given the
+/// offsets of their line programs, and one per function with its
+/// parameters and automatic variables. Variables have no type, which the
+/// symbol table does not give. cfi tells whether there is a .debug_frame.
+pub fn debug_info(
+    cus: &[CompileUnit],
+    line_offsets: &[u32],
+    arch: MachineArch,
+    cfi: bool,
+    is_64bit: bool,
+) -> (Vec<u8>, Vec<u8>) {
+    let big_endian = arch.big_endian();
+    let mut a = Writer::new(big_endian, is_64bit);
+    for (code, (tag, children, attributes)) in (1..).zip(ABBREVIATIONS) {
+        a.uleb(code);
+        a.uleb(tag);
+        a.u8(children);
+        for &(at, form) in attributes {
+            a.uleb(at);
+            a.uleb(form);
+        }
+        a.uleb(0);
+        a.uleb(0);
+    }
+    a.u8(0);
+
+    let mut w = Writer::new(big_endian, is_64bit);
+    for (cu, &line_offset) in cus.iter().zip(line_offsets) {
+        let unit = w.start_length();
+        w.u16(4);
+        w.u32(0);
+        w.u8(w.address_size);
+        w.uleb(ABBREV_COMPILE_UNIT);
+        w.string(&cu.name);
+        w.u16(DW_LANG_C89);
+        w.u32(line_offset);
+        w.address(cu.low_pc);
+        w.address(cu.high_pc);
+        for f in cu.functions.iter().filter(|f| !f.range.is_empty()) {
+            let base = frame_base(arch, cfi, f.frame_size);
+            w.uleb(match base {
+                Some(_) => ABBREV_SUBPROGRAM,
+                None => ABBREV_SUBPROGRAM_NO_FRAME,
+            });
+            w.string(f.name);
+            w.u8(f.external as u8);
+            w.address(cu.base + f.range.start);
+            w.address(cu.base + f.range.end);
+            let Some(base) = base else {
+                continue;
+            };
+            exprloc(&mut w, &base);
+            for &(name, offset) in &f.params {
+                w.uleb(ABBREV_PARAMETER);
+                w.string(name);
+                frame_offset(&mut w, offset as i64);
+            }
+            for &(name, offset) in &f.autos {
+                w.uleb(ABBREV_VARIABLE);
+                w.string(name);
+                frame_offset(&mut w, -(offset as i64));
+            }
+            w.u8(0);
+        }
+        w.u8(0);
+        w.end_length(unit);
+    }
+    (w.data, a.data)
+}
+
+// ch_type of the compression header
+const ELFCOMPRESS_ZLIB: u32 = 1;
+const ELFCOMPRESS_ZSTD: u32 = 2;
+
+/// Contents of a SHF_COMPRESSED section: the compression header with the
+/// size and alignment of data, then data compressed.
+pub fn compress(
+    data: &[u8],
+    compression: DebugCompression,
+    align: u64,
+    is_64bit: bool,
+    big_endian: bool,
+) -> std::io::Result<Vec<u8>> {
+    let mut w = Writer::new(big_endian, is_64bit);
+    let ch_type = match compression {
+        DebugCompression::None => return Ok(data.to_vec()),
+        DebugCompression::Zlib => ELFCOMPRESS_ZLIB,
+        DebugCompression::Zstd => ELFCOMPRESS_ZSTD,
+    };
+    w.u32(ch_type);
+    if is_64bit {
+        // ch_reserved
+        w.u32(0);
+    }
+    w.address(data.len() as u64);
+    w.address(align);
+    match compression {
+        #[cfg(feature = "gzip")]
+        DebugCompression::Zlib => {
+            use std::io::Write;
+            let level = flate2::Compression::best();
+            let mut e = flate2::write::ZlibEncoder::new(w.data, level);
+            e.write_all(data)?;
+            e.finish()
+        }
+        #[cfg(feature = "zstd")]
+        DebugCompression::Zstd => zstd::stream::copy_encode(data, &mut w.data, 0).map(|_| w.data),
+        #[allow(unreachable_patterns)]
+        c => Err(std::io::Error::other(format!(
+            "{c:?} compression is not compiled in"
+        ))),
+    }
+}
@@ -0,0 +1,419 @@
+// Post-conversion editing of ELF files produced by this tool.
+// https://refspecs.linuxfoundation.org/elf/gabi4+/ch4.sheader.html
+// https://refspecs.linuxfoundation.org/elf/gabi4+/ch5.pheader.html
+use crate::{Error, addrmath};
+
+const EI_CLASS: usize = 4;
+const EI_DATA: usize = 5;
+const CLASS_64: u8 = 2;
+const DATA_BIG_ENDIAN: u8 = 2;
+
+// sizes of the ELF header, a program header and a section header
+const HEADER_SIZES_32: (usize, usize, usize) = (0x34, 0x20, 0x28);
+const HEADER_SIZES_64: (usize, usize, usize) = (0x40, 0x38, 0x40);
+
+const SHT_SYMTAB: u32 = 2;
+const SHT_RELA: u32 = 4;
+const SHT_DYNSYM: u32 = 11;
+const SHT_NOBITS: u32 = 8;
+const SHT_REL: u32 = 9;
+const SHN_LORESERVE: u64 = 0xff00;
+const SHF_ALLOC: u64 = 1 << 1;
+
+#[derive(Clone, Debug)]
+pub struct EditorSection {
+    pub name: String,
+    pub section_type: u32,
+    pub flags: u64,
+    pub offset: u64,
+    pub size: u64,
+    pub addr_align: u64,
+    header_offset: usize,
+}
+
+/// Opens an ELF, locates sections by name and replaces their contents,
+/// rewriting the offsets of everything stored behind them.
+#[derive(Clone, Debug)]
+pub struct SectionEditor {
+    data: Vec<u8>,
+    is_64bit: bool,
+    big_endian: bool,
+}
+
+impl SectionEditor {
+    pub fn open(data: Vec<u8>) -> Result<Self, Error> {
+        if !data.starts_with(&[0x7f, b'E', b'L', b'F']) {
+            return Err(Error::Invalid("not an ELF file".to_string()));
+        }
+        let is_64bit = data.get(EI_CLASS) == Some(&CLASS_64);
+        let big_endian = data.get(EI_DATA) == Some(&DATA_BIG_ENDIAN);
+        let e = Self {
+            data,
+            is_64bit,
+            big_endian,
+        };
+        let (header_size, ph_entry, sh_entry) = e.header_sizes();
+        if e.data.len() < header_size {
+            return Err(Error::Invalid("ELF header truncated".to_string()));
+        }
+        // make sure all headers are within the file before handing it out
+        let (ph_off, ph_size, ph_count) = e.program_header_table();
+        let (sh_off, sh_size, sh_count) = e.section_header_table();
+        if ph_count > 0 && ph_size < ph_entry {
+            return Err(Error::Invalid(format!(
+                "program header size {ph_size} is below {ph_entry}"
+            )));
+        }
+        if sh_count > 0 && sh_size < sh_entry {
+            return Err(Error::Invalid(format!(
+                "section header size {sh_size} is below {sh_entry}"
+            )));
+        }
+        let within = |off: usize, size: usize, count: usize| {
+            size.checked_mul(count)
+                .and_then(|n| n.checked_add(off))
+                .is_some_and(|end| end <= e.data.len())
+        };
+        if !within(ph_off, ph_size, ph_count) || !within(sh_off, sh_size, sh_count) {
+            return Err(Error::Invalid("header tables exceed file size".to_string()));
+        }
+        for s in e.sections() {
+            let end = s.offset.checked_add(s.size);
+            if s.section_type != SHT_NOBITS && end.is_none_or(|end| end > e.data.len() as u64) {
+                let name = crate::printable(&s.name);
+                return Err(Error::Invalid(format!("section {name} exceeds file size")));
+            }
+        }
+        Ok(e)
+    }
+
+    fn read(&self, off: usize, size: usize) -> u64 {
+        self.decode(&self.data[off..off + size])
+    }
+
+    fn decode(&self, b: &[u8]) -> u64 {
+        let size = b.len();
+        let mut v = 0u64;
+        for i in 0..size {
+            let byte = if self.big_endian {
+                b[i]
+            } else {
+                b[size - 1 - i]
+            };
+            v = (v << 8) | byte as u64;
+        }
+        v
+    }
+
+    fn write(&mut self, off: usize, size: usize, v: u64) {
+        for i in 0..size {
+            let byte = (v >> (8 * i)) as u8;
+            let pos = if self.big_endian { size - 1 - i } else { i };
+            self.data[off + pos] = byte;
+        }
+    }
+
+    fn word_size(&self) -> usize {
+        if self.is_64bit { 8 } else { 4 }
+    }
+
+    fn header_sizes(&self) -> (usize, usize, usize) {
+        if self.is_64bit {
+            HEADER_SIZES_64
+        } else {
+            HEADER_SIZES_32
+        }
+    }
+
+    // (offset, entry size, entry count)
+    fn program_header_table(&self) -> (usize, usize, usize) {
+        if self.is_64bit {
+            let o = self.read(0x20, 8) as usize;
+            (o, self.read(0x36, 2) as usize, self.read(0x38, 2) as usize)
+        } else {
+            let o = self.read(0x1c, 4) as usize;
+            (o, self.read(0x2a, 2) as usize, self.read(0x2c, 2) as usize)
+        }
+    }
+
+    fn section_header_table(&self) -> (usize, usize, usize) {
+        if self.is_64bit {
+            let o = self.read(0x28, 8) as usize;
+            (o, self.read(0x3a, 2) as usize, self.read(0x3c, 2) as usize)
+        } else {
+            let o = self.read(0x20, 4) as usize;
+            (o, self.read(0x2e, 2) as usize, self.read(0x30, 2) as usize)
+        }
+    }
+
+    fn string_table_index(&self) -> usize {
+        let off = if self.is_64bit { 0x3e } else { 0x32 };
+        self.read(off, 2) as usize
+    }
+
+    // None if the header is not within the file
+    fn raw_section(&self, header_offset: usize) -> Option<EditorSection> {
+        let w = self.word_size();
+        let (_, _, sh_entry) = self.header_sizes();
+        let h = self
+            .data
+            .get(header_offset..header_offset.checked_add(sh_entry)?)?;
+        let field = |at: usize, size: usize| self.decode(&h[at..at + size]);
+        // name, type, flags, addr, offset, size, link, info, addralign
+        Some(EditorSection {
+            name: String::new(),
+            section_type: field(4, 4) as u32,
+            flags: field(8, w),
+            offset: field(8 + 2 * w, w),
+            size: field(8 + 3 * w, w),
+            addr_align: field(16 + 4 * w, w),
+            header_offset,
+        })
+    }
+
+    fn name_at(&self, strtab: &EditorSection, off: u32) -> String {
+        let start = (strtab.offset + off as u64) as usize;
+        let end = (strtab.offset + strtab.size) as usize;
+        if start >= end || end > self.data.len() {
+            return String::new();
+        }
+        let s = &self.data[start..end];
+        let len = s.iter().position(|b| *b == 0).unwrap_or(s.len());
+        String::from_utf8_lossy(&s[..len]).to_string()
+    }
+
+    pub fn sections(&self) -> Vec<EditorSection> {
+        let (sh_off, sh_size, sh_count) = self.section_header_table();
+        let mut sections: Vec<EditorSection> = (0..sh_count)
+            .map_while(|i| self.raw_section(sh_off + i * sh_size))
+            .collect();
+        if let Some(strtab) = sections.get(self.string_table_index()).cloned() {
+            for s in sections.iter_mut() {
+                let name = self.read(s.header_offset, 4) as u32;
+                s.name = self.name_at(&strtab, name);
+            }
+        }
+        sections
+    }
+
+    pub fn find(&self, name: &str) -> Option<EditorSection> {
+        self.sections().into_iter().find(|s| s.name == name)
+    }
+
+    pub fn contents(&self, name: &str) -> Option<&[u8]> {
+        let s = self.find(name)?;
+        if s.section_type == SHT_NOBITS {
+            return Some(&[]);
+        }
+        Some(&self.data[s.offset as usize..(s.offset + s.size) as usize])
+    }
+
+    /// Replace the contents of the named section. Sections and segments
+    /// stored after it are moved by a multiple of their alignment.
+    pub fn replace(&mut self, name: &str, contents: &[u8]) -> Result<(), Error> {
+        let Some(target) = self.find(name) else {
+            let name = crate::printable(name);
+            return Err(Error::Invalid(format!("no section named {name}")));
+        };
+        let name = crate::printable(name);
+        if target.section_type == SHT_NOBITS {
+            return Err(Error::Invalid(format!(
+                "section {name} has no file contents"
+            )));
+        }
+        let old_size = target.size;
+        let new_size = contents.len() as u64;
+        if target.flags & SHF_ALLOC != 0 && new_size != old_size {
+            return Err(Error::Invalid(format!(
+                "section {name} is loaded to memory, its size ({old_size}) cannot change"
+            )));
+        }
+
+        let old_end = target.offset + old_size;
+        let sections = self.sections();
+        let (ph_off, ph_size, ph_count) = self.program_header_table();
+        let (sh_off, _, _) = self.section_header_table();
+        let w = self.word_size();
+
+        // Everything after the section has to keep its alignment.
+        let mut align = 1u64;
+        for s in sections.iter().filter(|s| s.offset >= old_end) {
+            align = align.max(s.addr_align);
+        }
+        for i in 0..ph_count {
+            let h = ph_off + i * ph_size;
+            let (off, a) = if self.is_64bit {
+                (self.read(h + 8, 8), self.read(h + 0x30, 8))
+            } else {
+                (self.read(h + 4, 4), self.read(h + 0x1c, 4))
+            };
+            if off >= old_end {
+                align = align.max(a);
+            }
+        }
+        if ph_off as u64 >= old_end || sh_off as u64 >= old_end {
+            align = align.max(w as u64);
+        }
+
+        let shift: i64 = if new_size >= old_size {
+            let grow = addrmath::align_up(new_size - old_size, align);
+            grow.ok_or_else(|| format!("section {name} is too large"))? as i64
+        } else {
+            -(addrmath::align_down(old_size - new_size, align) as i64)
+        };
+        let slot = (old_size as i64 + shift) as usize;
+
+        let start = target.offset as usize;
+        let mut replacement = contents.to_vec();
+        replacement.resize(slot, 0);
+        self.data.splice(start..old_end as usize, replacement);
+
+        let moved = |v: u64| -> u64 {
+            if v >= old_end {
+                (v as i64 + shift) as u64
+            } else {
+                v
+            }
+        };
+
+        // ELF header
+        let (ph_field, sh_field) = if self.is_64bit {
+            (0x20, 0x28)
+        } else {
+            (0x1c, 0x20)
+        };
+        let new_ph_off = moved(ph_off as u64);
+        let new_sh_off = moved(sh_off as u64);
+        self.write(ph_field, w, new_ph_off);
+        self.write(sh_field, w, new_sh_off);
+
+        // section headers
+        for s in sections.iter() {
+            let h = (s.header_offset as i64 - sh_off as i64 + new_sh_off as i64) as usize;
+            if s.header_offset == target.header_offset {
+                self.write(h + 8 + 3 * w, w, new_size);
+            } else if s.offset >= old_end {
+                self.write(h + 8 + 2 * w, w, moved(s.offset));
+            }
+        }
+
+        // program headers
+        for i in 0..ph_count {
+            let h = new_ph_off as usize + i * ph_size;
+            let (off_field, filesz_field, memsz_field) = if self.is_64bit {
+                (h + 8, h + 0x20, h + 0x28)
+            } else {
+                (h + 4, h + 0x10, h + 0x14)
+            };
+            let off = self.read(off_field, w);
+            let filesz = self.read(filesz_field, w);
+            if off >= old_end {
+                self.write(off_field, w, moved(off));
+            } else if off <= target.offset && old_end <= off + filesz && filesz > 0 {
+                // non-alloc sections only; grow or shrink the enclosing range
+                let memsz = self.read(memsz_field, w);
+                let filesz_new = (filesz as i64 + new_size as i64 - old_size as i64) as u64;
+                self.write(filesz_field, w, filesz_new);
+                if memsz == filesz {
+                    self.write(memsz_field, w, filesz_new);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove the named section, which must not be loaded to memory, with
+    /// its contents and header. References to the sections behind it by
+    /// index, in headers and symbols, are renumbered.
+    pub fn remove(&mut self, name: &str) -> Result<(), Error> {
+        let Some(target) = self.find(name) else {
+            let name = crate::printable(name);
+            return Err(Error::Invalid(format!("no section named {name}")));
+        };
+        let printable = crate::printable(name);
+        if target.flags & SHF_ALLOC != 0 {
+            return Err(Error::Invalid(format!(
+                "section {printable} is loaded to memory"
+            )));
+        }
+        // open made sure headers are at least as large as in the ELF spec
+        let (sh_off, sh_size, sh_count) = self.section_header_table();
+        let index = (target.header_offset - sh_off) / sh_size;
+        if index == 0 || index == self.string_table_index() {
+            return Err(Error::Invalid(format!(
+                "section {printable} cannot be removed"
+            )));
+        }
+        // symbols refer to sections by index, they are walked to renumber
+        let w = self.word_size();
+        let symbol_size = if self.is_64bit { 24 } else { 16 };
+        for s in self.sections() {
+            if !matches!(s.section_type, SHT_SYMTAB | SHT_DYNSYM) {
+                continue;
+            }
+            let entry_size = self.read(s.header_offset + 16 + 5 * w, w);
+            if entry_size < symbol_size {
+                let name = crate::printable(&s.name);
+                return Err(Error::Invalid(format!(
+                    "symbol table {name} has entries of size {entry_size}, below {symbol_size}"
+                )));
+            }
+        }
+        if target.section_type != SHT_NOBITS {
+            self.replace(name, &[])?;
+        }
+
+        // renumber, then drop the header from the table
+        let (sh_off, _, _) = self.section_header_table();
+        let w = self.word_size();
+        let renumber = |i: u64| match i {
+            0 => 0,
+            i if i == index as u64 => 0,
+            i if i > index as u64 && i < SHN_LORESERVE => i - 1,
+            i => i,
+        };
+        for s in self.sections() {
+            let link = s.header_offset + 8 + 4 * w;
+            let v = self.read(link, 4);
+            self.write(link, 4, renumber(v));
+            if matches!(s.section_type, SHT_REL | SHT_RELA) {
+                let v = self.read(link + 4, 4);
+                self.write(link + 4, 4, renumber(v));
+            }
+            if matches!(s.section_type, SHT_SYMTAB | SHT_DYNSYM) {
+                let entry_size = self.read(s.header_offset + 16 + 5 * w, w) as usize;
+                let shndx = if self.is_64bit { 6 } else { 14 };
+                let (start, end) = (s.offset as usize, (s.offset + s.size) as usize);
+                for e in (start..end).step_by(entry_size) {
+                    if e + entry_size > end {
+                        break;
+                    }
+                    let v = self.read(e + shndx, 2);
+                    self.write(e + shndx, 2, renumber(v));
+                }
+            }
+        }
+        let shstrndx = self.string_table_index();
+        let (shstrndx_field, shnum_field) = if self.is_64bit {
+            (0x3e, 0x3c)
+        } else {
+            (0x32, 0x30)
+        };
+        self.write(shstrndx_field, 2, renumber(shstrndx as u64));
+        self.write(shnum_field, 2, sh_count as u64 - 1);
+        let at = sh_off + index * sh_size;
+        let table_end = sh_off + sh_count * sh_size;
+        self.data.copy_within(at + sh_size..table_end, at);
+        if table_end == self.data.len() {
+            self.data.truncate(table_end - sh_size);
+        } else {
+            self.data[table_end - sh_size..table_end].fill(0);
+        }
+        Ok(())
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+}
@@ -0,0 +1,324 @@
+// Conversion of simple static ELF executables back to Plan 9 a.out, e.g.
+// for kernels linked by GNU or LLVM tools and booted by Plan 9 loaders.
+//
+// The a.out header has no addresses besides the entry point: loaders put
+// the text there and the data on the page after the end of the text. The ELF has
+// to follow that layout; when its data starts further on, text is padded
+// with zeros up to it, and data not on a page boundary is padded in front.
+use clap::ValueEnum;
+use goblin::container::Ctx;
+use goblin::elf::Elf;
+use goblin::elf::header::{
+    EI_CLASS, ELFCLASS64, ELFMAG, ET_EXEC, header32, header64, machine_to_str,
+};
+use goblin::elf::program_header::{PF_W, PT_DYNAMIC, PT_INTERP, PT_LOAD, ProgramHeader};
+use goblin::elf::section_header::{SHF_ALLOC, SHN_UNDEF, SHT_NOBITS, SectionHeader};
+use goblin::elf::sym::{STB_LOCAL, STT_FILE, STT_SECTION};
+use log::info;
+use zerocopy::IntoBytes;
+use zerocopy::byteorder::big_endian::U32;
+
+use crate::addrmath::{align_down, data_address};
+use crate::{
+    Aout, Error, MachineArch, Profile, SYM_BSS_SEGMENT, SYM_DATA, SYM_STATIC_BSS_SEGMENT,
+    SYM_STATIC_DATA, SYM_STATIC_TEXT, SYM_TEXT,
+};
+
+/// Options for converting an ELF to a.out.
+#[derive(Clone, Debug, Default)]
+pub struct AoutOptions {
+    /// architecture instead of the one from the ELF header
+    pub machine: Option<MachineArch>,
+    /// leave out the symbol table made from .symtab
+    pub strip: bool,
+}
+
+// MIPS ELF tells the instruction set in the flags, a.out in the magic.
+const EF_MIPS_ARCH: u32 = 0xf000_0000;
+const EF_MIPS_ARCH_3: u32 = 0x2000_0000;
+
+fn arch_of(elf: &Elf) -> Result<MachineArch, Error> {
+    let m = elf.header.e_machine;
+    let arch = MachineArch::value_variants()
+        .iter()
+        .copied()
+        .find(|a| *a != MachineArch::Unknown && a.elf_machine() as u16 == m);
+    let mips4000 = elf.header.e_flags & EF_MIPS_ARCH >= EF_MIPS_ARCH_3;
+    match arch {
+        Some(MachineArch::Mips) => Ok(match (elf.little_endian, mips4000) {
+            (false, false) => MachineArch::Mips,
+            (false, true) => MachineArch::Mips4000,
+            (true, false) => MachineArch::Spim,
+            (true, true) => MachineArch::Spim4000,
+        }),
+        Some(a) => Ok(a),
+        None => Err(Error::Invalid(format!(
+            "no Plan 9 architecture for {}",
+            machine_to_str(m)
+        ))),
+    }
+}
+
+// Contents of the segments from start on, with gaps between them zeroed,
+// and the size they take in memory.
+fn contents(d: &[u8], segments: &[&ProgramHeader], start: u64) -> Result<(Vec<u8>, u64), Error> {
+    let mut image = vec![];
+    let mut memory = 0;
+    for p in segments {
+        let at = (p.p_vaddr - start) as usize;
+        let range = p.p_offset as usize..p.p_offset.saturating_add(p.p_filesz) as usize;
+        let Some(b) = d.get(range) else {
+            return Err(Error::Truncated("segment"));
+        };
+        if image.len() < at + b.len() {
+            image.resize(at + b.len(), 0);
+        }
+        image[at..at + b.len()].copy_from_slice(b);
+        memory = memory.max(p.p_vaddr - start + p.p_memsz);
+    }
+    Ok((image, memory))
+}
+
+// Size of an a.out part, which has 32 bits.
+fn size(what: &str, v: u64) -> Result<u32, Error> {
+    u32::try_from(v).map_err(|_| Error::OutOfRange(format!("{what} of {v:#x} bytes")))
+}
+
+// Whether the headers, the tables of program and section headers, and the
+// sections with contents are all within the file, so that a truncated one
+// is told apart from a malformed one.
+fn check_extents(d: &[u8]) -> Result<(), Error> {
+    if !d.starts_with(ELFMAG) {
+        return Err(Error::Invalid("not an ELF file".to_string()));
+    }
+    let header_size = match d.get(EI_CLASS) {
+        Some(&ELFCLASS64) => header64::SIZEOF_EHDR,
+        _ => header32::SIZEOF_EHDR,
+    };
+    if d.len() < header_size {
+        return Err(Error::Truncated("ELF header"));
+    }
+    let malformed = |e: goblin::error::Error| format!("not an ELF file: {e}");
+    let h = Elf::parse_header(d).map_err(malformed)?;
+    let within = |off: u64, size: u16, count: u16| {
+        off.checked_add(u64::from(size) * u64::from(count))
+            .is_some_and(|end| end <= d.len() as u64)
+    };
+    if !within(h.e_phoff, h.e_phentsize, h.e_phnum) {
+        return Err(Error::Truncated("program headers"));
+    }
+    if !within(h.e_shoff, h.e_shentsize, h.e_shnum) {
+        return Err(Error::Truncated("section headers"));
+    }
+    let ctx = Ctx::new(
+        h.container().map_err(malformed)?,
+        h.endianness().map_err(malformed)?,
+    );
+    let shs =
+        SectionHeader::parse(d, h.e_shoff as usize, h.e_shnum as usize, ctx).map_err(malformed)?;
+    if shs.iter().any(|s| {
+        s.sh_type != SHT_NOBITS
+            && s.sh_offset
+                .checked_add(s.sh_size)
+                .is_none_or(|end| end > d.len() as u64)
+    }) {
+        return Err(Error::Truncated("section"));
+    }
+    Ok(())
+}
+
+/// Converts a static ELF executable made of text, data and bss to a Plan 9
+/// a.out, with a symbol table from .symtab unless stripping.
+pub fn elf_to_aout(d: &[u8], opts: &AoutOptions) -> Result<Vec<u8>, Error> {
+    check_extents(d)?;
+    let elf = Elf::parse(d).map_err(|e| format!("not an ELF file: {e}"))?;
+    let arch = match opts.machine {
+        Some(m) => m,
+        None => arch_of(&elf)?,
+    };
+    if arch.big_endian() == elf.little_endian {
+        let e = format!("{arch} differs from the ELF in byte order");
+        return Err(Error::Invalid(e));
+    }
+    if elf.header.e_type != ET_EXEC {
+        let e = "not an executable, a.out has no relocations";
+        return Err(Error::Invalid(e.to_string()));
+    }
+    let phs = &elf.program_headers;
+    if phs
+        .iter()
+        .any(|p| p.p_type == PT_INTERP || p.p_type == PT_DYNAMIC)
+    {
+        let e = "dynamically linked, a.out has to be static";
+        return Err(Error::Invalid(e.to_string()));
+    }
+
+    // GNU ld maps the headers in a segment of their own, without sections
+    let has_sections = |p: &ProgramHeader| {
+        let shs = &elf.section_headers;
+        shs.is_empty()
+            || shs.iter().any(|s| {
+                let end = p.p_vaddr.saturating_add(p.p_memsz);
+                s.sh_flags & SHF_ALLOC as u64 != 0
+                    && s.sh_size > 0
+                    && (p.p_vaddr..end).contains(&s.sh_addr)
+            })
+    };
+    // text is whatever is not writable, e.g. also read-only data
+    let mut loads: Vec<&ProgramHeader> = phs
+        .iter()
+        .filter(|p| p.p_type == PT_LOAD && p.p_memsz > 0 && has_sections(p))
+        .collect();
+    loads.sort_by_key(|p| p.p_vaddr);
+    let (data_segments, text_segments): (Vec<_>, Vec<_>) =
+        loads.into_iter().partition(|p| p.p_flags & PF_W != 0);
+    let Some(text_start) = text_segments.first().map(|p| p.p_vaddr) else {
+        return Err(Error::Invalid("no text segment".to_string()));
+    };
+    let entry = elf.header.e_entry;
+    if entry != text_start {
+        return Err(Error::Invalid(format!(
+            "entry {entry:#x} is not at the start of the text, {text_start:#x}, \
+             where a.out loaders put it"
+        )));
+    }
+
+    // addresses are 32 bits above a base, for most targets 0
+    let base = entry & !0xffff_ffff;
+    let kernel_base = Profile::Kernel.virtual_base(arch.elf_machine(), entry);
+    if !arch.expanded_header() && base != 0 && Some(base) != kernel_base {
+        let e = format!("entry {entry:#x} does not fit the {arch} a.out header");
+        return Err(Error::OutOfRange(e));
+    }
+    let end = text_segments
+        .iter()
+        .chain(&data_segments)
+        .map(|p| p.p_vaddr.saturating_add(p.p_memsz))
+        .max()
+        .unwrap_or(entry);
+    if text_segments
+        .iter()
+        .chain(&data_segments)
+        .any(|p| p.p_vaddr < base)
+        || end - base > 1 << 32
+    {
+        let e = format!("segments beyond 4 GiB from {base:#x}");
+        return Err(Error::OutOfRange(e));
+    }
+
+    let (mut text, text_memory) = contents(d, &text_segments, entry)?;
+    // bss within text has to be in the file
+    text.resize(text_memory as usize, 0);
+    let (data, data_start, bss) = match data_segments.first() {
+        None => (vec![], None, 0),
+        Some(first) => {
+            // data starts on the page after the end of the text
+            let rounding = arch.default_profile().data_rounding;
+            let start = align_down(first.p_vaddr, rounding);
+            if start < entry + text.len() as u64 {
+                return Err(Error::Invalid(format!(
+                    "data at {:#x} shares a page with the text, a.out loaders \
+                     put it on the next page",
+                    first.p_vaddr
+                )));
+            }
+            let ts = size("text", text.len() as u64)?;
+            if data_address(entry as u32, ts, rounding) != start - base {
+                info!("Padding text up to the data at {:#x}", first.p_vaddr);
+                text.resize((start - entry) as usize, 0);
+            }
+            let (data, memory) = contents(d, &data_segments, start)?;
+            let bss = memory - data.len() as u64;
+            (data, Some(start), bss)
+        }
+    };
+
+    let ts = size("text", text.len() as u64)?;
+    let ds = size("data", data.len() as u64)?;
+    let bs = size("bss", bss)?;
+    let syms = match opts.strip {
+        true => vec![],
+        false => symbols(
+            &elf,
+            arch,
+            base,
+            entry..entry + ts as u64,
+            data_start,
+            ds,
+            bs,
+        ),
+    };
+
+    let h = Aout {
+        magic: arch.magic().to_be(),
+        text_size: U32::new(ts),
+        data_size: U32::new(ds),
+        bss_size: U32::new(bs),
+        symbol_table_size: U32::new(size("symbol table", syms.len() as u64)?),
+        entry_point: U32::new(entry as u32),
+        sp_size: U32::ZERO,
+        pc_size: U32::ZERO,
+    };
+    let mut a = h.as_bytes().to_vec();
+    if arch.expanded_header() {
+        a.extend_from_slice(&entry.to_be_bytes());
+    }
+    a.extend_from_slice(&text);
+    a.extend_from_slice(&data);
+    a.extend_from_slice(&syms);
+    Ok(a)
+}
+
+// Plan 9 symbols for the ELF symbols within text, data and bss, in address
+// order. Values are 64 bits wide with the expanded header, else 32 bits
+// above the base.
+fn symbols(
+    elf: &Elf,
+    arch: MachineArch,
+    base: u64,
+    text: std::ops::Range<u64>,
+    data_start: Option<u64>,
+    ds: u32,
+    bs: u32,
+) -> Vec<u8> {
+    let data = data_start.map_or(0..0, |s| s..s + ds as u64);
+    let bss = data.end..data.end + bs as u64;
+    let mut syms: Vec<(u64, u8, &str)> = vec![];
+    for s in elf.syms.iter() {
+        if s.st_shndx == SHN_UNDEF as usize || matches!(s.st_type(), STT_FILE | STT_SECTION) {
+            continue;
+        }
+        let Some(name) = elf.strtab.get_at(s.st_name).filter(|n| !n.is_empty()) else {
+            continue;
+        };
+        let v = s.st_value;
+        let (global, local) = if text.contains(&v) || v == text.end {
+            (SYM_TEXT, SYM_STATIC_TEXT)
+        } else if data.contains(&v) {
+            (SYM_DATA, SYM_STATIC_DATA)
+        } else if bss.contains(&v) || (data_start.is_some() && v == bss.end) {
+            (SYM_BSS_SEGMENT, SYM_STATIC_BSS_SEGMENT)
+        } else {
+            continue;
+        };
+        let t = if s.st_bind() == STB_LOCAL {
+            local
+        } else {
+            global
+        };
+        syms.push((v, t, name));
+    }
+    syms.sort_by_key(|(v, _, _)| *v);
+
+    let mut st = vec![];
+    for (v, t, name) in syms {
+        match arch.expanded_header() {
+            true => st.extend_from_slice(&v.to_be_bytes()),
+            false => st.extend_from_slice(&((v - base) as u32).to_be_bytes()),
+        }
+        st.push(t | 0x80);
+        st.extend_from_slice(name.as_bytes());
+        st.push(0);
+    }
+    st
+}
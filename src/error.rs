@@ -0,0 +1,56 @@
+// Errors of the conversion and of reading and writing its files.
+use std::fmt::Display;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The input ends within the named part.
+    Truncated(&'static str),
+    /// The a.out magic names no supported architecture.
+    BadMagic(u32),
+    /// An offset, size or address does not fit where it has to go.
+    OutOfRange(String),
+    /// Reading or writing the named file failed; the name may be empty.
+    Io(String, std::io::Error),
+    /// Any other invalid input or option.
+    Invalid(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Truncated(what) => write!(f, "{what} exceeds the file"),
+            Error::BadMagic(m) => write!(f, "unsupported architecture: {m:08x}"),
+            Error::OutOfRange(e) | Error::Invalid(e) => write!(f, "{e}"),
+            Error::Io(n, e) if n.is_empty() => write!(f, "{e}"),
+            Error::Io(n, e) => write!(f, "{n}: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(String::new(), e)
+    }
+}
+
+// Most internal steps still report plain messages.
+impl From<String> for Error {
+    fn from(e: String) -> Self {
+        Error::Invalid(e)
+    }
+}
+
+impl From<Error> for String {
+    fn from(e: Error) -> Self {
+        e.to_string()
+    }
+}
@@ -0,0 +1,54 @@
+// Side-by-side hexdump of the rows where two files differ.
+use std::io::IsTerminal;
+
+const ROW: usize = 16;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const RESET: &str = "\x1b[0m";
+
+fn hex_row(b: &[u8], other: &[u8], start: usize, color: Option<&str>) -> String {
+    let mut s = vec![];
+    for i in start..start + ROW {
+        let differs = b.get(i) != other.get(i);
+        let h = match b.get(i) {
+            Some(v) => format!("{v:02x}"),
+            None => "  ".to_string(),
+        };
+        match color {
+            Some(c) if differs => s.push(format!("{c}{h}{RESET}")),
+            _ => s.push(h),
+        }
+    }
+    s.join(" ")
+}
+
+/// Prints differing rows with their file offset and the address given by
+/// `vaddr`, coloring the differing bytes on a terminal. Returns the number
+/// of differing bytes.
+pub fn print(old: &[u8], new: &[u8], vaddr: &dyn Fn(usize) -> Option<u64>) -> usize {
+    let color = std::io::stdout().is_terminal();
+    let len = old.len().max(new.len());
+    let mut count = 0;
+    let mut last_row = None;
+    for start in (0..len).step_by(ROW) {
+        let end = (start + ROW).min(len);
+        let diffs = (start..end).filter(|i| old.get(*i) != new.get(*i)).count();
+        if diffs == 0 {
+            continue;
+        }
+        count += diffs;
+        if last_row.is_some_and(|l| l + ROW != start) {
+            println!("...");
+        }
+        last_row = Some(start);
+        let va = match vaddr(start) {
+            Some(a) => format!("{a:016x}"),
+            None => "-".repeat(16),
+        };
+        let o = hex_row(old, new, start, color.then_some(RED));
+        let n = hex_row(new, old, start, color.then_some(GREEN));
+        println!("{start:08x} {va} | {o} | {n}");
+    }
+    count
+}
@@ -0,0 +1,712 @@
+// Intermediate model of an ELF image and the layout engine placing it.
+//
+// The file is laid out as follows:
+// - ELF header
+// - program headers
+// - section headers
+// - section contents, in order, with LOAD segments congruent to their
+//   virtual addresses modulo their alignment
+// - raw contents of segments without sections
+//
+// Headers and symbol tables are built in little endian, whatever the host's
+// byte order, and only swapped when encoding a big-endian image; text and
+// data are kept as they are.
+use std::borrow::Cow;
+use std::io::Write;
+
+use serde::Serialize;
+
+use crate::addrmath::{align_up, congruence_padding, contains, is_congruent, overlaps};
+use crate::strtab::StringTable;
+use crate::timings;
+use crate::{
+    ELF_IDENT_SIZE, ELF32_HEADER_FIELDS, ELF32_HEADER_SIZE, ELF32_PROGRAM_HEADER_FIELDS,
+    ELF32_PROGRAM_HEADER_SIZE, ELF32_SECTION_HEADER_FIELDS, ELF32_SECTION_HEADER_SIZE,
+    ELF32_SYMBOL_FIELDS, ELF64_HEADER_FIELDS, ELF64_HEADER_SIZE, ELF64_PROGRAM_HEADER_FIELDS,
+    ELF64_PROGRAM_HEADER_SIZE, ELF64_SECTION_HEADER_FIELDS, ELF64_SECTION_HEADER_SIZE,
+    ELF64_SYMBOL_FIELDS, Elf32ProgramHeader, Elf32SectionHeader, Elf64ProgramHeader,
+    Elf64SectionHeader, ElfClass, ElfDataEncoding, ElfHeader, ElfId, ElfMachine, ElfOsAbi,
+    ElfProgramHeader, ElfProgramType, ElfSectionHeader, ElfSectionType, swap_fields,
+};
+
+// https://refspecs.linuxfoundation.org/elf/gabi4+/ch4.sheader.html#sh_flags
+pub const SH_FLAG_WRITE: u64 = 1 << 0;
+pub const SH_FLAG_ALLOC: u64 = 1 << 1;
+pub const SH_FLAG_EXEC: u64 = 1 << 2;
+pub const SH_FLAG_COMPRESSED: u64 = 1 << 11;
+
+pub const PH_FLAG_READ: u32 = 1 << 2;
+pub const PH_FLAG_WRITE: u32 = 1 << 1;
+pub const PH_FLAG_EXEC: u32 = 1 << 0;
+
+#[derive(Clone, Debug)]
+pub struct Section<'a> {
+    pub name: String,
+    // offset into the section header string table
+    pub name_offset: u32,
+    pub section_type: ElfSectionType,
+    pub flags: u64,
+    pub addr: u64,
+    // contents, empty for NOBITS sections
+    pub data: Cow<'a, [u8]>,
+    // size of NOBITS sections, which is never allocated
+    pub nobits_size: u64,
+    // name of the section referred to by sh_link
+    pub link: Option<String>,
+    pub info: u32,
+    pub addr_align: u64,
+    pub entry_size: u64,
+}
+
+impl Section<'_> {
+    pub fn size(&self) -> u64 {
+        match self.section_type {
+            ElfSectionType::NoBits => self.nobits_size,
+            _ => self.data.len() as u64,
+        }
+    }
+
+    pub fn is_alloc(&self) -> bool {
+        self.flags & SH_FLAG_ALLOC != 0
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Segment<'a> {
+    pub program_type: ElfProgramType,
+    pub flags: u32,
+    pub virtual_addr: u64,
+    pub physical_addr: u64,
+    pub align: u64,
+    // names of the sections covered, which must be adjacent in the file
+    pub sections: Vec<String>,
+    // contents for segments that do not cover any section
+    pub data: Cow<'a, [u8]>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ElfImage<'a> {
+    pub machine: ElfMachine,
+    pub is_64bit: bool,
+    pub big_endian: bool,
+    // e_flags
+    pub flags: u32,
+    pub os_abi: ElfOsAbi,
+    pub entry: u64,
+    // the NULL section at index 0 is implied
+    pub sections: Vec<Section<'a>>,
+    pub segments: Vec<Segment<'a>>,
+}
+
+// Offsets and sizes as they end up in the file.
+#[derive(Clone, Debug, Default)]
+pub struct Placement {
+    pub offset: u64,
+    pub file_size: u64,
+    pub memory_size: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct Layout {
+    pub sections: Vec<Placement>,
+    pub segments: Vec<Placement>,
+    pub size: u64,
+}
+
+// Where a byte range of the output came from: copied from the input, or
+// generated (headers, padding, converted tables).
+#[derive(Clone, Debug, Serialize)]
+pub struct Provenance {
+    pub offset: u64,
+    pub size: u64,
+    pub origin: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_offset: Option<u64>,
+}
+
+// offset of data within input, if it was borrowed from there
+fn input_offset(data: &[u8], input: &[u8]) -> Option<u64> {
+    let start = input.as_ptr() as usize;
+    let p = data.as_ptr() as usize;
+    if data.is_empty() || p < start || p + data.len() > start + input.len() {
+        return None;
+    }
+    Some((p - start) as u64)
+}
+
+// ELF32 fields are 32 bits wide; truncating would yield a wrong image.
+fn fit32(v: u64, what: &str, owner: &str) -> Result<u32, String> {
+    u32::try_from(v).map_err(|_| {
+        format!("{what} {v:#x} of {owner} does not fit in ELF32, ELF64 output is needed")
+    })
+}
+
+impl<'a> ElfImage<'a> {
+    fn headers_size(&self) -> u64 {
+        let ph_count = self.segments.len();
+        // +1 for the NULL section
+        let sh_count = self.sections.len() + 1;
+        let size = if self.is_64bit {
+            ELF64_HEADER_SIZE
+                + ph_count * ELF64_PROGRAM_HEADER_SIZE
+                + sh_count * ELF64_SECTION_HEADER_SIZE
+        } else {
+            ELF32_HEADER_SIZE
+                + ph_count * ELF32_PROGRAM_HEADER_SIZE
+                + sh_count * ELF32_SECTION_HEADER_SIZE
+        };
+        size as u64
+    }
+
+    // section header table index, accounting for the NULL section
+    pub fn section_index(&self, name: &str) -> Option<u32> {
+        let i = self.sections.iter().position(|s| s.name == name)?;
+        Some(i as u32 + 1)
+    }
+
+    // Build .shstrtab from the section names as they are now, appending the
+    // section itself if there is none yet.
+    pub fn assign_names(&mut self) {
+        if self.section_index(".shstrtab").is_none() {
+            self.sections.push(Section {
+                name: ".shstrtab".to_string(),
+                name_offset: 0,
+                section_type: ElfSectionType::SymbolStringTable,
+                flags: 0,
+                addr: 0,
+                data: Cow::Borrowed(&[]),
+                nobits_size: 0,
+                link: None,
+                info: 0,
+                addr_align: 1,
+                entry_size: 0,
+            });
+        }
+        let mut sh_str_tab = StringTable::new();
+        for s in self.sections.iter_mut() {
+            s.name_offset = sh_str_tab.add(&s.name);
+        }
+        for s in self.sections.iter_mut().filter(|s| s.name == ".shstrtab") {
+            s.data = Cow::Owned(sh_str_tab.as_bytes().to_vec());
+        }
+    }
+
+    // Reorder sections as given, with "*" standing for all sections not
+    // listed, which are otherwise appended. Names may omit the leading dot.
+    // Symbols refer to sections by index, so symbol tables are updated.
+    pub fn order_sections(&mut self, order: &[String]) -> Result<(), String> {
+        let resolve = |n: &String| -> Result<String, String> {
+            let dotted = format!(".{n}");
+            self.sections
+                .iter()
+                .find(|s| &s.name == n || s.name == dotted)
+                .map(|s| s.name.clone())
+                .ok_or(format!("no section {n} to order"))
+        };
+        let mut names: Vec<Option<String>> = vec![];
+        for n in order {
+            if n == "*" {
+                names.push(None);
+            } else {
+                let n = resolve(n)?;
+                if names.contains(&Some(n.clone())) {
+                    return Err(format!("{n} is listed twice"));
+                }
+                names.push(Some(n));
+            }
+        }
+        if !names.contains(&None) {
+            names.push(None);
+        }
+
+        let listed: Vec<&String> = names.iter().flatten().collect();
+        let mut ordered: Vec<String> = vec![];
+        for n in names.iter() {
+            match n {
+                Some(n) => ordered.push(n.clone()),
+                None => ordered.extend(
+                    self.sections
+                        .iter()
+                        .map(|s| s.name.clone())
+                        .filter(|n| !listed.contains(&n)),
+                ),
+            }
+        }
+
+        // old index -> new index, both accounting for the NULL section
+        let mut new_index = vec![0u16; self.sections.len() + 1];
+        for (i, s) in self.sections.iter().enumerate() {
+            let pos = ordered.iter().position(|n| n == &s.name).unwrap();
+            new_index[i + 1] = pos as u16 + 1;
+        }
+        self.sections
+            .sort_by_key(|s| ordered.iter().position(|n| n == &s.name));
+
+        // st_shndx sits at offset 6 in ELF64 and 14 in ELF32 symbols, which
+        // are still in little endian here
+        let shndx_offset = if self.is_64bit { 6 } else { 14 };
+        for s in self.sections.iter_mut() {
+            if !matches!(s.section_type, ElfSectionType::SymbolTable) || s.entry_size == 0 {
+                continue;
+            }
+            let mut data = s.data.to_vec();
+            for e in data.chunks_exact_mut(s.entry_size as usize) {
+                let f = &mut e[shndx_offset..shndx_offset + 2];
+                let i = u16::from_le_bytes([f[0], f[1]]) as usize;
+                // reserved indices such as SHN_ABS stay
+                if i > 0 && i < new_index.len() {
+                    f.copy_from_slice(&new_index[i].to_le_bytes());
+                }
+            }
+            s.data = Cow::Owned(data);
+        }
+        Ok(())
+    }
+
+    fn link_index(&self, s: &Section) -> Result<u32, String> {
+        match &s.link {
+            None => Ok(0),
+            Some(l) => self
+                .section_index(l)
+                .ok_or(format!("{} links to missing section {l}", s.name)),
+        }
+    }
+
+    fn segment_of(&self, name: &str) -> Option<usize> {
+        self.segments.iter().position(|s| {
+            matches!(s.program_type, ElfProgramType::Load) && s.sections.iter().any(|n| n == name)
+        })
+    }
+
+    pub fn layout(&self) -> Result<Layout, String> {
+        let mut offset = self.headers_size();
+        let mut sections = vec![Placement::default(); self.sections.len()];
+        let mut segments = vec![Placement::default(); self.segments.len()];
+        let mut segment_offsets: Vec<Option<u64>> = vec![None; self.segments.len()];
+
+        for (i, s) in self.sections.iter().enumerate() {
+            let o = match self.segment_of(&s.name) {
+                Some(si) => {
+                    let seg = &self.segments[si];
+                    let seg_offset = match segment_offsets[si] {
+                        Some(o) => o,
+                        None => {
+                            // file offset and virtual address must be congruent
+                            let o =
+                                offset + congruence_padding(offset, seg.virtual_addr, seg.align);
+                            segment_offsets[si] = Some(o);
+                            o
+                        }
+                    };
+                    if s.addr < seg.virtual_addr {
+                        return Err(format!("{} lies below its segment", s.name));
+                    }
+                    let o = seg_offset + (s.addr - seg.virtual_addr);
+                    if o < offset {
+                        return Err(format!("{} overlaps the preceding section", s.name));
+                    }
+                    o
+                }
+                None => align_up(offset, s.addr_align)
+                    .ok_or_else(|| format!("{} does not fit in the file", s.name))?,
+            };
+            let size = s.size();
+            // NOBITS sections occupy no space in the file
+            let file_size = match s.section_type {
+                ElfSectionType::NoBits => 0,
+                _ => size,
+            };
+            sections[i] = Placement {
+                offset: o,
+                file_size,
+                memory_size: size,
+            };
+            offset = o + file_size;
+        }
+
+        for (i, seg) in self.segments.iter().enumerate() {
+            if seg.sections.is_empty() {
+                let o = align_up(offset, seg.align)
+                    .ok_or_else(|| format!("segment {i} does not fit in the file"))?;
+                let size = seg.data.len() as u64;
+                segments[i] = Placement {
+                    offset: o,
+                    file_size: size,
+                    memory_size: size,
+                };
+                offset = o + size;
+                continue;
+            }
+            let mut start = u64::MAX;
+            let mut end = 0;
+            let mut mem_end = seg.virtual_addr;
+            for name in seg.sections.iter() {
+                let Some(si) = self.sections.iter().position(|s| &s.name == name) else {
+                    return Err(format!("segment {i} refers to missing section {name}"));
+                };
+                let p = &sections[si];
+                start = start.min(p.offset);
+                end = end.max(p.offset + p.file_size);
+                mem_end = mem_end.max(self.sections[si].addr + p.memory_size);
+            }
+            segments[i] = Placement {
+                offset: start,
+                file_size: end - start,
+                memory_size: mem_end - seg.virtual_addr,
+            };
+        }
+
+        Ok(Layout {
+            sections,
+            segments,
+            size: offset,
+        })
+    }
+
+    // Each alloc section has to lie within exactly one LOAD segment, both in
+    // the file and in memory, and LOAD segments have to be congruent.
+    pub fn check_segment_mapping(&self, layout: &Layout) -> Result<(), String> {
+        let loads: Vec<(&Segment, &Placement)> = self
+            .segments
+            .iter()
+            .zip(layout.segments.iter())
+            .filter(|(s, _)| matches!(s.program_type, ElfProgramType::Load))
+            .collect();
+        // symbol containers describe addresses without mapping anything
+        if loads.is_empty() {
+            return Ok(());
+        }
+
+        for (seg, p) in loads.iter() {
+            let align = seg.align.max(1);
+            if !is_congruent(p.offset, seg.virtual_addr, align) {
+                return Err(format!(
+                    "LOAD segment @ {:08x} is not congruent to {:08x} modulo {align:#x}",
+                    p.offset, seg.virtual_addr
+                ));
+            }
+        }
+
+        for (s, p) in self.sections.iter().zip(layout.sections.iter()) {
+            if !s.is_alloc() {
+                continue;
+            }
+            let Some(owner) = self.segment_of(&s.name) else {
+                return Err(format!("{} is not in any LOAD segment", s.name));
+            };
+            let (seg, sp) = (&self.segments[owner], &layout.segments[owner]);
+            let in_file = contains(sp.offset, sp.file_size, p.offset, p.file_size);
+            let in_memory = contains(seg.virtual_addr, sp.memory_size, s.addr, p.memory_size);
+            if !in_file || !in_memory {
+                return Err(format!("{} lies outside its LOAD segment", s.name));
+            }
+            // empty sections cannot overlap anything
+            let others = loads
+                .iter()
+                .filter(|(other, op)| {
+                    !std::ptr::eq(*other, seg)
+                        && (overlaps(op.offset, op.file_size, p.offset, p.file_size)
+                            || overlaps(other.virtual_addr, op.memory_size, s.addr, p.memory_size))
+                })
+                .count();
+            if others > 0 {
+                return Err(format!(
+                    "{} is covered by {} LOAD segments",
+                    s.name,
+                    others + 1
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn program_headers(&self, layout: &Layout) -> Result<Vec<ElfProgramHeader>, String> {
+        let mut program_headers = vec![];
+        for (i, (s, p)) in self.segments.iter().zip(layout.segments.iter()).enumerate() {
+            let ph = if self.is_64bit {
+                ElfProgramHeader::Elf64(Elf64ProgramHeader {
+                    program_type: s.program_type,
+                    flags: s.flags,
+                    offset: p.offset,
+                    virtual_addr: s.virtual_addr,
+                    physical_addr: s.physical_addr,
+                    file_size: p.file_size,
+                    memory_size: p.memory_size,
+                    align: s.align,
+                })
+            } else {
+                let owner = &format!("segment {i}");
+                ElfProgramHeader::Elf32(Elf32ProgramHeader {
+                    program_type: s.program_type,
+                    offset: fit32(p.offset, "file offset", owner)?,
+                    virtual_addr: fit32(s.virtual_addr, "virtual address", owner)?,
+                    physical_addr: fit32(s.physical_addr, "physical address", owner)?,
+                    file_size: fit32(p.file_size, "file size", owner)?,
+                    memory_size: fit32(p.memory_size, "memory size", owner)?,
+                    flags: s.flags,
+                    align: fit32(s.align, "alignment", owner)?,
+                })
+            };
+            program_headers.push(ph);
+        }
+        Ok(program_headers)
+    }
+
+    fn section_headers(&self, layout: &Layout) -> Result<Vec<ElfSectionHeader>, String> {
+        let mut section_headers = vec![];
+
+        // NOTE: empty section, necessary for symbol resolution to work
+        if self.is_64bit {
+            let sh = Elf64SectionHeader {
+                name: 0,
+                section_type: ElfSectionType::Null,
+                flags: 0,
+                addr: 0,
+                offset: 0,
+                size: 0,
+                link: 0,
+                info: 0,
+                addr_align: 0,
+                entry_size: 0,
+            };
+            section_headers.push(ElfSectionHeader::Elf64(sh));
+        } else {
+            let sh = Elf32SectionHeader {
+                name: 0,
+                section_type: ElfSectionType::Null,
+                flags: 0,
+                addr: 0,
+                offset: 0,
+                size: 0,
+                link: 0,
+                info: 0,
+                addr_align: 0,
+                entry_size: 0,
+            };
+            section_headers.push(ElfSectionHeader::Elf32(sh));
+        }
+
+        for (s, p) in self.sections.iter().zip(layout.sections.iter()) {
+            let link = self.link_index(s)?;
+            let sh = if self.is_64bit {
+                ElfSectionHeader::Elf64(Elf64SectionHeader {
+                    name: s.name_offset,
+                    section_type: s.section_type,
+                    flags: s.flags,
+                    addr: s.addr,
+                    offset: p.offset,
+                    size: p.memory_size,
+                    link,
+                    info: s.info,
+                    addr_align: s.addr_align,
+                    entry_size: s.entry_size,
+                })
+            } else {
+                let owner = &s.name;
+                ElfSectionHeader::Elf32(Elf32SectionHeader {
+                    name: s.name_offset,
+                    section_type: s.section_type,
+                    flags: fit32(s.flags, "flags", owner)?,
+                    addr: fit32(s.addr, "address", owner)?,
+                    offset: fit32(p.offset, "file offset", owner)?,
+                    size: fit32(p.memory_size, "size", owner)?,
+                    link,
+                    info: s.info,
+                    addr_align: fit32(s.addr_align, "alignment", owner)?,
+                    entry_size: fit32(s.entry_size, "entry size", owner)?,
+                })
+            };
+            section_headers.push(sh);
+        }
+        Ok(section_headers)
+    }
+
+    // Map every byte of the output to its origin.
+    pub fn provenance(&self, input: &[u8]) -> Result<Vec<Provenance>, String> {
+        let layout = self.layout()?;
+        let mut ranges = vec![Provenance {
+            offset: 0,
+            size: self.headers_size(),
+            origin: "headers".to_string(),
+            input_offset: None,
+        }];
+        for (s, p) in self.sections.iter().zip(layout.sections.iter()) {
+            ranges.push(Provenance {
+                offset: p.offset,
+                size: p.file_size,
+                origin: s.name.clone(),
+                input_offset: input_offset(&s.data, input),
+            });
+        }
+        for (i, (s, p)) in self.segments.iter().zip(layout.segments.iter()).enumerate() {
+            if s.sections.is_empty() {
+                ranges.push(Provenance {
+                    offset: p.offset,
+                    size: p.file_size,
+                    origin: format!("segment {i}"),
+                    input_offset: input_offset(&s.data, input),
+                });
+            }
+        }
+        ranges.retain(|r| r.size > 0);
+        ranges.sort_by_key(|r| r.offset);
+
+        let mut res = vec![];
+        let mut offset = 0;
+        for r in ranges {
+            if r.offset > offset {
+                res.push(Provenance {
+                    offset,
+                    size: r.offset - offset,
+                    origin: "padding".to_string(),
+                    input_offset: None,
+                });
+            }
+            offset = r.offset + r.size;
+            res.push(r);
+        }
+        Ok(res)
+    }
+
+    // Write the headers to one sink and everything after them to another.
+    pub fn write_split(&self, meta: &mut dyn Write, payload: &mut dyn Write) -> Result<(), String> {
+        let mut w = Split {
+            at: self.headers_size(),
+            offset: 0,
+            meta,
+            payload,
+        };
+        self.write(&mut w)
+    }
+
+    pub fn write(&self, w: &mut dyn Write) -> Result<(), String> {
+        let layout = timings::stage("layout", || {
+            let layout = self.layout()?;
+            self.check_segment_mapping(&layout)?;
+            Ok::<_, String>(layout)
+        })?;
+        timings::stage("encode", || self.encode(&layout, w))
+    }
+
+    fn encode(&self, layout: &Layout, w: &mut dyn Write) -> Result<(), String> {
+        let Some(sh_string_table_index) = self.section_index(".shstrtab") else {
+            return Err("missing .shstrtab".to_string());
+        };
+        let entry = if self.is_64bit {
+            self.entry
+        } else {
+            fit32(self.entry, "entry point", "the image")? as u64
+        };
+        let class = if self.is_64bit {
+            ElfClass::Elf64
+        } else {
+            ElfClass::Elf32
+        };
+        let data_encoding = if self.big_endian {
+            ElfDataEncoding::BigEndian
+        } else {
+            ElfDataEncoding::LittleEndian
+        };
+        let eh = ElfHeader::new(
+            ElfId::new(class, data_encoding, self.os_abi),
+            self.segments.len(),
+            self.sections.len() + 1,
+            sh_string_table_index as u16,
+            entry,
+            self.machine,
+            self.flags,
+        );
+
+        let big_endian = self.big_endian;
+        let (header_fields, ph_fields, sh_fields, sym_fields) = if self.is_64bit {
+            (
+                ELF64_HEADER_FIELDS,
+                ELF64_PROGRAM_HEADER_FIELDS,
+                ELF64_SECTION_HEADER_FIELDS,
+                ELF64_SYMBOL_FIELDS,
+            )
+        } else {
+            (
+                ELF32_HEADER_FIELDS,
+                ELF32_PROGRAM_HEADER_FIELDS,
+                ELF32_SECTION_HEADER_FIELDS,
+                ELF32_SYMBOL_FIELDS,
+            )
+        };
+
+        let mut headers = eh.to_le_bytes();
+        if big_endian {
+            swap_fields(&mut headers[ELF_IDENT_SIZE..], header_fields);
+        }
+        for ph in self.program_headers(layout)? {
+            let at = headers.len();
+            headers.extend_from_slice(&ph.to_le_bytes());
+            if big_endian {
+                swap_fields(&mut headers[at..], ph_fields);
+            }
+        }
+        for sh in self.section_headers(layout)? {
+            let at = headers.len();
+            headers.extend_from_slice(&sh.to_le_bytes());
+            if big_endian {
+                swap_fields(&mut headers[at..], sh_fields);
+            }
+        }
+
+        let mut chunks: Vec<(u64, Cow<[u8]>)> = vec![(0, Cow::Owned(headers))];
+        for (s, p) in self.sections.iter().zip(layout.sections.iter()) {
+            let mut data = Cow::Borrowed(&s.data[..p.file_size as usize]);
+            let sym_size: usize = sym_fields.iter().sum();
+            if big_endian
+                && matches!(s.section_type, ElfSectionType::SymbolTable)
+                && s.entry_size == sym_size as u64
+            {
+                swap_fields(data.to_mut(), sym_fields);
+            }
+            chunks.push((p.offset, data));
+        }
+        for (s, p) in self.segments.iter().zip(layout.segments.iter()) {
+            if s.sections.is_empty() {
+                chunks.push((p.offset, Cow::Borrowed(&s.data)));
+            }
+        }
+
+        let mut offset = 0u64;
+        for (at, b) in chunks {
+            if at > offset {
+                let mut pad = std::io::Read::take(std::io::repeat(0), at - offset);
+                std::io::copy(&mut pad, w).map_err(|e| e.to_string())?;
+            }
+            w.write_all(&b).map_err(|e| e.to_string())?;
+            offset = at + b.len() as u64;
+        }
+        Ok(())
+    }
+}
+
+// Routes bytes before `at` to one sink and the rest to the other.
+struct Split<'a> {
+    at: u64,
+    offset: u64,
+    meta: &'a mut dyn Write,
+    payload: &'a mut dyn Write,
+}
+
+impl Write for Split<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = if self.offset < self.at {
+            let len = buf.len().min((self.at - self.offset) as usize);
+            self.meta.write(&buf[..len])?
+        } else {
+            self.payload.write(buf)?
+        };
+        self.offset += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.meta.flush()?;
+        self.payload.flush()
+    }
+}
@@ -0,0 +1,2511 @@
+//! Conversion of Plan 9 a.out executables to ELF.
+//!
+//! The a.out side consists of the header ([`Aout`]), its symbol table
+//! ([`parse_aout_symbols`]) and the regions of the file ([`aout::AoutFile`]).
+//! Conversion goes through an intermediate model ([`layout::ElfImage`]),
+//! built by [`aout_to_image`] and written by [`aout_to_elf`] and
+//! [`aout_to_elf_into`] with the given [`ConvertOptions`]. Formats other
+//! than the built-in ones can be added as a [`backend::Backend`].
+//!
+//! ```no_run
+//! use p9aout2elf::{ConvertOptions, aout_to_elf};
+//!
+//! let d = std::fs::read("9k").unwrap();
+//! let elf = aout_to_elf(&d, &ConvertOptions::default()).unwrap();
+//! std::fs::write("9k.elf", elf).unwrap();
+//! ```
+#![allow(unused)]
+use std::borrow::Cow;
+use std::ffi::CStr;
+use std::fmt::Display;
+use std::io::Write;
+use std::str::FromStr;
+
+use clap::ValueEnum;
+use log::{debug, info, warn};
+use zerocopy::byteorder::big_endian::U32;
+use zerocopy::{FromBytes, IntoBytes, TryFromBytes};
+use zerocopy_derive::{FromBytes, Immutable, IntoBytes, TryFromBytes};
+
+pub mod addrmath;
+pub mod aout;
+pub mod backend;
+pub mod dwarf;
+pub mod editor;
+pub mod elf2aout;
+mod error;
+pub mod layout;
+#[cfg(feature = "macho")]
+pub mod macho;
+pub mod magic;
+pub mod pcline;
+pub mod plan9;
+pub mod strtab;
+pub mod timings;
+
+use aout::RegionKind;
+use editor::SectionEditor;
+pub use error::Error;
+use layout::{
+    ElfImage, PH_FLAG_EXEC, PH_FLAG_READ, PH_FLAG_WRITE, SH_FLAG_ALLOC, SH_FLAG_COMPRESSED,
+    SH_FLAG_EXEC, SH_FLAG_WRITE, Section, Segment,
+};
+use magic::Magic;
+use strtab::StringTable;
+
+/// Parses decimal as well as 0x-prefixed hexadecimal numbers.
+pub fn parse_number(s: &str) -> Result<u64, String> {
+    let r = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(h) => u64::from_str_radix(h, 16),
+        None => s.parse(),
+    };
+    r.map_err(|e| format!("{s}: {e}"))
+}
+
+/// Plan 9 a.out header, with sizes in big endian.
+///
+/// See <https://9p.io/magic/man2html/6/a.out>
+/// and 9front sys/include/a.out.h
+#[derive(FromBytes, Immutable, IntoBytes, Clone, Copy, Debug)]
+#[repr(C, packed)]
+pub struct Aout {
+    pub magic: u32,
+    pub text_size: U32,         /* binary code segment */
+    pub data_size: U32,         /* initialized data */
+    pub bss_size: U32,          /* uninitialized data */
+    pub symbol_table_size: U32, /* symbol table */
+    pub entry_point: U32,       /* entry point */
+    pub sp_size: U32,           /* pc/sp offset table */
+    pub pc_size: U32,           /* pc/line number table */
+}
+
+#[derive(FromBytes, Immutable, IntoBytes, Clone, Copy, Debug)]
+#[repr(C, packed)]
+/// Fixed part of a symbol table entry, followed by the name.
+pub struct AoutSymbolHeader {
+    pub spacer: [u8; 4],
+    pub value: U32,
+    pub sym_type: u8,
+}
+
+/// Symbol table entry, borrowing its name from the table.
+#[derive(Clone, Debug)]
+pub struct AoutSymbol<'a> {
+    pub header: AoutSymbolHeader,
+    pub name: &'a str,
+    // big-endian f symbol indices of z and Z symbols, empty for others
+    pub indices: &'a [u8],
+}
+
+// https://en.wikipedia.org/wiki/Executable_and_Linkable_Format
+// https://gist.github.com/DhavalKapil/2243db1b732b211d0c16fd5d9140ab0b
+
+#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
+#[repr(u16)]
+pub enum ElfType {
+    None,
+    Relocatable,
+    Executable,
+    SharedObject,
+    Core,
+    LoOS = 0xfe00,
+    HiOS = 0xfeff,
+    LoProc = 0xff00,
+    HiProc = 0xffff,
+}
+
+#[derive(Immutable, IntoBytes, Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+#[repr(u8)]
+pub enum ElfClass {
+    #[value(skip)]
+    None,
+    #[value(name = "elf32", alias = "32")]
+    Elf32,
+    #[value(name = "elf64", alias = "64")]
+    Elf64,
+}
+
+impl Display for ElfClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.to_possible_value() {
+            Some(v) => write!(f, "{}", v.get_name()),
+            None => write!(f, "none"),
+        }
+    }
+}
+
+impl FromStr for ElfClass {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        <Self as ValueEnum>::from_str(s, true).map_err(|_| format!("unknown ELF class: {s}"))
+    }
+}
+
+#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
+#[repr(u8)]
+pub enum ElfDataEncoding {
+    Invalid,
+    LittleEndian,
+    BigEndian,
+}
+
+// https://refspecs.linuxfoundation.org/elf/gabi4+/ch4.eheader.html#elfid
+#[derive(Immutable, IntoBytes, Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+#[repr(u8)]
+pub enum ElfOsAbi {
+    #[value(alias = "sysv")]
+    None = 0,
+    #[value(name = "hpux")]
+    HpUx = 1,
+    #[value(name = "netbsd")]
+    NetBsd = 2,
+    #[value(alias = "gnu")]
+    Linux = 3,
+    Hurd = 4,
+    #[value(name = "solaris")]
+    SunSolaris = 6,
+    Aix = 7,
+    Irix = 8,
+    #[value(name = "freebsd")]
+    FreeBsd = 9,
+    #[value(name = "tru64")]
+    Tru64Unix = 10,
+    #[value(name = "modesto")]
+    NovellModesto = 11,
+    #[value(name = "openbsd")]
+    OpenBsd = 12,
+    #[value(name = "openvms")]
+    OpenVms = 13,
+    #[value(name = "nsk")]
+    HpNonStopKernel = 14,
+    Standalone = 255,
+}
+
+impl Display for ElfOsAbi {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.to_possible_value() {
+            Some(v) => write!(f, "{}", v.get_name()),
+            None => write!(f, "{}", *self as u8),
+        }
+    }
+}
+
+impl FromStr for ElfOsAbi {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        <Self as ValueEnum>::from_str(s, true).map_err(|_| format!("unknown OS/ABI: {s}"))
+    }
+}
+
+// https://refspecs.linuxfoundation.org/elf/gabi4+/ch4.eheader.html
+#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
+#[repr(C, packed)]
+pub struct ElfId {
+    pub magic: [u8; 4],
+    pub class: ElfClass,
+    pub data_encoding: ElfDataEncoding,
+    pub header_version: u8,
+    pub os_abi: ElfOsAbi,
+    pub abi_version: u8,
+    pub _res: [u8; 7],
+}
+
+// NOTE: This is the complete list from Wikipedia as of 2025-06-04.
+// Plan 9 a.out only supports few targets as of now, so we do not need them all.
+#[derive(Immutable, IntoBytes, TryFromBytes, Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u16)]
+pub enum ElfMachine {
+    None = 0x00,
+    AttWe32100 = 0x01,
+    Sparc = 0x02,
+    X86 = 0x03,
+    M68k = 0x04,
+    M88k = 0x05,
+    IntelMcu = 0x06,
+    Intel80860 = 0x07,
+    Mips = 0x08,
+    IbmSystem370 = 0x09,
+    MipsRs3000LittleEndian = 0x0A,
+    // 0x0B – 0x0E Reserved for future use
+    HpPaRisc = 0x0F,
+    Intel80960 = 0x13,
+    PowerPC = 0x14,
+    PowerPC64 = 0x15,
+    S390 = 0x16,
+    IbmSpuSpc = 0x17,
+    // 0x18 – 0x23 Reserved for future use
+    NecV800 = 0x24,
+    FujitsuFr20 = 0x25,
+    TrwRh32 = 0x26,
+    MotorolaRce = 0x27,
+    Aarch32 = 0x28,
+    DigitalAlpha = 0x29,
+    SuperH = 0x2A,
+    SparcVersion9 = 0x2B,
+    SiemensTriCoreEmbeddedProcessor = 0x2C,
+    ArgonautRiscCore = 0x2D,
+    HitachiH8300 = 0x2E,
+    HitachiH8300H = 0x2F,
+    HitachiH8S = 0x30,
+    HitachiH8_500 = 0x31,
+    Ia64 = 0x32,
+    StanfordMipsX = 0x33,
+    MotorolaColdFire = 0x34,
+    MotorolaM68Hc12 = 0x35,
+    FujitsuMmaMultimediaAccelerator = 0x36,
+    SiemensPcp = 0x37,
+    SonyNCpuEmbeddedRisc = 0x38,
+    DensoNdr1Microprocessor = 0x39,
+    MotorolaStarCoreProcessor = 0x3A,
+    ToyotaMe16Processor = 0x3B,
+    STMicroelectronicsST100 = 0x3C,
+    AdvancedLogicTinyJEmbeddedProcessor = 0x3D,
+    Amd64 = 0x3E,
+    SonyDsp = 0x3F,
+    DigitalPdp10 = 0x40,
+    DigitalPdp11 = 0x41,
+    SiemensFx66Microcontroller = 0x42,
+    STMicroelectronicsST9Plus8_16bitMicrocontroller = 0x43,
+    STMicroelectronicsST7_8bitMicrocontroller = 0x44,
+    MotorolaMC68HC16Microcontroller = 0x45,
+    MotorolaMC68HC11Microcontroller = 0x46,
+    MotorolaMC68HC08Microcontroller = 0x47,
+    MotorolaMC68HC05Microcontroller = 0x48,
+    SiliconGraphicsSVx = 0x49,
+    STMicroelectronicsST19_8bitMicrocontroller = 0x4A,
+    DigitalVax = 0x4B,
+    Axis32bitEmbeddedProcessor = 0x4C,
+    Infineon32bitEmbeddedProcessor = 0x4D,
+    Element14_64bitDsp = 0x4E,
+    LsiLogic16bitDsp = 0x4F,
+    Tms320C6000Family = 0x8C,
+    McstElbrusE2k = 0xAF,
+    Aarch64 = 0xB7,
+    ZilogZ80 = 0xDC,
+    RiscV = 0xF3,
+    BerkeleyPacketFilter = 0xF7,
+    WDC65C816 = 0x101,
+    LoongArch = 0x102,
+}
+
+impl Display for ElfMachine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let m = *self as u16;
+        write!(f, "{self:?} ({m:#04x})")
+    }
+}
+
+impl ElfMachine {
+    /// The machine of an e_machine value, if it is in the list.
+    pub fn from_u16(m: u16) -> Option<Self> {
+        Self::try_read_from_bytes(m.as_bytes()).ok()
+    }
+}
+
+// Names as printed, e.g. "RiscV (0xf3)", or bare, also as goblin prints
+// them, e.g. "RISCV", or the number of e_machine.
+impl FromStr for ElfMachine {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.split_once(" (").map_or(s, |(n, _)| n);
+        let found = match parse_number(name) {
+            Ok(m) => u16::try_from(m).ok().and_then(Self::from_u16),
+            Err(_) => (0..=ElfMachine::LoongArch as u16).find_map(|m| {
+                let machine = Self::from_u16(m)?;
+                let goblin = goblin::elf::header::machine_to_str(m);
+                (format!("{machine:?}").eq_ignore_ascii_case(name)
+                    || goblin.eq_ignore_ascii_case(name))
+                .then_some(machine)
+            }),
+        };
+        found.ok_or_else(|| format!("unknown ELF machine: {s}"))
+    }
+}
+
+// NOTE: extracted for convenience, not an official thing.
+#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
+#[repr(C, packed)]
+pub struct ElfExtra {
+    pub flags: u32,
+    pub elf_header_size: u16,
+    pub program_header_entry_size: u16,
+    pub program_header_entry_count: u16,
+    pub section_header_entry_size: u16,
+    pub section_header_entry_count: u16,
+    pub section_header_index_entry: u16,
+}
+
+// https://refspecs.linuxfoundation.org/elf/gabi4+/ch4.eheader.html
+#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
+#[repr(C, packed)]
+pub struct Elf32Header {
+    pub id: ElfId,
+    pub elf_type: ElfType,
+    pub machine: ElfMachine,
+    pub version: u32,
+    pub entry: u32,
+    pub program_header_offset: u32,
+    pub section_header_offset: u32,
+    pub extra: ElfExtra,
+}
+
+// NOTE: only entry point address and program/section header offsets differ.
+#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
+#[repr(C, packed)]
+pub struct Elf64Header {
+    pub id: ElfId,
+    pub elf_type: ElfType,
+    pub machine: ElfMachine,
+    pub version: u32,
+    pub entry: u64,
+    pub program_header_offset: u64,
+    pub section_header_offset: u64,
+    pub extra: ElfExtra,
+}
+
+#[derive(Immutable, Clone, Copy, Debug)]
+#[repr(C)]
+pub enum ElfHeader {
+    Elf32(Elf32Header),
+    Elf64(Elf64Header),
+}
+
+impl ElfId {
+    pub fn new(class: ElfClass, data_encoding: ElfDataEncoding, os_abi: ElfOsAbi) -> Self {
+        Self {
+            magic: ELF_MAGIC,
+            class,
+            data_encoding,
+            header_version: 1, // fixed
+            os_abi,
+            abi_version: 0,
+            _res: [0, 0, 0, 0, 0, 0, 0],
+        }
+    }
+}
+
+// NOTE: Many things are hardcoded here.
+impl ElfHeader {
+    /// The header for the given identification, which tells the class.
+    pub fn new(
+        id: ElfId,
+        program_header_entry_count: usize,
+        section_header_entry_count: usize,
+        section_header_index_entry: u16,
+        entry: u64,
+        machine: ElfMachine,
+        flags: u32,
+    ) -> Self {
+        let is_64bit = id.class == ElfClass::Elf64;
+        let elf_header_size = if is_64bit {
+            ELF64_HEADER_SIZE
+        } else {
+            ELF32_HEADER_SIZE
+        };
+        let elf_program_header_size = if is_64bit {
+            ELF64_PROGRAM_HEADER_SIZE
+        } else {
+            ELF32_PROGRAM_HEADER_SIZE
+        };
+        let elf_section_header_size = if is_64bit {
+            ELF64_SECTION_HEADER_SIZE
+        } else {
+            ELF32_SECTION_HEADER_SIZE
+        };
+
+        let extra = ElfExtra {
+            flags,
+            elf_header_size: elf_header_size as u16,
+            program_header_entry_size: elf_program_header_size as u16,
+            program_header_entry_count: program_header_entry_count as u16,
+            section_header_entry_size: elf_section_header_size as u16,
+            section_header_entry_count: section_header_entry_count as u16,
+            section_header_index_entry,
+        };
+
+        // NOTE: There are only few entries, so they always fit in u32.
+        let ph_size = (program_header_entry_count * elf_program_header_size) as u32;
+        let ph_offset = elf_header_size as u32;
+        let sh_offset = ph_offset + ph_size;
+
+        if is_64bit {
+            ElfHeader::Elf64(Elf64Header {
+                id,
+                elf_type: ElfType::Executable,
+                machine,
+                version: 1,
+                entry,
+                program_header_offset: ph_offset as u64,
+                section_header_offset: sh_offset as u64,
+                extra,
+            })
+        } else {
+            ElfHeader::Elf32(Elf32Header {
+                id,
+                elf_type: ElfType::Executable,
+                machine,
+                version: 1,
+                entry: entry as u32,
+                program_header_offset: ph_offset,
+                section_header_offset: sh_offset,
+                extra,
+            })
+        }
+    }
+
+    /// The header in little endian, e_ident as is.
+    pub fn to_le_bytes(&self) -> Vec<u8> {
+        let (native, fields) = match self {
+            ElfHeader::Elf32(h) => (h.as_bytes(), ELF32_HEADER_FIELDS),
+            ElfHeader::Elf64(h) => (h.as_bytes(), ELF64_HEADER_FIELDS),
+        };
+        let (id, rest) = native.split_at(ELF_IDENT_SIZE);
+        [id, &le_bytes(rest, fields)].concat()
+    }
+}
+
+#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
+#[repr(u32)]
+pub enum ElfProgramType {
+    Null,
+    Load,
+    Dynamic,
+    Note,
+    Interpreted,
+    ProgramHeader,
+}
+
+// https://refspecs.linuxfoundation.org/elf/gabi4+/ch5.pheader.html
+#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
+#[repr(C, packed)]
+pub struct Elf32ProgramHeader {
+    pub program_type: ElfProgramType,
+    pub offset: u32,
+    pub virtual_addr: u32,
+    pub physical_addr: u32,
+    pub file_size: u32,
+    pub memory_size: u32,
+    pub flags: u32,
+    pub align: u32,
+}
+
+#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
+#[repr(C, packed)]
+pub struct Elf64ProgramHeader {
+    pub program_type: ElfProgramType,
+    pub flags: u32,
+    pub offset: u64,
+    pub virtual_addr: u64,
+    pub physical_addr: u64,
+    pub file_size: u64,
+    pub memory_size: u64,
+    pub align: u64,
+}
+
+#[derive(Immutable, Clone, Copy, Debug)]
+#[repr(C)]
+pub enum ElfProgramHeader {
+    Elf32(Elf32ProgramHeader),
+    Elf64(Elf64ProgramHeader),
+}
+
+impl ElfProgramHeader {
+    pub fn to_le_bytes(&self) -> Vec<u8> {
+        match self {
+            ElfProgramHeader::Elf32(h) => le_bytes(h.as_bytes(), ELF32_PROGRAM_HEADER_FIELDS),
+            ElfProgramHeader::Elf64(h) => le_bytes(h.as_bytes(), ELF64_PROGRAM_HEADER_FIELDS),
+        }
+    }
+}
+
+// https://refspecs.linuxfoundation.org/elf/gabi4+/ch4.sheader.html
+#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
+#[repr(u32)]
+pub enum ElfSectionType {
+    Null,
+    ProgBits,
+    SymbolTable,
+    SymbolStringTable,
+    RelocationEntriesWithAddends,
+    SymbolHashTable,
+    Dynamic,
+    Note,
+    NoBits,
+    Rel,
+    Shlib,
+    DynamicSymbols,
+    // mind the gap
+    InitArray = 14,
+    FiniArray,
+    PreinitArray,
+    Group,
+    SymbolTableIndex,
+    LoOS = 0x60000000,
+    HiOS = 0x6fffffff,
+    LoProc = 0x70000000,
+    // https://github.com/riscv-non-isa/riscv-elf-psabi-doc/blob/master/riscv-elf.adoc#section-types
+    RiscvAttributes = 0x70000003,
+    HiProc = 0x7fffffff,
+    LoUser = 0x80000000,
+    HiUser = 0xffffffff,
+}
+
+impl ElfSectionType {
+    // processor-specific types share values, and the enum cannot repeat them
+    // https://github.com/ARM-software/abi-aa/blob/main/aaelf32/aaelf32.rst#section-types
+    #[allow(non_upper_case_globals)]
+    pub const ArmAttributes: ElfSectionType = ElfSectionType::RiscvAttributes;
+}
+
+// https://refspecs.linuxfoundation.org/elf/gabi4+/ch4.sheader.html
+#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
+#[repr(C, packed)]
+pub struct Elf32SectionHeader {
+    pub name: u32,
+    pub section_type: ElfSectionType,
+    pub flags: u32,
+    pub addr: u32,
+    pub offset: u32,
+    pub size: u32,
+    pub link: u32,
+    pub info: u32,
+    pub addr_align: u32,
+    pub entry_size: u32,
+}
+
+#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
+#[repr(C, packed)]
+pub struct Elf64SectionHeader {
+    pub name: u32,
+    pub section_type: ElfSectionType,
+    pub flags: u64,
+    pub addr: u64,
+    pub offset: u64,
+    pub size: u64,
+    pub link: u32,
+    pub info: u32,
+    pub addr_align: u64,
+    pub entry_size: u64,
+}
+
+#[derive(Immutable, Clone, Copy, Debug)]
+#[repr(C)]
+pub enum ElfSectionHeader {
+    Elf32(Elf32SectionHeader),
+    Elf64(Elf64SectionHeader),
+}
+
+impl ElfSectionHeader {
+    pub fn to_le_bytes(&self) -> Vec<u8> {
+        match self {
+            ElfSectionHeader::Elf32(h) => le_bytes(h.as_bytes(), ELF32_SECTION_HEADER_FIELDS),
+            ElfSectionHeader::Elf64(h) => le_bytes(h.as_bytes(), ELF64_SECTION_HEADER_FIELDS),
+        }
+    }
+}
+
+// `man elf`
+// https://refspecs.linuxfoundation.org/elf/gabi4+/ch4.symtab.html
+#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
+#[repr(C, packed)]
+pub struct Elf32SymbolTableEntry {
+    pub name_offset: u32, // offset into string table
+    pub value: u32,
+    pub size: u32,
+    pub info: u8,
+    pub other: u8,
+    pub section_index: u16,
+}
+
+#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
+#[repr(C, packed)]
+pub struct Elf64SymbolTableEntry {
+    pub name_offset: u32, // offset into string table
+    pub info: u8,
+    pub other: u8,
+    pub section_index: u16,
+    pub value: u64,
+    pub size: u64,
+}
+
+// https://refspecs.linuxfoundation.org/elf/gabi4+/ch4.symtab.html
+#[derive(Immutable, Clone, Copy, Debug)]
+#[repr(C)]
+pub enum ElfSymbolTableEntry {
+    Elf32(Elf32SymbolTableEntry),
+    Elf64(Elf64SymbolTableEntry),
+}
+
+impl ElfSymbolTableEntry {
+    pub fn to_le_bytes(&self) -> Vec<u8> {
+        match self {
+            ElfSymbolTableEntry::Elf32(e) => le_bytes(e.as_bytes(), ELF32_SYMBOL_FIELDS),
+            ElfSymbolTableEntry::Elf64(e) => le_bytes(e.as_bytes(), ELF64_SYMBOL_FIELDS),
+        }
+    }
+
+    /// st_info, i.e., binding and type
+    pub fn info(&self) -> u8 {
+        match self {
+            ElfSymbolTableEntry::Elf32(e) => e.info,
+            ElfSymbolTableEntry::Elf64(e) => e.info,
+        }
+    }
+}
+
+// Field widths of the structures written, from e_type on for the ELF
+// header, as e_ident consists of single bytes.
+pub(crate) const ELF32_HEADER_FIELDS: &[usize] = &[2, 2, 4, 4, 4, 4, 4, 2, 2, 2, 2, 2, 2];
+pub(crate) const ELF64_HEADER_FIELDS: &[usize] = &[2, 2, 4, 8, 8, 8, 4, 2, 2, 2, 2, 2, 2];
+pub(crate) const ELF32_PROGRAM_HEADER_FIELDS: &[usize] = &[4; 8];
+pub(crate) const ELF64_PROGRAM_HEADER_FIELDS: &[usize] = &[4, 4, 8, 8, 8, 8, 8, 8];
+pub(crate) const ELF32_SECTION_HEADER_FIELDS: &[usize] = &[4; 10];
+pub(crate) const ELF64_SECTION_HEADER_FIELDS: &[usize] = &[4, 4, 8, 8, 8, 8, 4, 4, 8, 8];
+pub(crate) const ELF32_SYMBOL_FIELDS: &[usize] = &[4, 4, 4, 1, 1, 2];
+pub(crate) const ELF64_SYMBOL_FIELDS: &[usize] = &[4, 1, 1, 2, 8, 8];
+pub(crate) const ELF_IDENT_SIZE: usize = 16;
+
+// Reverses the byte order of each field in consecutive records.
+pub(crate) fn swap_fields(b: &mut [u8], fields: &[usize]) {
+    let size = fields.iter().sum();
+    for r in b.chunks_exact_mut(size) {
+        let mut o = 0;
+        for w in fields {
+            r[o..o + w].reverse();
+            o += w;
+        }
+    }
+}
+
+// The structures hold numbers in the byte order of the host; their bytes
+// in little endian, with the fields given, are the same on any host.
+fn le_bytes(native: &[u8], fields: &[usize]) -> Vec<u8> {
+    let mut b = native.to_vec();
+    if cfg!(target_endian = "big") {
+        swap_fields(&mut b, fields);
+    }
+    b
+}
+
+pub const AOUT_HEADER_SIZE: usize = std::mem::size_of::<Aout>();
+
+pub const ELF32_HEADER_SIZE: usize = std::mem::size_of::<Elf32Header>();
+pub const ELF64_HEADER_SIZE: usize = std::mem::size_of::<Elf64Header>();
+
+pub const ELF32_PROGRAM_HEADER_SIZE: usize = std::mem::size_of::<Elf32ProgramHeader>();
+pub const ELF64_PROGRAM_HEADER_SIZE: usize = std::mem::size_of::<Elf64ProgramHeader>();
+
+pub const ELF32_SECTION_HEADER_SIZE: usize = std::mem::size_of::<Elf32SectionHeader>();
+pub const ELF64_SECTION_HEADER_SIZE: usize = std::mem::size_of::<Elf64SectionHeader>();
+
+pub const ELF32_SYMBOL_TABLE_ENTRY_SIZE: usize = std::mem::size_of::<Elf32SymbolTableEntry>();
+pub const ELF64_SYMBOL_TABLE_ENTRY_SIZE: usize = std::mem::size_of::<Elf64SymbolTableEntry>();
+
+// https://www.gnu.org/software/grub/manual/multiboot/multiboot.html
+pub(crate) const MULTIBOOT_HEADER_SIZE: usize = 0x48;
+
+// TODO: Multiboot struct
+
+pub(crate) const PAD_BASIC_SIZE: usize = 4;
+pub const PAD_EXTRA_SIZE: usize = 8;
+pub(crate) const PAD_SIZE: usize = PAD_BASIC_SIZE + PAD_EXTRA_SIZE;
+
+/// ELF machine for the architecture in the header.
+pub fn aout_mach_to_elf(aout: &Aout) -> Result<ElfMachine, Error> {
+    let m = aout.magic;
+    match MachineArch::from_magic(m) {
+        MachineArch::Unknown => Err(Error::BadMagic(m)),
+        arch => Ok(arch.elf_machine()),
+    }
+}
+
+// 🧝✨
+pub const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+
+// SHN_ABS, the section index of absolute symbols
+pub const SECTION_ABS: u16 = 0xfff1;
+
+// sys/man/6/a.out
+pub const SYM_TEXT: u8 = b'T';
+pub const SYM_STATIC_TEXT: u8 = b't';
+pub const SYM_LEAF_FN: u8 = b'L';
+pub const SYM_STATIC_LEAF_FN: u8 = b'l';
+pub const SYM_DATA: u8 = b'D';
+pub const SYM_STATIC_DATA: u8 = b'd';
+pub const SYM_BSS_SEGMENT: u8 = b'B';
+pub const SYM_STATIC_BSS_SEGMENT: u8 = b'b';
+pub const SYM_AUTO_VAR: u8 = b'a';
+pub const SYM_FN_PARAM: u8 = b'p';
+pub const SYM_FRAME_SYMBOL: u8 = b'm';
+pub const SYM_SRC_COMP: u8 = b'f';
+pub const SYM_SRC_FILE: u8 = b'z';
+pub const SYM_SRC_OFFSET: u8 = b'Z';
+pub const SYM_E: u8 = b'e';
+pub const SYM_G: u8 = b'g';
+pub const SYM_I: u8 = b'I';
+pub const SYM_O: u8 = b'o';
+pub const SYM_S: u8 = b'S';
+pub const SYM_U: u8 = b'u';
+pub const SYM_V: u8 = b'v';
+pub const SYM_W: u8 = b'w';
+pub const SYM__: u8 = b'_';
+pub const SYM_0: u8 = b'0';
+pub const SYM_CURLY: u8 = b'{';
+
+/// Kind of a symbol, from its type letter.
+#[derive(Debug, Eq, PartialEq)]
+pub enum AoutSymbolType {
+    TextSegment,
+    StaticTextSegment,
+    LeafFunction,
+    StaticLeafFunction,
+    DataSegment,
+    StaticDataSegment,
+    BssSegment,
+    StaticBssSegment,
+    AutoVariable,
+    FunctionParam,
+    FrameSymbol,
+    SourceFileNameComp,
+    SourceFileName,
+    SourceFileOffset,
+    ____X,
+    Curly,
+    E,
+    G,
+    I,
+    M,
+    O,
+    S,
+    U,
+    V,
+    W,
+    Zero,
+    Unknown,
+}
+
+impl AoutSymbolType {
+    /// Whether the symbol is a function, including leaf functions.
+    pub fn is_text(&self) -> bool {
+        matches!(
+            self,
+            AoutSymbolType::TextSegment
+                | AoutSymbolType::StaticTextSegment
+                | AoutSymbolType::LeafFunction
+                | AoutSymbolType::StaticLeafFunction
+        )
+    }
+}
+
+pub(crate) fn aout_symbol_type(s: &AoutSymbol) -> AoutSymbolType {
+    // First bit needs to be discarded.
+    match s.header.sym_type & !0x80 {
+        SYM_TEXT => AoutSymbolType::TextSegment,
+        SYM_STATIC_TEXT => AoutSymbolType::StaticTextSegment,
+        SYM_LEAF_FN => AoutSymbolType::LeafFunction,
+        SYM_STATIC_LEAF_FN => AoutSymbolType::StaticLeafFunction,
+        SYM_DATA => AoutSymbolType::DataSegment,
+        SYM_STATIC_DATA => AoutSymbolType::StaticDataSegment,
+        SYM_STATIC_BSS_SEGMENT => AoutSymbolType::StaticBssSegment,
+        SYM_BSS_SEGMENT => AoutSymbolType::BssSegment,
+        SYM_AUTO_VAR => AoutSymbolType::AutoVariable,
+        SYM_FN_PARAM => AoutSymbolType::FunctionParam,
+        SYM_FRAME_SYMBOL => AoutSymbolType::FrameSymbol,
+        SYM_SRC_COMP => AoutSymbolType::SourceFileNameComp,
+        SYM_SRC_FILE => AoutSymbolType::SourceFileName,
+        SYM_SRC_OFFSET => AoutSymbolType::SourceFileOffset,
+        SYM_E => AoutSymbolType::E,
+        SYM_G => AoutSymbolType::G,
+        SYM_I => AoutSymbolType::I,
+        SYM_O => AoutSymbolType::O,
+        SYM_S => AoutSymbolType::S,
+        SYM_U => AoutSymbolType::U,
+        SYM_V => AoutSymbolType::V,
+        SYM_W => AoutSymbolType::W,
+        SYM__ => AoutSymbolType::____X,
+        SYM_0 => AoutSymbolType::Zero,
+        SYM_CURLY => AoutSymbolType::Curly,
+        // TODO: What else?
+        _ => AoutSymbolType::Unknown,
+    }
+}
+
+// Absolute symbols defined by the user, e.g. for lookup at runtime. They are
+// global and thus have to follow all local symbols.
+pub(crate) fn defined_syms_to_elf(
+    defines: &[(String, u64)],
+    is_64bit: bool,
+    sym_str_tab: &mut StringTable,
+) -> Result<Vec<ElfSymbolTableEntry>, String> {
+    const SYM_GLOBAL: u8 = 1 << 4;
+
+    let mut elf_sym_tab = vec![];
+    for (name, value) in defines {
+        let name_offset = sym_str_tab.add(name);
+        let e = if is_64bit {
+            ElfSymbolTableEntry::Elf64(Elf64SymbolTableEntry {
+                name_offset,
+                value: *value,
+                size: 0,
+                info: SYM_GLOBAL,
+                other: 0,
+                section_index: SECTION_ABS,
+            })
+        } else {
+            let Ok(v) = u32::try_from(*value) else {
+                return Err(format!(
+                    "value {value:#x} of {name} does not fit in ELF32, ELF64 output is needed"
+                ));
+            };
+            ElfSymbolTableEntry::Elf32(Elf32SymbolTableEntry {
+                name_offset,
+                value: v,
+                size: 0,
+                info: SYM_GLOBAL,
+                other: 0,
+                section_index: SECTION_ABS,
+            })
+        };
+        elf_sym_tab.push(e);
+    }
+    Ok(elf_sym_tab)
+}
+
+// The preferred alignment of a section, lowered to what its address has:
+// headers of the expanded format, e.g. amd64, push text off the boundary.
+fn address_align(addr: u64, preferred: u64) -> u64 {
+    match addr {
+        0 => preferred,
+        _ => preferred.min(1 << addr.trailing_zeros()),
+    }
+}
+
+// Text symbols as address ranges, each extending to the next or text_end.
+fn text_ranges(syms: &[AoutSymbol], text_end: u64) -> Vec<std::ops::Range<u64>> {
+    let mut starts: Vec<u64> = syms
+        .iter()
+        .filter(|s| s.get_type().is_text())
+        // within the 32 bits of the header's addresses, which the virtual
+        // base is added to
+        .map(|s| s.value() & u32::MAX as u64)
+        .collect();
+    starts.sort();
+    let ends = starts.iter().skip(1).copied().chain([text_end]);
+    starts
+        .iter()
+        .zip(ends)
+        .map(|(s, e)| *s..e.max(*s))
+        .collect()
+}
+
+/// Section header indices the converted symbols refer to.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SymbolSections {
+    pub text: u16,
+    pub data: u16,
+    pub bss: u16,
+}
+
+/// ELF symbol table entries and string table for the text, data and BSS
+/// symbols. Each symbol extends to the next one of its kind, the last one
+/// to text_end or data_end, i.e., the end of the BSS.
+pub fn aout_syms_to_elf(
+    aout_syms: Vec<AoutSymbol>,
+    is_64bit: bool,
+    sections: SymbolSections,
+    text_end: u64,
+    data_end: u64,
+    sym_str_tab: &mut StringTable,
+) -> Vec<ElfSymbolTableEntry> {
+    // TODO: enums, ElfInfo struct
+    const SYM_LOCAL: u8 = 0 << 4;
+    const SYM_GLOBAL: u8 = 1 << 4;
+    const SYM_OBJECT: u8 = 1;
+    const SYM_FUNCTION: u8 = 2;
+
+    let sorted = |keep: fn(AoutSymbolType) -> bool| {
+        let mut syms: Vec<&AoutSymbol> = aout_syms.iter().filter(|s| keep(s.get_type())).collect();
+        syms.sort_by_key(|e| e.header.value);
+        syms
+    };
+    let t_syms = sorted(|t| t.is_text());
+    let d_syms = sorted(|t| {
+        matches!(
+            t,
+            AoutSymbolType::DataSegment
+                | AoutSymbolType::StaticDataSegment
+                | AoutSymbolType::BssSegment
+                | AoutSymbolType::StaticBssSegment
+        )
+    });
+
+    let mut elf_sym_tab: Vec<ElfSymbolTableEntry> = vec![];
+
+    // first is the undefined symbol by convention
+    if is_64bit {
+        let e = Elf64SymbolTableEntry {
+            name_offset: 0,
+            value: 0,
+            size: 0,
+            info: 0,
+            other: 0,
+            section_index: 0,
+        };
+        elf_sym_tab.push(ElfSymbolTableEntry::Elf64(e));
+    } else {
+        let e = Elf32SymbolTableEntry {
+            name_offset: 0,
+            value: 0,
+            size: 0,
+            info: 0,
+            other: 0,
+            section_index: 0,
+        };
+        elf_sym_tab.push(ElfSymbolTableEntry::Elf32(e));
+    };
+
+    // https://docs.oracle.com/cd/E23824_01/html/819-0690/chapter6-79797.html
+    // > In executable and shared object files, st_value holds a virtual address.
+    let mut push = |name: &str, value: u32, size: u64, info: u8, section_index: u16| {
+        // static symbols of the same name share the string
+        let name_offset = sym_str_tab.add(name);
+        if is_64bit {
+            let e = Elf64SymbolTableEntry {
+                name_offset,
+                value: value as u64,
+                size,
+                info,
+                other: 0,
+                section_index,
+            };
+            elf_sym_tab.push(ElfSymbolTableEntry::Elf64(e));
+        } else {
+            let e = Elf32SymbolTableEntry {
+                name_offset,
+                value,
+                size: size as u32,
+                info,
+                other: 0,
+                section_index,
+            };
+            elf_sym_tab.push(ElfSymbolTableEntry::Elf32(e));
+        };
+    };
+
+    // upper case letters are for global symbols, lower case for static ones
+    let binding = |s: &AoutSymbol| match s.get_type() {
+        AoutSymbolType::TextSegment
+        | AoutSymbolType::LeafFunction
+        | AoutSymbolType::DataSegment
+        | AoutSymbolType::BssSegment => SYM_GLOBAL,
+        _ => SYM_LOCAL,
+    };
+
+    // each symbol extends to the next or the end
+    let ends = |syms: &[&AoutSymbol], end: u64| {
+        let next = syms
+            .iter()
+            .skip(1)
+            .map(|s| u32::from(s.header.value) as u64);
+        next.chain([end]).collect::<Vec<_>>()
+    };
+
+    let mut entries = vec![];
+    for (s, end) in t_syms.iter().zip(ends(&t_syms, text_end)) {
+        let value: u32 = s.header.value.into();
+        let size = end.saturating_sub(value as u64);
+        entries.push((
+            s.name,
+            value,
+            size,
+            binding(s) | SYM_FUNCTION,
+            sections.text,
+        ));
+    }
+
+    for (s, end) in d_syms.iter().zip(ends(&d_syms, data_end)) {
+        let value: u32 = s.header.value.into();
+        let section_index = match s.get_type() {
+            AoutSymbolType::BssSegment | AoutSymbolType::StaticBssSegment => sections.bss,
+            _ => sections.data,
+        };
+        let size = end.saturating_sub(value as u64);
+        entries.push((s.name, value, size, binding(s) | SYM_OBJECT, section_index));
+    }
+
+    // local symbols have to come first, see sh_info
+    entries.sort_by_key(|(_, _, _, info, _)| *info & SYM_GLOBAL);
+    for (name, value, size, info, section_index) in entries {
+        push(name, value, size, info, section_index);
+    }
+
+    elf_sym_tab
+}
+
+// 386 and arm kernels are linked at KZERO already
+pub(crate) const VIRTUAL_BASE_386: u64 = 0x0000_0000;
+pub(crate) const VIRTUAL_BASE_ARM: u64 = 0x0000_0000;
+pub(crate) const VIRTUAL_BASE_MIPS: u64 = 0x0000_0000;
+pub(crate) const VIRTUAL_BASE_SPARC: u64 = 0x0000_0000;
+pub(crate) const VIRTUAL_BASE_POWER: u64 = 0x0000_0000;
+pub(crate) const VIRTUAL_BASE_68020: u64 = 0x0000_0000;
+// 32-bit kernel addresses are sign-extended, so KZERO is at the top
+pub(crate) const VIRTUAL_BASE_ALPHA: u64 = 0xffff_ffff_0000_0000;
+pub(crate) const KZERO_386: u32 = 0xf000_0000;
+// bcm, i.e., Raspberry Pi
+pub(crate) const KZERO_ARM: u32 = 0x8000_0000;
+// KSEG0, unmapped and cached
+pub(crate) const KZERO_MIPS: u32 = 0x8000_0000;
+pub(crate) const KZERO_ALPHA: u32 = 0x8000_0000;
+// low half of 0xffffffff80000000, as in the 32-bit header
+pub(crate) const KZERO_AMD64: u32 = 0x8000_0000;
+
+/// Whether the ELF output for the machine is ELF64, if it has a Plan 9
+/// target.
+///
+/// ```
+/// use p9aout2elf::{ElfMachine, is_64bit};
+///
+/// assert_eq!(is_64bit(ElfMachine::RiscV), Some(true));
+/// assert_eq!(is_64bit(ElfMachine::X86), Some(false));
+/// assert_eq!(is_64bit(ElfMachine::None), None);
+/// ```
+pub fn is_64bit(machine: ElfMachine) -> Option<bool> {
+    let b = match machine {
+        ElfMachine::Amd64
+        | ElfMachine::X86
+        | ElfMachine::Aarch32
+        | ElfMachine::Mips
+        | ElfMachine::Sparc
+        | ElfMachine::PowerPC
+        | ElfMachine::M68k => false,
+        ElfMachine::RiscV
+        | ElfMachine::Aarch64
+        | ElfMachine::SparcVersion9
+        | ElfMachine::PowerPC64
+        | ElfMachine::DigitalAlpha => true,
+        _ => return None,
+    };
+    Some(b)
+}
+
+/// File format to write the converted image in.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Elf,
+    /// Mach-O executable, amd64 only
+    Macho,
+}
+
+impl OutputFormat {
+    // whether support for this was compiled in
+    pub fn is_available(&self) -> bool {
+        match self {
+            OutputFormat::Elf => true,
+            OutputFormat::Macho => cfg!(feature = "macho"),
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Elf => ".elf",
+            OutputFormat::Macho => ".macho",
+        }
+    }
+}
+
+/// Replacement for the middle dot (·) in symbol names, which some tools
+/// do not expect
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum RenameRunes {
+    #[value(alias = ".")]
+    Dot,
+    #[value(alias = "_")]
+    Underscore,
+}
+
+impl RenameRunes {
+    pub fn replacement(&self) -> char {
+        match self {
+            Self::Dot => '.',
+            Self::Underscore => '_',
+        }
+    }
+}
+
+impl FromStr for RenameRunes {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        <Self as ValueEnum>::from_str(s, true).map_err(|_| format!("unknown replacement: {s}"))
+    }
+}
+
+/// Compression of the .debug_* sections, marked SHF_COMPRESSED
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum DebugCompression {
+    #[default]
+    None,
+    Zlib,
+    Zstd,
+}
+
+impl DebugCompression {
+    // whether support for this was compiled in
+    pub fn is_available(&self) -> bool {
+        match self {
+            DebugCompression::None => true,
+            DebugCompression::Zlib => cfg!(feature = "gzip"),
+            DebugCompression::Zstd => cfg!(feature = "zstd"),
+        }
+    }
+}
+
+impl FromStr for DebugCompression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        <Self as ValueEnum>::from_str(s, true).map_err(|_| format!("unknown compression: {s}"))
+    }
+}
+
+/// What to do with a text or data segment of size 0, e.g. in data-only payloads
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum EmptySegments {
+    /// Leave out the section and its LOAD segment
+    #[default]
+    Omit,
+    /// Emit an empty section and LOAD segment
+    Keep,
+}
+
+/// Conversion defaults for a kind of image
+///
+/// ```
+/// use p9aout2elf::{ElfMachine, Profile};
+///
+/// // 64-bit kernels are linked high, as the expanded header tells
+/// let entry = 0xffff_ffff_8011_0028;
+/// assert_eq!(
+///     Profile::Kernel.virtual_base(ElfMachine::Amd64, entry),
+///     Some(0xffff_ffff_0000_0000)
+/// );
+/// // 9boot runs where it is loaded
+/// assert_eq!(Profile::NineBoot.virtual_base(ElfMachine::Amd64, entry), Some(0));
+/// // and there is no kernel for machines without a Plan 9 target
+/// assert_eq!(Profile::Kernel.virtual_base(ElfMachine::None, entry), None);
+/// assert!(!Profile::NineBoot.keep_symbols());
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum Profile {
+    /// Kernel mapped at its virtual base, with symbols
+    #[default]
+    Kernel,
+    /// 9front second-stage bootloader (9boot) for chain-loading:
+    /// identity-mapped at the load address, without symbols
+    #[value(name = "9boot")]
+    NineBoot,
+}
+
+impl Profile {
+    /// Base to add to the 32-bit addresses of the a.out, if the machine has
+    /// a Plan 9 target; entry64 is the entry point from the expanded header
+    /// of 64-bit targets.
+    pub fn virtual_base(&self, machine: ElfMachine, entry64: u64) -> Option<u64> {
+        let base = match (self, machine) {
+            (Profile::NineBoot, _) => 0,
+            // the expanded header has the high bits of the entry point
+            (
+                Profile::Kernel,
+                ElfMachine::Amd64 | ElfMachine::RiscV | ElfMachine::Aarch64 | ElfMachine::PowerPC64,
+            ) => entry64 & !0xffff_ffff,
+            (Profile::Kernel, ElfMachine::X86) => VIRTUAL_BASE_386,
+            (Profile::Kernel, ElfMachine::Aarch32) => VIRTUAL_BASE_ARM,
+            (Profile::Kernel, ElfMachine::Mips) => VIRTUAL_BASE_MIPS,
+            (Profile::Kernel, ElfMachine::Sparc | ElfMachine::SparcVersion9) => VIRTUAL_BASE_SPARC,
+            (Profile::Kernel, ElfMachine::PowerPC) => VIRTUAL_BASE_POWER,
+            (Profile::Kernel, ElfMachine::M68k) => VIRTUAL_BASE_68020,
+            (Profile::Kernel, ElfMachine::DigitalAlpha) => VIRTUAL_BASE_ALPHA,
+            _ => return None,
+        };
+        Some(base)
+    }
+
+    pub fn keep_symbols(&self) -> bool {
+        *self == Profile::Kernel
+    }
+}
+
+/// Conversion defaults for images of an architecture: its default ones, or
+/// those of a named board or loader.
+///
+/// ```
+/// use p9aout2elf::{ElfClass, MachineArch, TargetProfile};
+///
+/// let t = TargetProfile::named("amd64-qemu").unwrap();
+/// assert_eq!(t.arch, MachineArch::Amd64);
+/// assert_eq!(t.class, Some(ElfClass::Elf32));
+/// assert!(MachineArch::Amd64.profiles().contains(t));
+/// ```
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TargetProfile {
+    pub name: &'static str,
+    pub arch: MachineArch,
+    pub profile: Profile,
+    /// virtual base instead of the one of the profile
+    pub vbase: Option<u64>,
+    /// the data starts at the end of the text rounded up to this
+    pub data_rounding: u64,
+    /// ELF class instead of the one for the machine and virtual base
+    pub class: Option<ElfClass>,
+    pub elf_flags: u32,
+    /// where gdb finds the running image, e.g. the gdb stub of an emulator
+    pub gdb_remote: Option<&'static str>,
+}
+
+// Boards and loaders that need other defaults than the architecture's.
+const NAMED_PROFILES: &[TargetProfile] = &[
+    // started by SBI firmware, e.g. OpenSBI, with the MMU off, so at the
+    // physical addresses the a.out has in its 32 bits
+    TargetProfile {
+        name: "riscv64-sbi",
+        vbase: Some(0),
+        // qemu-system-riscv64 -s
+        gdb_remote: Some(":1234"),
+        ..MachineArch::Riscv64.default_profile()
+    },
+    // qemu-system-x86_64 -kernel loads multiboot kernels from ELF32 only
+    TargetProfile {
+        name: "amd64-qemu",
+        vbase: Some(0),
+        class: Some(ElfClass::Elf32),
+        gdb_remote: Some(":1234"),
+        ..MachineArch::Amd64.default_profile()
+    },
+];
+
+impl TargetProfile {
+    pub fn named(name: &str) -> Option<&'static Self> {
+        NAMED_PROFILES.iter().find(|t| t.name == name)
+    }
+
+    /// Names of the named profiles of all architectures.
+    pub fn names() -> Vec<&'static str> {
+        NAMED_PROFILES.iter().map(|t| t.name).collect()
+    }
+}
+
+/// Options for converting an a.out; the default converts a kernel to ELF.
+#[derive(Clone, Debug, Default)]
+pub struct ConvertOptions {
+    pub empty_segments: EmptySegments,
+    pub profile: Profile,
+    /// copy inputs that are ELF already
+    pub passthrough: bool,
+    pub format: OutputFormat,
+    /// registered backend to write with instead of format
+    pub backend: Option<&'static str>,
+    /// extra absolute symbols
+    pub defines: Vec<(String, u64)>,
+    /// warn about damaged data and symbols instead of failing
+    pub best_effort: bool,
+    /// text and data without contents, and no LOAD segments
+    pub symbols_only: bool,
+    /// leave out the original symbol and pc tables, .plan9.*
+    pub drop_plan9_symtab: bool,
+    /// no symbol, debug or Plan 9 table sections at all
+    pub strip: bool,
+    /// section names in the order to lay them out
+    pub section_order: Vec<String>,
+    /// virtual base to use instead of the profile's
+    pub vbase: Option<u64>,
+    /// entry point to use instead of the a.out's
+    pub entry: Option<u64>,
+    /// architecture to assume instead of the one the magic tells
+    pub machine: Option<MachineArch>,
+    /// named profile to use instead of the default one of the architecture,
+    /// with its own kind of image instead of profile
+    pub target: Option<TargetProfile>,
+    /// ELF class to write instead of the one for the machine
+    pub class: Option<ElfClass>,
+    /// e_flags to write instead of the ones for the architecture
+    pub elf_flags: Option<u32>,
+    /// OS/ABI to write instead of none
+    pub os_abi: Option<ElfOsAbi>,
+    /// replacement for the middle dot in symbol names
+    pub rename_runes: Option<RenameRunes>,
+    /// compression of the debug sections
+    pub compress_debug: DebugCompression,
+    /// describe the address fields of the .plan9.* sections in fixup tables
+    pub plan9_fixups: bool,
+    /// byte order of the words in the .plan9.* sections
+    pub plan9_byte_order: plan9::ByteOrder,
+}
+
+// Parsed and intermediate structures only hold plain data, so conversions
+// may run on several threads at once, each with their own input and sink.
+// Sinks themselves are not Send, since compressors need not be.
+const _: () = {
+    const fn send_sync<T: Send + Sync>() {}
+    send_sync::<Aout>();
+    send_sync::<AoutSymbol>();
+    send_sync::<ConvertOptions>();
+    send_sync::<ElfImage>();
+    send_sync::<SectionEditor>();
+};
+
+/// Converts an a.out image to an ELF file in memory.
+pub fn aout_to_elf(d: &[u8], opts: &ConvertOptions) -> Result<Vec<u8>, Error> {
+    if opts.passthrough && d.starts_with(&ELF_MAGIC) {
+        timings::check_available(d.len() as u64, "the output image")?;
+        let mut out = vec![];
+        aout_to_elf_into(d, opts, &mut out)?;
+        return Ok(out);
+    }
+    let image = aout_to_image(d, opts)?;
+    let mut out = vec![];
+    if opts.format == OutputFormat::Elf && opts.backend.is_none() {
+        let size = image.layout()?.size;
+        timings::check_available(size, "the output image")?;
+        out.reserve_exact(size as usize);
+    }
+    write_image(&image, opts, &mut out)?;
+    Ok(out)
+}
+
+/// Converts an a.out image, writing the output file to w.
+// TODO: Something with the memory sizes is strange.
+pub fn aout_to_elf_into(d: &[u8], opts: &ConvertOptions, w: &mut dyn Write) -> Result<(), Error> {
+    if opts.passthrough && d.starts_with(&ELF_MAGIC) {
+        goblin::elf::Elf::parse(d).map_err(|e| format!("input is not a valid ELF file: {e}"))?;
+        return Ok(w.write_all(d)?);
+    }
+    let image = aout_to_image(d, opts)?;
+    write_image(&image, opts, w)
+}
+
+fn write_image(image: &ElfImage, opts: &ConvertOptions, w: &mut dyn Write) -> Result<(), Error> {
+    if let Some(name) = opts.backend {
+        let b = backend::find(name).ok_or_else(|| format!("no backend {name} registered"))?;
+        return b.write(image, w);
+    }
+    let res = match opts.format {
+        OutputFormat::Elf => image.write(w),
+        #[cfg(feature = "macho")]
+        OutputFormat::Macho => macho::write(image, w),
+        #[allow(unreachable_patterns)]
+        f => Err(format!("{f:?} output is not compiled in")),
+    };
+    Ok(res?)
+}
+
+// 9front builds riscv64 kernels for RV64GC with a 16-byte aligned stack.
+pub(crate) const RISCV_ARCH: &str = "rv64i2p1_m2p0_a2p1_f2p2_d2p2_c2p0_zicsr2p0_zifencei2p0";
+pub(crate) const RISCV_STACK_ALIGN: u8 = 16;
+
+// Contents of .riscv.attributes, see
+// https://github.com/riscv-non-isa/riscv-elf-psabi-doc/blob/master/riscv-elf.adoc#attributes
+pub(crate) fn riscv_attributes() -> Vec<u8> {
+    const FORMAT_VERSION: u8 = b'A';
+    const TAG_FILE: u8 = 1;
+    const TAG_RISCV_STACK_ALIGN: u8 = 4;
+    const TAG_RISCV_ARCH: u8 = 5;
+
+    // values are ULEB128, which is a single byte for small numbers
+    let mut attrs = vec![TAG_RISCV_STACK_ALIGN, RISCV_STACK_ALIGN, TAG_RISCV_ARCH];
+    attrs.extend_from_slice(RISCV_ARCH.as_bytes());
+    attrs.push(0);
+
+    // tag and length, followed by the attributes
+    let mut file = vec![TAG_FILE];
+    file.extend_from_slice(&(5 + attrs.len() as u32).to_le_bytes());
+    file.extend_from_slice(&attrs);
+
+    // length, vendor, followed by the sub-subsection
+    let vendor = b"riscv\0";
+    let mut res = vec![FORMAT_VERSION];
+    res.extend_from_slice(&(4 + (vendor.len() + file.len()) as u32).to_le_bytes());
+    res.extend_from_slice(vendor);
+    res.extend_from_slice(&file);
+    res
+}
+
+// 5c compiles for ARMv4 in ARM state, and floating point is emulated.
+pub(crate) const ARM_CPU_ARCH: u8 = 1;
+
+// Contents of .ARM.attributes, see
+// https://github.com/ARM-software/abi-aa/blob/main/addenda32/addenda32.rst#build-attributes
+pub(crate) fn arm_attributes() -> Vec<u8> {
+    const FORMAT_VERSION: u8 = b'A';
+    const TAG_FILE: u8 = 1;
+    const TAG_CPU_ARCH: u8 = 6;
+    const TAG_ARM_ISA_USE: u8 = 8;
+    const TAG_THUMB_ISA_USE: u8 = 9;
+    const TAG_FP_ARCH: u8 = 10;
+
+    // values are ULEB128, which is a single byte for small numbers
+    let attrs = [
+        TAG_CPU_ARCH,
+        ARM_CPU_ARCH,
+        TAG_ARM_ISA_USE,
+        1,
+        TAG_THUMB_ISA_USE,
+        0,
+        TAG_FP_ARCH,
+        0,
+    ];
+
+    // tag and length, followed by the attributes
+    let mut file = vec![TAG_FILE];
+    file.extend_from_slice(&(5 + attrs.len() as u32).to_le_bytes());
+    file.extend_from_slice(&attrs);
+
+    // length, vendor, followed by the sub-subsection
+    let vendor = b"aeabi\0";
+    let mut res = vec![FORMAT_VERSION];
+    res.extend_from_slice(&(4 + (vendor.len() + file.len()) as u32).to_le_bytes());
+    res.extend_from_slice(vendor);
+    res.extend_from_slice(&file);
+    res
+}
+
+/// Builds the intermediate model from an a.out image, e.g. to inspect or
+/// modify it before writing.
+///
+/// ```
+/// use p9aout2elf::{ConvertOptions, MachineArch, aout_to_image};
+///
+/// // an i386 kernel: header, 0x20 bytes of text, 8 of data, 0x10 of bss
+/// let mut d = vec![];
+/// for v in [MachineArch::I386.magic(), 0x20, 8, 0x10, 0, 0xf010_0020, 0, 0] {
+///     d.extend_from_slice(&u32::to_be_bytes(v));
+/// }
+/// d.extend_from_slice(&[0x90; 0x20]);
+/// d.extend_from_slice(&[1; 8]);
+///
+/// let image = aout_to_image(&d, &ConvertOptions::default()).unwrap();
+/// assert_eq!(image.entry, 0xf010_0020);
+/// let text = image.sections.iter().find(|s| s.name == ".text").unwrap();
+/// assert_eq!((text.addr, text.size()), (0xf010_0020, 0x20));
+/// // data on the page after the text, bss right behind it
+/// let bss = image.sections.iter().find(|s| s.name == ".bss").unwrap();
+/// assert_eq!((bss.addr, bss.size()), (0xf010_1008, 0x10));
+///
+/// // where everything goes in the file
+/// let layout = image.layout().unwrap();
+/// assert_eq!(layout.sections.len(), image.sections.len());
+/// assert!(layout.size > 0x28);
+/// ```
+pub fn aout_to_image<'a>(d: &'a [u8], opts: &ConvertOptions) -> Result<ElfImage<'a>, Error> {
+    // Without this, the ELF header would be taken for an a.out header.
+    if d.starts_with(&ELF_MAGIC) {
+        let e = "input is already an ELF file, use --passthrough to copy it";
+        return Err(Error::Invalid(e.to_string()));
+    }
+    if let Ok((aout, _)) = Aout::read_from_prefix(d) {
+        let f = aout::AoutFile::parse_as(d, opts.machine)?;
+        let arch = f.arch;
+        let machine_target = arch.elf_machine();
+
+        let target = match opts.target {
+            Some(t) if t.arch != arch => {
+                let e = format!("profile {} is for {}, not {arch}", t.name, t.arch);
+                return Err(Error::Invalid(e));
+            }
+            Some(t) => t,
+            None => TargetProfile {
+                profile: opts.profile,
+                ..arch.default_profile()
+            },
+        };
+        let profile = target.profile;
+
+        let entry64 = f.entry64().unwrap_or_default();
+        let virtual_base = match opts.vbase.or(target.vbase) {
+            Some(v) => v,
+            None => profile
+                .virtual_base(machine_target, entry64)
+                .ok_or_else(|| format!("no virtual base for {machine_target}, try --vbase"))?,
+        };
+        // e.g. amd64 kernels are ELF32 for 9boot, but ELF64 at their high
+        // virtual addresses
+        let is_64bit = match opts.class.or(target.class) {
+            Some(ElfClass::Elf64) => true,
+            // addresses are checked to fit when writing the headers
+            Some(ElfClass::Elf32) => false,
+            _ => {
+                let b = is_64bit(machine_target);
+                b.ok_or_else(|| format!("no ELF class for {machine_target}"))?
+                    || virtual_base > u32::MAX as u64
+            }
+        };
+
+        let entry: u32 = aout.entry_point.into();
+
+        // a.out only gives us sizes
+        let ts: u32 = aout.text_size.into();
+        let ds: u32 = aout.data_size.into();
+        let bs: u32 = aout.bss_size.into();
+        let ss: u32 = aout.symbol_table_size.into();
+
+        // so offsets have to be calculated
+        let t_offset = AOUT_HEADER_SIZE + arch.pad_size();
+        let d_offset = t_offset + ts as usize;
+        let s_offset = d_offset + ds as usize;
+
+        let data_load_addr = addrmath::data_address(entry, ts, target.data_rounding);
+        // where a loader without paging has to place the segments
+        let physical = |addr: u64| match arch {
+            MachineArch::I386 => addr & !(KZERO_386 as u64),
+            MachineArch::Arm => addr & !(KZERO_ARM as u64),
+            MachineArch::Mips
+            | MachineArch::Mips4000
+            | MachineArch::Spim
+            | MachineArch::Spim4000 => addr & !(KZERO_MIPS as u64),
+            MachineArch::Alpha => addr & !(KZERO_ALPHA as u64),
+            // 9boot loads the image as linked, without paging
+            MachineArch::Amd64 if virtual_base != 0 => addr & !(KZERO_AMD64 as u64),
+            _ => addr,
+        };
+
+        // Symbols, pc tables and bss all lie within 32 bits or the end of
+        // bss, so rebasing these keeps all addresses within 64 bits.
+        let rebase = |addr: u64| {
+            virtual_base.checked_add(addr).ok_or_else(|| {
+                Error::OutOfRange(format!(
+                    "address {addr:#x} exceeds 64 bits at virtual base {virtual_base:#x}"
+                ))
+            })
+        };
+        let bss_end = data_load_addr + ds as u64 + bs as u64;
+        rebase(bss_end.max(u32::MAX as u64))?;
+        let text_addr = rebase(entry as u64)?;
+        let data_addr = rebase(data_load_addr)?;
+        let bss_addr = rebase(data_load_addr + ds as u64)?;
+
+        // Without the complete text, there is nothing to boot.
+        let Some(text) = d.get(t_offset..d_offset) else {
+            return Err(Error::Truncated("text"));
+        };
+        let data = match d.get(d_offset..s_offset) {
+            Some(data) => data,
+            None if opts.best_effort => {
+                warn!("Data exceeds the file, using what is there");
+                &d[d_offset..]
+            }
+            None => return Err(Error::Truncated("data")),
+        };
+
+        let mut sections = vec![
+            // --- text (code) and data
+            Section {
+                name: ".text".to_string(),
+                name_offset: 0,
+                section_type: ElfSectionType::ProgBits,
+                flags: SH_FLAG_ALLOC | SH_FLAG_EXEC,
+                addr: text_addr,
+                data: Cow::Borrowed(text),
+                nobits_size: 0,
+                link: None,
+                info: 0,
+                addr_align: address_align(text_addr, 64),
+                entry_size: 0,
+            },
+            Section {
+                name: ".data".to_string(),
+                name_offset: 0,
+                section_type: ElfSectionType::ProgBits,
+                flags: SH_FLAG_ALLOC | SH_FLAG_WRITE,
+                addr: data_addr,
+                data: Cow::Borrowed(data),
+                nobits_size: 0,
+                link: None,
+                info: 0,
+                addr_align: address_align(data_addr, 32),
+                entry_size: 0,
+            },
+            // zeroed by the loader, right after the data
+            Section {
+                name: ".bss".to_string(),
+                name_offset: 0,
+                section_type: ElfSectionType::NoBits,
+                flags: SH_FLAG_ALLOC | SH_FLAG_WRITE,
+                addr: bss_addr,
+                data: Cow::Borrowed(&[]),
+                nobits_size: bs as u64,
+                link: None,
+                info: 0,
+                addr_align: address_align(bss_addr, 8),
+                entry_size: 0,
+            },
+        ];
+
+        // production images are usually stripped
+        let stripped = ss == 0;
+        if stripped {
+            info!("No symbol table, the image is stripped");
+        }
+        // with symbols from --define-sym only, there is still a table
+        let keep_symbols =
+            profile.keep_symbols() && !opts.strip && !(stripped && opts.defines.is_empty());
+
+        if opts.symbols_only {
+            if !profile.keep_symbols() {
+                return Err(Error::Invalid(
+                    "profile drops all symbols, nothing to write".to_string(),
+                ));
+            }
+            if opts.strip {
+                return Err(Error::Invalid(
+                    "stripping drops all symbols, nothing to write".to_string(),
+                ));
+            }
+            if !keep_symbols {
+                return Err(Error::Invalid(
+                    "image is stripped, nothing to write".to_string(),
+                ));
+            }
+            if opts.format != OutputFormat::Elf || opts.backend.is_some() {
+                return Err(Error::Invalid(
+                    "symbol containers are only written as ELF".to_string(),
+                ));
+            }
+            // keep addresses and sizes for the debugger, not the contents
+            for s in sections.iter_mut() {
+                s.nobits_size = s.size();
+                s.section_type = ElfSectionType::NoBits;
+                s.data = Cow::Borrowed(&[]);
+            }
+        }
+
+        if opts.empty_segments == EmptySegments::Omit {
+            for s in sections.iter().filter(|s| s.size() == 0) {
+                info!("Omitting empty {} section and segment", s.name);
+            }
+            sections.retain(|s| s.size() > 0);
+        }
+
+        // symbols refer to sections by index
+        let index = |name: &str| {
+            let i = sections.iter().position(|s| s.name == name);
+            i.map(|i| i as u16 + 1)
+        };
+        // with empty data or BSS omitted, their symbols go to the other
+        let (data, bss) = (index(".data"), index(".bss"));
+        let symbol_sections = SymbolSections {
+            text: index(".text").unwrap_or(0),
+            data: data.or(bss).unwrap_or(SECTION_ABS),
+            bss: bss.or(data).unwrap_or(SECTION_ABS),
+        };
+        let text_end = entry as u64 + ts as u64;
+        let data_end = data_load_addr + ds as u64 + bs as u64;
+
+        let mut sym_table_data = match d.get(s_offset..s_offset + ss as usize) {
+            Some(st) => st,
+            None if opts.best_effort => {
+                warn!("Symbol table exceeds the file, using what is there");
+                &d[s_offset.min(d.len())..]
+            }
+            None => return Err(Error::Truncated("symbol table")),
+        };
+        let wide_table = aout::wide_symbols(arch, sym_table_data);
+        let mut wide_table: &[u8] = &wide_table;
+        if let Err((o, e)) = check_aout_symbols(wide_table) {
+            if !opts.best_effort {
+                let e = format!("symbol table is corrupt at {o:#x}: {e}, try --best-effort");
+                return Err(Error::Invalid(e));
+            }
+            warn!("Symbol table is corrupt at {o:#x}: {e}, dropping the rest");
+            wide_table = &wide_table[..o];
+            // offsets only match the original for 64-bit targets
+            if arch.expanded_header() {
+                sym_table_data = &sym_table_data[..o];
+            }
+        }
+        // Symbols take the most memory besides the input, as each is held
+        // several times over. Entries are a header and a NUL at least, and
+        // vectors may grow to twice their length.
+        let most = wide_table.len() / (SYM_HEADER_SIZE + 1);
+        let size = 2 * most * size_of::<AoutSymbol>();
+        timings::check_available(size as u64, "parsing the symbols")?;
+        let syms = timings::stage("parse", || parse_aout_symbols(wide_table, false));
+        // text symbols as functions for source units and frames, in vectors
+        // that may grow to twice their length, then all as ELF entries and
+        // their bytes in .symtab, with the names again in .strtab
+        let text = syms.iter().filter(|s| s.get_type().is_text()).count();
+        let per_function = 2 * size_of::<aout::Function>()
+            + 2 * size_of::<(aout::Function, usize)>()
+            + 2 * size_of::<std::ops::Range<u64>>();
+        let per_symbol = size_of::<ElfSymbolTableEntry>() + ELF64_SYMBOL_TABLE_ENTRY_SIZE;
+        let size = text * per_function + syms.len() * per_symbol + wide_table.len();
+        timings::check_available(size as u64, "converting the symbols")?;
+        // functions for the call frame information
+        let funcs = text_ranges(&syms, text_end);
+        // and source files for the line information
+        let units = aout::source_units(&syms, text_end);
+        let mut sym_str_tab = match opts.rename_runes {
+            Some(r) => StringTable::with_replacement(r.replacement()),
+            None => StringTable::new(),
+        };
+        let mut elf_sym_tab = timings::stage("symbols", || {
+            aout_syms_to_elf(
+                syms,
+                is_64bit,
+                symbol_sections,
+                text_end,
+                data_end,
+                &mut sym_str_tab,
+            )
+        });
+        for (a, b) in sym_str_tab.collisions() {
+            if !opts.best_effort {
+                let e = format!("renaming runes makes {a} and {b} the same, try --best-effort");
+                return Err(Error::Invalid(e));
+            }
+            warn!("Renaming runes makes {a} and {b} the same");
+        }
+        // a.out symbol values are the low 32 bits, like the header's
+        let moved = |value: u64| {
+            let v = value.checked_add(virtual_base);
+            v.ok_or_else(|| {
+                let e = format!("symbol at {value:#x} exceeds 64 bits at {virtual_base:#x}");
+                Error::OutOfRange(e)
+            })
+        };
+        for e in elf_sym_tab.iter_mut().skip(1) {
+            match e {
+                ElfSymbolTableEntry::Elf64(e) => e.value = moved(e.value)?,
+                ElfSymbolTableEntry::Elf32(e) => {
+                    let v = moved(e.value as u64)?;
+                    e.value = u32::try_from(v).map_err(|_| {
+                        let e = format!(
+                            "symbol value {v:#x} does not fit in ELF32, ELF64 output is needed"
+                        );
+                        Error::OutOfRange(e)
+                    })?;
+                }
+            }
+        }
+        // sh_info is the index of the first global symbol
+        let elf_sym_tab_locals = elf_sym_tab
+            .iter()
+            .position(|e| e.info() >> 4 != 0)
+            .unwrap_or(elf_sym_tab.len()) as u32;
+        elf_sym_tab.extend(defined_syms_to_elf(
+            &opts.defines,
+            is_64bit,
+            &mut sym_str_tab,
+        )?);
+        if !opts.defines.is_empty() && !profile.keep_symbols() {
+            warn!("Profile drops all symbols, ignoring defined symbols");
+        } else if !opts.defines.is_empty() && opts.strip {
+            warn!("Stripping drops all symbols, ignoring defined symbols");
+        }
+
+        let elf_sym_tab_entry_size = if is_64bit {
+            ELF64_SYMBOL_TABLE_ENTRY_SIZE
+        } else {
+            ELF32_SYMBOL_TABLE_ENTRY_SIZE
+        };
+        let elf_sym_tab_align = if is_64bit { 8 } else { 4 };
+        let mut stb = Vec::with_capacity(elf_sym_tab.len() * elf_sym_tab_entry_size);
+        for s in elf_sym_tab {
+            stb.extend_from_slice(&s.to_le_bytes());
+        }
+
+        if keep_symbols {
+            // --- symbols and strings
+            sections.extend([
+                Section {
+                    name: ".symtab".to_string(),
+                    name_offset: 0,
+                    section_type: ElfSectionType::SymbolTable,
+                    flags: 0,
+                    addr: 0,
+                    data: Cow::Owned(stb),
+                    nobits_size: 0,
+                    link: Some(".strtab".to_string()),
+                    info: elf_sym_tab_locals,
+                    addr_align: elf_sym_tab_align,
+                    entry_size: elf_sym_tab_entry_size as u64,
+                },
+                Section {
+                    name: ".strtab".to_string(),
+                    name_offset: 0,
+                    section_type: ElfSectionType::SymbolStringTable,
+                    flags: 0,
+                    addr: 0,
+                    data: Cow::Owned(sym_str_tab.into_bytes()),
+                    nobits_size: 0,
+                    link: None,
+                    info: 0,
+                    addr_align: 1,
+                    entry_size: 0,
+                },
+            ]);
+        }
+
+        let sp_table = f.bytes(RegionKind::SpOff).filter(|t| !t.is_empty());
+        let mut cfi = false;
+        if keep_symbols && let Some(t) = sp_table {
+            let offsets = pcline::sp_offsets(t, entry as u64, arch.pc_quantum());
+            let big_endian = arch.big_endian();
+            match dwarf::debug_frame(arch, is_64bit, big_endian, &funcs, &offsets, virtual_base) {
+                Some(d) => {
+                    cfi = true;
+                    sections.push(Section {
+                        name: ".debug_frame".to_string(),
+                        name_offset: 0,
+                        section_type: ElfSectionType::ProgBits,
+                        flags: 0,
+                        addr: 0,
+                        data: Cow::Owned(d),
+                        nobits_size: 0,
+                        link: None,
+                        info: 0,
+                        addr_align: elf_sym_tab_align,
+                        entry_size: 0,
+                    })
+                }
+                None => info!("No call frame information for {arch}"),
+            }
+        }
+
+        // a compile unit per source file, with line programs without rows if
+        // there is no pc/line table
+        let pc_table = f.bytes(RegionKind::PcLine).filter(|t| !t.is_empty());
+        if let Some(t) = pc_table {
+            // a row per byte at most, and then the line programs
+            let size = 2 * t.len() * size_of::<(u64, i64)>() + 4 * t.len();
+            timings::check_available(size as u64, "converting the pc/line table")?;
+        }
+        let lines = pc_table.map_or(vec![], |t| {
+            pcline::lines(t, entry as u64, arch.pc_quantum())
+        });
+        let cus = dwarf::compile_units(&units, &lines, virtual_base);
+        if keep_symbols && !cus.is_empty() {
+            let big_endian = arch.big_endian();
+            let (line, offsets) = dwarf::debug_line(&cus, is_64bit, big_endian);
+            let (info, abbrev) = dwarf::debug_info(&cus, &offsets, arch, cfi, is_64bit);
+            for (name, d) in [
+                (".debug_abbrev", abbrev),
+                (".debug_info", info),
+                (".debug_line", line),
+            ] {
+                sections.push(Section {
+                    name: name.to_string(),
+                    name_offset: 0,
+                    section_type: ElfSectionType::ProgBits,
+                    flags: 0,
+                    addr: 0,
+                    data: Cow::Owned(d),
+                    nobits_size: 0,
+                    link: None,
+                    info: 0,
+                    addr_align: 1,
+                    entry_size: 0,
+                });
+            }
+        }
+
+        if opts.compress_debug != DebugCompression::None {
+            let c = opts.compress_debug;
+            if !c.is_available() {
+                return Err(Error::Invalid(format!(
+                    "{c:?} compression is not compiled in"
+                )));
+            }
+            let big_endian = arch.big_endian();
+            for s in sections
+                .iter_mut()
+                .filter(|s| s.name.starts_with(".debug_"))
+            {
+                let d = dwarf::compress(&s.data, c, s.addr_align, is_64bit, big_endian)
+                    .map_err(|e| format!("compressing {}: {e}", s.name))?;
+                s.data = Cow::Owned(d);
+                s.flags |= SH_FLAG_COMPRESSED;
+                // that of the compression header
+                s.addr_align = if is_64bit { 8 } else { 4 };
+            }
+        }
+
+        if matches!(machine_target, ElfMachine::RiscV) {
+            sections.push(Section {
+                name: ".riscv.attributes".to_string(),
+                name_offset: 0,
+                section_type: ElfSectionType::RiscvAttributes,
+                flags: 0,
+                addr: 0,
+                data: Cow::Owned(riscv_attributes()),
+                nobits_size: 0,
+                link: None,
+                info: 0,
+                addr_align: 1,
+                entry_size: 0,
+            });
+        }
+        if matches!(machine_target, ElfMachine::Aarch32) {
+            sections.push(Section {
+                name: ".ARM.attributes".to_string(),
+                name_offset: 0,
+                section_type: ElfSectionType::ArmAttributes,
+                flags: 0,
+                addr: 0,
+                data: Cow::Owned(arm_attributes()),
+                nobits_size: 0,
+                link: None,
+                info: 0,
+                addr_align: 1,
+                entry_size: 0,
+            });
+        }
+
+        if keep_symbols && !stripped && !opts.drop_plan9_symtab {
+            // retain original symbol, pc/sp and pc/line tables
+            let tables = [
+                (".plan9.symtab", Some(sym_table_data)),
+                (".plan9.pcsp", sp_table),
+                (".plan9.pcline", pc_table),
+            ];
+            let big_endian = arch.big_endian();
+            let order = opts.plan9_byte_order;
+            for (name, data) in tables {
+                let Some(data) = data else {
+                    continue;
+                };
+                let contents = match (order.swaps(), name) {
+                    (false, _) => Cow::Borrowed(data),
+                    (true, ".plan9.symtab") => Cow::Owned(plan9::swap_symbols(arch, data)),
+                    (true, _) => Cow::Owned(plan9::swap_pc_table(data)),
+                };
+                sections.push(Section {
+                    name: name.to_string(),
+                    name_offset: 0,
+                    section_type: ElfSectionType::ProgBits,
+                    flags: 0,
+                    addr: 0,
+                    data: contents,
+                    nobits_size: 0,
+                    link: None,
+                    info: 0,
+                    addr_align: 1,
+                    entry_size: 0,
+                });
+                if !opts.plan9_fixups {
+                    continue;
+                }
+                let fixups = match name {
+                    ".plan9.symtab" => plan9::symbol_fixups(arch, data, order, big_endian),
+                    _ => plan9::pc_table_fixups(order, big_endian),
+                };
+                sections.push(Section {
+                    name: name.replace(".plan9.", ".plan9.fixups."),
+                    name_offset: 0,
+                    section_type: ElfSectionType::ProgBits,
+                    flags: 0,
+                    addr: 0,
+                    data: Cow::Owned(fixups),
+                    nobits_size: 0,
+                    link: Some(name.to_string()),
+                    info: 0,
+                    addr_align: 4,
+                    entry_size: plan9::FIXUP_SIZE,
+                });
+            }
+            if order.swaps() {
+                sections.push(Section {
+                    name: ".note.plan9".to_string(),
+                    name_offset: 0,
+                    section_type: ElfSectionType::Note,
+                    flags: 0,
+                    addr: 0,
+                    data: Cow::Owned(plan9::byte_order_note(order, big_endian)),
+                    nobits_size: 0,
+                    link: None,
+                    info: 0,
+                    addr_align: 4,
+                    entry_size: 0,
+                });
+            }
+        }
+
+        let mut segments = vec![
+            // text segment
+            Segment {
+                program_type: ElfProgramType::Load,
+                flags: PH_FLAG_READ | PH_FLAG_EXEC,
+                virtual_addr: text_addr,
+                physical_addr: physical(entry as u64),
+                align: 4 * 1024,
+                sections: vec![".text".to_string()],
+                data: Cow::Borrowed(&[]),
+            },
+            // data segment
+            Segment {
+                program_type: ElfProgramType::Load,
+                flags: PH_FLAG_READ | PH_FLAG_WRITE,
+                virtual_addr: data_addr,
+                physical_addr: physical(data_load_addr),
+                align: 4 * 1024,
+                // memsz covers the BSS as well
+                sections: vec![".data".to_string(), ".bss".to_string()],
+                data: Cow::Borrowed(&[]),
+            },
+        ];
+        if opts.symbols_only {
+            segments.retain(|seg| !matches!(seg.program_type, ElfProgramType::Load));
+        }
+        // e.g. data without BSS, or the other way around
+        for seg in segments.iter_mut() {
+            seg.sections
+                .retain(|n| sections.iter().any(|s| &s.name == n));
+        }
+        segments.retain(|seg| !seg.sections.is_empty() || !seg.data.is_empty());
+
+        if let Some(e) = opts.entry
+            && !addrmath::contains(text_addr, ts as u64, e, 1)
+        {
+            warn!("Entry point {e:#x} is outside of text");
+        }
+        let mut image = ElfImage {
+            machine: machine_target,
+            is_64bit,
+            big_endian: arch.big_endian(),
+            flags: opts.elf_flags.unwrap_or(target.elf_flags),
+            os_abi: opts.os_abi.unwrap_or(ElfOsAbi::None),
+            entry: opts.entry.unwrap_or(text_addr),
+            sections,
+            segments,
+        };
+        image.assign_names();
+        if !opts.section_order.is_empty() {
+            image.order_sections(&opts.section_order)?;
+        }
+        Ok(image)
+    } else {
+        Err(Error::Truncated("a.out header"))
+    }
+}
+
+/// Names come from untrusted images. Escape control characters and bidi
+/// overrides so they cannot drive the terminal; printable runes stay as-is.
+pub fn printable(s: &str) -> String {
+    let is_bidi = |c: char| matches!(c, '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}');
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c.is_control() || is_bidi(c) {
+            out.extend(c.escape_default());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+impl Display for AoutSymbol<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let t = self.get_type();
+        let sym_type = match t {
+            AoutSymbolType::Unknown => format!("{:02x?}", self.header.sym_type),
+            _ => format!("{t:?}"),
+        };
+        let sym_name = match t {
+            AoutSymbolType::SourceFileName | AoutSymbolType::SourceFileOffset => {
+                let i: Vec<String> = self.file_indices().map(|i| i.to_string()).collect();
+                format!("[{}]", i.join(" "))
+            }
+            _ => printable(self.name),
+        };
+        let v = self.header.value;
+        write!(f, "Symbol {v:08x}: {sym_type:20} {sym_name}")
+    }
+}
+
+impl AoutSymbol<'_> {
+    /// Size of the entry in the symbol table, which is never empty.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        if is_encoded_name(self.header.sym_type) {
+            // leading NUL and the 0 index
+            return SYM_HEADER_SIZE + 1 + self.indices.len() + 2;
+        }
+        SYM_HEADER_SIZE + self.name().len() + 1
+    }
+
+    pub fn get_type(&self) -> AoutSymbolType {
+        aout_symbol_type(self)
+    }
+
+    /// Full value of an entry from a table widened by `wide_symbols`, where
+    /// the spacer holds the high 32 bits, which are 0 on 32-bit targets.
+    pub fn value(&self) -> u64 {
+        (u32::from_be_bytes(self.header.spacer) as u64) << 32 | u64::from(self.header.value)
+    }
+
+    pub fn name(&self) -> String {
+        self.name.to_string()
+    }
+
+    /// Indices of the f symbols a z or Z symbol is made of.
+    pub fn file_indices(&self) -> impl Iterator<Item = u16> + '_ {
+        self.indices
+            .chunks_exact(2)
+            .map(|p| u16::from_be_bytes([p[0], p[1]]))
+    }
+}
+
+pub(crate) const SYM_HEADER_SIZE: usize = 9;
+
+// The names of z and Z symbols are not strings, but a NUL followed by
+// 16-bit big-endian indices of f symbols, up to a 0 index.
+fn is_encoded_name(sym_type: u8) -> bool {
+    matches!(sym_type & !0x80, SYM_SRC_FILE | SYM_SRC_OFFSET)
+}
+
+/// Size of a name in the symbol table including its terminator; None if it
+/// is not terminated.
+pub(crate) fn name_size(sym_type: u8, name: &[u8]) -> Option<usize> {
+    if is_encoded_name(sym_type) {
+        let indices = name.get(1..)?;
+        let n = indices.chunks_exact(2).position(|p| p == [0, 0])?;
+        return Some(1 + 2 * n + 2);
+    }
+    CStr::from_bytes_until_nul(name)
+        .ok()
+        .map(|n| n.count_bytes() + 1)
+}
+
+// None if the entry is cut short
+pub(crate) fn parse_sym(st: &[u8]) -> Option<AoutSymbol<'_>> {
+    let (header, _) = AoutSymbolHeader::read_from_prefix(st).ok()?;
+    let s = &st[SYM_HEADER_SIZE..];
+    if is_encoded_name(header.sym_type) {
+        let size = name_size(header.sym_type, s)?;
+        let indices = &s[1..size - 2];
+        return Some(AoutSymbol {
+            header,
+            name: "",
+            indices,
+        });
+    }
+    // names run up to the NUL, however long they are
+    let namex = CStr::from_bytes_until_nul(s).unwrap_or(c"");
+    let name = namex.to_str().unwrap_or("[noname]");
+
+    Some(AoutSymbol {
+        header,
+        name,
+        indices: &[],
+    })
+}
+
+/// Finds the offset of the first entry that would be misread, e.g. in a
+/// damaged tail.
+pub fn check_aout_symbols(st: &[u8]) -> Result<(), (usize, String)> {
+    let mut offset = 0;
+    while offset < st.len() {
+        let e = &st[offset..];
+        if e.len() <= SYM_HEADER_SIZE {
+            return Err((offset, "truncated entry".to_string()));
+        }
+        if e[SYM_HEADER_SIZE - 1] & 0x80 == 0 {
+            return Err((
+                offset,
+                format!("invalid type {:02x}", e[SYM_HEADER_SIZE - 1]),
+            ));
+        }
+        let sym_type = e[SYM_HEADER_SIZE - 1];
+        let name = &e[SYM_HEADER_SIZE..];
+        let Some(n) = name_size(sym_type, name) else {
+            return Err((offset, "unterminated name".to_string()));
+        };
+        if !is_encoded_name(sym_type) && std::str::from_utf8(&name[..n - 1]).is_err() {
+            return Err((offset, "name is not UTF-8".to_string()));
+        }
+        offset += SYM_HEADER_SIZE + n;
+    }
+    Ok(())
+}
+
+/// Parses a symbol table, which has to pass check_aout_symbols, up to a
+/// truncated entry; dump prints each symbol.
+///
+/// ```
+/// use p9aout2elf::{AoutSymbolType, parse_aout_symbols};
+///
+/// // entries with 8-byte values, as aout::wide_symbols makes them
+/// let mut st = 0xffff_ffff_8011_0000u64.to_be_bytes().to_vec();
+/// st.extend_from_slice(b"\xd4main\0");
+/// let syms = parse_aout_symbols(&st, false);
+/// assert_eq!(syms[0].name, "main");
+/// assert_eq!(syms[0].value(), 0xffff_ffff_8011_0000);
+/// assert_eq!(syms[0].get_type(), AoutSymbolType::TextSegment);
+/// ```
+pub fn parse_aout_symbols(st: &[u8], dump: bool) -> Vec<AoutSymbol<'_>> {
+    let mut syms: Vec<AoutSymbol> = vec![];
+    let mut offset = 0;
+
+    while offset < st.len() {
+        let Some(sym) = parse_sym(&st[offset..]) else {
+            break;
+        };
+        if dump {
+            match sym.get_type() {
+                AoutSymbolType::Unknown => {
+                    let t = sym.header.sym_type;
+                    let v = sym.header.value;
+                    let h = format!("{t:02x?} {v:08x}");
+                    println!(" {offset:08x}: Unknown symbol {h}");
+                }
+                _ => {
+                    println!(" {offset:08x}: {sym}");
+                }
+            }
+        }
+        offset += sym.len();
+        syms.push(sym);
+    }
+
+    syms
+}
+
+/// Architecture of an a.out, as told by its magic.
+///
+/// ```
+/// use p9aout2elf::MachineArch;
+///
+/// let arch: MachineArch = "amd64".parse().unwrap();
+/// // the header holds the magic big endian
+/// assert_eq!(MachineArch::from_magic(arch.magic().to_be()), arch);
+/// assert!(arch.expanded_header());
+/// assert!(!arch.big_endian());
+/// ```
+#[derive(Debug, Eq, PartialEq, Clone, Copy, ValueEnum)]
+pub enum MachineArch {
+    #[value(alias = "x86_64", alias = "x86-64")]
+    Amd64,
+    #[value(alias = "rv64")]
+    Riscv64,
+    #[value(name = "386", alias = "i386")]
+    I386,
+    Arm,
+    Arm64,
+    // mips 3000, big endian
+    Mips,
+    // mips 4000, big endian
+    Mips4000,
+    // mips 3000, little endian
+    Spim,
+    // mips 4000, little endian
+    Spim4000,
+    Sparc,
+    Sparc64,
+    Power,
+    Power64,
+    #[value(name = "68020", alias = "m68k")]
+    M68020,
+    Alpha,
+    #[value(skip)]
+    Unknown,
+}
+
+impl MachineArch {
+    /// Architecture for the magic as read from the header, i.e., in host
+    /// byte order of its big-endian bytes.
+    pub fn from_magic(magic: u32) -> Self {
+        let Some(m) = Magic::decode(u32::from_be(magic)) else {
+            return MachineArch::Unknown;
+        };
+        match (m.arch_id, m.hdr, m.dynamic) {
+            (26, true, false) => MachineArch::Amd64,
+            (30, true, false) => MachineArch::Riscv64,
+            (11, false, false) => MachineArch::I386,
+            (20, false, false) => MachineArch::Arm,
+            (28, true, false) => MachineArch::Arm64,
+            (16, false, false) => MachineArch::Mips,
+            (18, false, false) => MachineArch::Mips4000,
+            (24, false, false) => MachineArch::Spim,
+            (22, false, false) => MachineArch::Spim4000,
+            (13, false, false) => MachineArch::Sparc,
+            (25, false, false) => MachineArch::Sparc64,
+            (21, false, false) => MachineArch::Power,
+            (27, true, false) => MachineArch::Power64,
+            (8, false, false) => MachineArch::M68020,
+            (23, false, false) => MachineArch::Alpha,
+            _ => MachineArch::Unknown,
+        }
+    }
+
+    /// Magic number of the architecture, the inverse of from_magic; the
+    /// header stores it big endian.
+    pub fn magic(&self) -> u32 {
+        let (arch_id, flags) = match self {
+            MachineArch::Amd64 => (26, magic::HDR_MAGIC),
+            MachineArch::Riscv64 => (30, magic::HDR_MAGIC),
+            MachineArch::I386 => (11, 0),
+            MachineArch::Arm => (20, 0),
+            MachineArch::Arm64 => (28, magic::HDR_MAGIC),
+            MachineArch::Mips => (16, 0),
+            MachineArch::Mips4000 => (18, 0),
+            MachineArch::Spim => (24, 0),
+            MachineArch::Spim4000 => (22, 0),
+            MachineArch::Sparc => (13, 0),
+            MachineArch::Sparc64 => (25, 0),
+            MachineArch::Power => (21, 0),
+            MachineArch::Power64 => (27, magic::HDR_MAGIC),
+            MachineArch::M68020 => (8, 0),
+            MachineArch::Alpha => (23, 0),
+            MachineArch::Unknown => return 0,
+        };
+        magic::magic(flags, arch_id)
+    }
+
+    // pc step of the pc/line table, i.e., the minimum instruction size
+    pub fn pc_quantum(&self) -> u64 {
+        match self {
+            MachineArch::Riscv64 | MachineArch::M68020 => 2,
+            MachineArch::Arm | MachineArch::Arm64 => 4,
+            MachineArch::Mips
+            | MachineArch::Mips4000
+            | MachineArch::Spim
+            | MachineArch::Spim4000 => 4,
+            MachineArch::Sparc | MachineArch::Sparc64 => 4,
+            MachineArch::Power | MachineArch::Power64 => 4,
+            MachineArch::Alpha => 4,
+            _ => 1,
+        }
+    }
+
+    /// Whether the header is followed by a 64-bit entry point and symbol
+    /// values have 8 bytes, as for most 64-bit targets (HDR_MAGIC); sparc64
+    /// predates it.
+    pub fn expanded_header(&self) -> bool {
+        matches!(
+            self,
+            MachineArch::Amd64 | MachineArch::Riscv64 | MachineArch::Arm64 | MachineArch::Power64
+        )
+    }
+
+    /// Whether text and data are big endian, and so the ELF output. The
+    /// a.out header and symbol table are big endian on all targets.
+    pub fn big_endian(&self) -> bool {
+        matches!(
+            self,
+            MachineArch::Mips
+                | MachineArch::Mips4000
+                | MachineArch::Sparc
+                | MachineArch::Sparc64
+                | MachineArch::Power
+                | MachineArch::Power64
+                | MachineArch::M68020
+        )
+    }
+
+    /// Size of the 64-bit entry point after the header, if any.
+    pub fn pad_size(&self) -> usize {
+        if self.expanded_header() {
+            PAD_EXTRA_SIZE
+        } else {
+            0
+        }
+    }
+
+    /// Defaults for converting kernels of the architecture.
+    pub const fn default_profile(&self) -> TargetProfile {
+        TargetProfile {
+            name: "kernel",
+            arch: *self,
+            profile: Profile::Kernel,
+            vbase: None,
+            data_rounding: addrmath::PAGE_SIZE,
+            class: None,
+            elf_flags: self.elf_flags(),
+            gdb_remote: None,
+        }
+    }
+
+    /// The default profile and the named ones of the architecture.
+    pub fn profiles(&self) -> Vec<TargetProfile> {
+        let named = NAMED_PROFILES.iter().filter(|t| t.arch == *self);
+        [self.default_profile()]
+            .into_iter()
+            .chain(named.copied())
+            .collect()
+    }
+
+    /// e_flags toolchains expect for code of the architecture.
+    pub const fn elf_flags(&self) -> u32 {
+        // RV64GC with doubles passed in registers (lp64d)
+        const EF_RISCV_RVC: u32 = 0x0001;
+        const EF_RISCV_FLOAT_ABI_DOUBLE: u32 = 0x0004;
+        // mips 3000 is MIPS I, mips 4000 is MIPS III
+        const EF_MIPS_ARCH_1: u32 = 0x0000_0000;
+        const EF_MIPS_ARCH_3: u32 = 0x2000_0000;
+        match self {
+            MachineArch::Riscv64 => EF_RISCV_RVC | EF_RISCV_FLOAT_ABI_DOUBLE,
+            MachineArch::Mips | MachineArch::Spim => EF_MIPS_ARCH_1,
+            MachineArch::Mips4000 | MachineArch::Spim4000 => EF_MIPS_ARCH_3,
+            _ => 0,
+        }
+    }
+
+    pub fn elf_machine(&self) -> ElfMachine {
+        match self {
+            MachineArch::Amd64 => ElfMachine::Amd64,
+            MachineArch::Riscv64 => ElfMachine::RiscV,
+            MachineArch::I386 => ElfMachine::X86,
+            MachineArch::Arm => ElfMachine::Aarch32,
+            MachineArch::Arm64 => ElfMachine::Aarch64,
+            MachineArch::Mips
+            | MachineArch::Mips4000
+            | MachineArch::Spim
+            | MachineArch::Spim4000 => ElfMachine::Mips,
+            MachineArch::Sparc => ElfMachine::Sparc,
+            MachineArch::Sparc64 => ElfMachine::SparcVersion9,
+            MachineArch::Power => ElfMachine::PowerPC,
+            MachineArch::Power64 => ElfMachine::PowerPC64,
+            MachineArch::M68020 => ElfMachine::M68k,
+            MachineArch::Alpha => ElfMachine::DigitalAlpha,
+            MachineArch::Unknown => ElfMachine::None,
+        }
+    }
+}
+
+impl Display for MachineArch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.to_possible_value() {
+            Some(v) => write!(f, "{}", v.get_name()),
+            None => write!(f, "unknown"),
+        }
+    }
+}
+
+// Plan 9 names, or those of ELF machines with a single architecture, e.g.
+// aarch64 for arm64 but not mips.
+impl FromStr for MachineArch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(arch) = <Self as ValueEnum>::from_str(s, true) {
+            return Ok(arch);
+        }
+        let unknown = || format!("unknown architecture: {s}");
+        let m: ElfMachine = s.parse().map_err(|_| unknown())?;
+        let arches: Vec<_> = Self::value_variants()
+            .iter()
+            .filter(|a| **a != MachineArch::Unknown && a.elf_machine() == m)
+            .collect();
+        match arches[..] {
+            [] => Err(unknown()),
+            [a] => Ok(*a),
+            _ => {
+                let names: Vec<_> = arches.iter().map(|a| a.to_string()).collect();
+                Err(format!("{s} is one of {}", names.join(", ")))
+            }
+        }
+    }
+}
@@ -0,0 +1,327 @@
+// Minimal Mach-O MH_EXECUTE writer for the intermediate model, for
+// firmware simulators on macOS hosts that only take Mach-O payloads.
+//
+// The file is laid out as follows:
+// - Mach header
+// - LC_SEGMENT_64 per LOAD segment (__TEXT or __DATA), one section each
+// - LC_SYMTAB
+// - LC_UNIXTHREAD with the entry point
+// - segment contents, each starting on a page boundary
+// - symbol table (nlist_64) and string table, reused from .symtab/.strtab
+//
+// See https://github.com/apple-oss-distributions/xnu/blob/main/EXTERNAL_HEADERS/mach-o/loader.h
+use std::io::Write;
+
+use zerocopy::IntoBytes;
+use zerocopy_derive::{Immutable, IntoBytes};
+
+use crate::addrmath::{PAGE_SIZE, align_up};
+use crate::layout::{ElfImage, PH_FLAG_EXEC, PH_FLAG_READ, PH_FLAG_WRITE};
+use crate::{ElfMachine, ElfProgramType, ElfSectionType};
+
+const MH_MAGIC_64: u32 = 0xfeed_facf;
+const MH_EXECUTE: u32 = 2;
+const MH_NOUNDEFS: u32 = 1;
+
+const CPU_TYPE_X86_64: u32 = 0x0100_0007;
+const CPU_SUBTYPE_X86_64_ALL: u32 = 3;
+
+const LC_SYMTAB: u32 = 0x2;
+const LC_UNIXTHREAD: u32 = 0x5;
+const LC_SEGMENT_64: u32 = 0x19;
+
+const VM_PROT_READ: u32 = 1;
+const VM_PROT_WRITE: u32 = 2;
+const VM_PROT_EXECUTE: u32 = 4;
+
+const S_ATTR_PURE_INSTRUCTIONS: u32 = 0x8000_0000;
+const S_ATTR_SOME_INSTRUCTIONS: u32 = 0x0000_0400;
+const S_ZEROFILL: u32 = 0x1;
+
+const N_SECT: u8 = 0xe;
+
+const X86_THREAD_STATE64: u32 = 4;
+const X86_THREAD_STATE64_COUNT: usize = 42;
+// index of rip in x86_thread_state64_t
+const X86_THREAD_STATE64_RIP: usize = 16;
+
+#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
+#[repr(C, packed)]
+struct MachHeader64 {
+    magic: u32,
+    cpu_type: u32,
+    cpu_subtype: u32,
+    file_type: u32,
+    command_count: u32,
+    commands_size: u32,
+    flags: u32,
+    _res: u32,
+}
+
+#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
+#[repr(C, packed)]
+struct SegmentCommand64 {
+    cmd: u32,
+    cmd_size: u32,
+    name: [u8; 16],
+    vm_addr: u64,
+    vm_size: u64,
+    file_offset: u64,
+    file_size: u64,
+    max_prot: u32,
+    init_prot: u32,
+    section_count: u32,
+    flags: u32,
+}
+
+#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
+#[repr(C, packed)]
+struct Section64 {
+    name: [u8; 16],
+    segment_name: [u8; 16],
+    addr: u64,
+    size: u64,
+    offset: u32,
+    // log2
+    align: u32,
+    reloc_offset: u32,
+    reloc_count: u32,
+    flags: u32,
+    _res: [u32; 3],
+}
+
+#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
+#[repr(C, packed)]
+struct SymtabCommand {
+    cmd: u32,
+    cmd_size: u32,
+    sym_offset: u32,
+    sym_count: u32,
+    str_offset: u32,
+    str_size: u32,
+}
+
+#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
+#[repr(C, packed)]
+struct ThreadCommand {
+    cmd: u32,
+    cmd_size: u32,
+    flavor: u32,
+    count: u32,
+    state: [u64; X86_THREAD_STATE64_COUNT / 2],
+}
+
+#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
+#[repr(C, packed)]
+struct Nlist64 {
+    name_offset: u32,
+    sym_type: u8,
+    section: u8,
+    desc: u16,
+    value: u64,
+}
+
+fn name16(s: &str) -> [u8; 16] {
+    let mut n = [0u8; 16];
+    let b = s.as_bytes();
+    let len = b.len().min(16);
+    n[..len].copy_from_slice(&b[..len]);
+    n
+}
+
+// .text -> __text
+fn section_name(elf_name: &str) -> String {
+    format!("__{}", elf_name.trim_start_matches('.'))
+}
+
+fn protection(flags: u32) -> u32 {
+    let mut p = 0;
+    if flags & PH_FLAG_READ != 0 {
+        p |= VM_PROT_READ;
+    }
+    if flags & PH_FLAG_WRITE != 0 {
+        p |= VM_PROT_WRITE;
+    }
+    if flags & PH_FLAG_EXEC != 0 {
+        p |= VM_PROT_EXECUTE;
+    }
+    p
+}
+
+// (name offset, value) of the ELF symbols, skipping the undefined one
+fn elf_symbols(image: &ElfImage) -> Vec<(u32, u64)> {
+    let Some(s) = image.sections.iter().find(|s| s.name == ".symtab") else {
+        return vec![];
+    };
+    let le32 = |b: &[u8]| u32::from_le_bytes(b[..4].try_into().unwrap());
+    let le64 = |b: &[u8]| u64::from_le_bytes(b[..8].try_into().unwrap());
+    s.data
+        .chunks_exact(s.entry_size as usize)
+        .skip(1)
+        .map(|e| {
+            if image.is_64bit {
+                (le32(e), le64(&e[8..]))
+            } else {
+                (le32(e), le32(&e[4..]) as u64)
+            }
+        })
+        .collect()
+}
+
+pub fn write(image: &ElfImage, w: &mut dyn Write) -> Result<(), String> {
+    let cpu_type = match image.machine {
+        ElfMachine::Amd64 => CPU_TYPE_X86_64,
+        m => return Err(format!("no Mach-O CPU type for {m}")),
+    };
+
+    // one Mach-O segment and section per LOAD segment
+    let mut loads = vec![];
+    for seg in image.segments.iter() {
+        if !matches!(seg.program_type, ElfProgramType::Load) {
+            continue;
+        }
+        for name in seg.sections.iter() {
+            let Some(s) = image.sections.iter().find(|s| &s.name == name) else {
+                return Err(format!("LOAD segment refers to missing section {name}"));
+            };
+            loads.push((seg, s));
+        }
+    }
+
+    let symbols = elf_symbols(image);
+    let str_tab: &[u8] = image
+        .sections
+        .iter()
+        .find(|s| s.name == ".strtab")
+        .map_or(&[], |s| &s.data);
+
+    let segment_cmd_size =
+        std::mem::size_of::<SegmentCommand64>() + std::mem::size_of::<Section64>();
+    let commands_size = loads.len() * segment_cmd_size
+        + std::mem::size_of::<SymtabCommand>()
+        + std::mem::size_of::<ThreadCommand>();
+    let headers_size = std::mem::size_of::<MachHeader64>() + commands_size;
+
+    let mut commands = vec![];
+    let mut chunks: Vec<(u64, &[u8])> = vec![];
+    let mut offset = headers_size as u64;
+    let mut text_ordinal = 0;
+    for (i, (seg, s)) in loads.iter().enumerate() {
+        let vm_addr = s.addr & !(PAGE_SIZE - 1);
+        let file_offset = align_up(offset, PAGE_SIZE).ok_or("segment does not fit")?;
+        let data_offset = file_offset + (s.addr - vm_addr);
+        // bss is zero filled by the loader, not read from the file
+        let zero_fill = matches!(s.section_type, ElfSectionType::NoBits);
+        let file_len = if zero_fill { 0 } else { s.size() };
+        let file_size = data_offset + file_len - file_offset;
+        let exec = seg.flags & PH_FLAG_EXEC != 0;
+        let segment_name = if exec { "__TEXT" } else { "__DATA" };
+        if s.name == ".text" {
+            // section ordinals start at 1
+            text_ordinal = i as u8 + 1;
+        }
+        let prot = protection(seg.flags);
+
+        let sc = SegmentCommand64 {
+            cmd: LC_SEGMENT_64,
+            cmd_size: segment_cmd_size as u32,
+            name: name16(segment_name),
+            vm_addr,
+            vm_size: align_up(data_offset + s.size() - file_offset, PAGE_SIZE)
+                .ok_or("segment does not fit")?,
+            file_offset,
+            file_size,
+            max_prot: prot,
+            init_prot: prot,
+            section_count: 1,
+            flags: 0,
+        };
+        let sect = Section64 {
+            name: name16(&section_name(&s.name)),
+            segment_name: name16(segment_name),
+            addr: s.addr,
+            size: s.size(),
+            offset: if zero_fill { 0 } else { data_offset as u32 },
+            align: s.addr_align.max(1).trailing_zeros(),
+            reloc_offset: 0,
+            reloc_count: 0,
+            flags: if exec {
+                S_ATTR_PURE_INSTRUCTIONS | S_ATTR_SOME_INSTRUCTIONS
+            } else if zero_fill {
+                S_ZEROFILL
+            } else {
+                0
+            },
+            _res: [0; 3],
+        };
+        commands.extend_from_slice(sc.as_bytes());
+        commands.extend_from_slice(sect.as_bytes());
+        chunks.push((data_offset, &s.data));
+        offset = data_offset + file_len;
+    }
+
+    let nlists: Vec<Nlist64> = symbols
+        .iter()
+        .map(|(name_offset, value)| Nlist64 {
+            name_offset: *name_offset,
+            sym_type: N_SECT,
+            section: text_ordinal,
+            desc: 0,
+            value: *value,
+        })
+        .collect();
+    let sym_bytes = nlists.as_bytes();
+    let sym_offset = align_up(offset, 8).ok_or("symbol table does not fit")?;
+    let str_offset = sym_offset + sym_bytes.len() as u64;
+    chunks.push((sym_offset, sym_bytes));
+    chunks.push((str_offset, str_tab));
+
+    let symtab = SymtabCommand {
+        cmd: LC_SYMTAB,
+        cmd_size: std::mem::size_of::<SymtabCommand>() as u32,
+        sym_offset: sym_offset as u32,
+        sym_count: nlists.len() as u32,
+        str_offset: str_offset as u32,
+        str_size: str_tab.len() as u32,
+    };
+    commands.extend_from_slice(symtab.as_bytes());
+
+    let mut state = [0u64; X86_THREAD_STATE64_COUNT / 2];
+    state[X86_THREAD_STATE64_RIP] = loads
+        .iter()
+        .find(|(_, s)| s.name == ".text")
+        .map_or(image.entry, |(_, s)| s.addr);
+    let thread = ThreadCommand {
+        cmd: LC_UNIXTHREAD,
+        cmd_size: std::mem::size_of::<ThreadCommand>() as u32,
+        flavor: X86_THREAD_STATE64,
+        count: X86_THREAD_STATE64_COUNT as u32,
+        state,
+    };
+    commands.extend_from_slice(thread.as_bytes());
+
+    let header = MachHeader64 {
+        magic: MH_MAGIC_64,
+        cpu_type,
+        cpu_subtype: CPU_SUBTYPE_X86_64_ALL,
+        file_type: MH_EXECUTE,
+        command_count: loads.len() as u32 + 2,
+        commands_size: commands_size as u32,
+        flags: MH_NOUNDEFS,
+        _res: 0,
+    };
+
+    let mut headers = header.as_bytes().to_vec();
+    headers.extend_from_slice(&commands);
+
+    let mut offset = 0u64;
+    for (at, b) in [(0, headers.as_slice())].into_iter().chain(chunks) {
+        if at > offset {
+            let pad = vec![0u8; (at - offset) as usize];
+            w.write_all(&pad).map_err(|e| e.to_string())?;
+        }
+        w.write_all(b).map_err(|e| e.to_string())?;
+        offset = at + b.len() as u64;
+    }
+    Ok(())
+}
@@ -0,0 +1,101 @@
+// Plan 9 a.out magic numbers, built by _MAGIC(f, b) = f | ((4*b + 0)*b + 7)
+// from flags f and an architecture number b.
+//
+// See <https://9p.io/magic/man2html/6/a.out> and 9front sys/include/a.out.h
+use std::fmt::Display;
+
+/// Flag for a header followed by a 64-bit entry point.
+pub const HDR_MAGIC: u32 = 0x0000_8000;
+/// Flag for dynamically loadable modules.
+pub const DYN_MAGIC: u32 = 0x8000_0000;
+
+// architecture numbers as listed in a.out.h, including retired ones
+const ARCHS: &[(u32, &str)] = &[
+    (8, "68020"),
+    (11, "intel 386"),
+    (12, "intel 960"),
+    (13, "sparc"),
+    (16, "mips 3000 BE"),
+    (17, "att dsp 3210"),
+    (18, "mips 4000 BE"),
+    (19, "amd 29000"),
+    (20, "arm"),
+    (21, "powerpc"),
+    (22, "mips 4000 LE"),
+    (23, "dec alpha"),
+    (24, "mips 3000 LE"),
+    (25, "sparc64"),
+    (26, "amd64"),
+    (27, "powerpc64"),
+    (28, "arm64"),
+    (30, "riscv64"),
+];
+
+/// Computes a magic number from flags and an architecture number.
+pub const fn magic(flags: u32, arch_id: u32) -> u32 {
+    flags | ((4 * arch_id) * arch_id + 7)
+}
+
+/// A magic number taken apart.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Magic {
+    pub arch_id: u32,
+    pub hdr: bool,
+    pub dynamic: bool,
+}
+
+impl Magic {
+    /// Decodes a magic number, i.e., the header field read as big endian;
+    /// None if it does not follow the formula.
+    pub fn decode(magic: u32) -> Option<Self> {
+        let b = magic & !(HDR_MAGIC | DYN_MAGIC);
+        if b < 7 || !(b - 7).is_multiple_of(4) {
+            return None;
+        }
+        let sq = (b - 7) / 4;
+        let arch_id = sq.isqrt();
+        if arch_id * arch_id != sq {
+            return None;
+        }
+        Some(Self {
+            arch_id,
+            hdr: magic & HDR_MAGIC != 0,
+            dynamic: magic & DYN_MAGIC != 0,
+        })
+    }
+
+    pub fn value(&self) -> u32 {
+        let mut f = 0;
+        if self.hdr {
+            f |= HDR_MAGIC;
+        }
+        if self.dynamic {
+            f |= DYN_MAGIC;
+        }
+        magic(f, self.arch_id)
+    }
+
+    /// Architecture as named in a.out.h, if known.
+    pub fn arch_name(&self) -> Option<&'static str> {
+        ARCHS
+            .iter()
+            .find(|(b, _)| *b == self.arch_id)
+            .map(|(_, n)| *n)
+    }
+}
+
+impl Display for Magic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let flags = match (self.hdr, self.dynamic) {
+            (false, false) => "0",
+            (true, false) => "HDR_MAGIC",
+            (false, true) => "DYN_MAGIC",
+            (true, true) => "HDR_MAGIC|DYN_MAGIC",
+        };
+        write!(f, "_MAGIC({flags}, {})", self.arch_id)?;
+        match self.arch_name() {
+            Some(n) => write!(f, ", {n}"),
+            None => write!(f, ", unknown architecture"),
+        }
+    }
+}
@@ -1,22 +1,197 @@
 #![allow(unused)]
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::ffi::CStr;
 use std::fmt::Display;
-use std::{fs, io::Write};
-
-use clap::{Parser, Subcommand};
-use log::{debug, error, info};
+use std::fs;
+use std::io::{Read, Write};
+use std::ops::Range;
+use std::process::ExitCode;
+use std::str::FromStr;
+
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use log::{debug, error, info, warn};
+use rayon::prelude::*;
+use serde::Serialize;
 use zerocopy::byteorder::big_endian::U32;
 use zerocopy::{FromBytes, IntoBytes};
 use zerocopy_derive::{FromBytes, Immutable, IntoBytes};
 
-#[derive(Debug, Subcommand)]
+mod diagnostics;
+mod hexdiff;
+mod manifest;
+mod naming;
+mod patch;
+mod serve;
+mod sink;
+
+use p9aout2elf::*;
+
+use manifest::Manifest;
+use p9aout2elf::aout::{self, AoutFile, RegionKind, SourceFiles};
+use p9aout2elf::editor::SectionEditor;
+use p9aout2elf::elf2aout::{AoutOptions, elf_to_aout};
+use p9aout2elf::layout::{
+    ElfImage, PH_FLAG_EXEC, PH_FLAG_READ, PH_FLAG_WRITE, SH_FLAG_ALLOC, SH_FLAG_EXEC,
+    SH_FLAG_WRITE, Section, Segment,
+};
+use p9aout2elf::magic::Magic;
+use p9aout2elf::pcline;
+use sink::Compression;
+
+#[global_allocator]
+static ALLOC: timings::CountingAlloc = timings::CountingAlloc;
+
+// Manifests are shared by batch jobs, like the library types are.
+const _: () = {
+    const fn send_sync<T: Send + Sync>() {}
+    send_sync::<Manifest>();
+};
+
+// parsed once per run, so the size of Convert does not matter
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug, Subcommand)]
 enum Command {
-    /// Convert the given a.out file to ELF, appending .elf.
+    /// Convert the given a.out files to ELF, appending .elf; - converts
+    /// stdin to stdout.
     Convert {
-        #[arg(index = 1)]
-        file_name: String,
+        #[arg(index = 1, required = true)]
+        file_names: Vec<String>,
+        /// Launch gdb-multiarch on the converted file
+        #[clap(long)]
+        gdb: bool,
+        /// Target for gdb to connect to, e.g. :1234 or host:port, instead of
+        /// the one of the profile
+        #[clap(long, value_name = "ADDR", requires = "gdb")]
+        gdb_remote: Option<String>,
+        /// Compress the output image
+        #[clap(long, value_enum, default_value_t = Compression::None)]
+        compress: Compression,
+        /// Handling of zero-sized text or data segments
+        #[clap(long, value_enum, default_value_t = EmptySegments::Omit)]
+        empty_segments: EmptySegments,
+        /// Conversion defaults for the kind of image, kernel or 9boot, or
+        /// for a board or loader: riscv64-sbi, amd64-qemu
+        #[clap(long, value_name = "PROFILE", default_value = "kernel", value_parser = parse_profile)]
+        profile: (Profile, Option<&'static TargetProfile>),
+        /// Write to the given file instead of next to the input, - for stdout
+        #[clap(long, short, conflicts_with_all = ["output_template", "split_output", "all"])]
+        output: Option<String>,
+        /// Name the output after a template, e.g. '{dir}/{stem}-{sha256:8}.elf';
+        /// also supports {name} and {arch}, and {{ and }} for braces
+        #[clap(long)]
+        output_template: Option<String>,
+        /// Copy inputs that are already ELF instead of refusing them
+        #[clap(long)]
+        passthrough: bool,
+        /// Output file format
+        #[clap(long, value_enum, default_value_t = OutputFormat::Elf)]
+        format: OutputFormat,
+        /// Write the ELF headers and the payload following them to two
+        /// separate files, e.g. meta.bin,payload.bin
+        #[clap(
+            long,
+            value_delimiter = ',',
+            value_name = "META,PAYLOAD",
+            conflicts_with_all = ["compress", "output_template", "passthrough", "format", "gdb"]
+        )]
+        split_output: Option<Vec<String>>,
+        /// Search the input for a.out images instead of expecting one at
+        /// the start, converting the first one found
+        #[clap(long, conflicts_with_all = ["split_output", "passthrough"])]
+        scan: bool,
+        /// With --scan, convert every image found, naming outputs by arch
+        #[clap(long, requires = "scan", conflicts_with = "gdb")]
+        all: bool,
+        /// Convert all a.out files found in the given directories, in
+        /// parallel, skipping other files
+        #[clap(long, conflicts_with_all = ["output", "gdb"])]
+        recursive: bool,
+        /// Overwrite existing output files
+        #[clap(long, short)]
+        force: bool,
+        #[command(flatten)]
+        region: Box<Region>,
+        /// Print a summary of the resulting ELF, as parse does
+        #[clap(long)]
+        show: bool,
+        /// Read the written ELF back and check it as verify does, failing if
+        /// text or data differ from the a.out or the structure is broken
+        #[clap(long, conflicts_with_all = ["compress", "format", "passthrough", "scan", "split_output", "symbols_only"])]
+        verify: bool,
+        /// Write a JSON map of output byte ranges to their origin in the input
+        #[clap(long, value_name = "FILE", conflicts_with_all = ["format", "passthrough", "scan", "split_output"])]
+        provenance: Option<String>,
+        /// Report what the ELF adds to and drops from the a.out, by size
+        #[clap(long, conflicts_with_all = ["format", "passthrough", "scan", "split_output"])]
+        stats: bool,
+        /// Convert damaged inputs as far as possible, warning about problems
+        #[clap(long)]
+        best_effort: bool,
+        /// Only write symbols with their sections' addresses and no loadable
+        /// content, e.g. for gdb's add-symbol-file
+        #[clap(long, conflicts_with_all = ["format", "passthrough"])]
+        symbols_only: bool,
+        /// Leave out the original Plan 9 symbol and pc tables, which are
+        /// otherwise kept as .plan9.symtab, .plan9.pcsp and .plan9.pcline
+        #[clap(long)]
+        drop_plan9_symtab: bool,
+        /// Leave out the symbol table, debugging information and Plan 9
+        /// tables, e.g. for smaller boot images
+        #[clap(long, conflicts_with = "symbols_only")]
+        strip: bool,
+        /// Order of sections in the file, e.g. .text,*,.shstrtab where *
+        /// stands for all sections not listed
+        #[clap(long, value_delimiter = ',')]
+        section_order: Vec<String>,
+        /// Report time and peak heap usage per conversion stage
+        #[clap(long)]
+        timings: bool,
+        /// Add an absolute symbol, e.g. boot_magic=0x2BADB002; repeatable
+        #[clap(long, value_name = "NAME=ADDR", value_parser = parse_define)]
+        define_sym: Vec<(String, u64)>,
+        /// Virtual base to add to the a.out addresses instead of the
+        /// profile's, e.g. 0xffffffff80000000, or 0 to keep them as linked
+        #[clap(long, value_name = "ADDR", value_parser = parse_number)]
+        vbase: Option<u64>,
+        /// Entry point to write instead of the a.out's, e.g. a trampoline
+        /// within text; segment addresses stay as they are
+        #[clap(long, value_name = "ADDR", value_parser = parse_number)]
+        entry: Option<u64>,
+        /// Architecture to assume instead of the one the magic tells,
+        /// e.g. for a mangled header; Plan 9 names or ELF ones, e.g. aarch64
+        #[clap(long, value_name = "ARCH", value_parser = <MachineArch as FromStr>::from_str)]
+        machine: Option<MachineArch>,
+        /// ELF class to write instead of the one for the architecture; all
+        /// addresses have to fit
+        #[clap(long, conflicts_with = "format")]
+        class: Option<ElfClass>,
+        /// e_flags to write instead of the ones for the architecture, e.g.
+        /// 0x5 for RVC and the double-float ABI on riscv64
+        #[clap(long, value_name = "FLAGS", value_parser = parse_flags, conflicts_with = "format")]
+        elf_flags: Option<u32>,
+        /// OS/ABI to write to the identification, e.g. standalone for
+        /// loaders that refuse none
+        #[clap(long, value_name = "OSABI", conflicts_with = "format")]
+        osabi: Option<ElfOsAbi>,
+        /// Replace the Plan 9 middle dot (·) in symbol names, for tools that
+        /// do not expect it
+        #[clap(long, value_name = "WITH")]
+        rename_runes: Option<RenameRunes>,
+        /// Compress the .debug_* sections, marked SHF_COMPRESSED, e.g. for
+        /// kernels with large line tables
+        #[clap(long, value_enum, default_value_t = DebugCompression::None)]
+        compress_debug_sections: DebugCompression,
+        /// Describe the addresses in the .plan9.* sections in
+        /// .plan9.fixups.* sections, for tools that move text or data later
+        #[clap(long, conflicts_with = "drop_plan9_symtab")]
+        plan9_fixups: bool,
+        /// Byte order of the words in the .plan9.* sections; when swapped,
+        /// .note.plan9 records it
+        #[clap(long, value_enum, value_name = "ORDER", default_value_t = plan9::ByteOrder::Big)]
+        plan9_byte_order: plan9::ByteOrder,
     },
-    /// Only parse the given file.
+    /// Only parse the given file, - for stdin.
     Parse {
         #[arg(index = 1)]
         file_name: String,
@@ -26,1269 +201,2160 @@ enum Command {
         /// Dump symbol table entries and more
         #[clap(long, short)]
         verbose: bool,
+        #[command(flatten)]
+        region: Region,
+        /// Architecture to assume instead of the one the magic tells
+        #[clap(long, value_name = "ARCH", value_parser = <MachineArch as FromStr>::from_str)]
+        machine: Option<MachineArch>,
+        /// Output format; JSON describes a.out files only
+        #[clap(long, value_enum, default_value_t = ParseFormat::Text)]
+        format: ParseFormat,
+    },
+    /// Check that the pc/line table covers all text symbols and nothing else.
+    CheckLines {
+        #[arg(index = 1)]
+        file_name: String,
+    },
+    /// Map a.out addresses to file:line from the pc/line table, like GNU
+    /// addr2line.
+    Addr2line {
+        #[arg(index = 1)]
+        file_name: String,
+        /// Addresses in hex, with or without 0x; read from stdin if none
+        #[arg(index = 2, value_parser = parse_hex)]
+        addresses: Vec<u64>,
+        /// Print the function name before each location
+        #[clap(long, short)]
+        functions: bool,
+    },
+    /// List the symbols of an a.out or converted ELF as Plan 9's nm does:
+    /// address, type letter and name, sorted by name.
+    Nm {
+        #[arg(index = 1)]
+        file_name: String,
+        /// Sort by address
+        #[clap(short = 'n', conflicts_with = "unsorted")]
+        numeric: bool,
+        /// Keep the order of the symbol table
+        #[clap(short = 's')]
+        unsorted: bool,
+    },
+    /// Compare the sizes of two a.out files, e.g. debug and release kernels,
+    /// or the symbols of an a.out or its ELF with another ELF.
+    Compare {
+        #[arg(index = 1)]
+        old: String,
+        #[arg(index = 2, required_unless_present = "against")]
+        new: Option<String>,
+        /// Show per-section and per-symbol size deltas
+        #[clap(long)]
+        sections: bool,
+        /// Show differing bytes as a side-by-side hexdump
+        #[clap(long)]
+        hex: bool,
+        /// Cross-reference symbols with an ELF built otherwise, e.g. by GCC
+        /// from the same tree
+        #[clap(long, value_name = "ELF", requires = "by_name", conflicts_with_all = ["new", "sections", "hex"])]
+        against: Option<String>,
+        /// Match symbols by name
+        #[clap(long, requires = "against")]
+        by_name: bool,
+    },
+    /// Check that an ELF is well-formed: header fields, sections and
+    /// segments within the file, string table and section indices, and the
+    /// entry point. Given the a.out first, also check that the ELF, e.g. from
+    /// an older version of this tool, was derived from it: code and data,
+    /// entry point and symbols.
+    Verify {
+        /// The ELF, or the a.out it was made from, followed by the ELF
+        #[arg(index = 1)]
+        file_name: String,
+        #[arg(index = 2)]
+        elf: Option<String>,
+    },
+    /// Check the environment and, optionally, an input file.
+    Doctor {
+        #[arg(index = 1)]
+        file_name: Option<String>,
+    },
+    /// Convert all jobs listed in a TOML manifest.
+    Batch {
+        #[arg(index = 1)]
+        manifest: String,
+        /// Overwrite existing output files, as force = true in every job
+        #[clap(long, short)]
+        force: bool,
+    },
+    /// Patch bytes in a.out regions or ELF sections, in place.
+    Patch {
+        #[arg(index = 1)]
+        file_name: String,
+        /// Edit such as 'text[0x120..0x124] = u32le(0xdeadbeef)' or
+        /// 'text[main+4..main+8] = u32le(0)'; repeatable
+        #[clap(long = "expr", required = true)]
+        exprs: Vec<patch::Patch>,
+    },
+    /// Drop the symbol and pc tables of an a.out, as 9front's strip does,
+    /// or the symbol, debugging and Plan 9 table sections of a converted ELF.
+    Strip {
+        #[arg(index = 1)]
+        file_name: String,
+        /// Write to the given file instead of in place
+        #[clap(short)]
+        output: Option<String>,
+    },
+    /// Convert a static ELF executable, made of text, data and bss, to a
+    /// Plan 9 a.out, e.g. to boot a kernel linked by GNU or LLVM tools with
+    /// Plan 9 loaders. The text has to start at the entry point.
+    Elf2aout {
+        #[arg(index = 1)]
+        file_name: String,
+        /// Write to the given file instead of the input name without .elf,
+        /// or with .aout appended; - writes to stdout
+        #[clap(short)]
+        output: Option<String>,
+        /// Overwrite an existing output file
+        #[clap(long, short)]
+        force: bool,
+        /// Architecture to write instead of the one the ELF header tells,
+        /// e.g. spim4000
+        #[clap(long, value_name = "ARCH", value_parser = <MachineArch as FromStr>::from_str)]
+        machine: Option<MachineArch>,
+        /// Leave out the symbol table otherwise made from .symtab
+        #[clap(long)]
+        strip: bool,
+    },
+    /// Convert and parse images sent as JSON lines on stdin, answering on
+    /// stdout, until stdin is closed.
+    Serve,
+    /// Replace the contents of a section in a converted ELF file.
+    SetSection {
+        #[arg(index = 1)]
+        file_name: String,
+        /// Name of the section, e.g. .config
+        #[arg(index = 2)]
+        section: String,
+        /// File holding the new section contents
+        #[arg(index = 3)]
+        contents: String,
     },
 }
 
-/// Convert Plan 9 a.out to ELF
-#[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
-struct Cli {
-    /// Command to run
-    #[command(subcommand)]
-    cmd: Command,
+// A kind of image for any architecture, or a named profile of one, which
+// brings its own kind of image.
+fn parse_profile(s: &str) -> Result<(Profile, Option<&'static TargetProfile>), String> {
+    if let Ok(p) = Profile::from_str(s, true) {
+        return Ok((p, None));
+    }
+    match TargetProfile::named(s) {
+        Some(t) => Ok((t.profile, Some(t))),
+        None => Err(format!(
+            "{s}: unknown profile, expected kernel, 9boot or one of {}",
+            TargetProfile::names().join(", ")
+        )),
+    }
 }
 
-// See https://9p.io/magic/man2html/6/a.out
-// and 9front sys/include/a.out.h
-#[derive(FromBytes, Immutable, IntoBytes, Clone, Copy, Debug)]
-#[repr(C, packed)]
-struct Aout {
-    magic: u32,
-    text_size: U32,         /* binary code segment */
-    data_size: U32,         /* initialized data */
-    bss_size: U32,          /* uninitialized data */
-    symbol_table_size: U32, /* symbol table */
-    entry_point: U32,       /* entry point */
-    sp_size: U32,           /* pc/sp offset table */
-    pc_size: U32,           /* pc/line number table */
+fn profile_or_default(
+    v: &Option<String>,
+) -> Result<(Profile, Option<&'static TargetProfile>), String> {
+    Ok(v.as_deref()
+        .map(parse_profile)
+        .transpose()?
+        .unwrap_or_default())
 }
 
-#[derive(FromBytes, Immutable, IntoBytes, Clone, Copy, Debug)]
-#[repr(C, packed)]
-struct AoutSymbolHeader {
-    spacer: [u8; 4],
-    value: U32,
-    sym_type: u8,
+fn parse_define(s: &str) -> Result<(String, u64), String> {
+    let Some((name, value)) = s.split_once('=') else {
+        return Err(format!("{s}: expected NAME=ADDR"));
+    };
+    if name.is_empty() || name.contains('\0') {
+        return Err(format!("{s}: invalid symbol name"));
+    }
+    Ok((name.to_string(), parse_number(value)?))
 }
 
-#[derive(Clone, Debug)]
-struct AoutSymbol<'a> {
-    header: AoutSymbolHeader,
-    name: &'a str,
+fn parse_flags(s: &str) -> Result<u32, String> {
+    let v = parse_number(s)?;
+    u32::try_from(v).map_err(|_| format!("{s}: flags have 32 bits"))
 }
 
-// https://en.wikipedia.org/wiki/Executable_and_Linkable_Format
-// https://gist.github.com/DhavalKapil/2243db1b732b211d0c16fd5d9140ab0b
-
-#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
-#[repr(u16)]
-enum ElfType {
-    None,
-    Relocatable,
-    Executable,
-    SharedObject,
-    Core,
-    LoOS = 0xfe00,
-    HiOS = 0xfeff,
-    LoProc = 0xff00,
-    HiProc = 0xffff,
+/// Part of the input file to operate on, e.g. a kernel inside a flash dump
+#[derive(Args, Clone, Debug)]
+struct Region {
+    /// Start of the image within the input file
+    #[clap(long, value_parser = parse_number)]
+    offset: Option<u64>,
+    /// Length of the image, up to the end of the file by default
+    #[clap(long, value_parser = parse_number)]
+    length: Option<u64>,
 }
 
-#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
-#[repr(u8)]
-enum ElfClass {
-    None,
-    Elf32,
-    Elf64,
+impl Region {
+    fn carve(&self, mut d: Vec<u8>) -> Result<Vec<u8>, String> {
+        let size = d.len() as u64;
+        let start = self.offset.unwrap_or(0);
+        let end = match self.length {
+            Some(l) => start.saturating_add(l),
+            None => size.max(start),
+        };
+        if start > size || end > size {
+            return Err(format!(
+                "region {start:#x}..{end:#x} exceeds the file size {size:#x}"
+            ));
+        }
+        d.truncate(end as usize);
+        d.drain(..start as usize);
+        Ok(d)
+    }
 }
 
-#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
-#[repr(u8)]
-enum ElfDataEncoding {
-    Invalid,
-    LittleEndian,
-    BigEndian,
+const EXIT_CODES: &str = "\
+Exit codes:
+  0  success
+  1  invalid input or options, or some of several files failed
+  2  invalid command line
+  3  reading or writing a file failed
+  4  unsupported architecture
+  5  input truncated or out of range";
+
+// Lets scripts tell failures apart, see EXIT_CODES.
+fn exit_code(e: &Error) -> u8 {
+    match e {
+        Error::Invalid(_) => 1,
+        Error::Io(..) => 3,
+        Error::BadMagic(_) => 4,
+        Error::Truncated(_) | Error::OutOfRange(_) => 5,
+    }
 }
 
-#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
-#[repr(u8)]
-enum ElfOsAbi {
-    None,
-    HpUx,
-    NetBsd,
-    Linux,
-    SunSolaris,
-    Aix,
-    Irix,
-    FreeBsd,
-    Tru64Unix,
-    NovellModesto,
-    OpenBsd,
-    OpenVms,
-    HpNonStopKernel,
+/// Convert Plan 9 a.out to ELF
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None, after_help = EXIT_CODES)]
+struct Cli {
+    /// Print version and capabilities as JSON
+    #[arg(long, exclusive = true)]
+    version_json: bool,
+    /// Refuse to hold more than this many MiB in memory
+    #[arg(long, global = true, value_name = "MB")]
+    max_memory: Option<usize>,
+    /// Write errors, warnings and timings as JSON, also on failure
+    #[arg(long, global = true, value_name = "FILE")]
+    diagnostics: Option<String>,
+    /// Command to run
+    #[command(subcommand)]
+    cmd: Option<Command>,
 }
 
-// https://refspecs.linuxfoundation.org/elf/gabi4+/ch4.eheader.html
-#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
-#[repr(C, packed)]
-struct ElfId {
-    magic: [u8; 4],
-    class: ElfClass,
-    data_encoding: ElfDataEncoding,
-    header_version: u8,
-    os_abi: ElfOsAbi,
-    abi_version: u8,
-    _res: [u8; 7],
+fn value_names<T: ValueEnum>(values: &[T]) -> Vec<String> {
+    values
+        .iter()
+        .filter_map(|v| v.to_possible_value())
+        .map(|p| p.get_name().to_string())
+        .collect()
 }
 
-// NOTE: This is the complete list from Wikipedia as of 2025-06-04.
-// Plan 9 a.out only supports few targets as of now, so we do not need them all.
-#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
-#[repr(u16)]
-enum ElfMachine {
-    None = 0x00,
-    AttWe32100 = 0x01,
-    Sparc = 0x02,
-    X86 = 0x03,
-    M68k = 0x04,
-    M88k = 0x05,
-    IntelMcu = 0x06,
-    Intel80860 = 0x07,
-    Mips = 0x08,
-    IbmSystem370 = 0x09,
-    MipsRs3000LittleEndian = 0x0A,
-    // 0x0B – 0x0E Reserved for future use
-    HpPaRisc = 0x0F,
-    Intel80960 = 0x13,
-    PowerPC = 0x14,
-    PowerPC64 = 0x15,
-    S390 = 0x16,
-    IbmSpuSpc = 0x17,
-    // 0x18 – 0x23 Reserved for future use
-    NecV800 = 0x24,
-    FujitsuFr20 = 0x25,
-    TrwRh32 = 0x26,
-    MotorolaRce = 0x27,
-    Aarch32 = 0x28,
-    DigitalAlpha = 0x29,
-    SuperH = 0x2A,
-    SparcVersion9 = 0x2B,
-    SiemensTriCoreEmbeddedProcessor = 0x2C,
-    ArgonautRiscCore = 0x2D,
-    HitachiH8300 = 0x2E,
-    HitachiH8300H = 0x2F,
-    HitachiH8S = 0x30,
-    HitachiH8_500 = 0x31,
-    Ia64 = 0x32,
-    StanfordMipsX = 0x33,
-    MotorolaColdFire = 0x34,
-    MotorolaM68Hc12 = 0x35,
-    FujitsuMmaMultimediaAccelerator = 0x36,
-    SiemensPcp = 0x37,
-    SonyNCpuEmbeddedRisc = 0x38,
-    DensoNdr1Microprocessor = 0x39,
-    MotorolaStarCoreProcessor = 0x3A,
-    ToyotaMe16Processor = 0x3B,
-    STMicroelectronicsST100 = 0x3C,
-    AdvancedLogicTinyJEmbeddedProcessor = 0x3D,
-    Amd64 = 0x3E,
-    SonyDsp = 0x3F,
-    DigitalPdp10 = 0x40,
-    DigitalPdp11 = 0x41,
-    SiemensFx66Microcontroller = 0x42,
-    STMicroelectronicsST9Plus8_16bitMicrocontroller = 0x43,
-    STMicroelectronicsST7_8bitMicrocontroller = 0x44,
-    MotorolaMC68HC16Microcontroller = 0x45,
-    MotorolaMC68HC11Microcontroller = 0x46,
-    MotorolaMC68HC08Microcontroller = 0x47,
-    MotorolaMC68HC05Microcontroller = 0x48,
-    SiliconGraphicsSVx = 0x49,
-    STMicroelectronicsST19_8bitMicrocontroller = 0x4A,
-    DigitalVax = 0x4B,
-    Axis32bitEmbeddedProcessor = 0x4C,
-    Infineon32bitEmbeddedProcessor = 0x4D,
-    Element14_64bitDsp = 0x4E,
-    LsiLogic16bitDsp = 0x4F,
-    Tms320C6000Family = 0x8C,
-    McstElbrusE2k = 0xAF,
-    Aarch64 = 0xB7,
-    ZilogZ80 = 0xDC,
-    RiscV = 0xF3,
-    BerkeleyPacketFilter = 0xF7,
-    WDC65C816 = 0x101,
-    LoongArch = 0x102,
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+enum ParseFormat {
+    /// For people
+    #[default]
+    Text,
+    /// For tools, with all symbols
+    Json,
 }
 
-// NOTE: extracted for convenience, not an official thing.
-#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
-#[repr(C, packed)]
-struct ElfExtra {
-    flags: u32,
-    elf_header_size: u16,
-    program_header_entry_size: u16,
-    program_header_entry_count: u16,
-    section_header_entry_size: u16,
-    section_header_entry_count: u16,
-    section_header_index_entry: u16,
+/// Version of the JSON that parse, serve, --version-json and --diagnostics
+/// write. New fields keep it; removing a field or changing its meaning
+/// bumps it.
+const SCHEMA_VERSION: u32 = 1;
+
+// What parse finds in an a.out, for tools.
+#[derive(Serialize, Debug)]
+struct ParseReport {
+    schema_version: u32,
+    magic: u32,
+    /// as Magic describes it, if known
+    magic_name: Option<String>,
+    arch: String,
+    header: HeaderReport,
+    regions: Vec<RegionReport>,
+    /// where the symbol table is corrupt, and the symbols before it
+    symbol_table_error: Option<String>,
+    symbols: Vec<SymbolReport>,
 }
 
-// https://refspecs.linuxfoundation.org/elf/gabi4+/ch4.eheader.html
-#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
-#[repr(C, packed)]
-struct Elf32Header {
-    id: ElfId,
-    elf_type: ElfType,
-    machine: ElfMachine,
-    version: u32,
+#[derive(Serialize, Debug)]
+struct HeaderReport {
+    text_size: u32,
+    data_size: u32,
+    bss_size: u32,
+    symbol_table_size: u32,
     entry: u32,
-    program_header_offset: u32,
-    section_header_offset: u32,
-    extra: ElfExtra,
+    /// from the expanded header of 64-bit targets
+    entry64: Option<u64>,
+    sp_size: u32,
+    pc_size: u32,
 }
 
-// NOTE: only entry point address and program/section header offsets differ.
-#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
-#[repr(C, packed)]
-struct Elf64Header {
-    id: ElfId,
-    elf_type: ElfType,
-    machine: ElfMachine,
-    version: u32,
-    entry: u64,
-    program_header_offset: u64,
-    section_header_offset: u64,
-    extra: ElfExtra,
+#[derive(Serialize, Debug)]
+struct RegionReport {
+    kind: String,
+    offset: usize,
+    len: usize,
 }
 
-#[derive(Immutable, Clone, Copy, Debug)]
-#[repr(C)]
-enum ElfHeader {
-    Elf32(Elf32Header),
-    Elf64(Elf64Header),
+#[derive(Serialize, Debug)]
+struct SymbolReport {
+    value: u64,
+    #[serde(rename = "type")]
+    sym_type: char,
+    name: String,
+    /// file name indices and the path they make, of z and Z symbols
+    #[serde(skip_serializing_if = "Option::is_none")]
+    files: Option<Vec<u16>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
 }
 
-// NOTE: These are fixed by our convention. Be careful with section changes.
-const SYM_STRING_TABLE_INDEX: u32 = 4;
-const SH_STRING_TABLE_INDEX: u32 = 5;
-
-impl ElfId {
-    fn new(class: ElfClass) -> Self {
-        Self {
-            magic: ELF_MAGIC,
-            class,
-            data_encoding: ElfDataEncoding::LittleEndian,
-            header_version: 1, // fixed
-            os_abi: ElfOsAbi::None,
-            abi_version: 0,
-            _res: [0, 0, 0, 0, 0, 0, 0],
-        }
+impl ParseReport {
+    fn new(d: &[u8], machine: Option<MachineArch>) -> Result<Self, Error> {
+        let f = AoutFile::parse_as(d, machine)?;
+        let h = &f.header;
+        let magic = u32::from_be(h.magic);
+        let regions = f.regions().into_iter().map(|r| RegionReport {
+            kind: r.kind.to_string(),
+            offset: r.offset,
+            len: r.len,
+        });
+
+        let st = f.symbol_table().unwrap_or_default();
+        let (st, symbol_table_error) = match check_aout_symbols(&st) {
+            Ok(()) => (&st[..], None),
+            Err((o, e)) => (&st[..o], Some(format!("corrupt at {o:#x}: {e}"))),
+        };
+        let syms = parse_aout_symbols(st, false);
+        let files = SourceFiles::new(&syms);
+        let symbols = syms.iter().map(|s| {
+            let t = s.get_type();
+            let encoded = matches!(
+                t,
+                AoutSymbolType::SourceFileName | AoutSymbolType::SourceFileOffset
+            );
+            SymbolReport {
+                value: s.value(),
+                sym_type: (s.header.sym_type & !0x80) as char,
+                name: s.name.to_string(),
+                files: encoded.then(|| s.file_indices().collect()),
+                path: encoded.then(|| files.path(s)),
+            }
+        });
+        Ok(Self {
+            schema_version: SCHEMA_VERSION,
+            magic,
+            magic_name: Magic::decode(magic).map(|m| m.to_string()),
+            arch: f.arch.to_string(),
+            header: HeaderReport {
+                text_size: h.text_size.into(),
+                data_size: h.data_size.into(),
+                bss_size: h.bss_size.into(),
+                symbol_table_size: h.symbol_table_size.into(),
+                entry: h.entry_point.into(),
+                entry64: f.entry64(),
+                sp_size: h.sp_size.into(),
+                pc_size: h.pc_size.into(),
+            },
+            regions: regions.collect(),
+            symbol_table_error,
+            symbols: symbols.collect(),
+        })
     }
 }
 
-// NOTE: Many things are hardcoded here.
-impl ElfHeader {
-    fn new(
-        program_header_entry_count: usize,
-        section_header_entry_count: usize,
-        entry: u32,
-        machine: ElfMachine,
-    ) -> Self {
-        let is_64bit = is_64bit(machine);
-        let elf_header_size = if is_64bit {
-            ELF64_HEADER_SIZE
-        } else {
-            ELF32_HEADER_SIZE
-        };
-        let elf_program_header_size = if is_64bit {
-            ELF64_PROGRAM_HEADER_SIZE
-        } else {
-            ELF32_PROGRAM_HEADER_SIZE
-        };
-        let elf_section_header_size = if is_64bit {
-            ELF64_SECTION_HEADER_SIZE
-        } else {
-            ELF32_SECTION_HEADER_SIZE
-        };
-
-        let extra = ElfExtra {
-            flags: 0x00,
-            elf_header_size: elf_header_size as u16,
-            program_header_entry_size: elf_program_header_size as u16,
-            program_header_entry_count: program_header_entry_count as u16,
-            section_header_entry_size: elf_section_header_size as u16,
-            section_header_entry_count: section_header_entry_count as u16,
-            section_header_index_entry: SH_STRING_TABLE_INDEX as u16,
-        };
-
-        // NOTE: There are only few entries, so they always fit in u32.
-        let ph_size = (program_header_entry_count * elf_program_header_size) as u32;
-        let ph_offset = elf_header_size as u32;
-        let sh_offset = ph_offset + ph_size;
+// What this build of the tool can do, for orchestration tooling.
+#[derive(Serialize, Debug)]
+struct Capabilities {
+    schema_version: u32,
+    name: &'static str,
+    version: &'static str,
+    architectures: Vec<String>,
+    /// kinds of image, then the named profiles of architectures
+    profiles: Vec<String>,
+    output_formats: Vec<String>,
+    output_classes: Vec<String>,
+    os_abis: Vec<String>,
+    compression: Vec<String>,
+    /// of the .debug_* sections, see --compress-debug-sections
+    debug_compression: Vec<String>,
+    features: Vec<&'static str>,
+}
 
-        match machine {
-            ElfMachine::Amd64 => ElfHeader::Elf32(Elf32Header {
-                id: ElfId::new(ElfClass::Elf32),
-                elf_type: ElfType::Executable,
-                machine,
-                version: 1,
-                entry,
-                program_header_offset: ph_offset,
-                section_header_offset: sh_offset,
-                extra,
-            }),
-            ElfMachine::RiscV => ElfHeader::Elf64(Elf64Header {
-                id: ElfId::new(ElfClass::Elf64),
-                elf_type: ElfType::Executable,
-                machine,
-                version: 1,
-                entry: entry as u64,
-                program_header_offset: ph_offset as u64,
-                section_header_offset: sh_offset as u64,
-                extra,
-            }),
-            _ => todo!("support more targets"),
+impl Capabilities {
+    fn new() -> Self {
+        let available: Vec<Compression> = Compression::value_variants()
+            .iter()
+            .copied()
+            .filter(|c| c.is_available())
+            .collect();
+        let debug: Vec<DebugCompression> = DebugCompression::value_variants()
+            .iter()
+            .copied()
+            .filter(|c| c.is_available())
+            .collect();
+        let mut features = vec![];
+        if cfg!(feature = "gzip") {
+            features.push("gzip");
         }
-    }
-
-    fn as_bytes(&self) -> &[u8] {
-        match self {
-            ElfHeader::Elf32(h) => h.as_bytes(),
-            ElfHeader::Elf64(h) => h.as_bytes(),
+        if cfg!(feature = "zstd") {
+            features.push("zstd");
+        }
+        if cfg!(feature = "macho") {
+            features.push("macho");
+        }
+        let formats: Vec<OutputFormat> = OutputFormat::value_variants()
+            .iter()
+            .copied()
+            .filter(|f| f.is_available())
+            .collect();
+        Self {
+            schema_version: SCHEMA_VERSION,
+            name: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+            architectures: value_names(MachineArch::value_variants()),
+            profiles: value_names(Profile::value_variants())
+                .into_iter()
+                .chain(TargetProfile::names().into_iter().map(str::to_string))
+                .collect(),
+            output_formats: value_names(&formats),
+            output_classes: value_names(ElfClass::value_variants()),
+            os_abis: value_names(ElfOsAbi::value_variants()),
+            compression: value_names(&available),
+            debug_compression: value_names(&debug),
+            features,
         }
     }
 }
 
-#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
-#[repr(u32)]
-enum ElfProgramType {
-    Null,
-    Load,
-    Dynamic,
-    Note,
-    Interpreted,
-    ProgramHeader,
+fn is_text_symbol(t: &AoutSymbolType) -> bool {
+    matches!(
+        t,
+        AoutSymbolType::TextSegment
+            | AoutSymbolType::StaticTextSegment
+            | AoutSymbolType::LeafFunction
+            | AoutSymbolType::StaticLeafFunction
+    )
 }
 
-// https://refspecs.linuxfoundation.org/elf/gabi4+/ch5.pheader.html
-#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
-#[repr(C, packed)]
-struct Elf32ProgramHeader {
-    program_type: ElfProgramType,
-    offset: u32,
-    virtual_addr: u32,
-    physical_addr: u32,
-    file_size: u32,
-    memory_size: u32,
-    flags: u32,
-    align: u32,
+fn is_data_symbol(t: &AoutSymbolType) -> bool {
+    matches!(
+        t,
+        AoutSymbolType::DataSegment
+            | AoutSymbolType::StaticDataSegment
+            | AoutSymbolType::BssSegment
+            | AoutSymbolType::StaticBssSegment
+    )
 }
 
-#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
-#[repr(C, packed)]
-struct Elf64ProgramHeader {
-    program_type: ElfProgramType,
-    flags: u32,
-    offset: u64,
-    virtual_addr: u64,
-    physical_addr: u64,
-    file_size: u64,
-    memory_size: u64,
-    align: u64,
+// Symbols of one kind sorted by address, each extending to the next or the end.
+fn symbol_ranges<'a>(
+    syms: &[AoutSymbol<'a>],
+    keep: fn(&AoutSymbolType) -> bool,
+    end: u64,
+) -> Vec<(&'a str, Range<u64>)> {
+    let mut starts: Vec<(&str, u64)> = syms
+        .iter()
+        .filter(|s| keep(&s.get_type()))
+        .map(|s| (s.name, s.value()))
+        .collect();
+    starts.sort_by_key(|(_, a)| *a);
+    let ends: Vec<u64> = starts
+        .iter()
+        .skip(1)
+        .map(|(_, a)| *a)
+        .chain([end])
+        .collect();
+    starts
+        .into_iter()
+        .zip(ends)
+        .map(|((n, s), e)| (n, s..e.max(s)))
+        .collect()
 }
 
-#[derive(Immutable, Clone, Copy, Debug)]
-#[repr(C)]
-enum ElfProgramHeader {
-    Elf32(Elf32ProgramHeader),
-    Elf64(Elf64ProgramHeader),
+// Addresses as GNU addr2line takes them, always in hex.
+fn parse_hex(s: &str) -> Result<u64, String> {
+    let h = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X"));
+    u64::from_str_radix(h.unwrap_or(s), 16).map_err(|e| format!("bad address {s}: {e}"))
 }
 
-impl ElfProgramHeader {
-    fn as_bytes(&self) -> &[u8] {
-        match self {
-            ElfProgramHeader::Elf32(h) => h.as_bytes(),
-            ElfProgramHeader::Elf64(h) => h.as_bytes(),
+// Prints file:line for each address, and the function before it if asked,
+// with ?? for what is unknown. Symbols are grouped by file: the z symbols
+// of a file's include history precede its text symbols. Line offsets from
+// Z symbols are not applied.
+fn addr2line(d: &[u8], addrs: &[u64], functions: bool) -> Result<(), String> {
+    let f = AoutFile::parse(d)?;
+    let arch = f.arch;
+    let ts: u32 = f.header.text_size.into();
+    let entry: u32 = f.header.entry_point.into();
+
+    let (Some(st), Some(pt)) = (f.symbol_table(), f.bytes(RegionKind::PcLine)) else {
+        return Err("symbol or pc/line table exceeds the file".to_string());
+    };
+    if pt.is_empty() {
+        return Err("no pc/line table".to_string());
+    }
+
+    let syms = parse_aout_symbols(&st, false);
+    let text = entry as u64..entry as u64 + ts as u64;
+    let units = aout::source_units(&syms, text.end);
+    // range, name and unit of each text symbol
+    let mut funcs: Vec<(&Range<u64>, &str, usize)> = units
+        .iter()
+        .enumerate()
+        .flat_map(|(u, unit)| unit.funcs.iter().map(move |f| (&f.range, f.name, u)))
+        .collect();
+    funcs.sort_by_key(|(r, _, _)| r.start);
+
+    for &pc in addrs {
+        let i = funcs.partition_point(|(r, _, _)| r.start <= pc);
+        let func = i
+            .checked_sub(1)
+            .map(|i| funcs[i])
+            .filter(|_| text.contains(&pc));
+        if functions {
+            println!(
+                "{}",
+                func.map_or("??".to_string(), |(_, n, _)| printable(n))
+            );
         }
+        let location = func
+            .and_then(|(_, _, h)| {
+                let aline = pcline::line(pt, text.start, arch.pc_quantum(), pc)?;
+                pcline::file_line(&units[h].history, aline)
+            })
+            .map(|(path, line)| format!("{}:{line}", printable(path)));
+        println!("{}", location.as_deref().unwrap_or("??:0"));
     }
+    Ok(())
 }
 
-// https://refspecs.linuxfoundation.org/elf/gabi4+/ch4.sheader.html
-#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
-#[repr(u32)]
-enum ElfSectionType {
-    Null,
-    ProgBits,
-    SymbolTable,
-    SymbolStringTable,
-    RelocationEntriesWithAddends,
-    SymbolHashTable,
-    Dynamic,
-    Note,
-    NoBits,
-    Rel,
-    Shlib,
-    DynamicSymbols,
-    // mind the gap
-    InitArray = 14,
-    FiniArray,
-    PreinitArray,
-    Group,
-    SymbolTableIndex,
-    LoOS = 0x60000000,
-    HiOS = 0x6fffffff,
-    LoProc = 0x70000000,
-    HiProc = 0x7fffffff,
-    LoUser = 0x80000000,
-    HiUser = 0xffffffff,
-}
+// value, type letter and name
+type NmSymbol = (u64, char, String);
+
+// Symbols and the width of addresses. ELF symbols get the letter of the
+// a.out symbol they were made from, where T also stands for L, and A for
+// absolute ones, e.g. from --define-sym.
+fn nm_symbols(d: &[u8]) -> Result<(Vec<NmSymbol>, usize), String> {
+    use goblin::elf::section_header::{SHN_ABS, SHT_NOBITS};
+    use goblin::elf::sym::{STB_LOCAL, STT_FUNC, STT_OBJECT};
+
+    if d.starts_with(&ELF_MAGIC) {
+        let elf = goblin::elf::Elf::parse(d).map_err(|e| format!("not an ELF file: {e}"))?;
+        let mut syms = vec![];
+        for s in elf.syms.iter().skip(1) {
+            let Some(name) = elf.strtab.get_at(s.st_name).filter(|n| !n.is_empty()) else {
+                continue;
+            };
+            let section = elf.section_headers.get(s.st_shndx);
+            let t = match s.st_type() {
+                STT_FUNC => 'T',
+                STT_OBJECT if section.is_some_and(|sh| sh.sh_type == SHT_NOBITS) => 'B',
+                STT_OBJECT => 'D',
+                _ if s.st_shndx == SHN_ABS as usize => 'A',
+                _ => continue,
+            };
+            let t = match s.st_bind() {
+                STB_LOCAL if t != 'A' => t.to_ascii_lowercase(),
+                _ => t,
+            };
+            syms.push((s.st_value, t, name.to_string()));
+        }
+        let width = if elf.is_64 { 16 } else { 8 };
+        return Ok((syms, width));
+    }
 
-// https://refspecs.linuxfoundation.org/elf/gabi4+/ch4.sheader.html
-#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
-#[repr(C, packed)]
-struct Elf32SectionHeader {
-    name: u32,
-    section_type: ElfSectionType,
-    flags: u32,
-    addr: u32,
-    offset: u32,
-    size: u32,
-    link: u32,
-    info: u32,
-    addr_align: u32,
-    entry_size: u32,
+    let f = AoutFile::parse(d)?;
+    let st = f
+        .symbol_table()
+        .ok_or("symbol table exceeds the file".to_string())?;
+    let st = match check_aout_symbols(&st) {
+        Ok(()) => &st[..],
+        Err((o, e)) => {
+            warn!("Symbol table is corrupt at {o:#x}: {e}, listing the symbols before");
+            &st[..o]
+        }
+    };
+    let syms = parse_aout_symbols(st, false)
+        .iter()
+        // frame symbols and source files
+        .filter(|s| !b"apmfzZ".contains(&(s.header.sym_type & !0x80)))
+        .map(|s| {
+            let t = (s.header.sym_type & !0x80) as char;
+            (s.value(), t, s.name.to_string())
+        })
+        .collect();
+    let width = if f.arch.expanded_header() { 16 } else { 8 };
+    Ok((syms, width))
 }
 
-#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
-#[repr(C, packed)]
-struct Elf64SectionHeader {
-    name: u32,
-    section_type: ElfSectionType,
-    flags: u64,
-    addr: u64,
-    offset: u64,
-    size: u64,
-    link: u32,
-    info: u32,
-    addr_align: u64,
-    entry_size: u64,
+fn nm(d: &[u8], numeric: bool, unsorted: bool) -> Result<(), String> {
+    let (mut syms, width) = nm_symbols(d)?;
+    // the compilers' own, e.g. .string and $f64.3ff0000000000000
+    let internal = |n: &str| n.starts_with('.') || n.starts_with('$');
+    syms.retain(|(_, t, n)| !("TLDBtldb".contains(*t) && internal(n)));
+    if numeric {
+        syms.sort_by(|a, b| (a.0, &a.2).cmp(&(b.0, &b.2)));
+    } else if !unsorted {
+        syms.sort_by(|a, b| a.2.cmp(&b.2));
+    }
+    let mut out = std::io::stdout().lock();
+    for (value, t, name) in syms {
+        let line = format!("{value:width$x} {t} {}", printable(&name));
+        if writeln!(out, "{line}").is_err() {
+            break;
+        }
+    }
+    Ok(())
 }
 
-#[derive(Immutable, Clone, Copy, Debug)]
-#[repr(C)]
-enum ElfSectionHeader {
-    Elf32(Elf32SectionHeader),
-    Elf64(Elf64SectionHeader),
+// Source files as the z symbols give them, with the line each starts at
+// in the include history; empty ones end an included file.
+fn print_source_files(syms: &[AoutSymbol]) {
+    let files = SourceFiles::new(syms);
+    let z = syms
+        .iter()
+        .filter(|s| s.get_type() == AoutSymbolType::SourceFileName);
+    let paths: Vec<(u32, String)> = z
+        .map(|s| (s.header.value.into(), files.path(s)))
+        .filter(|(_, p)| !p.is_empty())
+        .collect();
+    if paths.is_empty() {
+        return;
+    }
+    println!();
+    println!("Source files:");
+    for (line, path) in paths {
+        println!(" {line:>8} {}", printable(&path));
+    }
 }
 
-impl ElfSectionHeader {
-    fn as_bytes(&self) -> &[u8] {
-        match self {
-            ElfSectionHeader::Elf32(h) => h.as_bytes(),
-            ElfSectionHeader::Elf64(h) => h.as_bytes(),
-        }
+fn check_lines(d: &[u8]) -> Result<(), String> {
+    let f = AoutFile::parse(d)?;
+    let arch = f.arch;
+    let ts: u32 = f.header.text_size.into();
+    // as linked, like the symbol values
+    let entry = f
+        .entry64()
+        .unwrap_or(u32::from(f.header.entry_point).into());
+
+    let (Some(st), Some(pt)) = (f.symbol_table(), f.bytes(RegionKind::PcLine)) else {
+        return Err("symbol or pc/line table exceeds the file".to_string());
+    };
+    if pt.is_empty() {
+        return Err("no pc/line table".to_string());
     }
-}
 
-// `man elf`
-// https://refspecs.linuxfoundation.org/elf/gabi4+/ch4.symtab.html
-#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
-#[repr(C, packed)]
-struct Elf32SymbolTableEntry {
-    name_offset: u32, // offset into string table
-    value: u32,
-    size: u32,
-    info: u8,
-    other: u8,
-    section_index: u16,
-}
+    let syms = parse_aout_symbols(&st, false);
+    let text_end = entry + ts as u64;
+    let ranges = symbol_ranges(&syms, is_text_symbol, text_end);
+    let pcs = pcline::pcs(pt, entry, arch.pc_quantum());
+    let c = pcline::coverage(&pcs, &ranges);
 
-#[derive(Immutable, IntoBytes, Clone, Copy, Debug)]
-#[repr(C, packed)]
-struct Elf64SymbolTableEntry {
-    name_offset: u32, // offset into string table
-    info: u8,
-    other: u8,
-    section_index: u16,
-    value: u64,
-    size: u64,
+    for (name, r) in c.missing.iter() {
+        let name = printable(name);
+        println!("no line info:   {:08x}-{:08x} {name}", r.start, r.end);
+    }
+    for r in c.orphans.iter() {
+        println!("no text symbol: {:08x}-{:08x}", r.start, r.end);
+    }
+    println!(
+        "{} of {} text symbols covered, {} ranges without symbol",
+        ranges.len() - c.missing.len(),
+        ranges.len(),
+        c.orphans.len()
+    );
+    Ok(())
 }
 
-// https://refspecs.linuxfoundation.org/elf/gabi4+/ch4.symtab.html
-#[derive(Immutable, Clone, Copy, Debug)]
-#[repr(C)]
-enum ElfSymbolTableEntry {
-    Elf32(Elf32SymbolTableEntry),
-    Elf64(Elf64SymbolTableEntry),
+// Section and symbol sizes of an a.out, for comparing two builds.
+struct AoutSizes {
+    file: u64,
+    sections: Vec<(&'static str, u64)>,
+    symbols: BTreeMap<String, u64>,
 }
 
-impl ElfSymbolTableEntry {
-    fn as_bytes(&self) -> &[u8] {
-        match self {
-            ElfSymbolTableEntry::Elf32(e) => e.as_bytes(),
-            ElfSymbolTableEntry::Elf64(e) => e.as_bytes(),
-        }
-    }
-}
+fn aout_sizes(d: &[u8]) -> Result<AoutSizes, String> {
+    let f = AoutFile::parse(d)?;
+    let aout = f.header;
 
-const AOUT_HEADER_SIZE: usize = std::mem::size_of::<Aout>();
+    let ts: u32 = aout.text_size.into();
+    let ds: u32 = aout.data_size.into();
+    let bs: u32 = aout.bss_size.into();
+    let ss: u32 = aout.symbol_table_size.into();
+    let sps: u32 = aout.sp_size.into();
+    let pcs: u32 = aout.pc_size.into();
+    let entry: u32 = aout.entry_point.into();
 
-const ELF32_HEADER_SIZE: usize = std::mem::size_of::<Elf32Header>();
-const ELF64_HEADER_SIZE: usize = std::mem::size_of::<Elf64Header>();
+    let Some(st) = f.symbol_table() else {
+        return Err("symbol table exceeds the file".to_string());
+    };
+    let syms = parse_aout_symbols(&st, false);
+
+    // symbol values are as linked, with the high bits of 64-bit targets
+    let high = f.entry64().map_or(0, |e| e & !(u32::MAX as u64));
+    let text_end = high + entry as u64 + ts as u64;
+    let rounding = f.arch.default_profile().data_rounding;
+    let data_end = high + addrmath::data_address(entry, ts, rounding) + ds as u64 + bs as u64;
+    let mut symbols = BTreeMap::new();
+    for (name, r) in symbol_ranges(&syms, is_text_symbol, text_end)
+        .into_iter()
+        .chain(symbol_ranges(&syms, is_data_symbol, data_end))
+    {
+        // static symbols may share a name
+        *symbols.entry(name.to_string()).or_default() += r.end - r.start;
+    }
 
-const ELF32_PROGRAM_HEADER_SIZE: usize = std::mem::size_of::<Elf32ProgramHeader>();
-const ELF64_PROGRAM_HEADER_SIZE: usize = std::mem::size_of::<Elf64ProgramHeader>();
+    Ok(AoutSizes {
+        file: d.len() as u64,
+        sections: vec![
+            (".text", ts as u64),
+            (".data", ds as u64),
+            (".bss", bs as u64),
+            ("symbols", ss as u64),
+            ("pc/sp", sps as u64),
+            ("pc/line", pcs as u64),
+        ],
+        symbols,
+    })
+}
 
-const ELF32_SECTION_HEADER_SIZE: usize = std::mem::size_of::<Elf32SectionHeader>();
-const ELF64_SECTION_HEADER_SIZE: usize = std::mem::size_of::<Elf64SectionHeader>();
+fn print_delta(name: &str, old: Option<u64>, new: Option<u64>) {
+    let delta = new.unwrap_or(0) as i64 - old.unwrap_or(0) as i64;
+    let tag = match (old, new) {
+        (None, _) => " [NEW]",
+        (_, None) => " [DEL]",
+        _ => "",
+    };
+    let old = old.unwrap_or(0);
+    let new = new.unwrap_or(0);
+    println!("{delta:+10} {old:10} -> {new:10}  {name}{tag}");
+}
 
-const ELF32_SYMBOL_TABLE_ENTRY_SIZE: usize = std::mem::size_of::<Elf32SymbolTableEntry>();
-const ELF64_SYMBOL_TABLE_ENTRY_SIZE: usize = std::mem::size_of::<Elf64SymbolTableEntry>();
+fn compare(old: &[u8], new: &[u8], sections: bool) -> Result<(), String> {
+    let o = aout_sizes(old).map_err(|e| format!("old: {e}"))?;
+    let n = aout_sizes(new).map_err(|e| format!("new: {e}"))?;
 
-// https://www.gnu.org/software/grub/manual/multiboot/multiboot.html
-const MULTIBOOT_HEADER_SIZE: usize = 0x48;
+    if sections {
+        println!("Sections:");
+        for ((name, os), (_, ns)) in o.sections.iter().zip(n.sections.iter()) {
+            print_delta(name, Some(*os), Some(*ns));
+        }
+        println!();
+
+        let mut deltas: Vec<(&str, Option<u64>, Option<u64>)> = o
+            .symbols
+            .keys()
+            .chain(n.symbols.keys())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|k| {
+                (
+                    k.as_str(),
+                    o.symbols.get(k).copied(),
+                    n.symbols.get(k).copied(),
+                )
+            })
+            .filter(|(_, os, ns)| os != ns)
+            .collect();
+        deltas.sort_by_key(|(_, os, ns)| {
+            std::cmp::Reverse((ns.unwrap_or(0) as i64 - os.unwrap_or(0) as i64).abs())
+        });
+        println!("Symbols:");
+        for (name, os, ns) in deltas {
+            print_delta(&printable(name), os, ns);
+        }
+        println!();
+    }
 
-// TODO: Multiboot struct
+    print_delta("TOTAL", Some(o.file), Some(n.file));
+    Ok(())
+}
 
-const PAD_BASIC_SIZE: usize = 4;
-const PAD_EXTRA_SIZE: usize = 8;
-const PAD_SIZE: usize = PAD_BASIC_SIZE + PAD_EXTRA_SIZE;
+// Address and size of the named symbols, leaving out those defined more
+// than once, e.g. static functions in several files, which are counted.
+fn elf_symbols_by_name(elf: &goblin::elf::Elf) -> (BTreeMap<String, (u64, u64)>, usize) {
+    use goblin::elf::sym::{STT_FILE, STT_SECTION};
 
-fn aout_mach_to_elf(aout: &Aout) -> ElfMachine {
-    let m = aout.magic;
-    match m {
-        0x978a_0000 => ElfMachine::Amd64,
-        0x178e_0000 => ElfMachine::RiscV,
-        _ => todo!("Architecture not yet supported: {m:08x}"),
+    let mut syms = BTreeMap::new();
+    let mut ambiguous = BTreeSet::new();
+    for s in elf.syms.iter() {
+        if s.st_shndx == 0 || matches!(s.st_type(), STT_FILE | STT_SECTION) {
+            continue;
+        }
+        let Some(name) = elf.strtab.get_at(s.st_name).filter(|n| !n.is_empty()) else {
+            continue;
+        };
+        if syms
+            .insert(name.to_string(), (s.st_value, s.st_size))
+            .is_some()
+        {
+            ambiguous.insert(name.to_string());
+        }
     }
+    for name in ambiguous.iter() {
+        syms.remove(name);
+    }
+    (syms, ambiguous.len())
 }
 
-fn align_4k(v: u32) -> u32 {
-    ((v - 1) / 4096 + 1) * 4096
+// Matches symbols by name, reporting those that differ in address or size.
+// An a.out is converted with the default options first.
+fn cross_reference(ours: &[u8], theirs: &[u8]) -> Result<(), Error> {
+    let ours = if ours.starts_with(&ELF_MAGIC) {
+        Cow::Borrowed(ours)
+    } else {
+        Cow::Owned(aout_to_elf(ours, &ConvertOptions::default())?)
+    };
+    let parse = |d| goblin::elf::Elf::parse(d).map_err(|e| format!("not an ELF file: {e}"));
+    let (o, o_ambiguous) = elf_symbols_by_name(&parse(&ours)?);
+    let (t, t_ambiguous) = elf_symbols_by_name(&parse(theirs)?);
+
+    let mut same = 0;
+    let mut differ = 0;
+    for (name, (oa, os)) in o.iter() {
+        let Some((ta, ts)) = t.get(name) else {
+            debug!("only ours: {}", printable(name));
+            continue;
+        };
+        if (oa, os) == (ta, ts) {
+            same += 1;
+            continue;
+        }
+        differ += 1;
+        let name = printable(name);
+        if oa != ta {
+            println!(
+                "  {name}: address {oa:#x}, theirs {ta:#x} ({:+#x})",
+                *ta as i64 - *oa as i64
+            );
+        }
+        if os != ts {
+            println!("  {name}: size {os:#x}, theirs {ts:#x}");
+        }
+    }
+    for name in t.keys().filter(|n| !o.contains_key(*n)) {
+        debug!("only theirs: {}", printable(name));
+    }
+    let only_ours = o.keys().filter(|n| !t.contains_key(*n)).count();
+    let only_theirs = t.keys().filter(|n| !o.contains_key(*n)).count();
+    println!();
+    println!("{same} symbols match, {differ} differ");
+    println!("{only_ours} only ours, {only_theirs} only theirs");
+    println!("{o_ambiguous} ours and {t_ambiguous} theirs defined more than once, skipped");
+    Ok(())
 }
 
-// 🧝✨
-const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
-
-// sys/man/6/a.out
-const SYM_TEXT: u8 = b'T';
-const SYM_STATIC_TEXT: u8 = b't';
-const SYM_LEAF_FN: u8 = b'L';
-const SYM_STATIC_LEAF_FN: u8 = b'l';
-const SYM_DATA: u8 = b'D';
-const SYM_STATIC_DATA: u8 = b'd';
-const SYM_BSS_SEGMENT: u8 = b'B';
-const SYM_STATIC_BSS_SEGMENT: u8 = b'b';
-const SYM_AUTO_VAR: u8 = b'a';
-const SYM_FN_PARAM: u8 = b'p';
-const SYM_FRAME_SYMBOL: u8 = b'm';
-const SYM_SRC_COMP: u8 = b'f';
-const SYM_SRC_FILE: u8 = b'z';
-const SYM_SRC_OFFSET: u8 = b'Z';
-const SYM_E: u8 = b'e';
-const SYM_G: u8 = b'g';
-const SYM_I: u8 = b'I';
-const SYM_O: u8 = b'o';
-const SYM_S: u8 = b'S';
-const SYM_U: u8 = b'u';
-const SYM_V: u8 = b'v';
-const SYM_W: u8 = b'w';
-const SYM__: u8 = b'_';
-const SYM_0: u8 = b'0';
-const SYM_CURLY: u8 = b'{';
-
-#[derive(Debug, Eq, PartialEq)]
-enum AoutSymbolType {
-    TextSegment,
-    StaticTextSegment,
-    LeafFunction,
-    StaticLeafFunction,
-    DataSegment,
-    StaticDataSegment,
-    BssSegment,
-    StaticBssSegment,
-    AutoVariable,
-    FunctionParam,
-    FrameSymbol,
-    SourceFileNameComp,
-    SourceFileName,
-    SourceFileOffset,
-    ____X,
-    Curly,
-    E,
-    G,
-    I,
-    M,
-    O,
-    S,
-    U,
-    V,
-    W,
-    Zero,
-    Unknown,
+// Address and contents of a section, without contents for NOBITS.
+fn elf_section<'a>(
+    elf: &goblin::elf::Elf,
+    d: &'a [u8],
+    name: &str,
+) -> Option<(u64, Option<&'a [u8]>)> {
+    use goblin::elf::section_header::SHT_NOBITS;
+
+    let sh = elf
+        .section_headers
+        .iter()
+        .find(|sh| elf.shdr_strtab.get_at(sh.sh_name) == Some(name))?;
+    let start = sh.sh_offset as usize;
+    let contents = match sh.sh_type {
+        SHT_NOBITS => None,
+        _ => d.get(start..start + sh.sh_size as usize),
+    };
+    Some((sh.sh_addr, contents))
 }
 
-fn aout_symbol_type(s: &AoutSymbol) -> AoutSymbolType {
-    // First bit needs to be discarded.
-    match s.header.sym_type & !0x80 {
-        SYM_TEXT => AoutSymbolType::TextSegment,
-        SYM_STATIC_TEXT => AoutSymbolType::StaticTextSegment,
-        SYM_LEAF_FN => AoutSymbolType::LeafFunction,
-        SYM_STATIC_LEAF_FN => AoutSymbolType::StaticLeafFunction,
-        SYM_DATA => AoutSymbolType::DataSegment,
-        SYM_STATIC_DATA => AoutSymbolType::StaticDataSegment,
-        SYM_STATIC_BSS_SEGMENT => AoutSymbolType::StaticBssSegment,
-        SYM_BSS_SEGMENT => AoutSymbolType::BssSegment,
-        SYM_AUTO_VAR => AoutSymbolType::AutoVariable,
-        SYM_FN_PARAM => AoutSymbolType::FunctionParam,
-        SYM_FRAME_SYMBOL => AoutSymbolType::FrameSymbol,
-        SYM_SRC_COMP => AoutSymbolType::SourceFileNameComp,
-        SYM_SRC_FILE => AoutSymbolType::SourceFileName,
-        SYM_SRC_OFFSET => AoutSymbolType::SourceFileOffset,
-        SYM_E => AoutSymbolType::E,
-        SYM_G => AoutSymbolType::G,
-        SYM_I => AoutSymbolType::I,
-        SYM_O => AoutSymbolType::O,
-        SYM_S => AoutSymbolType::S,
-        SYM_U => AoutSymbolType::U,
-        SYM_V => AoutSymbolType::V,
-        SYM_W => AoutSymbolType::W,
-        SYM__ => AoutSymbolType::____X,
-        SYM_0 => AoutSymbolType::Zero,
-        SYM_CURLY => AoutSymbolType::Curly,
-        // TODO: What else?
-        _ => AoutSymbolType::Unknown,
+// Checks the derivation of an ELF from an a.out, returning the number of
+// failed checks. Extra absolute symbols, as from --define-sym, are fine.
+fn verify(aout: &[u8], elf_data: &[u8]) -> Result<usize, Error> {
+    use goblin::elf::program_header::{PF_X, PT_LOAD};
+    use goblin::elf::section_header::SHN_ABS;
+
+    let f = AoutFile::parse(aout)?;
+    let elf = goblin::elf::Elf::parse(elf_data).map_err(|e| format!("not an ELF file: {e}"))?;
+    let mark = |ok: bool| if ok { "ok  " } else { "FAIL" };
+    let mut failed = 0;
+    let mut check = |ok: bool, what: String| {
+        println!("  {} {what}", mark(ok));
+        failed += usize::from(!ok);
+    };
+
+    let machine = f.arch.elf_machine();
+    let m = elf.header.e_machine;
+    check(
+        m == machine as u16,
+        format!("machine {m:#x}, a.out is {}", f.arch),
+    );
+
+    let entry = u32::from(f.header.entry_point) as u64;
+    let contents_failed = verify_contents(&f, &elf, elf_data)?;
+
+    // The text segment starts at the entry point, so it gives the virtual
+    // base the ELF was made with, whatever the profile and --vbase were.
+    // Symbol containers only have the text section.
+    let text_section = elf_section(&elf, elf_data, ".text").map(|(addr, _)| addr);
+    let text_segment = elf
+        .program_headers
+        .iter()
+        .find(|p| p.p_type == PT_LOAD && p.p_flags & PF_X != 0)
+        .map(|p| p.p_vaddr);
+    let text = text_segment.or(text_section).unwrap_or_default();
+    let base = text.wrapping_sub(entry);
+    check(
+        text_section == Some(text),
+        format!("text at {text:#x}, virtual base {base:#x}"),
+    );
+    let e = elf.header.e_entry;
+    check(
+        e == base.wrapping_add(entry),
+        format!("entry {e:#x}, a.out entry {entry:#x}"),
+    );
+
+    let st = f.symbol_table().unwrap_or_default();
+    let st = match check_aout_symbols(&st) {
+        Ok(()) => &st[..],
+        Err((o, _)) => &st[..o],
+    };
+    // Values above 32 bits are those of the virtual base the a.out was
+    // linked at, which the ELF may have replaced.
+    let mut values: HashMap<&str, Vec<u64>> = HashMap::new();
+    for a in parse_aout_symbols(st, false) {
+        let value = a.value() & u32::MAX as u64;
+        values.entry(a.name).or_default().push(value);
+    }
+    let mut missing = vec![];
+    let mut count = 0;
+    for s in elf.syms.iter().filter(|s| s.st_shndx != SHN_ABS as usize) {
+        let Some(name) = elf.strtab.get_at(s.st_name).filter(|n| !n.is_empty()) else {
+            continue;
+        };
+        count += 1;
+        let value = s.st_value.wrapping_sub(base);
+        let found = values.get(name).is_some_and(|v| v.contains(&value));
+        if !found {
+            missing.push(printable(name));
+        }
+    }
+    check(
+        missing.is_empty(),
+        format!("{count} symbols, {} not in the a.out", missing.len()),
+    );
+    for n in missing {
+        println!("       {n}");
     }
+    Ok(failed + contents_failed)
 }
 
-fn aout_syms_to_elf(
-    aout_syms: Vec<AoutSymbol>,
-    is_64bit: bool,
-) -> (Vec<ElfSymbolTableEntry>, Vec<u8>) {
-    // TODO: enums, ElfInfo struct
-    const SYM_LOCAL: u8 = 0 << 4;
-    const SYM_GLOBAL: u8 = 1 << 4;
-    const SYM_FUNCTION: u8 = 2;
-
-    // NOTE: For now, text symbols only.
-    let mut t_syms = aout_syms.iter().filter(|s| {
-        let t = s.get_type();
-        t == AoutSymbolType::TextSegment || t == AoutSymbolType::StaticTextSegment
-    });
-    let mut t_syms: Vec<&AoutSymbol> = t_syms.collect();
-    t_syms.sort_by_key(|e| e.header.value);
-
-    // string table
-    let f = [0u8].as_bytes();
-    let mut sym_str_tab = f.to_vec();
-
-    let mut elf_sym_tab: Vec<ElfSymbolTableEntry> = vec![];
-    // first is a 0-byte
-    let mut name_offset: u32 = 1;
-
-    // first is the undefined symbol by convention
-    if is_64bit {
-        let e = Elf64SymbolTableEntry {
-            name_offset: 0,
-            value: 0,
-            size: 0,
-            info: 0,
-            other: 0,
-            section_index: 0,
-        };
-        elf_sym_tab.push(ElfSymbolTableEntry::Elf64(e));
-    } else {
-        let e = Elf32SymbolTableEntry {
-            name_offset: 0,
-            value: 0,
-            size: 0,
-            info: 0,
-            other: 0,
-            section_index: 0,
+// Checks that text and data made it into the ELF byte for byte, returning
+// the number of failed checks.
+fn verify_contents(f: &AoutFile, elf: &goblin::elf::Elf, elf_data: &[u8]) -> Result<usize, Error> {
+    let mark = |ok: bool| if ok { "ok  " } else { "FAIL" };
+    let mut failed = 0;
+    let text = f.bytes(RegionKind::Text).ok_or(Error::Truncated("text"))?;
+    let data = f.bytes(RegionKind::Data).ok_or(Error::Truncated("data"))?;
+    for (name, want) in [("text", text), ("data", data)] {
+        let got = elf_section(elf, elf_data, &format!(".{name}")).map(|(_, b)| b);
+        let problem = match got {
+            Some(Some(b)) if b == want => None,
+            _ if want.is_empty() => None,
+            None => Some("missing".to_string()),
+            Some(None) => Some("without contents".to_string()),
+            Some(Some(b)) if b.len() != want.len() => Some(format!("{:#x} bytes", b.len())),
+            Some(Some(b)) => {
+                let at = b.iter().zip(want).position(|(x, y)| x != y);
+                Some(format!("first difference at {:#x}", at.unwrap_or_default()))
+            }
         };
-        elf_sym_tab.push(ElfSymbolTableEntry::Elf32(e));
+        println!(
+            "  {} {name}, {:#x} bytes",
+            mark(problem.is_none()),
+            want.len()
+        );
+        if let Some(p) = &problem {
+            println!("       {p}");
+        }
+        failed += usize::from(problem.is_some());
+    }
+    Ok(failed)
+}
+
+// Checks an ELF against the spec, as far as the tool writes such files,
+// returning the number of failed checks.
+fn verify_elf(d: &[u8]) -> Result<usize, String> {
+    use goblin::elf::program_header::{PF_X, PT_LOAD};
+    use goblin::elf::section_header::{
+        SHF_ALLOC, SHN_ABS, SHN_COMMON, SHN_LORESERVE, SHT_NOBITS, SHT_STRTAB, SHT_SYMTAB,
+    };
+    use goblin::elf::sym::STB_LOCAL;
+
+    let elf = goblin::elf::Elf::parse(d).map_err(|e| format!("not an ELF file: {e}"))?;
+    let mark = |ok: bool| if ok { "ok  " } else { "FAIL" };
+    let mut failed = 0;
+    let mut check = |what: String, problems: Vec<String>| {
+        println!("  {} {what}", mark(problems.is_empty()));
+        for p in problems.iter().take(10) {
+            println!("       {p}");
+        }
+        if problems.len() > 10 {
+            println!("       and {} more", problems.len() - 10);
+        }
+        failed += usize::from(!problems.is_empty());
+    };
+    let within = |offset: u64, size: u64| {
+        offset
+            .checked_add(size)
+            .is_some_and(|e| e <= d.len() as u64)
+    };
+    let sections = &elf.section_headers;
+    let section_name = |i: usize| {
+        let n = sections
+            .get(i)
+            .and_then(|s| elf.shdr_strtab.get_at(s.sh_name));
+        n.filter(|n| !n.is_empty())
+            .map_or(format!("section {i}"), printable)
     };
 
-    // https://docs.oracle.com/cd/E23824_01/html/819-0690/chapter6-79797.html
-    // > In executable and shared object files, st_value holds a virtual address.
-
-    for s in t_syms.windows(2) {
-        // symbol name
-        let curr_name = s[0].name;
-        sym_str_tab.extend_from_slice(curr_name.as_bytes());
-        sym_str_tab.extend_from_slice(f);
-
-        // symbol
-        let curr_value: u32 = s[0].header.value.into();
-        let next_value: u32 = s[1].header.value.into();
-        let size = next_value - curr_value;
-        let value = curr_value;
-        if is_64bit {
-            let e = Elf64SymbolTableEntry {
-                name_offset,
-                value: value as u64,
-                size: size as u64,
-                info: SYM_LOCAL | SYM_FUNCTION,
-                other: 0,
-                section_index: 1,
-            };
-            elf_sym_tab.push(ElfSymbolTableEntry::Elf64(e));
-        } else {
-            let e = Elf32SymbolTableEntry {
-                name_offset,
-                value,
-                size,
-                info: SYM_LOCAL | SYM_FUNCTION,
-                other: 0,
-                section_index: 1,
-            };
-            elf_sym_tab.push(ElfSymbolTableEntry::Elf32(e));
+    let h = &elf.header;
+    let (ehsize, phentsize, shentsize, symsize) = if elf.is_64 {
+        (64, 56, 64, 24)
+    } else {
+        (52, 32, 40, 16)
+    };
+    let phnum = elf.program_headers.len() as u64;
+    let shnum = sections.len() as u64;
+    let mut p = vec![];
+    if h.e_version != 1 {
+        p.push(format!("version {}", h.e_version));
+    }
+    if h.e_ehsize != ehsize {
+        p.push(format!("header size {}, not {ehsize}", h.e_ehsize));
+    }
+    if phnum > 0 && h.e_phentsize != phentsize {
+        p.push(format!(
+            "program header size {}, not {phentsize}",
+            h.e_phentsize
+        ));
+    }
+    if shnum > 0 && h.e_shentsize != shentsize {
+        p.push(format!(
+            "section header size {}, not {shentsize}",
+            h.e_shentsize
+        ));
+    }
+    if !within(h.e_phoff, phnum * h.e_phentsize as u64) {
+        p.push("program headers exceed the file".to_string());
+    }
+    if !within(h.e_shoff, shnum * h.e_shentsize as u64) {
+        p.push("section headers exceed the file".to_string());
+    }
+    let shstrtab = sections.get(h.e_shstrndx as usize);
+    if shnum > 0 && shstrtab.is_none_or(|s| s.sh_type != SHT_STRTAB) {
+        p.push(format!(
+            "section names in {}, not a string table",
+            h.e_shstrndx
+        ));
+    }
+    let class = if elf.is_64 { "ELF64" } else { "ELF32" };
+    check(format!("header, {class}"), p);
+
+    let mut p = vec![];
+    let names_size = shstrtab.map_or(0, |s| s.sh_size);
+    // what takes up room in the file, to find overlaps
+    let mut extents = vec![
+        (0, ehsize as u64, "ELF header".to_string()),
+        (
+            h.e_phoff,
+            phnum * h.e_phentsize as u64,
+            "program headers".to_string(),
+        ),
+        (
+            h.e_shoff,
+            shnum * h.e_shentsize as u64,
+            "section headers".to_string(),
+        ),
+    ];
+    for (i, s) in sections.iter().enumerate().skip(1) {
+        let name = section_name(i);
+        if s.sh_name as u64 >= names_size.max(1) {
+            p.push(format!(
+                "{name}: name at {:#x} outside the names",
+                s.sh_name
+            ));
+        }
+        if s.sh_type != SHT_NOBITS {
+            if !within(s.sh_offset, s.sh_size) {
+                p.push(format!("{name}: exceeds the file"));
+            }
+            extents.push((s.sh_offset, s.sh_size, name.clone()));
+        }
+        let align = s.sh_addralign;
+        if align > 1 && !align.is_power_of_two() {
+            p.push(format!("{name}: alignment {align} is not a power of two"));
+        } else if align > 1 && s.sh_addr % align != 0 {
+            p.push(format!(
+                "{name}: address {:#x} not aligned to {align}",
+                s.sh_addr
+            ));
+        }
+        if s.sh_link as u64 >= shnum {
+            p.push(format!("{name}: link {} is not a section", s.sh_link));
+        }
+    }
+    extents.retain(|(_, size, _)| *size > 0);
+    extents.sort_by_key(|(offset, _, _)| *offset);
+    for w in extents.windows(2) {
+        let ((o, size, a), (next, _, b)) = (&w[0], &w[1]);
+        if o + size > *next {
+            p.push(format!("{a} overlaps {b} at {next:#x}"));
+        }
+    }
+    check(format!("{shnum} sections"), p);
+
+    let mut p = vec![];
+    let loads: Vec<_> = elf
+        .program_headers
+        .iter()
+        .filter(|ph| ph.p_type == PT_LOAD)
+        .collect();
+    for (i, ph) in elf.program_headers.iter().enumerate() {
+        if !within(ph.p_offset, ph.p_filesz) {
+            p.push(format!("segment {i}: exceeds the file"));
+        }
+        if ph.p_filesz > ph.p_memsz {
+            p.push(format!(
+                "segment {i}: file size {:#x} exceeds memory size {:#x}",
+                ph.p_filesz, ph.p_memsz
+            ));
+        }
+        let align = ph.p_align;
+        if ph.p_type == PT_LOAD && align > 1 && ph.p_offset % align != ph.p_vaddr % align {
+            p.push(format!(
+                "segment {i}: offset and address differ modulo {align}"
+            ));
+        }
+    }
+    // symbol containers have no LOAD segments at all
+    for (i, s) in sections.iter().enumerate() {
+        if loads.is_empty() || s.sh_flags & SHF_ALLOC as u64 == 0 || s.sh_size == 0 {
+            continue;
+        }
+        let contains = |ph: &&goblin::elf::ProgramHeader| {
+            let in_memory =
+                ph.p_vaddr <= s.sh_addr && s.sh_addr + s.sh_size <= ph.p_vaddr + ph.p_memsz;
+            let in_file = s.sh_type == SHT_NOBITS
+                || (s.sh_offset.wrapping_sub(ph.p_offset) == s.sh_addr - ph.p_vaddr
+                    && s.sh_offset + s.sh_size <= ph.p_offset + ph.p_filesz);
+            in_memory && in_file
         };
+        if !loads.iter().any(contains) {
+            p.push(format!("{}: not within a LOAD segment", section_name(i)));
+        }
+    }
+    check(format!("{phnum} segments"), p);
 
-        // account for 0-byte
-        name_offset += curr_name.len() as u32 + 1;
+    if let Some(symtab) = sections.iter().find(|s| s.sh_type == SHT_SYMTAB) {
+        let mut p = vec![];
+        if symtab.sh_entsize != symsize {
+            p.push(format!("entry size {}, not {symsize}", symtab.sh_entsize));
+        }
+        let strtab = sections.get(symtab.sh_link as usize);
+        if strtab.is_none_or(|s| s.sh_type != SHT_STRTAB) {
+            p.push(format!("names in {}, not a string table", symtab.sh_link));
+        }
+        let names_size = strtab.map_or(0, |s| s.sh_size);
+        let count = elf.syms.len();
+        let locals = elf.syms.iter().position(|s| s.st_bind() != STB_LOCAL);
+        let locals = locals.unwrap_or(count);
+        if symtab.sh_info as usize != locals {
+            p.push(format!(
+                "info {}, but {locals} local symbols",
+                symtab.sh_info
+            ));
+        }
+        if elf
+            .syms
+            .iter()
+            .skip(locals)
+            .any(|s| s.st_bind() == STB_LOCAL)
+        {
+            p.push("local symbols after global ones".to_string());
+        }
+        for (i, s) in elf.syms.iter().enumerate().skip(1) {
+            let name = elf
+                .strtab
+                .get_at(s.st_name)
+                .map_or(format!("symbol {i}"), printable);
+            if s.st_name as u64 >= names_size {
+                p.push(format!(
+                    "{name}: name at {:#x} outside the names",
+                    s.st_name
+                ));
+            }
+            match s.st_shndx {
+                0 => {}
+                x if x == SHN_ABS as usize || x == SHN_COMMON as usize => {}
+                x if x >= SHN_LORESERVE as usize => {}
+                x if x as u64 >= shnum => p.push(format!("{name}: section {x} does not exist")),
+                x => {
+                    let sh = &sections[x];
+                    let end = sh.sh_addr + sh.sh_size;
+                    let alloc = sh.sh_flags & SHF_ALLOC as u64 != 0;
+                    if alloc && !(sh.sh_addr..=end).contains(&s.st_value) {
+                        p.push(format!(
+                            "{name}: {:#x} outside {}",
+                            s.st_value,
+                            section_name(x)
+                        ));
+                    }
+                }
+            }
+        }
+        check(format!("{count} symbols"), p);
     }
 
-    (elf_sym_tab, sym_str_tab)
+    let e = h.e_entry;
+    if loads.is_empty() {
+        check(
+            format!("entry {e:#x}, not checked without LOAD segments"),
+            vec![],
+        );
+    } else {
+        let executable = loads
+            .iter()
+            .any(|ph| ph.p_flags & PF_X != 0 && ph.p_vaddr <= e && e < ph.p_vaddr + ph.p_memsz);
+        let p = match executable {
+            true => vec![],
+            false => vec!["not in an executable LOAD segment".to_string()],
+        };
+        check(format!("entry {e:#x}"), p);
+    }
+    Ok(failed)
 }
 
-const VIRTUAL_BASE_AMD64: u64 = 0x8000_0000;
-const VIRTUAL_BASE_RISCV64: u64 = 0x0000_0000;
+// Creates an output file, refusing to replace an existing one unless forced.
+fn create(file_name: &str, force: bool) -> Result<fs::File, Error> {
+    let res = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .create_new(!force)
+        .open(file_name);
+    match res {
+        Ok(f) => Ok(f),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Err(Error::Invalid(format!(
+            "{file_name} exists, use --force to overwrite it"
+        ))),
+        Err(e) => Err(Error::Io(file_name.to_string(), e)),
+    }
+}
 
-fn is_64bit(machine: ElfMachine) -> bool {
-    match machine {
-        ElfMachine::Amd64 => false,
-        ElfMachine::RiscV => true,
-        _ => todo!(),
+// Convert an a.out image, writing the ELF through the requested sink.
+// Nothing is left behind on failure.
+fn convert_file(
+    d: &[u8],
+    out_file_name: &str,
+    opts: &ConvertOptions,
+    compress: Compression,
+    force: bool,
+) -> Result<(), Error> {
+    let io = |e| Error::Io(out_file_name.to_string(), e);
+    let f: Box<dyn Write> = match out_file_name {
+        "-" => Box::new(std::io::stdout().lock()),
+        n => Box::new(create(n, force)?),
+    };
+    let mut sink = sink::sink(f, compress).map_err(io)?;
+    if let Err(e) = aout_to_elf_into(d, opts, &mut sink) {
+        drop(sink);
+        if out_file_name != "-" {
+            let _ = fs::remove_file(out_file_name);
+        }
+        return Err(e);
     }
+    sink.finish().map_err(io)
 }
 
-// TODO: Something with the memory sizes is strange.
-fn aout_to_elf(d: &[u8]) -> Result<Vec<u8>, String> {
-    if let Ok((aout, _)) = Aout::read_from_prefix(d) {
-        let machine_target = aout_mach_to_elf(&aout);
+fn print_elf_summary(elf: &goblin::elf::Elf) {
+    use goblin::elf::{program_header::pt_to_str, section_header::sht_to_str};
+
+    let h = &elf.header;
+    let class = if elf.is_64 { "ELF64" } else { "ELF32" };
+    let machine = goblin::elf::header::machine_to_str(h.e_machine);
+    println!("This is an {class} for {machine}");
+    println!("Entry point:  {:08x}", h.e_entry);
+    println!();
+
+    println!("Segments:");
+    for ph in elf.program_headers.iter() {
+        let r = if ph.is_read() { "r" } else { "-" };
+        let w = if ph.is_write() { "w" } else { "-" };
+        let x = if ph.is_executable() { "x" } else { "-" };
+        println!(
+            "  {:8} {r}{w}{x} {:08x} bytes @ {:08x} -> {:08x} ({:08x} in memory)",
+            pt_to_str(ph.p_type),
+            ph.p_filesz,
+            ph.p_offset,
+            ph.p_vaddr,
+            ph.p_memsz,
+        );
+    }
+    println!();
+
+    println!("Sections:");
+    for sh in elf.section_headers.iter().skip(1) {
+        let name = elf.shdr_strtab.get_at(sh.sh_name).unwrap_or("?");
+        println!(
+            "  {:12} {:12} {:08x} bytes @ {:08x} -> {:08x}",
+            printable(name),
+            sht_to_str(sh.sh_type),
+            sh.sh_size,
+            sh.sh_offset,
+            sh.sh_addr,
+        );
+    }
+    println!();
+    println!("{} symbols", elf.syms.len());
+}
 
-        let is_64bit = is_64bit(machine_target);
+fn image_arch(d: &[u8]) -> MachineArch {
+    match Aout::read_from_prefix(d) {
+        Ok((aout, _)) => MachineArch::from_magic(aout.magic),
+        Err(_) => MachineArch::Unknown,
+    }
+}
 
-        let virtual_base = match machine_target {
-            ElfMachine::Amd64 => VIRTUAL_BASE_AMD64,
-            ElfMachine::RiscV => VIRTUAL_BASE_RISCV64,
-            _ => todo!(),
+// Find a.out images placed back to back or embedded in a larger blob.
+// Headers are recognized by a known magic and sizes fitting the blob.
+fn scan_aouts(d: &[u8]) -> Vec<Range<usize>> {
+    let mut found = vec![];
+    let mut offset = 0;
+    while offset + AOUT_HEADER_SIZE <= d.len() {
+        let Ok((aout, _)) = Aout::read_from_prefix(&d[offset..]) else {
+            break;
         };
+        let arch = MachineArch::from_magic(aout.magic);
+        let sizes = [
+            aout.text_size,
+            aout.data_size,
+            aout.symbol_table_size,
+            aout.sp_size,
+            aout.pc_size,
+        ];
+        let len = sizes
+            .iter()
+            .map(|s| u32::from(*s) as usize)
+            .fold(AOUT_HEADER_SIZE + arch.pad_size(), usize::saturating_add);
+        let known = arch != MachineArch::Unknown;
+        if known && u32::from(aout.text_size) > 0 && len <= d.len() - offset {
+            found.push(offset..offset + len);
+            offset += len;
+        } else {
+            offset += 1;
+        }
+    }
+    found
+}
 
-        let entry: u32 = aout.entry_point.into();
-
-        // TODO: calculate
-        let program_header_entry_count = 3;
-        // TODO: calculate
-        let section_header_entry_count = 6;
-
-        // a.out only gives us sizes
-        let ts: u32 = aout.text_size.into();
-        let ds: u32 = aout.data_size.into();
-        let ss: u32 = aout.symbol_table_size.into();
+fn write_provenance(d: &[u8], opts: &ConvertOptions, file_name: &str) -> Result<(), Error> {
+    let image = aout_to_image(d, opts)?;
+    let map = image.provenance(d)?;
+    let json = serde_json::to_string_pretty(&map).map_err(|e| e.to_string())?;
+    fs::write(file_name, json + "\n").map_err(|e| Error::Io(file_name.to_string(), e))
+}
 
-        // so offsets have to be calculated
-        let t_offset = AOUT_HEADER_SIZE + PAD_EXTRA_SIZE;
-        let d_offset = t_offset + ts as usize;
-        let s_offset = d_offset + ds as usize;
+// Size difference between an a.out and its (uncompressed) ELF, made up of
+// what the ELF adds and what it leaves out of the a.out.
+fn print_stats(d: &[u8], opts: &ConvertOptions) -> Result<(), Error> {
+    let image = aout_to_image(d, opts)?;
+    let ranges = image.provenance(d)?;
+    let elf_size: u64 = ranges.iter().map(|r| r.size).sum();
+    let delta = elf_size as i64 - d.len() as i64;
+    let percent = delta as f64 * 100.0 / d.len().max(1) as f64;
+    println!(
+        "a.out {} bytes, ELF {elf_size} bytes, {delta:+} ({percent:+.1}%)",
+        d.len()
+    );
+
+    // generated contributors, keeping their order in the file
+    let mut added: Vec<(&str, u64)> = vec![];
+    for r in ranges.iter().filter(|r| r.input_offset.is_none()) {
+        match added.iter_mut().find(|(o, _)| *o == r.origin) {
+            Some((_, size)) => *size += r.size,
+            None => added.push((&r.origin, r.size)),
+        }
+    }
+    for (origin, size) in added {
+        println!("  {:+10}  {origin}", size as i64);
+    }
 
-        let data_load_addr = entry + align_4k(ts);
+    let copied: Vec<Range<u64>> = ranges
+        .iter()
+        .filter_map(|r| r.input_offset.map(|o| o..o + r.size))
+        .collect();
+    for r in AoutFile::parse(d)?.regions() {
+        let (start, end) = (r.offset as u64, (r.offset + r.len).min(d.len()) as u64);
+        let kept: u64 = copied
+            .iter()
+            .map(|c| c.end.min(end).saturating_sub(c.start.max(start)))
+            .sum();
+        let dropped = end.saturating_sub(start) - kept;
+        if dropped > 0 {
+            println!("  {:+10}  a.out {}", -(dropped as i64), r.kind);
+        }
+    }
+    Ok(())
+}
 
-        // the offset in the ELF file, needed to calculate other offsets
-        let main_offset = if is_64bit {
-            (ELF64_HEADER_SIZE
-                + program_header_entry_count * ELF64_PROGRAM_HEADER_SIZE
-                + section_header_entry_count * ELF64_SECTION_HEADER_SIZE
-                + PAD_SIZE) as u32
-        } else {
-            (ELF32_HEADER_SIZE
-                + program_header_entry_count * ELF32_PROGRAM_HEADER_SIZE
-                + section_header_entry_count * ELF32_SECTION_HEADER_SIZE
-                + PAD_SIZE) as u32
-        };
+// Remove what convert --strip leaves out, matching sections by name.
+fn strip_elf(d: Vec<u8>) -> Result<Vec<u8>, String> {
+    let mut e = SectionEditor::open(d)?;
+    let names: Vec<String> = e
+        .sections()
+        .into_iter()
+        .map(|s| s.name)
+        .filter(|n| {
+            n == ".symtab"
+                || n == ".strtab"
+                || n.starts_with(".debug_")
+                || n.starts_with(".plan9.")
+                || n == ".note.plan9"
+        })
+        .collect();
+    if names.is_empty() {
+        return Err("already stripped".to_string());
+    }
+    for n in names {
+        e.remove(&n)?;
+    }
+    Ok(e.into_bytes())
+}
 
-        // we will reappend this later
-        let data = &d[t_offset..];
-
-        // ----------- program headers
-        let program_headers = {
-            let mut program_headers: Vec<ElfProgramHeader> = vec![];
-
-            const PH_FLAG_READ: u32 = 1 << 2;
-            const PH_FLAG_WRITE: u32 = 1 << 1;
-            const PH_FLAG_EXEC: u32 = 1 << 0;
-
-            if is_64bit {
-                // text segment
-                let virtual_addr = virtual_base + entry as u64;
-                let ph = Elf64ProgramHeader {
-                    program_type: ElfProgramType::Load,
-                    offset: main_offset as u64,
-                    virtual_addr,
-                    physical_addr: entry as u64,
-                    file_size: ts as u64,
-                    memory_size: ts as u64,
-                    flags: PH_FLAG_READ | PH_FLAG_EXEC,
-                    align: 4 * 1024,
-                };
-                program_headers.push(ElfProgramHeader::Elf64(ph));
-
-                // data segment
-                let offset = (main_offset + ts) as u64;
-                let virtual_addr = virtual_base + data_load_addr as u64;
-                let ph = Elf64ProgramHeader {
-                    program_type: ElfProgramType::Load,
-                    offset,
-                    virtual_addr,
-                    physical_addr: data_load_addr as u64,
-                    file_size: ds as u64,
-                    memory_size: ds as u64,
-                    flags: PH_FLAG_READ | PH_FLAG_WRITE,
-                    align: 4 * 1024,
-                };
-                program_headers.push(ElfProgramHeader::Elf64(ph));
-
-                // retain original symbol table
-                let offset = offset + ds as u64;
-                let ph = Elf64ProgramHeader {
-                    program_type: ElfProgramType::Null,
-                    offset,
-                    virtual_addr: 0,
-                    physical_addr: 0,
-                    file_size: ss as u64,
-                    memory_size: ss as u64,
-                    flags: PH_FLAG_READ,
-                    align: 4,
-                };
-                program_headers.push(ElfProgramHeader::Elf64(ph));
-            } else {
-                // text segment
-                let ph = Elf32ProgramHeader {
-                    program_type: ElfProgramType::Load,
-                    offset: main_offset,
-                    virtual_addr: virtual_base as u32 + entry,
-                    physical_addr: entry,
-                    file_size: ts,
-                    memory_size: ts,
-                    flags: PH_FLAG_READ | PH_FLAG_EXEC,
-                    align: 4 * 1024,
-                };
-                program_headers.push(ElfProgramHeader::Elf32(ph));
-
-                // data segment
-                let offset = main_offset + ts;
-                let ph = Elf32ProgramHeader {
-                    program_type: ElfProgramType::Load,
-                    offset,
-                    virtual_addr: virtual_base as u32 + data_load_addr,
-                    physical_addr: data_load_addr,
-                    file_size: ds,
-                    memory_size: ds,
-                    flags: PH_FLAG_READ | PH_FLAG_WRITE,
-                    align: 4 * 1024,
-                };
-                program_headers.push(ElfProgramHeader::Elf32(ph));
-
-                // retain original symbol table
-                let offset = offset + ds;
-                let ph = Elf32ProgramHeader {
-                    program_type: ElfProgramType::Null,
-                    offset,
-                    virtual_addr: 0,
-                    physical_addr: 0,
-                    file_size: ss,
-                    memory_size: ss,
-                    flags: PH_FLAG_READ,
-                    align: 4,
-                };
-                program_headers.push(ElfProgramHeader::Elf32(ph));
+// Apply all patches or none, checking each against its region.
+fn patch_file(d: Vec<u8>, patches: &[patch::Patch]) -> Result<Vec<u8>, String> {
+    if d.starts_with(&ELF_MAGIC) {
+        use goblin::elf::section_header::SHF_ALLOC;
+
+        // symbols are looked up relative to the address of the section
+        let elf = goblin::elf::Elf::parse(&d).map_err(|e| format!("not an ELF file: {e}"))?;
+        let mut values = HashMap::new();
+        for s in elf.syms.iter() {
+            if let Some(n) = elf.strtab.get_at(s.st_name) {
+                values.entry(n.to_string()).or_insert(s.st_value);
             }
+        }
+        let addrs: HashMap<String, u64> = elf
+            .section_headers
+            .iter()
+            .filter(|sh| sh.sh_flags & SHF_ALLOC as u64 != 0)
+            .filter_map(|sh| Some((elf.shdr_strtab.get_at(sh.sh_name)?.to_string(), sh.sh_addr)))
+            .collect();
+        drop(elf);
+
+        let mut e = SectionEditor::open(d)?;
+        for p in patches {
+            let Some(c) = e.contents(&p.region) else {
+                return Err(format!("no section {}", printable(&p.region)));
+            };
+            let mut c = c.to_vec();
+            let addr = addrs.get(&p.region);
+            p.apply(&mut c, |n| values.get(n)?.checked_sub(*addr?))?;
+            e.replace(&p.region, &c)?;
+        }
+        return Ok(e.into_bytes());
+    }
 
-            program_headers
-        };
+    let f = AoutFile::parse(&d)?;
+    // regions with the addresses of text and data, and symbol values within
+    // the 32 bits of the header's addresses, like those
+    let regions: Vec<_> = f
+        .regions()
+        .into_iter()
+        .map(|r| (f.vaddr(r.offset), r))
+        .collect();
+    let st = f.symbol_table().unwrap_or_default();
+    let mut values = HashMap::new();
+    for s in parse_aout_symbols(&st, false) {
+        values
+            .entry(s.name.to_string())
+            .or_insert(s.value() & u32::MAX as u64);
+    }
+    drop(st);
 
-        let sym_table_data = &d[s_offset..s_offset + ss as usize];
-        let syms = parse_aout_symbols(sym_table_data, false);
-        let (elf_sym_tab, sym_str_tab) = aout_syms_to_elf(syms, is_64bit);
-
-        // section header string table
-        let sh_str_tab = {
-            let f = [0u8].as_bytes();
-            let te = c".text".to_bytes_with_nul();
-            let da = c".data".to_bytes_with_nul();
-            let sy = c".symtab".to_bytes_with_nul();
-            let st = c".strtab".to_bytes_with_nul();
-            let sh = c".shstrtab".to_bytes_with_nul();
-            [f, te, da, sy, st, sh].concat()
+    let mut d = d;
+    for p in patches {
+        let Some((addr, r)) = regions.iter().find(|(_, r)| r.kind.to_string() == p.region) else {
+            return Err(format!("no region {}", printable(&p.region)));
         };
-
-        let elf_sym_tab_entry_size = if is_64bit {
-            ELF64_SYMBOL_TABLE_ENTRY_SIZE
-        } else {
-            ELF32_SYMBOL_TABLE_ENTRY_SIZE
+        let Some(b) = d.get_mut(r.offset..r.offset + r.len) else {
+            return Err(format!("{} exceeds the file", r.kind));
         };
+        p.apply(b, |n| values.get(n)?.checked_sub((*addr)?))?;
+    }
+    Ok(d)
+}
 
-        // https://refspecs.linuxfoundation.org/elf/gabi4+/ch4.sheader.html#sh_flags
-        let section_headers = {
-            const SH_FLAG_WRITE: u32 = 1 << 0;
-            const SH_FLAG_ALLOC: u32 = 1 << 1;
-            const SH_FLAG_EXEC: u32 = 1 << 2;
-
-            let mut section_headers: Vec<ElfSectionHeader> = vec![];
-
-            if is_64bit {
-                // NOTE: empty section, necessary for symbol resolution to work
-                let sh = Elf64SectionHeader {
-                    name: 0,
-                    section_type: ElfSectionType::Null,
-                    flags: 0,
-                    addr: 0,
-                    offset: 0,
-                    size: 0,
-                    link: 0,
-                    info: 0,
-                    addr_align: 0,
-                    entry_size: 0,
-                };
-                section_headers.push(ElfSectionHeader::Elf64(sh));
-
-                // --- text (code) and data
-
-                // .text
-                let offset = main_offset as u64;
-                let sh = Elf64SectionHeader {
-                    name: 1,
-                    section_type: ElfSectionType::ProgBits,
-                    flags: (SH_FLAG_ALLOC | SH_FLAG_EXEC) as u64,
-                    addr: virtual_base as u64 + entry as u64,
-                    offset,
-                    size: ts as u64,
-                    link: 1,
-                    info: 0,
-                    addr_align: 64,
-                    entry_size: 0,
-                };
-                section_headers.push(ElfSectionHeader::Elf64(sh));
-                // .data
-                let offset = offset + ts as u64;
-                let sh = Elf64SectionHeader {
-                    name: 7,
-                    section_type: ElfSectionType::ProgBits,
-                    flags: (SH_FLAG_ALLOC | SH_FLAG_WRITE) as u64,
-                    addr: virtual_base as u64 + data_load_addr as u64,
-                    offset,
-                    size: ds as u64,
-                    link: 1,
-                    info: 0,
-                    addr_align: 32,
-                    entry_size: 0,
-                };
-                section_headers.push(ElfSectionHeader::Elf64(sh));
-
-                // --- symbols and strings
-
-                // .symtab
-                let elf_sym_tab_count = elf_sym_tab.len();
-                let size = (elf_sym_tab_count * elf_sym_tab_entry_size) as u64;
-                let offset = main_offset as u64 + data.len() as u64;
-                let sh = Elf64SectionHeader {
-                    name: 13,
-                    section_type: ElfSectionType::SymbolTable,
-                    flags: 0,
-                    addr: 0,
-                    offset,
-                    size,
-                    link: SYM_STRING_TABLE_INDEX,
-                    info: elf_sym_tab_count as u32,
-                    addr_align: 8,
-                    entry_size: elf_sym_tab_entry_size as u64,
-                };
-                section_headers.push(ElfSectionHeader::Elf64(sh));
-
-                // .strtab
-                let offset = offset + size;
-                let size = sym_str_tab.len() as u64;
-                let sh = Elf64SectionHeader {
-                    name: 21,
-                    section_type: ElfSectionType::SymbolStringTable,
-                    flags: 0,
-                    addr: 0,
-                    offset,
-                    size,
-                    link: 0,
-                    info: 0,
-                    addr_align: 1,
-                    entry_size: 0,
-                };
-                section_headers.push(ElfSectionHeader::Elf64(sh));
-                // .shstrtab
-                let offset = offset + size;
-                let size = sh_str_tab.len() as u64;
-                let sh = Elf64SectionHeader {
-                    name: 29,
-                    section_type: ElfSectionType::SymbolStringTable,
-                    flags: 0,
-                    addr: 0,
-                    offset,
-                    size,
-                    link: 0,
-                    info: 0,
-                    addr_align: 1,
-                    entry_size: 0,
-                };
-                section_headers.push(ElfSectionHeader::Elf64(sh));
-            } else {
-                // NOTE: empty section, necessary for symbol resolution to work
-                let sh = Elf32SectionHeader {
-                    name: 0,
-                    section_type: ElfSectionType::Null,
-                    flags: 0,
-                    addr: 0,
-                    offset: 0,
-                    size: 0,
-                    link: 0,
-                    info: 0,
-                    addr_align: 0,
-                    entry_size: 0,
-                };
-                section_headers.push(ElfSectionHeader::Elf32(sh));
-
-                // --- text (code) and data
-
-                // .text
-                let offset = main_offset;
-                let sh = Elf32SectionHeader {
-                    name: 1,
-                    section_type: ElfSectionType::ProgBits,
-                    flags: SH_FLAG_ALLOC | SH_FLAG_EXEC,
-                    addr: virtual_base as u32 + entry as u32,
-                    offset,
-                    size: ts,
-                    link: 1,
-                    info: 0,
-                    addr_align: 64,
-                    entry_size: 0,
-                };
-                section_headers.push(ElfSectionHeader::Elf32(sh));
-                // .data
-                let offset = offset + ts;
-                let sh = Elf32SectionHeader {
-                    name: 7,
-                    section_type: ElfSectionType::ProgBits,
-                    flags: SH_FLAG_ALLOC | SH_FLAG_WRITE,
-                    addr: virtual_base as u32 + data_load_addr,
-                    offset,
-                    size: ds,
-                    link: 1,
-                    info: 0,
-                    addr_align: 32,
-                    entry_size: 0,
-                };
-                section_headers.push(ElfSectionHeader::Elf32(sh));
-
-                // --- symbols and strings
-
-                // .symtab
-                let elf_sym_tab_count = elf_sym_tab.len() as u32;
-                let size = elf_sym_tab_count * elf_sym_tab_entry_size as u32;
-                let offset = main_offset + data.len() as u32;
-                let sh = Elf32SectionHeader {
-                    name: 13,
-                    section_type: ElfSectionType::SymbolTable,
-                    flags: 0,
-                    addr: 0,
-                    offset,
-                    size,
-                    link: SYM_STRING_TABLE_INDEX,
-                    info: elf_sym_tab_count,
-                    addr_align: 8,
-                    entry_size: elf_sym_tab_entry_size as u32,
-                };
-                section_headers.push(ElfSectionHeader::Elf32(sh));
-
-                // .strtab
-                let offset = offset + size;
-                let size = sym_str_tab.len() as u32;
-                let sh = Elf32SectionHeader {
-                    name: 21,
-                    section_type: ElfSectionType::SymbolStringTable,
-                    flags: 0,
-                    addr: 0,
-                    offset,
-                    size,
-                    link: 0,
-                    info: 0,
-                    addr_align: 1,
-                    entry_size: 0,
-                };
-                section_headers.push(ElfSectionHeader::Elf32(sh));
-                // .shstrtab
-                let offset = offset + size;
-                let size = sh_str_tab.len() as u32;
-                let sh = Elf32SectionHeader {
-                    name: 29,
-                    section_type: ElfSectionType::SymbolStringTable,
-                    flags: 0,
-                    addr: 0,
-                    offset,
-                    size,
-                    link: 0,
-                    info: 0,
-                    addr_align: 1,
-                    entry_size: 0,
-                };
-                section_headers.push(ElfSectionHeader::Elf32(sh));
-            }
+// Headers and payload go to separate files; together they form the ELF.
+fn convert_split(
+    d: &[u8],
+    meta_file_name: &str,
+    payload_file_name: &str,
+    opts: &ConvertOptions,
+    force: bool,
+) -> Result<(), Error> {
+    let image = aout_to_image(d, opts)?;
+    let mut meta = create(meta_file_name, force)?;
+    let mut payload = create(payload_file_name, force).inspect_err(|_| {
+        let _ = fs::remove_file(meta_file_name);
+    })?;
+    let res = image.write_split(&mut meta, &mut payload);
+    if res.is_err() {
+        let _ = fs::remove_file(meta_file_name);
+        let _ = fs::remove_file(payload_file_name);
+    }
+    Ok(res?)
+}
 
-            section_headers
-        };
+// Content-addressed names need the final bytes, so encode in memory first.
+fn convert_templated(
+    d: &[u8],
+    file_name: &str,
+    template: &str,
+    opts: &ConvertOptions,
+    compress: Compression,
+    force: bool,
+) -> Result<String, Error> {
+    // the output is about as large as the input
+    timings::check_available(d.len() as u64, "encoding for --output-template")?;
+    let mut image = vec![];
+    let mut sink = sink::sink(&mut image, compress)?;
+    aout_to_elf_into(d, opts, &mut sink)?;
+    sink.finish()?;
+
+    let arch = image_arch(d);
+    let out_file_name = naming::render(template, file_name, &arch.to_string(), &image)?;
+    create(&out_file_name, force)?
+        .write_all(&image)
+        .map_err(|e| Error::Io(out_file_name.clone(), e))?;
+    Ok(out_file_name)
+}
 
-        // -------- assemble final ELF header and data slice
+fn value_or_default<T: ValueEnum + Default>(v: &Option<String>) -> Result<T, String> {
+    match v {
+        Some(v) => T::from_str(v, true),
+        None => Ok(T::default()),
+    }
+}
 
-        let eh = ElfHeader::new(
-            program_header_entry_count,
-            section_header_entry_count,
-            entry,
-            machine_target,
-        );
-        let eb = eh.as_bytes();
+fn run_job(m: &Manifest, job: &manifest::Job, force: bool) -> Result<String, String> {
+    let (profile, target) = profile_or_default(&job.profile)?;
+    let opts = ConvertOptions {
+        empty_segments: value_or_default(&job.empty_segments)?,
+        profile,
+        target: target.copied(),
+        passthrough: job.passthrough.unwrap_or_default(),
+        format: value_or_default(&job.format)?,
+        backend: None,
+        defines: job
+            .define_sym
+            .iter()
+            .map(|d| parse_define(d))
+            .collect::<Result<_, _>>()?,
+        best_effort: job.best_effort.unwrap_or_default(),
+        symbols_only: job.symbols_only.unwrap_or_default(),
+        drop_plan9_symtab: job.drop_plan9_symtab.unwrap_or_default(),
+        strip: job.strip.unwrap_or_default(),
+        section_order: job.section_order.clone(),
+        vbase: job.vbase.as_deref().map(parse_number).transpose()?,
+        entry: job.entry.as_deref().map(parse_number).transpose()?,
+        machine: job.machine.as_deref().map(str::parse).transpose()?,
+        class: job.class.as_deref().map(str::parse).transpose()?,
+        elf_flags: job.elf_flags.as_deref().map(parse_flags).transpose()?,
+        os_abi: job.osabi.as_deref().map(str::parse).transpose()?,
+        rename_runes: job.rename_runes.as_deref().map(str::parse).transpose()?,
+        compress_debug: value_or_default(&job.compress_debug_sections)?,
+        plan9_fixups: job.plan9_fixups.unwrap_or_default(),
+        plan9_byte_order: value_or_default(&job.plan9_byte_order)?,
+    };
+    let compress: Compression = value_or_default(&job.compress)?;
+
+    let input = m.path(&job.input);
+    let output = match &job.output {
+        Some(o) => m.path(o),
+        None => format!("{input}{}{}", opts.format.extension(), compress.extension()),
+    };
+    let d = fs::read(&input).map_err(|e| format!("{input}: {e}"))?;
+    let force = force || job.force.unwrap_or_default();
+    convert_file(&d, &output, &opts, compress, force)?;
+    Ok(output)
+}
+
+fn find_in_path(program: &str) -> Option<std::path::PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|d| d.join(program))
+        .find(|p| p.is_file())
+}
+
+// Report what this build and the host offer, and what the input looks like.
+fn doctor(file_name: Option<&str>) {
+    let mark = |ok: bool| if ok { "ok  " } else { "MISS" };
 
-        let mut phb = vec![0u8; 0];
-        for ph in program_headers {
-            let b = ph.as_bytes();
-            phb.extend_from_slice(b);
+    println!("Features:");
+    for c in Compression::value_variants() {
+        if *c != Compression::None {
+            println!("  {} compression {c:?}", mark(c.is_available()));
         }
-        let mut shb = vec![0u8; 0];
-        for sh in section_headers {
-            let b = sh.as_bytes();
-            shb.extend_from_slice(b);
+    }
+    for f in OutputFormat::value_variants() {
+        println!("  {} output format {f:?}", mark(f.is_available()));
+    }
+    println!();
+
+    println!("Helpers:");
+    let helpers = [
+        ("gdb-multiarch", "convert --gdb"),
+        ("readelf", "inspecting output"),
+        ("qemu-system-x86_64", "booting amd64 kernels"),
+        ("qemu-system-riscv64", "booting riscv64 kernels"),
+    ];
+    for (h, purpose) in helpers {
+        match find_in_path(h) {
+            Some(p) => println!("  ok   {h} ({})", p.display()),
+            None => println!("  MISS {h}, needed for {purpose}"),
         }
-        let pad = vec![0u8; PAD_SIZE];
+    }
 
-        let mut stb = vec![0u8; 0];
-        for s in elf_sym_tab {
-            let b = s.as_bytes();
-            stb.extend_from_slice(b);
+    let Some(file_name) = file_name else {
+        return;
+    };
+    println!();
+    println!("Input {file_name}:");
+    let d = match fs::read(file_name) {
+        Ok(d) => d,
+        Err(e) => {
+            println!("  MISS cannot read: {e}");
+            return;
+        }
+    };
+    if d.starts_with(&ELF_MAGIC) {
+        match goblin::elf::Elf::parse(&d) {
+            Ok(_) => println!("  ok   ELF, convert with --passthrough to copy it"),
+            Err(e) => println!("  MISS ELF magic, but does not parse: {e}"),
         }
+        return;
+    }
+    let f = match AoutFile::parse(&d) {
+        Ok(f) => f,
+        Err(e) => {
+            println!("  MISS not a supported a.out: {e}");
+            let found = scan_aouts(&d);
+            if !found.is_empty() {
+                println!("       {} a.out images inside, try --scan", found.len());
+            }
+            return;
+        }
+    };
+    println!("  ok   a.out for {}", f.arch);
+    for r in f.regions() {
+        let ok = r.offset + r.len <= d.len();
+        println!(
+            "  {} {:8} {:08x} bytes @ {:08x}",
+            mark(ok),
+            r.kind,
+            r.len,
+            r.offset
+        );
+    }
+}
 
-        Ok([eb, &phb, &shb, &pad, data, &stb, &sym_str_tab, &sh_str_tab].concat())
-    } else {
-        Err("Could not parse a.out".to_string())
+// Names as understood by gdb's `set architecture`.
+fn gdb_arch(machine: ElfMachine) -> &'static str {
+    match machine {
+        ElfMachine::Amd64 => "i386:x86-64",
+        ElfMachine::X86 => "i386",
+        ElfMachine::Aarch32 => "arm",
+        ElfMachine::Aarch64 => "aarch64",
+        ElfMachine::Mips => "mips",
+        ElfMachine::Sparc => "sparc",
+        ElfMachine::SparcVersion9 => "sparc:v9",
+        ElfMachine::PowerPC => "powerpc:common",
+        ElfMachine::PowerPC64 => "powerpc:common64",
+        ElfMachine::M68k => "m68k",
+        ElfMachine::DigitalAlpha => "alpha",
+        ElfMachine::RiscV => "riscv:rv64",
+        _ => "auto",
     }
 }
 
-impl Display for AoutSymbol<'_> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let t = self.get_type();
-        let sym_type = match t {
-            AoutSymbolType::Unknown => format!("{:02x?}", self.header.sym_type),
-            _ => format!("{t:?}"),
-        };
-        let sym_name = self.name();
-        let v = self.header.value;
-        write!(f, "Symbol {v:08x}: {sym_type:20} {sym_name}")
+// Without a remote, gdb only loads the file.
+fn launch_gdb(
+    elf_file_name: &str,
+    machine: ElfMachine,
+    remote: Option<&str>,
+) -> std::io::Result<()> {
+    let arch = gdb_arch(machine);
+    info!("Launching gdb-multiarch for {elf_file_name} ({arch})");
+    let mut cmd = std::process::Command::new("gdb-multiarch");
+    cmd.arg("-ex").arg(format!("set architecture {arch}"));
+    if let Some(r) = remote {
+        cmd.arg("-ex").arg(format!("target remote {r}"));
+    }
+    let status = cmd.arg(elf_file_name).status()?;
+    if !status.success() {
+        error!("gdb-multiarch exited with {status}");
     }
+    Ok(())
 }
 
-impl AoutSymbol<'_> {
-    pub fn len(&self) -> usize {
-        SYM_HEADER_SIZE + self.name().len() + 1
+// - stands for stdin
+fn read(file_name: &str) -> Result<Vec<u8>, Error> {
+    let io = |e| Error::Io(file_name.to_string(), e);
+    if file_name == "-" {
+        // stdin has no size to check beforehand, the buffer just cannot grow
+        let mut d = vec![];
+        return match std::io::stdin().read_to_end(&mut d) {
+            Ok(_) => Ok(d),
+            Err(e) if e.kind() == std::io::ErrorKind::OutOfMemory => Err(Error::OutOfRange(
+                "reading stdin exceeds --max-memory".to_string(),
+            )),
+            Err(e) => Err(io(e)),
+        };
     }
+    let size = fs::metadata(file_name).map_err(io)?.len();
+    timings::check_available(size, &format!("reading {file_name}"))?;
+    fs::read(file_name).map_err(io)
+}
+
+fn write(file_name: &str, d: &[u8]) -> Result<(), Error> {
+    fs::write(file_name, d).map_err(|e| Error::Io(file_name.to_string(), e))
+}
 
-    pub fn get_type(&self) -> AoutSymbolType {
-        aout_symbol_type(self)
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    if cli.version_json {
+        let c = Capabilities::new();
+        println!("{}", serde_json::to_string_pretty(&c).unwrap());
+        return ExitCode::SUCCESS;
+    }
+    let Some(cmd) = cli.cmd else {
+        let _ = Cli::command().print_help();
+        return ExitCode::SUCCESS;
+    };
+    // Default to log level "info". Otherwise, you get no "regular" logs.
+    let env = env_logger::Env::default().default_filter_or("info");
+    diagnostics::init(env_logger::Builder::from_env(env).build());
+    if let Some(mb) = cli.max_memory {
+        timings::set_limit(mb.saturating_mul(1024 * 1024));
+    }
+    if cli.diagnostics.is_some() {
+        timings::enable();
     }
 
-    pub fn name(&self) -> String {
-        self.name.to_string()
+    let res = run(cmd);
+    if let Err(e) = &res {
+        error!("{e}");
+    }
+    if let Some(d) = cli.diagnostics
+        && let Err(e) = diagnostics::write(&d, res.as_ref().err().map(|e| e.to_string()))
+    {
+        error!("{d}: {e}");
+    }
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => ExitCode::from(exit_code(&e)),
     }
 }
 
-const SYM_HEADER_SIZE: usize = 9;
-// returns the symbol size
-fn parse_sym(st: &[u8]) -> AoutSymbol {
-    if let Ok((header, _)) = AoutSymbolHeader::read_from_prefix(st) {
-        let max_len = 0x80.min(st.len() - SYM_HEADER_SIZE);
-        let s = &st[SYM_HEADER_SIZE..SYM_HEADER_SIZE + max_len];
-        let namex = CStr::from_bytes_until_nul(s).unwrap_or(c"");
-        let name = namex.to_str().unwrap_or("[noname]");
-
-        AoutSymbol { header, name }
-    } else {
-        panic!();
+// Files below the given directories that start with an a.out magic, in
+// path order. Symbolic links are not followed.
+fn find_aouts(dirs: &[String]) -> Result<Vec<String>, Error> {
+    let mut found = vec![];
+    let mut todo: Vec<std::path::PathBuf> = dirs.iter().map(Into::into).collect();
+    while let Some(dir) = todo.pop() {
+        let entries = fs::read_dir(&dir).map_err(|e| Error::Io(dir.display().to_string(), e))?;
+        for entry in entries {
+            let entry = entry.map_err(|e| Error::Io(dir.display().to_string(), e))?;
+            let path = entry.path();
+            let t = entry.file_type()?;
+            if t.is_dir() {
+                todo.push(path);
+                continue;
+            }
+            if !t.is_file() {
+                continue;
+            }
+            let mut head = [0u8; AOUT_HEADER_SIZE];
+            let is_aout = fs::File::open(&path)
+                .and_then(|mut f| f.read_exact(&mut head))
+                .is_ok_and(|_| AoutFile::parse(&head).is_ok());
+            if is_aout {
+                found.push(path.display().to_string());
+            } else {
+                debug!("Skipping {}", path.display());
+            }
+        }
     }
+    found.sort();
+    Ok(found)
 }
 
-fn parse_aout_symbols(st: &[u8], dump: bool) -> Vec<AoutSymbol> {
-    let mut syms: Vec<AoutSymbol> = vec![];
-    let mut offset = 0;
+// Converts each file on its own, reporting all results at the end.
+fn convert_each(cmd: &Command, file_names: &[String]) -> Result<(), Error> {
+    let Command::Convert {
+        output,
+        gdb,
+        recursive,
+        ..
+    } = cmd
+    else {
+        unreachable!();
+    };
+    if output.is_some() || *gdb {
+        let e = "--output and --gdb take a single input file";
+        return Err(Error::Invalid(e.to_string()));
+    }
+    let convert = |f: &String| {
+        let mut c = cmd.clone();
+        if let Command::Convert {
+            file_names,
+            recursive,
+            ..
+        } = &mut c
+        {
+            *file_names = vec![f.clone()];
+            *recursive = false;
+        }
+        run(c)
+    };
+    let results: Vec<_> = if *recursive {
+        file_names.par_iter().map(convert).collect()
+    } else {
+        file_names.iter().map(convert).collect()
+    };
 
-    while offset < st.len() {
-        let sym = parse_sym(&st[offset..]);
-        if dump {
-            match sym.get_type() {
-                AoutSymbolType::Unknown => {
-                    let t = sym.header.sym_type;
-                    let v = sym.header.value;
-                    let h = format!("{t:02x?} {v:08x}");
-                    println!(" {offset:08x}: Unknown symbol {h}");
-                }
-                _ => {
-                    println!(" {offset:08x}: {sym}");
+    println!();
+    let mut failed = 0;
+    for (f, res) in file_names.iter().zip(results) {
+        match res {
+            Ok(()) => println!(" OK      {f}"),
+            Err(e) => {
+                failed += 1;
+                match e {
+                    // already names the file
+                    Error::Io(..) => println!(" FAILED  {e}"),
+                    e => println!(" FAILED  {f}: {e}"),
                 }
             }
         }
-        offset += sym.len();
-        syms.push(sym);
     }
-
-    syms
+    println!("{} succeeded, {failed} failed", file_names.len() - failed);
+    if failed > 0 {
+        return Err(Error::Invalid(format!("{failed} files failed")));
+    }
+    Ok(())
 }
 
-#[derive(Debug, Eq, PartialEq)]
-enum MachineArch {
-    Amd64,
-    Riscv64,
-    Unknown,
-}
+fn run(cmd: Command) -> Result<(), Error> {
+    if let Command::Convert {
+        file_names,
+        recursive: true,
+        ..
+    } = &cmd
+    {
+        let found = find_aouts(file_names)?;
+        if found.is_empty() {
+            return Err(Error::Invalid("no a.out files found".to_string()));
+        }
+        return convert_each(&cmd, &found);
+    }
+    if let Command::Convert { file_names, .. } = &cmd
+        && file_names.len() > 1
+    {
+        return convert_each(&cmd, file_names);
+    }
+    match cmd {
+        Command::Convert {
+            file_names,
+            gdb,
+            gdb_remote,
+            compress,
+            empty_segments,
+            profile,
+            output,
+            output_template,
+            passthrough,
+            format,
+            split_output,
+            scan,
+            all,
+            recursive: _,
+            force,
+            region,
+            show,
+            verify,
+            define_sym,
+            provenance,
+            stats,
+            timings,
+            best_effort,
+            symbols_only,
+            drop_plan9_symtab,
+            strip,
+            section_order,
+            vbase,
+            entry,
+            machine,
+            class,
+            elf_flags,
+            osabi,
+            rename_runes,
+            compress_debug_sections,
+            plan9_fixups,
+            plan9_byte_order,
+        } => {
+            if timings {
+                timings::enable();
+            }
+            let file_name = file_names[0].clone();
+            let (profile, target) = profile;
+            let opts = ConvertOptions {
+                empty_segments,
+                profile,
+                target: target.copied(),
+                passthrough,
+                format,
+                backend: None,
+                defines: define_sym,
+                best_effort,
+                symbols_only,
+                drop_plan9_symtab,
+                strip,
+                section_order,
+                vbase,
+                entry,
+                machine,
+                class,
+                elf_flags,
+                os_abi: osabi,
+                rename_runes,
+                compress_debug: compress_debug_sections,
+                plan9_fixups,
+                plan9_byte_order,
+            };
+            let remote = gdb_remote
+                .as_deref()
+                .or(opts.target.and_then(|t| t.gdb_remote));
+            // the image goes to stdout when reading from stdin
+            let to_stdout = match (&output, &output_template) {
+                (Some(o), _) => o == "-",
+                (None, None) => file_name == "-" && split_output.is_none() && !scan,
+                _ => false,
+            };
+            if to_stdout && (show || gdb || verify) {
+                let e = "--show, --gdb and --verify need an output file, see --output";
+                return Err(Error::Invalid(e.to_string()));
+            }
+            if !to_stdout {
+                println!("File: {file_name}");
+            }
+            let d = region
+                .carve(read(&file_name)?)
+                .map_err(|e| format!("{file_name}: {e}"))?;
+            // damaged inputs may still be converted in part
+            let hint = |e| {
+                if let Error::Truncated("data" | "symbol table") = e
+                    && !best_effort
+                {
+                    info!("Try --best-effort to convert what is there");
+                }
+                e
+            };
 
-fn main() -> std::io::Result<()> {
-    let cmd = Cli::parse().cmd;
-    // Default to log level "info". Otherwise, you get no "regular" logs.
-    let env = env_logger::Env::default().default_filter_or("info");
-    env_logger::Builder::from_env(env).init();
+            if let Some(names) = split_output {
+                let [meta, payload] = names.as_slice() else {
+                    let e = "--split-output takes two file names, e.g. meta.bin,payload.bin";
+                    return Err(Error::Invalid(e.to_string()));
+                };
+                convert_split(&d, meta, payload, &opts, force).map_err(hint)?;
+                info!("Wrote {meta} and {payload}");
+                return Ok(());
+            }
 
-    match cmd {
-        Command::Convert { file_name } => {
-            println!("File: {file_name}");
-            let elf_file_name = format!("{file_name}.elf");
+            if scan {
+                let found = scan_aouts(&d);
+                if found.is_empty() {
+                    let e = format!("No a.out image found in {file_name}");
+                    return Err(Error::Invalid(e));
+                }
+                let count = if all { found.len() } else { 1 };
+                let mut names: Vec<String> = vec![];
+                let mut arches: Vec<MachineArch> = vec![];
+                let mut failed = 0;
+                for r in found.into_iter().take(count) {
+                    let img = &d[r.clone()];
+                    let res = match (&output_template, &output) {
+                        (Some(t), _) => {
+                            convert_templated(img, &file_name, t, &opts, compress, force)
+                        }
+                        (None, Some(n)) => {
+                            convert_file(img, n, &opts, compress, force).map(|_| n.clone())
+                        }
+                        (None, None) => {
+                            let arch = image_arch(img);
+                            let mut n = format!("{file_name}.{arch}");
+                            // several kernels for one arch
+                            let same = arches.iter().filter(|a| **a == arch).count();
+                            arches.push(arch);
+                            if same > 0 {
+                                n = format!("{n}-{same}");
+                            }
+                            let n = format!("{n}{}{}", format.extension(), compress.extension());
+                            convert_file(img, &n, &opts, compress, force).map(|_| n)
+                        }
+                    };
+                    match res {
+                        Ok(n) => {
+                            info!("Wrote {n} from {:#x}..{:#x}", r.start, r.end);
+                            names.push(n);
+                        }
+                        Err(e) => {
+                            error!("{:#x}..{:#x}: {e}", r.start, r.end);
+                            failed += 1;
+                        }
+                    }
+                }
+                if failed > 0 {
+                    let e = format!("{failed} of {count} images in {file_name} failed");
+                    return Err(Error::Invalid(e));
+                }
+                if gdb && let [n] = names.as_slice() {
+                    launch_gdb(n, image_arch(&d[..]).elf_machine(), remote)?;
+                }
+                return Ok(());
+            }
+
+            let elf_file_name = if let Some(t) = output_template {
+                convert_templated(&d, &file_name, &t, &opts, compress, force).map_err(hint)?
+            } else {
+                let ext = compress.extension();
+                let elf_file_name = match output {
+                    Some(o) => o,
+                    None if to_stdout => "-".to_string(),
+                    None => format!("{file_name}{}{ext}", format.extension()),
+                };
+                convert_file(&d, &elf_file_name, &opts, compress, force).map_err(hint)?;
+                elf_file_name
+            };
+            info!("Wrote {elf_file_name}");
+            if verify {
+                let o = read(&elf_file_name)?;
+                let elf =
+                    goblin::elf::Elf::parse(&o).map_err(|e| format!("{elf_file_name}: {e}"))?;
+                println!("Verify {elf_file_name}:");
+                let failed = verify_contents(&AoutFile::parse_as(&d, opts.machine)?, &elf, &o)?
+                    + verify_elf(&o).map_err(|e| format!("{elf_file_name}: {e}"))?;
+                if failed > 0 {
+                    let e = format!("{elf_file_name}: {failed} checks failed");
+                    return Err(Error::Invalid(e));
+                }
+            }
+            if timings && to_stdout {
+                timings::report(&mut std::io::stderr())?;
+            } else if timings {
+                timings::report(&mut std::io::stdout())?;
+            }
 
-            let d = fs::read(file_name).unwrap();
+            if stats && let Err(e) = print_stats(&d, &opts) {
+                error!("stats: {e}");
+            }
 
-            if let Ok(image) = aout_to_elf(&d) {
-                let mut f = fs::File::create(elf_file_name)?;
-                f.write_all(&image);
+            if let Some(p) = provenance {
+                match write_provenance(&d, &opts, &p) {
+                    Ok(()) => info!("Wrote {p}"),
+                    Err(e) => error!("{p}: {e}"),
+                }
+            }
+
+            if show && (compress != Compression::None || format != OutputFormat::Elf) {
+                error!("Can only show uncompressed ELF output");
+            } else if show {
+                let o = read(&elf_file_name)?;
+                match goblin::elf::Elf::parse(&o) {
+                    Ok(elf) => print_elf_summary(&elf),
+                    Err(e) => error!("{elf_file_name}: {e}"),
+                }
+            }
+
+            if gdb && compress != Compression::None {
+                error!("Cannot debug a compressed image, skipping gdb");
+            } else if gdb && format != OutputFormat::Elf {
+                error!("gdb needs ELF output, skipping gdb");
+            } else if gdb && d.starts_with(&ELF_MAGIC) {
+                // gdb knows the architecture from the ELF header
+                launch_gdb(&elf_file_name, ElfMachine::None, remote)?;
+            } else if gdb && let Some(m) = machine {
+                launch_gdb(&elf_file_name, m.elf_machine(), remote)?;
+            } else if gdb && let Ok((aout, _)) = Aout::read_from_prefix(&d) {
+                launch_gdb(&elf_file_name, aout_mach_to_elf(&aout)?, remote)?;
             }
         }
         Command::Parse {
             file_name,
             debug,
             verbose,
+            region,
+            machine,
+            format,
         } => {
+            let d = region
+                .carve(read(&file_name)?)
+                .map_err(|e| format!("{file_name}: {e}"))?;
+            if format == ParseFormat::Json {
+                if d.starts_with(&ELF_MAGIC) {
+                    return Err(format!("{file_name}: JSON describes a.out files only").into());
+                }
+                let r = ParseReport::new(&d, machine).map_err(|e| format!("{file_name}: {e}"))?;
+                println!("{}", serde_json::to_string_pretty(&r).unwrap());
+                return Ok(());
+            }
             println!("File: {file_name}");
-            let d = fs::read(file_name).unwrap();
 
             // TODO: parse Multiboot header, starting with magic 0x1BAD_B002
 
             if let Ok(goblin::Object::Elf(elf)) = goblin::Object::parse(&d) {
-                println!("This is an ELF: {:#02x?}", &elf);
+                if debug {
+                    println!("This is an ELF: {:#02x?}", &elf);
+                } else {
+                    print_elf_summary(&elf);
+                }
                 return Ok(());
             }
 
             if let Ok((aout, _)) = Aout::read_from_prefix(&d) {
-                let m = aout.magic;
-                let arch = match m {
-                    0x978a_0000 => MachineArch::Amd64,
-                    0x178e_0000 => MachineArch::Riscv64,
-                    _ => MachineArch::Unknown,
-                };
+                let m = u32::from_be(aout.magic);
+                match Magic::decode(m) {
+                    Some(magic) => println!("Magic:        {m:08x} {magic}"),
+                    None if machine.is_some() => println!("Magic:        {m:08x} not recognized"),
+                    None => {
+                        println!("a.out not recognized: {m:08x}");
+                        return Ok(());
+                    }
+                }
 
+                let arch = machine.unwrap_or_else(|| MachineArch::from_magic(aout.magic));
                 if arch == MachineArch::Unknown {
-                    println!("a.out not recognized or unsupported architecture: {m:08x}");
+                    println!("Unsupported architecture");
                     return Ok(());
                 }
 
-                println!("Architecture: {arch:?}");
+                println!("Architecture: {arch}");
 
                 let ts: u32 = aout.text_size.into();
                 let ds: u32 = aout.data_size.into();
@@ -1303,40 +2369,218 @@ fn main() -> std::io::Result<()> {
                 // - data
                 // - symbols
                 // - bss?
-                let t_offset = AOUT_HEADER_SIZE + PAD_EXTRA_SIZE;
+                let t_offset = AOUT_HEADER_SIZE + arch.pad_size();
                 let d_offset = t_offset + ts as usize;
                 let st_offset = d_offset + ds as usize;
 
-                let x = if debug {
-                    let pd = &d[t_offset..t_offset + 16];
-                    format!(" {pd:02x?}")
-                } else {
-                    "".to_string()
+                let preview = |offset: usize, len: u32| {
+                    if !debug {
+                        return "".to_string();
+                    }
+                    // regions may be shorter than a preview or cut off
+                    let want = (len as usize).min(16);
+                    let b = d.get(offset..).unwrap_or(&[]);
+                    let b = &b[..want.min(b.len())];
+                    let note = if b.len() < want { " (truncated)" } else { "" };
+                    format!(" {b:02x?}{note}")
                 };
+
+                let x = preview(t_offset, ts);
                 println!("Code:    {ts:08x} bytes @ {t_offset:08x}{x}");
 
-                let x = if debug {
-                    let dd = &d[d_offset..d_offset + 16];
-                    format!(" {dd:02x?}")
-                } else {
-                    "".to_string()
-                };
+                let x = preview(d_offset, ds);
                 println!("Data:    {ds:08x} bytes @ {d_offset:08x}{x}");
 
-                let x = if debug {
-                    let std = &d[st_offset..st_offset + 16];
-                    format!(" {std:02x?}")
-                } else {
-                    "".to_string()
-                };
+                let x = preview(st_offset, sts);
                 println!("Symbols: {sts:08x} bytes @ {st_offset:08x}{x}");
 
+                if verbose && let Ok(f) = AoutFile::parse_as(&d, Some(arch)) {
+                    println!();
+                    for r in f.regions() {
+                        println!("  {:8} {:08x} bytes @ {:08x}", r.kind, r.len, r.offset);
+                    }
+                }
+
                 println!();
-                let sym_table_data = &d[st_offset..st_offset + sts as usize];
+                let st = d.get(st_offset..).unwrap_or(&[]);
+                let st = &st[..st.len().min(sts as usize)];
+                if st.len() < sts as usize {
+                    println!("Symbol table truncated to {:08x} bytes", st.len());
+                }
+                let wide = p9aout2elf::aout::wide_symbols(arch, st);
+                let mut sym_table_data: &[u8] = &wide;
+                if let Err((o, e)) = check_aout_symbols(sym_table_data) {
+                    println!("Symbol table corrupt at {o:08x}: {e}");
+                    sym_table_data = &sym_table_data[..o];
+                }
                 let syms = parse_aout_symbols(sym_table_data, verbose);
                 println!("{} symbols read", syms.len());
+                if verbose {
+                    print_source_files(&syms);
+                }
+            } else {
+                println!("Too short for an a.out header");
+            }
+        }
+        Command::CheckLines { file_name } => {
+            let d = read(&file_name)?;
+            check_lines(&d).map_err(|e| format!("{file_name}: {e}"))?;
+        }
+        Command::Addr2line {
+            file_name,
+            mut addresses,
+            functions,
+        } => {
+            let d = read(&file_name)?;
+            if addresses.is_empty() {
+                for l in std::io::stdin().lines() {
+                    let l = l.map_err(|e| format!("stdin: {e}"))?;
+                    for a in l.split_whitespace() {
+                        addresses.push(parse_hex(a)?);
+                    }
+                }
+            }
+            addr2line(&d, &addresses, functions).map_err(|e| format!("{file_name}: {e}"))?;
+        }
+        Command::Nm {
+            file_name,
+            numeric,
+            unsorted,
+        } => {
+            let d = read(&file_name)?;
+            nm(&d, numeric, unsorted).map_err(|e| format!("{file_name}: {e}"))?;
+        }
+        Command::Compare {
+            old,
+            against: Some(against),
+            ..
+        } => {
+            let o = read(&old)?;
+            let a = read(&against)?;
+            println!("Symbols of {old} against {against}:");
+            cross_reference(&o, &a)?;
+        }
+        Command::Compare {
+            old,
+            new,
+            sections,
+            hex,
+            ..
+        } => {
+            let o = read(&old)?;
+            let n = read(&new.unwrap_or_default())?;
+            if hex {
+                // addresses as in the old file
+                let f = AoutFile::parse(&o).ok();
+                let vaddr = |off: usize| f.as_ref().and_then(|f| f.vaddr(off));
+                let count = hexdiff::print(&o, &n, &vaddr);
+                println!("{count} bytes differ");
+                println!();
+            }
+            compare(&o, &n, sections)?;
+        }
+        Command::Verify { file_name, elf } => {
+            let mut failed = 0;
+            let elf = match elf {
+                Some(elf) => {
+                    let a = read(&file_name)?;
+                    let e = read(&elf)?;
+                    println!("Verify {elf} against {file_name}:");
+                    failed += verify(&a, &e)?;
+                    elf
+                }
+                None => file_name,
+            };
+            let e = read(&elf)?;
+            println!("Verify the structure of {elf}:");
+            failed += verify_elf(&e).map_err(|e| format!("{elf}: {e}"))?;
+            if failed > 0 {
+                return Err(Error::Invalid(format!("{failed} checks failed")));
+            }
+        }
+        Command::Doctor { file_name } => doctor(file_name.as_deref()),
+        Command::Serve => serve::run()?,
+        Command::Batch { manifest, force } => {
+            let m = Manifest::load(&manifest)?;
+            println!("Batch: {} jobs", m.jobs.len());
+            let mut failed = 0;
+            for job in m.jobs.iter() {
+                match run_job(&m, job, force) {
+                    Ok(out) => println!(" OK      {} -> {out}", job.input),
+                    Err(e) => {
+                        failed += 1;
+                        println!(" FAILED  {}: {e}", job.input);
+                    }
+                }
+            }
+            println!("{} succeeded, {failed} failed", m.jobs.len() - failed);
+            if failed > 0 {
+                return Err(Error::Invalid(format!("{failed} jobs failed")));
             }
         }
+        Command::Patch { file_name, exprs } => {
+            let d = read(&file_name)?;
+            let d = patch_file(d, &exprs).map_err(|e| format!("{file_name}: {e}"))?;
+            write(&file_name, &d)?;
+            info!("Applied {} patches to {file_name}", exprs.len());
+        }
+        Command::Strip { file_name, output } => {
+            let d = read(&file_name)?;
+            let s = if d.starts_with(&ELF_MAGIC) {
+                strip_elf(d)
+            } else {
+                AoutFile::parse(&d)
+                    .and_then(|a| a.stripped())
+                    .map_err(|e| e.to_string())
+            }
+            .map_err(|e| format!("{file_name}: {e}"))?;
+            let out = output.unwrap_or(file_name);
+            write(&out, &s)?;
+            info!("Wrote {out}");
+        }
+        Command::Elf2aout {
+            file_name,
+            output,
+            force,
+            machine,
+            strip,
+        } => {
+            let d = read(&file_name)?;
+            let opts = AoutOptions { machine, strip };
+            let a = elf_to_aout(&d, &opts).map_err(|e| format!("{file_name}: {e}"))?;
+            let out = match output {
+                Some(o) => o,
+                None if file_name == "-" => "-".to_string(),
+                None => match file_name.strip_suffix(".elf") {
+                    Some(n) => n.to_string(),
+                    None => format!("{file_name}.aout"),
+                },
+            };
+            let io = |e| Error::Io(out.clone(), e);
+            if out == "-" {
+                std::io::stdout().write_all(&a).map_err(io)?;
+            } else {
+                create(&out, force)?.write_all(&a).map_err(io)?;
+                info!("Wrote {out}");
+            }
+        }
+        Command::SetSection {
+            file_name,
+            section,
+            contents,
+        } => {
+            let d = read(&file_name)?;
+            let c = read(&contents)?;
+
+            let e = SectionEditor::open(d)
+                .and_then(|mut e| {
+                    e.replace(&section, &c)?;
+                    Ok(e)
+                })
+                .map_err(|e| format!("{file_name}: {e}"))?;
+            write(&file_name, &e.into_bytes())?;
+            info!("Replaced {section} in {file_name} ({} bytes)", c.len());
+        }
     }
 
     Ok(())
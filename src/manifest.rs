@@ -0,0 +1,72 @@
+// Batch conversion jobs read from a TOML manifest, e.g.:
+//
+// [[job]]
+// input = "amd64/9pc64"
+// output = "out/9pc64.elf"
+// profile = "kernel"
+// empty_segments = "omit"
+// compress = "gz"
+// force = true
+//
+// Relative paths are resolved against the directory of the manifest.
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Job {
+    pub input: String,
+    pub output: Option<String>,
+    pub force: Option<bool>,
+    pub profile: Option<String>,
+    pub empty_segments: Option<String>,
+    pub compress: Option<String>,
+    pub passthrough: Option<bool>,
+    pub best_effort: Option<bool>,
+    pub symbols_only: Option<bool>,
+    pub drop_plan9_symtab: Option<bool>,
+    pub strip: Option<bool>,
+    pub format: Option<String>,
+    #[serde(default)]
+    pub define_sym: Vec<String>,
+    #[serde(default)]
+    pub section_order: Vec<String>,
+    // a string, as TOML integers are signed
+    pub vbase: Option<String>,
+    pub entry: Option<String>,
+    pub machine: Option<String>,
+    pub class: Option<String>,
+    pub elf_flags: Option<String>,
+    pub osabi: Option<String>,
+    pub rename_runes: Option<String>,
+    pub compress_debug_sections: Option<String>,
+    pub plan9_fixups: Option<bool>,
+    pub plan9_byte_order: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    #[serde(rename = "job", default)]
+    pub jobs: Vec<Job>,
+    #[serde(skip)]
+    pub base: PathBuf,
+}
+
+impl Manifest {
+    pub fn load(file_name: &str) -> Result<Self, String> {
+        let s = fs::read_to_string(file_name).map_err(|e| format!("{file_name}: {e}"))?;
+        let mut m: Manifest = toml::from_str(&s).map_err(|e| format!("{file_name}: {e}"))?;
+        m.base = Path::new(file_name)
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        Ok(m)
+    }
+
+    pub fn path(&self, p: &str) -> String {
+        self.base.join(p).to_string_lossy().to_string()
+    }
+}
@@ -0,0 +1,116 @@
+// Output file names from templates such as '{dir}/{stem}-{sha256:8}.elf'.
+//
+// Placeholders:
+// - {dir}: directory of the input file
+// - {name}: file name of the input
+// - {stem}: file name of the input without extension
+// - {arch}: architecture detected from the a.out magic
+// - {sha256} or {sha256:N}: hex digest of the output, optionally shortened
+//
+// Braces are doubled to stand for themselves, as in Rust format strings.
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+pub fn render(template: &str, input: &str, arch: &str, content: &[u8]) -> Result<String, String> {
+    let p = Path::new(input);
+    let dir = match p.parent() {
+        Some(d) if !d.as_os_str().is_empty() => d.to_string_lossy().to_string(),
+        _ => ".".to_string(),
+    };
+    let name = p.file_name().unwrap_or_default().to_string_lossy();
+    let stem = p.file_stem().unwrap_or_default().to_string_lossy();
+
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        out.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            out.push_str(&tail[..1]);
+            rest = &tail[2..];
+            continue;
+        }
+        if tail.starts_with('}') {
+            return Err(format!("unmatched }} in {template}, use }}}} for a brace"));
+        }
+        let Some(len) = tail.find('}') else {
+            return Err(format!("unterminated placeholder in {template}"));
+        };
+        let key = &rest[start + 1..start + len];
+        let (key, arg) = match key.split_once(':') {
+            Some((k, a)) => (k, Some(a)),
+            None => (key, None),
+        };
+        match (key, arg) {
+            ("dir", None) => out.push_str(&dir),
+            ("name", None) => out.push_str(&name),
+            ("stem", None) => out.push_str(&stem),
+            ("arch", None) => out.push_str(arch),
+            ("sha256", _) => {
+                let digest: String = Sha256::digest(content)
+                    .iter()
+                    .map(|b| format!("{b:02x}"))
+                    .collect();
+                let n = match arg {
+                    Some(a) => a
+                        .parse::<usize>()
+                        .map_err(|_| format!("bad digest length in {{{key}:{a}}}"))?,
+                    None => digest.len(),
+                };
+                out.push_str(&digest[..n.min(digest.len())]);
+            }
+            _ => return Err(format!("unknown placeholder {{{key}}} in {template}")),
+        }
+        rest = &rest[start + len + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA256_ABC: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    fn render_abc(template: &str) -> Result<String, String> {
+        render(template, "/build/amd64/9pc64.aout", "amd64", b"abc")
+    }
+
+    #[test]
+    fn placeholders() {
+        let r = render_abc("{dir}/{stem}-{arch}.elf").unwrap();
+        assert_eq!(r, "/build/amd64/9pc64-amd64.elf");
+        assert_eq!(render_abc("out/{name}").unwrap(), "out/9pc64.aout");
+        assert_eq!(render_abc("{sha256}").unwrap(), SHA256_ABC);
+        assert_eq!(render_abc("{stem}-{sha256:8}").unwrap(), "9pc64-ba7816bf");
+        // no longer than the digest
+        assert_eq!(render_abc("{sha256:100}").unwrap(), SHA256_ABC);
+        // inputs in the current directory
+        let r = render("{dir}/{stem}.elf", "9pc", "386", b"").unwrap();
+        assert_eq!(r, "./9pc.elf");
+    }
+
+    #[test]
+    fn unknown_keys() {
+        for (t, e) in [
+            ("{size}", "unknown placeholder {size} in {size}"),
+            ("{arch:x}", "unknown placeholder {arch} in {arch:x}"),
+            ("{}", "unknown placeholder {} in {}"),
+            ("{sha256:x}", "bad digest length in {sha256:x}"),
+            ("{stem", "unterminated placeholder in {stem"),
+        ] {
+            assert_eq!(render_abc(t).unwrap_err(), e, "{t}");
+        }
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(render_abc("{{stem}}").unwrap(), "{stem}");
+        assert_eq!(render_abc("{{{stem}}}.elf").unwrap(), "{9pc64}.elf");
+        assert_eq!(render_abc("a}}b{{").unwrap(), "a}b{");
+        let e = render_abc("{stem}}").unwrap_err();
+        assert!(e.starts_with("unmatched }"), "{e}");
+    }
+}
@@ -0,0 +1,258 @@
+// Small scripted edits, written as expressions such as
+//
+// text[0x120..0x124] = u32le(0xdeadbeef)
+// .data[0..2] = bytes(cafe)
+//
+// The left side names a region of an a.out (header, pad, text, data, symtab,
+// pc/sp, pc/line, trailer) or a section of an ELF, with a byte range relative
+// to its start. The ends of the range may also be given as a symbol plus an
+// offset, e.g. text[main+4..main+8], for regions with addresses. The right
+// side is one of u8, u16le, u16be, u32le, u32be, u64le, u64be or bytes with
+// hex digits, and has to match the range length.
+use std::ops::Range;
+use std::str::FromStr;
+
+use crate::parse_number;
+
+/// An end of the range, relative to the start of the region once resolved.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Bound {
+    Offset(u64),
+    Symbol(String, u64),
+}
+
+#[derive(Clone, Debug)]
+pub struct Patch {
+    pub region: String,
+    pub start: Bound,
+    pub end: Bound,
+    pub bytes: Vec<u8>,
+}
+
+fn parse_bound(s: &str) -> Result<Bound, String> {
+    let s = s.trim();
+    if s.starts_with(|c: char| c.is_ascii_digit()) {
+        return Ok(Bound::Offset(parse_number(s)?));
+    }
+    let (name, offset) = match s.split_once('+') {
+        Some((n, o)) => (n.trim(), parse_number(o.trim())?),
+        None => (s, 0),
+    };
+    if name.is_empty() {
+        return Err(format!("expected a number or SYMBOL+OFFSET, got {s}"));
+    }
+    Ok(Bound::Symbol(name.to_string(), offset))
+}
+
+fn parse_value(s: &str) -> Result<Vec<u8>, String> {
+    let Some((func, arg)) = s.strip_suffix(')').and_then(|s| s.split_once('(')) else {
+        return Err(format!("expected a value like u32le(1), got {s}"));
+    };
+    let arg = arg.trim();
+    if func == "bytes" {
+        let hex: String = arg.split_whitespace().collect();
+        if !hex.is_ascii() || !hex.len().is_multiple_of(2) {
+            return Err(format!("expected pairs of hex digits in {s}"));
+        }
+        return (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|e| format!("{s}: {e}")))
+            .collect();
+    }
+
+    let v = parse_number(arg)?;
+    let (size, big_endian) = match func {
+        "u8" => (1, false),
+        "u16le" => (2, false),
+        "u16be" => (2, true),
+        "u32le" => (4, false),
+        "u32be" => (4, true),
+        "u64le" => (8, false),
+        "u64be" => (8, true),
+        _ => return Err(format!("unknown value type {func}")),
+    };
+    if size < 8 && v >> (size * 8) != 0 {
+        return Err(format!("{v:#x} does not fit in {func}"));
+    }
+    let b = if big_endian {
+        v.to_be_bytes()[8 - size..].to_vec()
+    } else {
+        v.to_le_bytes()[..size].to_vec()
+    };
+    Ok(b)
+}
+
+impl FromStr for Patch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((target, value)) = s.split_once('=') else {
+            return Err(format!("expected REGION[START..END] = VALUE, got {s}"));
+        };
+        let target = target.trim();
+        let Some((region, range)) = target.strip_suffix(']').and_then(|t| t.split_once('[')) else {
+            return Err(format!("expected REGION[START..END], got {target}"));
+        };
+        let Some((start, end)) = range.split_once("..") else {
+            return Err(format!("expected START..END, got {range}"));
+        };
+        let patch = Patch {
+            region: region.trim().to_string(),
+            start: parse_bound(start)?,
+            end: parse_bound(end)?,
+            bytes: parse_value(value.trim())?,
+        };
+        // the length is known up front unless the ends are different symbols
+        let len = match (&patch.start, &patch.end) {
+            (Bound::Offset(s), Bound::Offset(e)) => e.checked_sub(*s),
+            (Bound::Symbol(a, s), Bound::Symbol(b, e)) if a == b => e.checked_sub(*s),
+            _ => Some(patch.bytes.len() as u64),
+        };
+        match len {
+            None => Err(format!("empty range {range}")),
+            Some(n) if n != patch.bytes.len() as u64 => Err(format!(
+                "{} bytes given for {n} bytes in {target}",
+                patch.bytes.len()
+            )),
+            Some(_) => Ok(patch),
+        }
+    }
+}
+
+impl Patch {
+    /// The byte range in the region, with symbols looked up as offsets
+    /// into it.
+    pub fn range(&self, lookup: impl Fn(&str) -> Option<u64>) -> Result<Range<usize>, String> {
+        let resolve = |b: &Bound| match b {
+            Bound::Offset(o) => Ok(*o),
+            Bound::Symbol(name, o) => {
+                let Some(v) = lookup(name) else {
+                    return Err(format!("no symbol {name} in {}", self.region));
+                };
+                v.checked_add(*o)
+                    .ok_or_else(|| format!("{name}+{o:#x} exceeds 64 bits"))
+            }
+        };
+        let (start, end) = (resolve(&self.start)?, resolve(&self.end)?);
+        if end.checked_sub(start) != Some(self.bytes.len() as u64) {
+            return Err(format!(
+                "{} bytes given for {}[{start:#x}..{end:#x}]",
+                self.bytes.len(),
+                self.region
+            ));
+        }
+        Ok(start as usize..end as usize)
+    }
+
+    /// Applies the patch to the contents of its region.
+    pub fn apply(
+        &self,
+        region: &mut [u8],
+        lookup: impl Fn(&str) -> Option<u64>,
+    ) -> Result<(), String> {
+        let range = self.range(lookup)?;
+        let len = region.len();
+        let Some(b) = region.get_mut(range.clone()) else {
+            return Err(format!(
+                "{}[{:#x}..{:#x}] exceeds its size {len:#x}",
+                self.region, range.start, range.end
+            ));
+        };
+        b.copy_from_slice(&self.bytes);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Result<Patch, String> {
+        s.parse()
+    }
+
+    #[test]
+    fn values() {
+        let p = parse("text[0x120..0x124] = u32le(0xdeadbeef)").unwrap();
+        assert_eq!(p.region, "text");
+        assert_eq!(
+            (p.start, p.end),
+            (Bound::Offset(0x120), Bound::Offset(0x124))
+        );
+        assert_eq!(p.bytes, [0xef, 0xbe, 0xad, 0xde]);
+
+        let p = parse(".data[0..2]=u16be(258)").unwrap();
+        assert_eq!((p.region.as_str(), p.bytes), (".data", vec![1, 2]));
+        let p = parse("data[8..11] = bytes(ca fe 00)").unwrap();
+        assert_eq!(p.bytes, [0xca, 0xfe, 0]);
+    }
+
+    #[test]
+    fn malformed() {
+        for (s, e) in [
+            ("text[0..4]", "expected REGION[START..END] = VALUE"),
+            ("text 0..4 = u32le(1)", "expected REGION[START..END]"),
+            ("text[4] = u32le(1)", "expected START..END"),
+            ("text[4..0] = bytes()", "empty range 4..0"),
+            ("text[0..4] = u32le 1", "expected a value like u32le(1)"),
+            ("text[0..4] = i32le(1)", "unknown value type i32le"),
+            ("text[0..2] = bytes(abc)", "expected pairs of hex digits"),
+            ("text[0..1] = bytes(zz)", "bytes(zz): invalid digit"),
+            (
+                "text[0..4] = u16le(1)",
+                "2 bytes given for 4 bytes in text[0..4]",
+            ),
+            (
+                "text[+4..8] = u32le(1)",
+                "expected a number or SYMBOL+OFFSET",
+            ),
+            ("text[main+x..main+4] = u32le(1)", "x: invalid digit"),
+        ] {
+            let got = parse(s).unwrap_err();
+            assert!(got.contains(e), "{s}: {got}");
+        }
+    }
+
+    #[test]
+    fn overflow() {
+        for (s, e) in [
+            ("text[0..1] = u8(0x100)", "0x100 does not fit in u8"),
+            ("text[0..2] = u16le(65536)", "0x10000 does not fit in u16le"),
+            (
+                "text[0..8] = u64le(0x1ffffffffffffffff)",
+                "number too large",
+            ),
+            ("text[0..0x10000000000000000] = u8(1)", "number too large"),
+        ] {
+            let got = parse(s).unwrap_err();
+            assert!(got.contains(e), "{s}: {got}");
+        }
+        let p = parse("text[main+0xffffffffffffffff..main+0xffffffffffffffff] = bytes()").unwrap();
+        let e = p.range(|_| Some(1)).unwrap_err();
+        assert!(e.contains("exceeds 64 bits"), "{e}");
+    }
+
+    #[test]
+    fn symbol_and_offset() {
+        let lookup = |n: &str| (n == "main").then_some(0x100);
+        let p = parse("text[main+4..main+8] = u32be(1)").unwrap();
+        assert_eq!(p.start, Bound::Symbol("main".to_string(), 4));
+        assert_eq!(p.range(lookup), Ok(0x104..0x108));
+
+        let p = parse("text[main..0x102] = u16le(1)").unwrap();
+        assert_eq!(p.range(lookup), Ok(0x100..0x102));
+        let p = parse("text[main..0x104] = u16le(1)").unwrap();
+        let e = p.range(lookup).unwrap_err();
+        assert!(e.contains("2 bytes given for text[0x100..0x104]"), "{e}");
+
+        let e = parse("text[main+8..main+4] = bytes()").unwrap_err();
+        assert!(e.contains("empty range"), "{e}");
+        let p = parse("text[idle..idle+1] = u8(1)").unwrap();
+        assert_eq!(p.range(lookup), Err("no symbol idle in text".to_string()));
+
+        let mut region = [0; 0x102];
+        let p = parse("text[main..main+4] = u32le(1)").unwrap();
+        let e = p.apply(&mut region, lookup).unwrap_err();
+        assert!(e.contains("exceeds its size 0x102"), "{e}");
+    }
+}
@@ -0,0 +1,159 @@
+// Plan 9 pc/line number table, see 9front sys/src/libmach/sym.c (pc2line).
+//
+// The table is a byte stream; each byte either changes the current line
+// or advances the pc, and every byte steps the pc by one quantum:
+// - 0: line += the following 4-byte big-endian value
+// - 1..=64: line += b
+// - 65..=128: line -= b - 64
+// - 129..: pc += (b - 129) * quantum
+//
+// The pc/sp offset table (pc2sp) is encoded the same way, but small deltas
+// are in units of 4 bytes.
+use std::ops::Range;
+
+// The value from each pc on, starting with 0 one quantum before start.
+fn decode(table: &[u8], start: u64, quantum: u64, scale: i64) -> Vec<(u64, i64)> {
+    let mut pc = start.wrapping_sub(quantum);
+    let mut value = 0;
+    let mut res = vec![(pc, value)];
+    let mut i = 0;
+    while i < table.len() {
+        let b = table[i];
+        i += 1;
+        match b {
+            0 => {
+                let Some(d) = table.get(i..i + 4) else {
+                    break;
+                };
+                value += i32::from_be_bytes([d[0], d[1], d[2], d[3]]) as i64;
+                i += 4;
+            }
+            1..=64 => value += scale * b as i64,
+            65..=128 => value -= scale * (b as i64 - 64),
+            _ => pc = pc.wrapping_add(quantum * (b as u64 - 129)),
+        }
+        pc = pc.wrapping_add(quantum);
+        res.push((pc, value));
+    }
+    res
+}
+
+/// Decodes the table into the line from each pc on, counted over the
+/// whole compilation as by line.
+pub fn lines(table: &[u8], start: u64, quantum: u64) -> Vec<(u64, i64)> {
+    decode(table, start, quantum, 1)
+}
+
+/// Decodes a pc/sp table into the stack offset from each pc on, i.e., how
+/// many bytes the function has taken off the stack pointer.
+pub fn sp_offsets(table: &[u8], start: u64, quantum: u64) -> Vec<(u64, i64)> {
+    decode(table, start, quantum, 4)
+}
+
+/// Decodes the table, returning every pc that has line information.
+pub fn pcs(table: &[u8], start: u64, quantum: u64) -> Vec<u64> {
+    let mut res = vec![];
+    let mut pc = start.wrapping_sub(quantum);
+    let mut i = 0;
+    while i < table.len() {
+        let b = table[i];
+        i += 1;
+        match b {
+            // line deltas do not matter for coverage
+            0 => i += 4,
+            1..=128 => {}
+            _ => pc = pc.wrapping_add(quantum * (b as u64 - 129)),
+        }
+        pc = pc.wrapping_add(quantum);
+        res.push(pc);
+    }
+    res
+}
+
+pub struct Coverage<'a> {
+    /// text symbols without any line information
+    pub missing: Vec<(&'a str, Range<u64>)>,
+    /// line information not belonging to any text symbol
+    pub orphans: Vec<Range<u64>>,
+}
+
+/// Checks sorted pcs against text symbol ranges.
+pub fn coverage<'a>(pcs: &[u64], syms: &[(&'a str, Range<u64>)]) -> Coverage<'a> {
+    let missing = syms
+        .iter()
+        .filter(|(_, r)| {
+            let i = pcs.partition_point(|pc| *pc < r.start);
+            pcs.get(i).is_none_or(|pc| !r.contains(pc))
+        })
+        .cloned()
+        .collect();
+
+    // consecutive pcs outside of any symbol form one range
+    let mut orphans: Vec<Range<u64>> = vec![];
+    let mut extend = false;
+    for &pc in pcs {
+        if syms.iter().any(|(_, r)| r.contains(&pc)) {
+            extend = false;
+            continue;
+        }
+        match orphans.last_mut() {
+            Some(r) if extend => r.end = pc + 1,
+            _ => orphans.push(pc..pc + 1),
+        }
+        extend = true;
+    }
+
+    Coverage { missing, orphans }
+}
+
+/// Line of pc counted over the whole compilation, i.e., including the lines
+/// of all files included, as libmach's pc2line; None if the table ends
+/// before pc.
+pub fn line(table: &[u8], start: u64, quantum: u64, pc: u64) -> Option<i64> {
+    let mut line: i64 = 0;
+    let mut curr = start.wrapping_sub(quantum);
+    if pc < curr {
+        return None;
+    }
+    let mut i = 0;
+    while i < table.len() && curr < pc {
+        let b = table[i];
+        i += 1;
+        match b {
+            0 => {
+                let d = table.get(i..i + 4)?;
+                line += i32::from_be_bytes([d[0], d[1], d[2], d[3]]) as i64;
+                i += 4;
+            }
+            1..=64 => line += b as i64,
+            65..=128 => line -= b as i64 - 64,
+            _ => curr = curr.wrapping_add(quantum * (b as u64 - 129)),
+        }
+        curr = curr.wrapping_add(quantum);
+    }
+    (curr >= pc).then_some(line)
+}
+
+/// File and line within it for a line of the whole compilation, given the
+/// include history of z symbols as (line, path) in order. A history starts
+/// at line 1, and an empty path ends the file included last.
+pub fn file_line(history: &[(u32, String)], aline: i64) -> Option<(&str, i64)> {
+    // path, line it starts at, lines of files included so far
+    let mut stack: Vec<(&str, i64, i64)> = vec![];
+    for (l, path) in history {
+        let l = *l as i64;
+        if l > aline {
+            break;
+        }
+        if l == 1 {
+            stack = vec![(path, l, 0)];
+        } else if path.is_empty() {
+            let (_, start, _) = stack.pop()?;
+            stack.last_mut()?.2 += l - start;
+        } else {
+            stack.push((path, l, 0));
+        }
+    }
+    let (path, start, offset) = stack.last()?;
+    Some((path, aline - start - offset + 1))
+}
@@ -0,0 +1,173 @@
+// The Plan 9 symbol, pc/sp and pc/line tables as kept in .plan9.* sections.
+//
+// Their addresses refer to the text and data of the a.out, so they go stale
+// when the image is relinked or moved after conversion. With fixup tables,
+// tools can find the fields to adjust: .plan9.fixups.X describes .plan9.X,
+// which its sh_link refers to, with records of 8 bytes in the byte order of
+// the ELF:
+// - u32: offset of the field in the section
+// - u8: width of the field in bytes, 0 for a table relative as a whole
+// - u8: where the address points to, FIXUP_TEXT or FIXUP_DATA
+// - u8: byte order of the field, 1 for little and 2 for big endian, as in
+//   e_ident
+// - u8: reserved, 0
+//
+// Symbol values are as in the a.out, i.e., 32 bits above the virtual base on
+// 32-bit targets. The pc tables advance from the start of text.
+//
+// The words in the tables are big-endian, as in the a.out: symbol values,
+// file name indices of z and Z symbols, and large deltas in the pc tables.
+// Tools on little-endian hosts may want them swapped, which .note.plan9
+// then records.
+use clap::ValueEnum;
+
+use crate::dwarf::Writer;
+use crate::{
+    MachineArch, SYM_BSS_SEGMENT, SYM_DATA, SYM_LEAF_FN, SYM_SRC_FILE, SYM_SRC_OFFSET,
+    SYM_STATIC_BSS_SEGMENT, SYM_STATIC_DATA, SYM_STATIC_LEAF_FN, SYM_STATIC_TEXT, SYM_TEXT,
+    name_size,
+};
+
+pub const FIXUP_TEXT: u8 = 1;
+pub const FIXUP_DATA: u8 = 2;
+pub const FIXUP_SIZE: u64 = 8;
+
+// byte orders as in e_ident
+const LITTLE_ENDIAN: u8 = 1;
+const BIG_ENDIAN: u8 = 2;
+
+/// Byte order of the words in the .plan9.* sections
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum ByteOrder {
+    /// as in the a.out
+    #[default]
+    Big,
+    Little,
+    /// that of the machine converting
+    Host,
+}
+
+impl ByteOrder {
+    /// Whether words are to be swapped from the big-endian a.out.
+    pub fn swaps(&self) -> bool {
+        match self {
+            ByteOrder::Big => false,
+            ByteOrder::Little => true,
+            ByteOrder::Host => cfg!(target_endian = "little"),
+        }
+    }
+
+    fn e_ident(&self) -> u8 {
+        if self.swaps() {
+            LITTLE_ENDIAN
+        } else {
+            BIG_ENDIAN
+        }
+    }
+}
+
+/// Entries of a symbol table as stored, i.e., with 4-byte values on 32-bit
+/// targets, as offset and type, up to the first one that cannot be read.
+pub fn symbol_entries(arch: MachineArch, st: &[u8]) -> Vec<(usize, u8)> {
+    let width = value_width(arch);
+    let mut entries = vec![];
+    let mut offset = 0;
+    while let Some(&sym_type) = st.get(offset + width) {
+        let name = &st[offset + width + 1..];
+        let Some(n) = name_size(sym_type, name) else {
+            break;
+        };
+        entries.push((offset, sym_type & !0x80));
+        offset += width + 1 + n;
+    }
+    entries
+}
+
+fn value_width(arch: MachineArch) -> usize {
+    if arch.expanded_header() { 8 } else { 4 }
+}
+
+fn record(w: &mut Writer, offset: usize, width: usize, kind: u8, order: ByteOrder) {
+    w.u32(offset as u32);
+    w.u8(width as u8);
+    w.u8(kind);
+    w.u8(order.e_ident());
+    w.u8(0);
+}
+
+/// Fixups for the values of text, data and bss symbols, which are in the
+/// given byte order.
+pub fn symbol_fixups(arch: MachineArch, st: &[u8], order: ByteOrder, big_endian: bool) -> Vec<u8> {
+    let mut w = Writer::new(big_endian, false);
+    let width = value_width(arch);
+    for (offset, sym_type) in symbol_entries(arch, st) {
+        let kind = match sym_type {
+            SYM_TEXT | SYM_STATIC_TEXT | SYM_LEAF_FN | SYM_STATIC_LEAF_FN => FIXUP_TEXT,
+            SYM_DATA | SYM_STATIC_DATA | SYM_BSS_SEGMENT | SYM_STATIC_BSS_SEGMENT => FIXUP_DATA,
+            _ => continue,
+        };
+        record(&mut w, offset, width, kind, order);
+    }
+    w.data
+}
+
+/// Fixup for a pc table, which is relative to the start of text as a whole.
+pub fn pc_table_fixups(order: ByteOrder, big_endian: bool) -> Vec<u8> {
+    let mut w = Writer::new(big_endian, false);
+    record(&mut w, 0, 0, FIXUP_TEXT, order);
+    w.data
+}
+
+/// The symbol table with its values and file name indices swapped.
+pub fn swap_symbols(arch: MachineArch, st: &[u8]) -> Vec<u8> {
+    let mut d = st.to_vec();
+    let width = value_width(arch);
+    for (offset, sym_type) in symbol_entries(arch, st) {
+        d[offset..offset + width].reverse();
+        if matches!(sym_type, SYM_SRC_FILE | SYM_SRC_OFFSET) {
+            // a 0 byte, then indices up to a 0 one
+            let mut at = offset + width + 2;
+            while d[at..at + 2] != [0, 0] {
+                d[at..at + 2].reverse();
+                at += 2;
+            }
+        }
+    }
+    d
+}
+
+/// The pc table with the 4-byte deltas following 0 swapped.
+pub fn swap_pc_table(t: &[u8]) -> Vec<u8> {
+    let mut d = t.to_vec();
+    let mut i = 0;
+    while i < d.len() {
+        if d[i] == 0 {
+            let Some(w) = d.get_mut(i + 1..i + 5) else {
+                break;
+            };
+            w.reverse();
+            i += 4;
+        }
+        i += 1;
+    }
+    d
+}
+
+// owner of the note, and its type telling the byte order of the tables
+const NOTE_NAME: &[u8] = b"Plan9\0";
+pub const NT_PLAN9_BYTE_ORDER: u32 = 1;
+
+/// Contents of .note.plan9, telling the byte order of the words in the
+/// .plan9.* sections as in e_ident.
+pub fn byte_order_note(order: ByteOrder, big_endian: bool) -> Vec<u8> {
+    let mut w = Writer::new(big_endian, false);
+    w.u32(NOTE_NAME.len() as u32);
+    w.u32(4);
+    w.u32(NT_PLAN9_BYTE_ORDER);
+    w.data.extend_from_slice(NOTE_NAME);
+    // name and descriptor are padded to 4 bytes
+    w.data.resize(w.data.len().next_multiple_of(4), 0);
+    w.u8(order.e_ident());
+    w.data.extend_from_slice(&[0; 3]);
+    w.data
+}
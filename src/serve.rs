@@ -0,0 +1,175 @@
+// Conversion as a long-running service, speaking JSON over stdin/stdout with
+// one request and one response per line, e.g.:
+//
+// {"id": 1, "op": "convert", "data": "<base64 a.out>", "profile": "9boot"}
+// {"id": 1, "ok": true, "data": "<base64 ELF>"}
+//
+// {"id": 2, "op": "parse", "data": "<base64 a.out>"}
+// {"id": 2, "ok": true, "arch": "amd64", "entry": 2097184, ...}
+//
+// Failed requests get "ok": false and an "error"; the service keeps running.
+// All responses carry the schema_version of the JSON.
+// The conversion options are those of batch jobs, without compression.
+use std::io::{BufRead, Write};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use p9aout2elf::aout::AoutFile;
+use p9aout2elf::timings;
+use p9aout2elf::{ConvertOptions, Error, aout_to_elf, check_aout_symbols, parse_aout_symbols};
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    op: String,
+    data: String,
+    profile: Option<String>,
+    empty_segments: Option<String>,
+    passthrough: Option<bool>,
+    best_effort: Option<bool>,
+    symbols_only: Option<bool>,
+    drop_plan9_symtab: Option<bool>,
+    strip: Option<bool>,
+    format: Option<String>,
+    #[serde(default)]
+    define_sym: Vec<String>,
+    #[serde(default)]
+    section_order: Vec<String>,
+    vbase: Option<String>,
+    entry: Option<String>,
+    machine: Option<String>,
+    class: Option<String>,
+    elf_flags: Option<String>,
+    osabi: Option<String>,
+    rename_runes: Option<String>,
+    compress_debug_sections: Option<String>,
+    plan9_fixups: Option<bool>,
+    plan9_byte_order: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct RegionInfo {
+    kind: String,
+    offset: usize,
+    len: usize,
+}
+
+impl Request {
+    fn options(&self) -> Result<ConvertOptions, String> {
+        let (profile, target) = crate::profile_or_default(&self.profile)?;
+        Ok(ConvertOptions {
+            empty_segments: crate::value_or_default(&self.empty_segments)?,
+            profile,
+            target: target.copied(),
+            passthrough: self.passthrough.unwrap_or_default(),
+            format: crate::value_or_default(&self.format)?,
+            backend: None,
+            defines: self
+                .define_sym
+                .iter()
+                .map(|d| crate::parse_define(d))
+                .collect::<Result<_, _>>()?,
+            best_effort: self.best_effort.unwrap_or_default(),
+            symbols_only: self.symbols_only.unwrap_or_default(),
+            drop_plan9_symtab: self.drop_plan9_symtab.unwrap_or_default(),
+            strip: self.strip.unwrap_or_default(),
+            section_order: self.section_order.clone(),
+            vbase: self
+                .vbase
+                .as_deref()
+                .map(p9aout2elf::parse_number)
+                .transpose()?,
+            entry: self
+                .entry
+                .as_deref()
+                .map(p9aout2elf::parse_number)
+                .transpose()?,
+            machine: self.machine.as_deref().map(str::parse).transpose()?,
+            class: self.class.as_deref().map(str::parse).transpose()?,
+            elf_flags: self
+                .elf_flags
+                .as_deref()
+                .map(crate::parse_flags)
+                .transpose()?,
+            os_abi: self.osabi.as_deref().map(str::parse).transpose()?,
+            rename_runes: self.rename_runes.as_deref().map(str::parse).transpose()?,
+            compress_debug: crate::value_or_default(&self.compress_debug_sections)?,
+            plan9_fixups: self.plan9_fixups.unwrap_or_default(),
+            plan9_byte_order: crate::value_or_default(&self.plan9_byte_order)?,
+        })
+    }
+}
+
+fn parse(d: &[u8]) -> Result<Value, Error> {
+    let f = AoutFile::parse(d)?;
+    let regions: Vec<RegionInfo> = f
+        .regions()
+        .iter()
+        .map(|r| RegionInfo {
+            kind: r.kind.to_string(),
+            offset: r.offset,
+            len: r.len,
+        })
+        .collect();
+    let st = f.symbol_table().ok_or(Error::Truncated("symbol table"))?;
+    let st = match check_aout_symbols(&st) {
+        Ok(()) => &st[..],
+        Err((o, _)) => &st[..o],
+    };
+    Ok(json!({
+        "arch": f.arch.to_string(),
+        // the full address on 64-bit targets, as the header only has 32 bits
+        "entry": f.entry64().unwrap_or(u32::from(f.header.entry_point).into()),
+        "regions": regions,
+        "symbols": parse_aout_symbols(st, false).len(),
+    }))
+}
+
+fn handle(line: &str) -> Value {
+    let req: Request = match serde_json::from_str(line) {
+        Ok(r) => r,
+        Err(e) => return json!({ "id": null, "ok": false, "error": e.to_string() }),
+    };
+    let res = BASE64
+        .decode(&req.data)
+        .map_err(|e| Error::Invalid(format!("data: {e}")))
+        .and_then(|d| match req.op.as_str() {
+            "convert" => {
+                let elf = aout_to_elf(&d, &req.options()?)?;
+                let size = elf.len().div_ceil(3) * 4;
+                timings::check_available(size as u64, "encoding the ELF as base64")?;
+                Ok(json!({ "data": BASE64.encode(elf) }))
+            }
+            "parse" => parse(&d),
+            op => Err(Error::Invalid(format!("unknown op {op}"))),
+        });
+    match res {
+        Ok(mut v) => {
+            v["id"] = req.id;
+            v["ok"] = true.into();
+            v
+        }
+        Err(e) => json!({ "id": req.id, "ok": false, "error": e.to_string() }),
+    }
+}
+
+/// Answers requests from stdin until it is closed.
+pub fn run() -> Result<(), Error> {
+    let mut out = std::io::stdout().lock();
+    for line in std::io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut res = handle(&line);
+        res["schema_version"] = crate::SCHEMA_VERSION.into();
+        writeln!(out, "{res}")?;
+        out.flush()?;
+    }
+    Ok(())
+}
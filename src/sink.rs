@@ -0,0 +1,88 @@
+// Output sinks for the ELF encoder, optionally compressing on the fly.
+// Offsets inside the image always refer to the uncompressed payload.
+use std::io::{self, Write};
+
+use clap::ValueEnum;
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum Compression {
+    #[default]
+    None,
+    Gz,
+    Zstd,
+}
+
+impl Compression {
+    // whether support for this was compiled in
+    pub fn is_available(&self) -> bool {
+        match self {
+            Compression::None => true,
+            Compression::Gz => cfg!(feature = "gzip"),
+            Compression::Zstd => cfg!(feature = "zstd"),
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Compression::None => "",
+            Compression::Gz => ".gz",
+            Compression::Zstd => ".zst",
+        }
+    }
+}
+
+/// A byte sink that may hold trailing state (e.g. a compressor) which
+/// has to be flushed once the image is complete.
+pub trait Sink: Write {
+    fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+struct Plain<W: Write>(W);
+
+impl<W: Write> Write for Plain<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl<W: Write> Sink for Plain<W> {
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl<W: Write> Sink for flate2::write::GzEncoder<W> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        (*self).finish().map(|_| ())
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl<W: Write> Sink for zstd::stream::write::Encoder<'static, W> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        (*self).finish().map(|_| ())
+    }
+}
+
+pub fn sink<'a, W: Write + 'a>(w: W, compression: Compression) -> io::Result<Box<dyn Sink + 'a>> {
+    match compression {
+        Compression::None => Ok(Box::new(Plain(w))),
+        #[cfg(feature = "gzip")]
+        Compression::Gz => {
+            let level = flate2::Compression::best();
+            Ok(Box::new(flate2::write::GzEncoder::new(w, level)))
+        }
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => Ok(Box::new(zstd::stream::write::Encoder::new(w, 0)?)),
+        #[allow(unreachable_patterns)]
+        c => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("{c:?} compression is not compiled in"),
+        )),
+    }
+}
@@ -0,0 +1,90 @@
+// ELF string tables, i.e., NUL-terminated names referred to by offset.
+//
+// See <https://refspecs.linuxfoundation.org/elf/gabi4+/ch4.strtab.html>
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// Plan 9 separates package and function names with a middle dot, e.g.
+/// in Alef and Go symbols.
+pub const MIDDLE_DOT: char = '·';
+
+/// A string table that stores each name once. Offset 0 holds the empty
+/// name, as ELF requires.
+#[derive(Clone, Debug)]
+pub struct StringTable {
+    data: Vec<u8>,
+    offsets: HashMap<String, u32>,
+    // for the middle dot, with the original names for each stored one
+    replacement: Option<char>,
+    originals: HashMap<String, String>,
+    collisions: Vec<(String, String)>,
+}
+
+impl Default for StringTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StringTable {
+    pub fn new() -> Self {
+        Self {
+            data: vec![0],
+            offsets: HashMap::from([(String::new(), 0)]),
+            replacement: None,
+            originals: HashMap::new(),
+            collisions: vec![],
+        }
+    }
+
+    /// A table that stores names with the middle dot replaced by c.
+    pub fn with_replacement(c: char) -> Self {
+        Self {
+            replacement: Some(c),
+            ..Self::new()
+        }
+    }
+
+    /// Offset of name, which is appended unless it is in the table already.
+    pub fn add(&mut self, name: &str) -> u32 {
+        let stored = match self.replacement {
+            Some(c) if name.contains(MIDDLE_DOT) => {
+                Cow::Owned(name.replace(MIDDLE_DOT, c.encode_utf8(&mut [0; 4])))
+            }
+            _ => Cow::Borrowed(name),
+        };
+        if self.replacement.is_some() {
+            let original = self
+                .originals
+                .entry(stored.to_string())
+                .or_insert_with(|| name.to_string());
+            if original != name {
+                let c = (original.clone(), name.to_string());
+                if !self.collisions.contains(&c) {
+                    self.collisions.push(c);
+                }
+            }
+        }
+        if let Some(o) = self.offsets.get(stored.as_ref()) {
+            return *o;
+        }
+        let o = self.data.len() as u32;
+        self.data.extend_from_slice(stored.as_bytes());
+        self.data.push(0);
+        self.offsets.insert(stored.into_owned(), o);
+        o
+    }
+
+    /// Pairs of different names that became the same by replacement.
+    pub fn collisions(&self) -> &[(String, String)] {
+        &self.collisions
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+}
@@ -0,0 +1,144 @@
+// Per-stage wall time and peak heap usage, reported with --timings.
+//
+// Heap usage is tracked by a counting wrapper around the system allocator,
+// which only costs a few atomic operations per allocation. It also enforces
+// the budget of --max-memory as a last resort, failing allocations beyond.
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// Allocator to install as the global one for heap usage to be reported.
+pub struct CountingAlloc;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+static LIMIT: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+// Counts size more bytes in use, unless that exceeds the limit, returning
+// the bytes in use then.
+fn reserve(size: usize) -> Option<usize> {
+    let now = CURRENT.fetch_add(size, Ordering::Relaxed) + size;
+    if now > LIMIT.load(Ordering::Relaxed) {
+        CURRENT.fetch_sub(size, Ordering::Relaxed);
+        return None;
+    }
+    Some(now)
+}
+
+// Runs an allocation by the system allocator that takes size more bytes,
+// counting them if it succeeds.
+fn counted(size: usize, alloc: impl FnOnce() -> *mut u8) -> *mut u8 {
+    let Some(now) = reserve(size) else {
+        return std::ptr::null_mut();
+    };
+    let p = alloc();
+    if p.is_null() {
+        CURRENT.fetch_sub(size, Ordering::Relaxed);
+    } else {
+        PEAK.fetch_max(now, Ordering::Relaxed);
+    }
+    p
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        counted(layout.size(), || unsafe { System.alloc(layout) })
+    }
+
+    // zeroed pages from the OS are not written, as the default would
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        counted(layout.size(), || unsafe { System.alloc_zeroed(layout) })
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    // growing in place saves the copy the default always makes
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let old_size = layout.size();
+        if new_size > old_size {
+            return counted(new_size - old_size, || unsafe {
+                System.realloc(ptr, layout, new_size)
+            });
+        }
+        let p = unsafe { System.realloc(ptr, layout, new_size) };
+        if !p.is_null() {
+            CURRENT.fetch_sub(old_size - new_size, Ordering::Relaxed);
+        }
+        p
+    }
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+struct Stage {
+    name: &'static str,
+    time: Duration,
+    // peak heap usage during the stage, above what was in use before
+    peak: usize,
+}
+
+static STAGES: Mutex<Vec<Stage>> = Mutex::new(vec![]);
+
+/// Limits heap usage to the given number of bytes.
+pub fn set_limit(bytes: usize) {
+    LIMIT.store(bytes, Ordering::Relaxed);
+}
+
+/// Heap bytes that may still be allocated, if there is a limit.
+pub fn available() -> Option<usize> {
+    let limit = LIMIT.load(Ordering::Relaxed);
+    (limit != usize::MAX).then(|| limit.saturating_sub(CURRENT.load(Ordering::Relaxed)))
+}
+
+/// Fails if holding size more bytes would exceed the limit. Checked before
+/// large allocations, so that they fail with an error instead of aborting.
+pub fn check_available(size: u64, what: &str) -> Result<(), crate::Error> {
+    match available() {
+        Some(a) if size > a as u64 => Err(crate::Error::OutOfRange(format!(
+            "{what} needs {size} bytes, but only {a} are left under --max-memory"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Runs f, recording its time and allocations if enabled.
+pub fn stage<T>(name: &'static str, f: impl FnOnce() -> T) -> T {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return f();
+    }
+    let base = CURRENT.load(Ordering::Relaxed);
+    PEAK.store(base, Ordering::Relaxed);
+    let start = Instant::now();
+    let res = f();
+    let time = start.elapsed();
+    let peak = PEAK.load(Ordering::Relaxed).saturating_sub(base);
+    STAGES.lock().unwrap().push(Stage { name, time, peak });
+    res
+}
+
+/// Recorded stages as name, time and peak heap usage.
+pub fn stages() -> Vec<(&'static str, Duration, usize)> {
+    let stages = STAGES.lock().unwrap();
+    stages.iter().map(|s| (s.name, s.time, s.peak)).collect()
+}
+
+pub fn report(w: &mut dyn Write) -> std::io::Result<()> {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    writeln!(w, "Timings:")?;
+    for s in STAGES.lock().unwrap().iter() {
+        let ms = s.time.as_secs_f64() * 1000.0;
+        writeln!(w, "  {:8} {ms:10.3} ms {:12} bytes peak", s.name, s.peak)?;
+    }
+    Ok(())
+}
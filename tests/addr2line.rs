@@ -0,0 +1,71 @@
+mod common;
+
+use common::*;
+
+fn zsym(value: u64, indices: &[u16]) -> Vec<u8> {
+    let mut s = value.to_be_bytes().to_vec();
+    s.push(b'z' | 0x80);
+    s.push(0);
+    for i in indices.iter().chain(&[0]) {
+        s.extend_from_slice(&i.to_be_bytes());
+    }
+    s
+}
+
+// main.c includes u.h at line 3 of the compilation, which ends at line 10
+fn kernel(dir: &std::path::Path) -> std::path::PathBuf {
+    let input = dir.join("9rv");
+    let mut syms = vec![];
+    for (i, c) in ["/", "sys", "main.c", "u.h"].iter().enumerate() {
+        syms.extend(sym(i as u64 + 1, b'f', c));
+    }
+    syms.extend(zsym(1, &[1, 2, 3]));
+    syms.extend(zsym(3, &[1, 2, 4]));
+    syms.extend(zsym(10, &[]));
+    syms.extend(sym(RISCV64_ENTRY, b'T', "main"));
+    syms.extend(sym(RISCV64_ENTRY + 0x6, b'T', "spllo"));
+    let mut image = aout(RISCV64_MAGIC, RISCV64_ENTRY, &[0x13; 0x20], &[], 0, &syms);
+
+    // line 12 at the entry, then two quanta further at line 4
+    let pcline = [12, 130, 72];
+    image[28..32].copy_from_slice(&(pcline.len() as u32).to_be_bytes());
+    image.extend_from_slice(&pcline);
+    std::fs::write(&input, image).unwrap();
+    input
+}
+
+#[test]
+fn addresses_map_to_file_and_line() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = kernel(dir.path());
+
+    let out = run(&[
+        "addr2line",
+        input.to_str().unwrap(),
+        "--functions",
+        &format!("{RISCV64_ENTRY:x}"),
+        &format!("{:#x}", RISCV64_ENTRY + 0x6),
+        "0x10",
+    ]);
+    assert!(out.status.success(), "{out:?}");
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(
+        lines,
+        ["main", "/sys/main.c:5", "spllo", "/sys/u.h:2", "??", "??:0"]
+    );
+}
+
+#[test]
+fn no_pcline_table() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9rv");
+    let syms = sym(RISCV64_ENTRY, b'T', "main");
+    let image = aout(RISCV64_MAGIC, RISCV64_ENTRY, &[0x13; 0x20], &[], 0, &syms);
+    std::fs::write(&input, image).unwrap();
+
+    let out = run(&["addr2line", input.to_str().unwrap(), "0"]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("no pc/line table"), "{stderr}");
+}
@@ -0,0 +1,78 @@
+use p9aout2elf::addrmath::*;
+use proptest::prelude::*;
+
+fn align() -> impl Strategy<Value = u64> {
+    prop_oneof![
+        Just(0u64),
+        Just(1),
+        (0u32..64).prop_map(|b| 1u64 << b),
+        1u64..10_000
+    ]
+}
+
+proptest! {
+    #[test]
+    fn align_up_is_smallest_multiple(v: u64, a in align()) {
+        match align_up(v, a) {
+            Some(r) if a <= 1 => prop_assert_eq!(r, v),
+            Some(r) => {
+                prop_assert!(r >= v && r.is_multiple_of(a));
+                prop_assert!(r - v < a);
+            }
+            None => prop_assert!(v.div_ceil(a) as u128 * a as u128 > u64::MAX as u128),
+        }
+    }
+
+    #[test]
+    fn align_down_is_largest_multiple(v: u64, a in align()) {
+        let r = align_down(v, a);
+        prop_assert!(r <= v);
+        if a > 1 {
+            prop_assert!(r.is_multiple_of(a) && v - r < a);
+        }
+    }
+
+    #[test]
+    fn padding_makes_congruent(offset in 0u64..u64::MAX / 2, addr: u64, a in align()) {
+        let pad = congruence_padding(offset, addr, a);
+        prop_assert!(is_congruent(offset + pad, addr, a));
+        prop_assert!(pad < a.max(1));
+    }
+
+    #[test]
+    fn overlaps_is_symmetric(a: u64, a_len: u64, b: u64, b_len: u64) {
+        prop_assert_eq!(overlaps(a, a_len, b, b_len), overlaps(b, b_len, a, a_len));
+    }
+
+    #[test]
+    fn contained_ranges_overlap(outer in 0..u64::MAX / 2, outer_len in 1u64..1 << 40, off: u64, len: u64) {
+        let off = off % outer_len;
+        let len = len % (outer_len - off) + 1;
+        prop_assert!(contains(outer, outer_len, outer + off, len));
+        prop_assert!(overlaps(outer, outer_len, outer + off, len));
+    }
+
+    #[test]
+    fn data_follows_text_on_next_page(entry: u32, ts: u32, page in (12u32..17).prop_map(|b| 1u64 << b)) {
+        let d = data_address(entry, ts, page);
+        let text_end = entry as u64 + ts as u64;
+        prop_assert!(d >= text_end);
+        prop_assert!(d.is_multiple_of(page));
+        prop_assert!(d - text_end < page);
+    }
+}
+
+#[test]
+fn edges() {
+    assert_eq!(align_up(u64::MAX, 4096), None);
+    assert_eq!(align_up(0, 4096), Some(0));
+    assert!(!overlaps(0x1000, 0, 0x800, 0x1000));
+    assert!(overlaps(u64::MAX - 1, 2, u64::MAX, 1));
+    assert!(contains(0, u64::MAX, u64::MAX - 1, 1));
+    assert_eq!(congruence_padding(0x1234, 0x8020_0000, 0x1000), 0xdcc);
+    // the header takes up the start of the first text page
+    assert_eq!(data_address(0xf010_0020, 0x20, PAGE_SIZE), 0xf010_1000);
+    assert_eq!(data_address(0xf010_0020, 0xfe0, PAGE_SIZE), 0xf010_1000);
+    assert_eq!(data_address(0xf010_0020, 0xfe1, PAGE_SIZE), 0xf010_2000);
+    assert_eq!(data_address(0xf010_0020, 0x20, 0x10000), 0xf011_0000);
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+// 41, the original alpha machine type
+use goblin::elf::header::EM_FAKE_ALPHA as EM_ALPHA;
+use goblin::elf::program_header::PT_LOAD;
+
+// kernels are linked at KZERO 0x80000000
+const ENTRY: u32 = 0x8040_0020;
+const VIRTUAL_ENTRY: u64 = 0xffff_ffff_8040_0020;
+
+#[test]
+fn kernel_is_elf64_with_sign_extended_addresses() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9apc");
+    let mut syms = sym32(ENTRY, b'T', "_main");
+    syms.extend(sym32(ENTRY + 0x10, b'T', "etext"));
+    let image = aout(
+        ALPHA_MAGIC,
+        ENTRY as u64,
+        &[0x1f; 0x20],
+        &[0xaa; 0x10],
+        0,
+        &syms,
+    );
+    std::fs::write(&input, image).unwrap();
+
+    let elf_image = convert(&input, &[]);
+    let elf = Elf::parse(&elf_image).unwrap();
+
+    assert!(elf.is_64);
+    assert!(elf.little_endian);
+    assert_eq!(elf.header.e_machine, EM_ALPHA);
+    assert_eq!(elf.header.e_entry, VIRTUAL_ENTRY);
+    let text = elf
+        .program_headers
+        .iter()
+        .find(|p| p.p_type == PT_LOAD)
+        .unwrap();
+    assert_eq!(text.p_vaddr, VIRTUAL_ENTRY);
+    assert_eq!(text.p_paddr, 0x40_0020);
+    let main = elf
+        .syms
+        .iter()
+        .find(|s| elf.strtab.get_at(s.st_name) == Some("_main"));
+    assert_eq!(main.unwrap().st_value, VIRTUAL_ENTRY);
+}
+
+// Found by fuzzing: rebasing data past the top of the address space
+// overflowed instead of failing.
+#[test]
+fn addresses_beyond_64_bits() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9apc");
+    let image = aout(
+        ALPHA_MAGIC,
+        0xffff_ffff,
+        &[0x1f; 0x20],
+        &[0xaa; 0x10],
+        0,
+        &[],
+    );
+    std::fs::write(&input, image).unwrap();
+
+    for extra in [
+        &[][..],
+        &["--best-effort"],
+        &["--class", "elf32"],
+        &["--verify"],
+        &["--vbase", "0xffffffffffff0000"],
+    ] {
+        let mut args = vec!["convert", "-f", input.to_str().unwrap()];
+        args.extend_from_slice(extra);
+        let out = run(&args);
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert_eq!(out.status.code(), Some(5), "{extra:?}: {stderr}");
+        assert!(stderr.contains("exceeds 64 bits"), "{extra:?}: {stderr}");
+    }
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+use goblin::elf::header::EM_X86_64;
+use goblin::elf::program_header::PT_LOAD;
+
+// 9front pc64 kernels are linked at KZERO 0xffffffff80000000.
+const ENTRY: u64 = 0xffff_ffff_8011_0028;
+
+fn pc64_kernel(dir: &std::path::Path) -> std::path::PathBuf {
+    let input = dir.join("9pc64");
+    let mut syms = sym(ENTRY, b'T', "_main");
+    syms.extend(sym(ENTRY + 0x10, b'T', "etext"));
+    let image = aout(AMD64_MAGIC, ENTRY, &[0x90; 0x20], &[0xaa; 0x10], 0, &syms);
+    std::fs::write(&input, image).unwrap();
+    input
+}
+
+#[test]
+fn kernel_is_elf64_at_the_expanded_entry() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = pc64_kernel(dir.path());
+
+    let image = convert(&input, &[]);
+    let elf = Elf::parse(&image).unwrap();
+
+    assert!(elf.is_64);
+    assert_eq!(elf.header.e_machine, EM_X86_64);
+    assert_eq!(elf.header.e_entry, ENTRY);
+    let text = elf
+        .program_headers
+        .iter()
+        .find(|p| p.p_type == PT_LOAD)
+        .unwrap();
+    assert_eq!(text.p_vaddr, ENTRY);
+    // loaded at 1 MiB and up
+    assert_eq!(text.p_paddr, 0x11_0028);
+    let main = elf
+        .syms
+        .iter()
+        .find(|s| elf.strtab.get_at(s.st_name) == Some("_main"));
+    assert_eq!(main.unwrap().st_value, ENTRY);
+}
+
+#[test]
+fn nineboot_image_stays_elf32() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = pc64_kernel(dir.path());
+
+    let image = convert(&input, &["--profile", "9boot"]);
+    let elf = Elf::parse(&image).unwrap();
+
+    assert!(!elf.is_64);
+    assert_eq!(elf.header.e_entry, ENTRY & 0xffff_ffff);
+}
@@ -0,0 +1,65 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+use goblin::elf::header::EM_ARM;
+use goblin::elf::program_header::PT_LOAD;
+
+// Raspberry Pi (bcm) kernels are linked at KZERO 0x80000000 and loaded at
+// 0x8000 by the firmware.
+const ENTRY: u64 = 0x8000_8000;
+
+#[test]
+fn bcm_kernel_is_elf32_at_kzero() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9pi");
+    let mut syms = sym32(ENTRY as u32, b'T', "_start");
+    syms.extend(sym32(ENTRY as u32 + 0x10, b'T', "etext"));
+    let image = aout(ARM_MAGIC, ENTRY, &[0xe1; 0x20], &[0xaa; 0x10], 0, &syms);
+    std::fs::write(&input, image).unwrap();
+
+    let image = convert(&input, &[]);
+    let elf = Elf::parse(&image).unwrap();
+
+    assert!(!elf.is_64);
+    assert_eq!(elf.header.e_machine, EM_ARM);
+    assert_eq!(elf.header.e_entry, ENTRY);
+    let text = elf
+        .program_headers
+        .iter()
+        .find(|p| p.p_type == PT_LOAD)
+        .unwrap();
+    assert_eq!(text.p_vaddr, ENTRY);
+    assert_eq!(text.p_paddr, 0x8000);
+
+    let start = elf
+        .syms
+        .iter()
+        .find(|s| elf.strtab.get_at(s.st_name) == Some("_start"));
+    assert_eq!(start.unwrap().st_value, ENTRY);
+}
+
+#[test]
+fn attributes() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9pi");
+    let syms = sym32(ENTRY as u32, b'T', "_start");
+    let image = aout(ARM_MAGIC, ENTRY, &[0xe1; 0x20], &[], 0, &syms);
+    std::fs::write(&input, image).unwrap();
+
+    let image = convert(&input, &[]);
+    let elf = Elf::parse(&image).unwrap();
+    let sh = elf
+        .section_headers
+        .iter()
+        .find(|s| elf.shdr_strtab.get_at(s.sh_name) == Some(".ARM.attributes"))
+        .expect("no .ARM.attributes");
+    // SHT_ARM_ATTRIBUTES, which goblin does not define
+    assert_eq!(sh.sh_type, 0x7000_0003);
+    let d = &image[sh.sh_offset as usize..][..sh.sh_size as usize];
+    // version, length, vendor, then the file attributes: v4, ARM but not
+    // Thumb, no FP
+    let mut expected = b"A\x17\0\0\0aeabi\0\x01\x0d\0\0\0".to_vec();
+    expected.extend_from_slice(&[6, 1, 8, 1, 9, 0, 10, 0]);
+    assert_eq!(d, expected);
+}
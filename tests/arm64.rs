@@ -0,0 +1,42 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+use goblin::elf::header::EM_AARCH64;
+use goblin::elf::program_header::PT_LOAD;
+
+// Only the expanded header has the high bits of the kernel address.
+const ENTRY: u64 = 0xffff_ffff_c010_0000;
+
+#[test]
+fn kernel_is_elf64_at_high_base() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9arm64");
+    let mut syms = sym(ENTRY, b'T', "_start");
+    syms.extend(sym(ENTRY + 0x10, b'T', "etext"));
+    let image = aout(ARM64_MAGIC, ENTRY, &[0x1f; 0x20], &[0xaa; 0x10], 0, &syms);
+    std::fs::write(&input, image).unwrap();
+
+    let image = convert(&input, &[]);
+    let elf = Elf::parse(&image).unwrap();
+
+    assert!(elf.is_64);
+    assert_eq!(elf.header.e_machine, EM_AARCH64);
+    assert_eq!(elf.header.e_entry, ENTRY);
+    let text = elf
+        .program_headers
+        .iter()
+        .find(|p| p.p_type == PT_LOAD)
+        .unwrap();
+    assert_eq!(text.p_vaddr, ENTRY);
+
+    let start = elf
+        .syms
+        .iter()
+        .find(|s| elf.strtab.get_at(s.st_name) == Some("_start"));
+    assert_eq!(start.unwrap().st_value, ENTRY);
+
+    let elf = format!("{}.elf", input.display());
+    let out = run(&["verify", input.to_str().unwrap(), &elf]);
+    assert!(out.status.success(), "{out:?}");
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use std::io::Write;
+
+use common::*;
+use p9aout2elf::backend::{self, Backend};
+use p9aout2elf::layout::ElfImage;
+use p9aout2elf::{ConvertOptions, Error, aout_to_elf};
+
+// entry point and section names
+struct Listing(&'static str);
+
+impl Backend for Listing {
+    fn name(&self) -> &'static str {
+        self.0
+    }
+
+    fn extension(&self) -> &'static str {
+        ".txt"
+    }
+
+    fn write(&self, image: &ElfImage, w: &mut dyn Write) -> Result<(), Error> {
+        writeln!(w, "{:#x}", image.entry)?;
+        for s in &image.sections {
+            writeln!(w, "{}", s.name)?;
+        }
+        Ok(())
+    }
+}
+
+static LISTING: Listing = Listing("listing");
+static ELF: Listing = Listing("elf");
+
+// One test, as the registry is shared by all threads.
+#[test]
+fn registered_backends() {
+    let syms = sym(RISCV64_ENTRY, b'T', "main");
+    let image = aout(
+        RISCV64_MAGIC,
+        RISCV64_ENTRY,
+        &[0x13; 0x20],
+        &[1; 8],
+        0,
+        &syms,
+    );
+    let opts = ConvertOptions {
+        backend: Some("listing"),
+        ..Default::default()
+    };
+    let e = aout_to_elf(&image, &opts).unwrap_err();
+    assert_eq!(e.to_string(), "no backend listing registered");
+
+    backend::register(&LISTING).unwrap();
+    assert!(backend::register(&LISTING).is_err());
+    assert!(backend::register(&ELF).is_err());
+    assert_eq!(backend::names(), ["listing"]);
+    assert_eq!(backend::find("listing").unwrap().extension(), ".txt");
+    assert!(backend::find("elf").is_none());
+
+    let out = aout_to_elf(&image, &opts).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines[0], "0x80200000");
+    assert_eq!(lines[1..3], [".text", ".data"]);
+
+    // symbol containers stay ELF
+    let opts = ConvertOptions {
+        symbols_only: true,
+        ..opts
+    };
+    let e = aout_to_elf(&image, &opts).unwrap_err();
+    assert_eq!(e.to_string(), "symbol containers are only written as ELF");
+}
@@ -0,0 +1,33 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn existing_outputs() {
+    let dir = tempfile::tempdir().unwrap();
+    let syms = sym(RISCV64_ENTRY, b'T', "main");
+    let image = aout(RISCV64_MAGIC, RISCV64_ENTRY, &[0x13; 0x20], &[], 0, &syms);
+    std::fs::write(dir.path().join("9rv"), image).unwrap();
+    let manifest = dir.path().join("jobs.toml");
+    let batch = |jobs: &str, extra: &[&str]| {
+        std::fs::write(&manifest, jobs).unwrap();
+        let mut args = vec!["batch", manifest.to_str().unwrap()];
+        args.extend_from_slice(extra);
+        run(&args)
+    };
+
+    let job = "[[job]]\ninput = \"9rv\"\noutput = \"9rv.elf\"\n";
+    let out = batch(job, &[]);
+    assert!(out.status.success(), "{out:?}");
+
+    // kept unless forced
+    let out = batch(job, &[]);
+    assert_eq!(out.status.code(), Some(1), "{out:?}");
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("exists, use --force"), "{stdout}");
+
+    let out = batch(job, &["--force"]);
+    assert!(out.status.success(), "{out:?}");
+    let out = batch(&format!("{job}force = true\n"), &[]);
+    assert!(out.status.success(), "{out:?}");
+}
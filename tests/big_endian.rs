@@ -0,0 +1,98 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+use goblin::elf::section_header::SHT_SYMTAB;
+
+const ENTRY: u32 = 0x8002_0000;
+
+// Every field of the headers and symbols has to be swapped, so compare
+// what a big-endian reader sees with what went in.
+fn check(magic: u32, extra: &[&str]) {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("be.out");
+    let mut syms = sym32(ENTRY, b'T', "_start");
+    syms.extend(sym32(ENTRY + 0x10, b'T', "etext"));
+    let image = aout(magic, ENTRY as u64, &[0x01; 0x20], &[0xaa; 0x10], 0, &syms);
+    std::fs::write(&input, image).unwrap();
+
+    let image = convert(&input, extra);
+    let elf = Elf::parse(&image).unwrap();
+    assert!(!elf.little_endian, "{magic:#x}");
+
+    let name = |i: usize| elf.shdr_strtab.get_at(elf.section_headers[i].sh_name);
+    let text = (0..elf.section_headers.len()).find(|i| name(*i) == Some(".text"));
+    let text = &elf.section_headers[text.unwrap()];
+    assert_eq!(text.sh_addr, ENTRY as u64);
+    assert_eq!(text.sh_size, 0x20);
+
+    let start = elf
+        .syms
+        .iter()
+        .find(|s| elf.strtab.get_at(s.st_name) == Some("_start"))
+        .unwrap();
+    assert_eq!(start.st_value, ENTRY as u64);
+    assert_eq!(start.st_size, 0x10);
+    assert_eq!(name(start.st_shndx), Some(".text"));
+}
+
+#[test]
+fn headers_and_symbols_are_big_endian() {
+    for magic in [
+        MIPS_MAGIC,
+        SPARC_MAGIC,
+        SPARC64_MAGIC,
+        POWER_MAGIC,
+        M68020_MAGIC,
+    ] {
+        check(magic, &[]);
+    }
+}
+
+#[test]
+fn reordered_sections_keep_symbol_indices() {
+    for magic in [MIPS_MAGIC, SPARC64_MAGIC] {
+        check(magic, &["--section-order", ".data,*"]);
+    }
+}
+
+// Bytes as written, independent of the byte order of the host: the ELF
+// header, and the _start symbol pointing at .text, which comes second after
+// reordering.
+#[test]
+fn encoded_bytes() {
+    let cases: [(u32, &str, &str); 2] = [
+        (
+            MIPS_MAGIC,
+            "7f454c460102010000000000000000000002000800000001800200000000003400000074\
+             00000000003400200002002800070006",
+            "00000001800200000000001012000002",
+        ),
+        (
+            SPIM_MAGIC,
+            "7f454c460101010000000000000000000200080001000000000002803400000074000000\
+             00000000340020000200280007000600",
+            "01000000000002801000000012000200",
+        ),
+    ];
+    for (magic, header, start) in cases {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("9mips");
+        let mut syms = sym32(ENTRY, b'T', "_start");
+        syms.extend(sym32(ENTRY + 0x10, b'T', "etext"));
+        let image = aout(magic, ENTRY as u64, &[0x01; 0x20], &[0xaa; 0x10], 0, &syms);
+        std::fs::write(&input, image).unwrap();
+
+        let image = convert(&input, &["--section-order", ".data,*"]);
+        let hex = |b: &[u8]| b.iter().map(|b| format!("{b:02x}")).collect::<String>();
+        assert_eq!(hex(&image[..0x34]), header, "{magic:#x}");
+        let elf = Elf::parse(&image).unwrap();
+        let symtab = elf
+            .section_headers
+            .iter()
+            .find(|s| s.sh_type == SHT_SYMTAB)
+            .unwrap();
+        let at = (symtab.sh_offset + symtab.sh_entsize) as usize;
+        assert_eq!(hex(&image[at..at + 16]), start, "{magic:#x}");
+    }
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+use goblin::elf::section_header::SHT_SYMTAB;
+use goblin::elf::sym::{STB_GLOBAL, STB_LOCAL};
+
+#[test]
+fn binding_follows_case() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9rv");
+    let data_addr = RISCV64_ENTRY + 0x1000;
+    let mut syms = sym(RISCV64_ENTRY, b'T', "_start");
+    syms.extend(sym(RISCV64_ENTRY + 0x8, b't', "helper"));
+    syms.extend(sym(RISCV64_ENTRY + 0x20, b'T', "etext"));
+    syms.extend(sym(data_addr, b'd', "nconf"));
+    syms.extend(sym(data_addr + 0x8, b'D', "conf"));
+    syms.extend(sym(data_addr + 0x10, b'b', "mach0"));
+    syms.extend(sym(data_addr + 0x18, b'B', "end"));
+    let image = aout(
+        RISCV64_MAGIC,
+        RISCV64_ENTRY,
+        &[0x13; 0x20],
+        &[0xaa; 0x10],
+        0x8,
+        &syms,
+    );
+    std::fs::write(&input, image).unwrap();
+
+    let image = convert(&input, &["--define-sym", "boot_magic=0x2badb002"]);
+    let elf = Elf::parse(&image).unwrap();
+    let symbol = |name| {
+        elf.syms
+            .iter()
+            .position(|s| elf.strtab.get_at(s.st_name) == Some(name))
+            .unwrap_or_else(|| panic!("no {name}"))
+    };
+    let symtab = elf
+        .section_headers
+        .iter()
+        .find(|s| s.sh_type == SHT_SYMTAB)
+        .unwrap();
+    let first_global = symtab.sh_info as usize;
+
+    for name in ["helper", "nconf", "mach0"] {
+        let i = symbol(name);
+        assert_eq!(elf.syms.get(i).unwrap().st_bind(), STB_LOCAL, "{name}");
+        assert!(i < first_global, "{name}");
+    }
+    for name in ["_start", "conf", "end", "boot_magic"] {
+        let i = symbol(name);
+        assert_eq!(elf.syms.get(i).unwrap().st_bind(), STB_GLOBAL, "{name}");
+        assert!(i >= first_global, "{name}");
+    }
+}
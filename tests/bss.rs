@@ -0,0 +1,87 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+use goblin::elf::program_header::PT_LOAD;
+use goblin::elf::section_header::SHT_NOBITS;
+
+fn riscv_kernel(dir: &std::path::Path, data: &[u8], bss: u32) -> std::path::PathBuf {
+    let input = dir.join("9rv");
+    let mut syms = sym(RISCV64_ENTRY, b'T', "_start");
+    syms.extend(sym(RISCV64_ENTRY + 0x10, b'T', "etext"));
+    let image = aout(
+        RISCV64_MAGIC,
+        RISCV64_ENTRY,
+        &[0x13; 0x20],
+        data,
+        bss,
+        &syms,
+    );
+    std::fs::write(&input, image).unwrap();
+    input
+}
+
+#[test]
+fn bss_follows_data() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = riscv_kernel(dir.path(), &[0xaa; 0x10], 0x1000);
+
+    let image = convert(&input, &[]);
+    let elf = Elf::parse(&image).unwrap();
+
+    let section = |name| {
+        elf.section_headers
+            .iter()
+            .find(|s| elf.shdr_strtab.get_at(s.sh_name) == Some(name))
+            .unwrap_or_else(|| panic!("no {name}"))
+    };
+    let data = section(".data");
+    let bss = section(".bss");
+    assert_eq!(bss.sh_type, SHT_NOBITS);
+    assert_eq!(bss.sh_addr, data.sh_addr + 0x10);
+    assert_eq!(bss.sh_size, 0x1000);
+
+    let mut load = elf.program_headers.iter().filter(|p| p.p_type == PT_LOAD);
+    let data_load = load.next_back().unwrap();
+    assert_eq!(data_load.p_vaddr, data.sh_addr);
+    assert_eq!(data_load.p_filesz, 0x10);
+    assert_eq!(data_load.p_memsz, 0x1010);
+}
+
+#[test]
+fn bss_without_data() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = riscv_kernel(dir.path(), &[], 0x100);
+
+    let image = convert(&input, &["--empty-segments", "omit"]);
+    let elf = Elf::parse(&image).unwrap();
+
+    let names: Vec<_> = elf
+        .section_headers
+        .iter()
+        .filter_map(|s| elf.shdr_strtab.get_at(s.sh_name))
+        .collect();
+    assert!(!names.contains(&".data"));
+    assert!(names.contains(&".bss"));
+    let mut load = elf.program_headers.iter().filter(|p| p.p_type == PT_LOAD);
+    let data_load = load.next_back().unwrap();
+    assert_eq!(data_load.p_filesz, 0);
+    assert_eq!(data_load.p_memsz, 0x100);
+}
+
+// NOBITS takes no room in the file, nor in memory while converting.
+#[test]
+fn huge_bss_is_not_allocated() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = riscv_kernel(dir.path(), &[0xaa; 0x10], 0xfff0_0000);
+
+    let image = convert(&input, &["--max-memory", "16"]);
+    let elf = Elf::parse(&image).unwrap();
+    let bss = elf
+        .section_headers
+        .iter()
+        .find(|s| elf.shdr_strtab.get_at(s.sh_name) == Some(".bss"))
+        .unwrap();
+    assert_eq!(bss.sh_size, 0xfff0_0000);
+    assert!(image.len() < 0x10000);
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+use p9aout2elf::Error;
+use p9aout2elf::elf2aout::{AoutOptions, elf_to_aout};
+
+fn riscv_kernel(dir: &std::path::Path) -> std::path::PathBuf {
+    let input = dir.join("9rv");
+    let mut syms = sym(RISCV64_ENTRY, b'T', "_start");
+    syms.extend(sym(RISCV64_ENTRY + 0x10, b'T', "etext"));
+    let image = aout(
+        RISCV64_MAGIC,
+        RISCV64_ENTRY,
+        &[0x13; 0x20],
+        &[0xaa; 0x10],
+        0,
+        &syms,
+    );
+    std::fs::write(&input, image).unwrap();
+    input
+}
+
+#[test]
+fn riscv64_as_elf32() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = riscv_kernel(dir.path());
+
+    let image = convert(&input, &["--class", "elf32"]);
+    let elf = Elf::parse(&image).unwrap();
+    assert!(!elf.is_64);
+    assert_eq!(elf.header.e_entry, RISCV64_ENTRY);
+    let start = elf
+        .syms
+        .iter()
+        .find(|s| elf.strtab.get_at(s.st_name) == Some("_start"));
+    assert_eq!(start.unwrap().st_value, RISCV64_ENTRY);
+}
+
+#[test]
+fn elf32_needs_addresses_to_fit() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = riscv_kernel(dir.path());
+
+    let out = run(&[
+        "convert",
+        input.to_str().unwrap(),
+        "--class",
+        "32",
+        "--vbase",
+        "0xffffffc000000000",
+    ]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("does not fit in ELF32"), "{stderr}");
+}
+
+#[test]
+fn truncated_elf32() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = riscv_kernel(dir.path());
+
+    let image = convert(&input, &["--class", "elf32"]);
+    assert!(elf_to_aout(&image, &AoutOptions::default()).is_ok());
+    // cut anywhere past the magic, it is an error instead of a panic
+    for n in 4..image.len() {
+        match elf_to_aout(&image[..n], &AoutOptions::default()) {
+            Err(Error::Truncated(_)) => {}
+            r => panic!("{n} of {} bytes: {r:?}", image.len()),
+        }
+    }
+}
@@ -0,0 +1,79 @@
+// Synthetic Plan 9 a.out images and helpers to run the CLI on them.
+#![allow(dead_code)]
+
+use std::path::Path;
+use std::process::{Command, Output};
+
+// _MAGIC(f, b), with f = HDR_MAGIC for 64-bit targets, see a.out(6)
+pub const AMD64_MAGIC: u32 = 0x8000 | (4 * 26 * 26 + 7);
+pub const RISCV64_MAGIC: u32 = 0x8000 | (4 * 30 * 30 + 7);
+pub const ARM64_MAGIC: u32 = 0x8000 | (4 * 28 * 28 + 7);
+pub const POWER64_MAGIC: u32 = 0x8000 | (4 * 27 * 27 + 7);
+pub const I386_MAGIC: u32 = 4 * 11 * 11 + 7;
+pub const ARM_MAGIC: u32 = 4 * 20 * 20 + 7;
+pub const MIPS_MAGIC: u32 = 4 * 16 * 16 + 7;
+pub const SPIM_MAGIC: u32 = 4 * 24 * 24 + 7;
+pub const SPARC_MAGIC: u32 = 4 * 13 * 13 + 7;
+pub const SPARC64_MAGIC: u32 = 4 * 25 * 25 + 7;
+pub const POWER_MAGIC: u32 = 4 * 21 * 21 + 7;
+pub const M68020_MAGIC: u32 = 4 * 8 * 8 + 7;
+pub const ALPHA_MAGIC: u32 = 4 * 23 * 23 + 7;
+
+pub const RISCV64_ENTRY: u64 = 0x8020_0000;
+
+pub fn sym(value: u64, sym_type: u8, name: &str) -> Vec<u8> {
+    let mut s = value.to_be_bytes().to_vec();
+    s.push(sym_type | 0x80);
+    s.extend_from_slice(name.as_bytes());
+    s.push(0);
+    s
+}
+
+// symbols of 32-bit targets have 4-byte values
+pub fn sym32(value: u32, sym_type: u8, name: &str) -> Vec<u8> {
+    let mut s = value.to_be_bytes().to_vec();
+    s.push(sym_type | 0x80);
+    s.extend_from_slice(name.as_bytes());
+    s.push(0);
+    s
+}
+
+pub fn aout(magic: u32, entry: u64, text: &[u8], data: &[u8], bss: u32, syms: &[u8]) -> Vec<u8> {
+    let mut d = vec![];
+    for v in [
+        magic,
+        text.len() as u32,
+        data.len() as u32,
+        bss,
+        syms.len() as u32,
+        entry as u32,
+        0,
+        0,
+    ] {
+        d.extend_from_slice(&v.to_be_bytes());
+    }
+    // only 64-bit targets have the 64-bit entry point
+    if magic & 0x8000 != 0 {
+        d.extend_from_slice(&entry.to_be_bytes());
+    }
+    d.extend_from_slice(text);
+    d.extend_from_slice(data);
+    d.extend_from_slice(syms);
+    d
+}
+
+pub fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_p9aout2elf"))
+        .args(args)
+        .output()
+        .expect("failed to run p9aout2elf")
+}
+
+pub fn convert(input: &Path, extra: &[&str]) -> Vec<u8> {
+    let mut args = vec!["convert", input.to_str().unwrap()];
+    args.extend_from_slice(extra);
+    let out = run(&args);
+    assert!(out.status.success(), "{out:?}");
+    let elf = format!("{}.elf", input.display());
+    std::fs::read(elf).expect("no ELF written")
+}
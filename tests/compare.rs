@@ -0,0 +1,36 @@
+mod common;
+
+use common::*;
+
+// linked above 4 GiB, so symbol values have high bits
+const ENTRY: u64 = 0xffff_ffff_8011_0020;
+
+fn kernel(text: usize) -> Vec<u8> {
+    let mut syms = sym(ENTRY, b'T', "main");
+    syms.extend(sym(ENTRY + 0x10, b'T', "idle"));
+    aout(AMD64_MAGIC, ENTRY, &vec![0x90; text], &[], 0, &syms)
+}
+
+#[test]
+fn symbol_sizes_of_64bit_kernels() {
+    let dir = tempfile::tempdir().unwrap();
+    let old = dir.path().join("old");
+    let new = dir.path().join("new");
+    std::fs::write(&old, kernel(0x20)).unwrap();
+    std::fs::write(&new, kernel(0x30)).unwrap();
+
+    let out = run(&[
+        "compare",
+        old.to_str().unwrap(),
+        new.to_str().unwrap(),
+        "--sections",
+    ]);
+    assert!(out.status.success(), "{out:?}");
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    // the last one extends to the end of the text
+    assert!(
+        stdout.contains("+16         16 ->         32  idle"),
+        "{stdout}"
+    );
+    assert!(!stdout.contains("main"), "{stdout}");
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use common::*;
+
+fn kernel(trap: u64) -> Vec<u8> {
+    let mut syms = sym(RISCV64_ENTRY, b'T', "_main");
+    syms.extend(sym(RISCV64_ENTRY + trap, b'T', "trap"));
+    syms.extend(sym(RISCV64_ENTRY + 0x30, b'T', "etext"));
+    aout(RISCV64_MAGIC, RISCV64_ENTRY, &[0x13; 0x40], &[], 0, &syms)
+}
+
+#[test]
+fn compare_against_reports_moved_symbols() {
+    let dir = tempfile::tempdir().unwrap();
+    let ours = dir.path().join("ours");
+    let theirs = dir.path().join("theirs");
+    std::fs::write(&ours, kernel(0x10)).unwrap();
+    std::fs::write(&theirs, kernel(0x14)).unwrap();
+    convert(&theirs, &[]);
+
+    let elf = format!("{}.elf", theirs.display());
+    let out = run(&[
+        "compare",
+        ours.to_str().unwrap(),
+        "--against",
+        &elf,
+        "--by-name",
+    ]);
+    assert!(out.status.success(), "{out:?}");
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(
+        stdout.contains("trap: address 0x80200010, theirs 0x80200014"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("_main: size 0x10, theirs 0x14"), "{stdout}");
+    assert!(stdout.contains("1 symbols match, 2 differ"), "{stdout}");
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+use goblin::elf::sym::{STT_FUNC, STT_OBJECT};
+
+#[test]
+fn data_and_bss_symbols() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9rv");
+    let data_addr = RISCV64_ENTRY + 0x1000;
+    let mut syms = sym(RISCV64_ENTRY, b'T', "_start");
+    syms.extend(sym(RISCV64_ENTRY + 0x10, b'T', "etext"));
+    syms.extend(sym(data_addr, b'D', "conf"));
+    syms.extend(sym(data_addr + 0x8, b'd', "nconf"));
+    syms.extend(sym(data_addr + 0x10, b'b', "mach0"));
+    syms.extend(sym(data_addr + 0x30, b'B', "end"));
+    let image = aout(
+        RISCV64_MAGIC,
+        RISCV64_ENTRY,
+        &[0x13; 0x20],
+        &[0xaa; 0x10],
+        0x20,
+        &syms,
+    );
+    std::fs::write(&input, image).unwrap();
+
+    let image = convert(&input, &[]);
+    let elf = Elf::parse(&image).unwrap();
+    let section = |name| {
+        elf.section_headers
+            .iter()
+            .position(|s| elf.shdr_strtab.get_at(s.sh_name) == Some(name))
+            .unwrap_or_else(|| panic!("no {name}"))
+    };
+    let symbol = |name| {
+        elf.syms
+            .iter()
+            .find(|s| elf.strtab.get_at(s.st_name) == Some(name))
+            .unwrap_or_else(|| panic!("no {name}"))
+    };
+
+    let start = symbol("_start");
+    assert_eq!(start.st_type(), STT_FUNC);
+    assert_eq!(start.st_shndx, section(".text"));
+
+    for (name, value, size, shndx) in [
+        ("conf", data_addr, 0x8, ".data"),
+        ("nconf", data_addr + 0x8, 0x8, ".data"),
+        ("mach0", data_addr + 0x10, 0x20, ".bss"),
+        ("end", data_addr + 0x30, 0, ".bss"),
+    ] {
+        let s = symbol(name);
+        assert_eq!(s.st_type(), STT_OBJECT, "{name}");
+        assert_eq!(s.st_value, value, "{name}");
+        assert_eq!(s.st_size, size, "{name}");
+        assert_eq!(s.st_shndx, section(shndx), "{name}");
+    }
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use common::*;
+use gimli::{
+    BaseAddresses, CfaRule, DebugFrame, LittleEndian, Register, RegisterRule, UnwindContext,
+    UnwindSection,
+};
+use goblin::elf::Elf;
+
+// Appends the pc/sp table, which follows the symbol table.
+fn with_sp_table(mut image: Vec<u8>, table: &[u8]) -> Vec<u8> {
+    image[24..28].copy_from_slice(&(table.len() as u32).to_be_bytes());
+    image.extend_from_slice(table);
+    image
+}
+
+fn debug_frame(image: &[u8]) -> Vec<u8> {
+    let elf = Elf::parse(image).unwrap();
+    let sh = elf
+        .section_headers
+        .iter()
+        .find(|s| elf.shdr_strtab.get_at(s.sh_name) == Some(".debug_frame"))
+        .expect("no .debug_frame");
+    image[sh.sh_offset as usize..][..sh.sh_size as usize].to_vec()
+}
+
+fn unwind(
+    frame: &[u8],
+    addr: u64,
+    address_size: u8,
+    ra: Register,
+) -> (CfaRule<usize>, RegisterRule<usize>) {
+    let mut frame = DebugFrame::new(frame, LittleEndian);
+    frame.set_address_size(address_size);
+    let mut ctx = UnwindContext::new();
+    let row = frame
+        .unwind_info_for_address(
+            &BaseAddresses::default(),
+            &mut ctx,
+            addr,
+            DebugFrame::cie_from_offset,
+        )
+        .unwrap_or_else(|e| panic!("no unwind info for {addr:#x}: {e}"));
+    (row.cfa().clone(), row.register(ra))
+}
+
+#[test]
+fn amd64_frames() {
+    // 9front pc64 kernels are linked at KZERO 0xffffffff80000000.
+    const ENTRY: u64 = 0xffff_ffff_8011_0028;
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9pc64");
+    let mut syms = sym(ENTRY, b'T', "main");
+    syms.extend(sym(ENTRY + 0x10, b'T', "idle"));
+    let image = aout(AMD64_MAGIC, ENTRY, &[0x90; 0x20], &[], 0, &syms);
+    // SUBQ $16, SP at main+3, ADDQ $16, SP at main+15
+    let image = with_sp_table(image, &[132, 4, 139, 68]);
+    std::fs::write(&input, image).unwrap();
+
+    let image = convert(&input, &[]);
+    let frame = debug_frame(&image);
+    let rip = Register(16);
+    let cfa = |offset| CfaRule::RegisterAndOffset {
+        register: Register(7),
+        offset,
+    };
+    for (addr, offset) in [
+        (ENTRY, 8),
+        (ENTRY + 3, 8),
+        (ENTRY + 4, 24),
+        (ENTRY + 0xf, 24),
+        (ENTRY + 0x10, 8),
+    ] {
+        let (c, r) = unwind(&frame, addr, 8, rip);
+        assert_eq!(c, cfa(offset), "{addr:#x}");
+        assert_eq!(r, RegisterRule::Offset(-8), "{addr:#x}");
+    }
+}
+
+#[test]
+fn riscv64_frames() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9rv");
+    let mut syms = sym(RISCV64_ENTRY, b'T', "main");
+    syms.extend(sym(RISCV64_ENTRY + 0x10, b'T', "idle"));
+    let image = aout(RISCV64_MAGIC, RISCV64_ENTRY, &[0x13; 0x20], &[], 0, &syms);
+    // ADD $-16, SP as the first instruction, which is 4 bytes
+    let image = with_sp_table(image, &[130, 4]);
+    std::fs::write(&input, image).unwrap();
+
+    let image = convert(&input, &[]);
+    let frame = debug_frame(&image);
+    let ra = Register(1);
+    let cfa = |offset| CfaRule::RegisterAndOffset {
+        register: Register(2),
+        offset,
+    };
+    let (c, r) = unwind(&frame, RISCV64_ENTRY, 8, ra);
+    assert_eq!(c, cfa(0));
+    assert_eq!(r, RegisterRule::SameValue);
+    // the link register is saved at 0(SP) along with it
+    let (c, r) = unwind(&frame, RISCV64_ENTRY + 4, 8, ra);
+    assert_eq!(c, cfa(16));
+    assert_eq!(r, RegisterRule::Offset(-16));
+}
+
+#[test]
+fn no_sp_table_no_debug_frame() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9rv");
+    let syms = sym(RISCV64_ENTRY, b'T', "main");
+    let image = aout(RISCV64_MAGIC, RISCV64_ENTRY, &[0x13; 0x20], &[], 0, &syms);
+    std::fs::write(&input, image).unwrap();
+
+    let image = convert(&input, &[]);
+    let elf = Elf::parse(&image).unwrap();
+    let names = elf.section_headers.iter();
+    let mut names = names.filter_map(|s| elf.shdr_strtab.get_at(s.sh_name));
+    assert!(!names.any(|n| n == ".debug_frame"));
+}
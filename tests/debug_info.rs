@@ -0,0 +1,156 @@
+mod common;
+
+use common::*;
+use gimli::{
+    AttributeValue, DW_AT_external, DW_AT_frame_base, DW_AT_high_pc, DW_AT_location, DW_AT_low_pc,
+    DW_AT_name, Dwarf, EndianSlice, LittleEndian, SectionId,
+};
+use goblin::elf::Elf;
+
+fn zsym(value: u64, indices: &[u16]) -> Vec<u8> {
+    let mut s = value.to_be_bytes().to_vec();
+    s.push(b'z' | 0x80);
+    s.push(0);
+    for i in indices.iter().chain(&[0]) {
+        s.extend_from_slice(&i.to_be_bytes());
+    }
+    s
+}
+
+// main.c with main(argc) and its local n, and a static spllo after it
+fn kernel(dir: &std::path::Path, sp_table: &[u8]) -> std::path::PathBuf {
+    let input = dir.join("9rv");
+    let mut syms = vec![];
+    for (i, c) in ["/", "sys", "main.c"].iter().enumerate() {
+        syms.extend(sym(i as u64 + 1, b'f', c));
+    }
+    syms.extend(zsym(1, &[1, 2, 3]));
+    syms.extend(sym(RISCV64_ENTRY, b'T', "main"));
+    syms.extend(sym(24, b'm', ".frame"));
+    syms.extend(sym(8, b'p', "argc"));
+    syms.extend(sym(12, b'a', "n"));
+    syms.extend(sym(RISCV64_ENTRY + 0x10, b't', "spllo"));
+    let mut image = aout(RISCV64_MAGIC, RISCV64_ENTRY, &[0x13; 0x20], &[], 0, &syms);
+    image[24..28].copy_from_slice(&(sp_table.len() as u32).to_be_bytes());
+    image.extend_from_slice(sp_table);
+    std::fs::write(&input, image).unwrap();
+    input
+}
+
+fn section<'a>(elf: &Elf, image: &'a [u8], name: &str) -> &'a [u8] {
+    let sh = elf
+        .section_headers
+        .iter()
+        .find(|s| elf.shdr_strtab.get_at(s.sh_name) == Some(name));
+    sh.map_or(&[], |sh| {
+        &image[sh.sh_offset as usize..][..sh.sh_size as usize]
+    })
+}
+
+// The entries as depth, tag and the attributes looked at here, formatted.
+fn entries(image: &[u8]) -> Vec<(isize, String, Vec<String>)> {
+    let elf = Elf::parse(image).unwrap();
+    let load = |id: SectionId| -> Result<_, gimli::Error> {
+        Ok(EndianSlice::new(
+            section(&elf, image, id.name()),
+            LittleEndian,
+        ))
+    };
+    let dwarf = Dwarf::load(load).unwrap();
+    let mut res = vec![];
+    let mut headers = dwarf.units();
+    while let Some(header) = headers.next().unwrap() {
+        let unit = dwarf.unit(header).unwrap();
+        let mut depth = 0;
+        let mut cursor = unit.entries();
+        while let Some((delta, e)) = cursor.next_dfs().unwrap() {
+            depth += delta;
+            let mut attrs = vec![];
+            for at in [
+                DW_AT_name,
+                DW_AT_external,
+                DW_AT_low_pc,
+                DW_AT_high_pc,
+                DW_AT_frame_base,
+                DW_AT_location,
+            ] {
+                let Some(v) = e.attr_value(at).unwrap() else {
+                    continue;
+                };
+                let v = match v {
+                    AttributeValue::String(s) => s.to_string_lossy().into_owned(),
+                    AttributeValue::Addr(a) => format!("{a:#x}"),
+                    AttributeValue::Flag(f) => f.to_string(),
+                    AttributeValue::Exprloc(e) => format!("{:x?}", e.0.slice()),
+                    v => format!("{v:?}"),
+                };
+                attrs.push(format!("{at}={v}"));
+            }
+            res.push((depth, e.tag().to_string(), attrs));
+        }
+    }
+    res
+}
+
+#[test]
+fn functions_and_variables() {
+    let dir = tempfile::tempdir().unwrap();
+    // ADD $-24, SP as the first instruction
+    let input = kernel(dir.path(), &[130, 6]);
+
+    let image = convert(&input, &[]);
+    let entries = entries(&image);
+    let tags: Vec<(isize, &str)> = entries.iter().map(|(d, t, _)| (*d, t.as_str())).collect();
+    assert_eq!(
+        tags,
+        [
+            (0, "DW_TAG_compile_unit"),
+            (1, "DW_TAG_subprogram"),
+            (2, "DW_TAG_formal_parameter"),
+            (2, "DW_TAG_variable"),
+            (1, "DW_TAG_subprogram"),
+        ]
+    );
+    assert!(entries[0].2.contains(&"DW_AT_name=/sys/main.c".to_string()));
+    assert_eq!(
+        entries[1].2,
+        [
+            "DW_AT_name=main",
+            "DW_AT_external=true",
+            "DW_AT_low_pc=0x80200000",
+            "DW_AT_high_pc=0x80200010",
+            // DW_OP_call_frame_cfa
+            "DW_AT_frame_base=[9c]",
+        ]
+    );
+    // DW_OP_fbreg 8 and -12
+    assert_eq!(entries[2].2, ["DW_AT_name=argc", "DW_AT_location=[91, 8]"]);
+    assert_eq!(entries[3].2, ["DW_AT_name=n", "DW_AT_location=[91, 74]"]);
+    assert_eq!(
+        entries[4].2[..2],
+        ["DW_AT_name=spllo", "DW_AT_external=false"]
+    );
+}
+
+#[test]
+fn frame_base_without_call_frame_information() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = kernel(dir.path(), &[]);
+
+    let image = convert(&input, &[]);
+    let entries = entries(&image);
+    // DW_OP_breg2 24, the stack pointer plus the frame size
+    assert!(
+        entries[1]
+            .2
+            .contains(&"DW_AT_frame_base=[72, 18]".to_string())
+    );
+    // spllo has no .frame symbol, so no frame base or variables
+    assert_eq!(entries.len(), 5);
+    assert!(
+        !entries[4]
+            .2
+            .iter()
+            .any(|a| a.starts_with("DW_AT_frame_base"))
+    );
+}
@@ -0,0 +1,174 @@
+mod common;
+
+use common::*;
+use gimli::{Dwarf, EndianSlice, LittleEndian, SectionId};
+use goblin::elf::Elf;
+
+fn zsym(value: u64, indices: &[u16]) -> Vec<u8> {
+    let mut s = value.to_be_bytes().to_vec();
+    s.push(b'z' | 0x80);
+    s.push(0);
+    for i in indices.iter().chain(&[0]) {
+        s.extend_from_slice(&i.to_be_bytes());
+    }
+    s
+}
+
+// main.c includes u.h at line 3 of the compilation, which ends at line 10
+fn kernel(dir: &std::path::Path) -> std::path::PathBuf {
+    let input = dir.join("9rv");
+    let mut syms = vec![];
+    for (i, c) in ["/", "sys", "main.c", "u.h"].iter().enumerate() {
+        syms.extend(sym(i as u64 + 1, b'f', c));
+    }
+    syms.extend(zsym(1, &[1, 2, 3]));
+    syms.extend(zsym(3, &[1, 2, 4]));
+    syms.extend(zsym(10, &[]));
+    syms.extend(sym(RISCV64_ENTRY, b'T', "main"));
+    syms.extend(sym(RISCV64_ENTRY + 0x6, b'T', "spllo"));
+    let mut image = aout(RISCV64_MAGIC, RISCV64_ENTRY, &[0x13; 0x20], &[], 0, &syms);
+
+    // line 12 at the entry, then two quanta further at line 4
+    let pcline = [12, 130, 72];
+    image[28..32].copy_from_slice(&(pcline.len() as u32).to_be_bytes());
+    image.extend_from_slice(&pcline);
+    std::fs::write(&input, image).unwrap();
+    input
+}
+
+fn section<'a>(elf: &Elf, image: &'a [u8], name: &str) -> &'a [u8] {
+    let sh = elf
+        .section_headers
+        .iter()
+        .find(|s| elf.shdr_strtab.get_at(s.sh_name) == Some(name));
+    sh.map_or(&[], |sh| {
+        &image[sh.sh_offset as usize..][..sh.sh_size as usize]
+    })
+}
+
+// address, path and line
+type Row = (u64, String, u64);
+
+// Compile unit names and the rows of their line programs.
+fn line_rows(image: &[u8]) -> Vec<(String, Vec<Row>)> {
+    let elf = Elf::parse(image).unwrap();
+    let load = |id: SectionId| -> Result<_, gimli::Error> {
+        Ok(EndianSlice::new(
+            section(&elf, image, id.name()),
+            LittleEndian,
+        ))
+    };
+    let dwarf = Dwarf::load(load).unwrap();
+    let mut units = vec![];
+    let mut headers = dwarf.units();
+    while let Some(header) = headers.next().unwrap() {
+        let unit = dwarf.unit(header).unwrap();
+        let name = unit.name.unwrap().to_string_lossy().into_owned();
+        let program = unit.line_program.clone().expect("no line program");
+        let mut rows = program.rows();
+        let mut lines = vec![];
+        while let Some((header, row)) = rows.next_row().unwrap() {
+            if row.end_sequence() {
+                lines.push((row.address(), String::new(), 0));
+                continue;
+            }
+            let file = row.file(header).unwrap();
+            let path = dwarf.attr_string(&unit, file.path_name()).unwrap();
+            let line = row.line().map_or(0, |l| l.get());
+            lines.push((row.address(), path.to_string_lossy().into_owned(), line));
+        }
+        units.push((name, lines));
+    }
+    units
+}
+
+#[test]
+fn lines_of_main_and_include() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = kernel(dir.path());
+
+    let image = convert(&input, &[]);
+    let units = line_rows(&image);
+    assert_eq!(units.len(), 1);
+    let (name, rows) = &units[0];
+    assert_eq!(name, "/sys/main.c");
+    let rows: Vec<(u64, &str, u64)> = rows.iter().map(|(a, p, l)| (*a, p.as_str(), *l)).collect();
+    assert_eq!(
+        rows,
+        [
+            (RISCV64_ENTRY, "/sys/main.c", 5),
+            (RISCV64_ENTRY + 6, "/sys/u.h", 2),
+            (RISCV64_ENTRY + 0x20, "", 0),
+        ]
+    );
+}
+
+#[test]
+fn no_pcline_table_no_debug_line() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9rv");
+    let syms = sym(RISCV64_ENTRY, b'T', "main");
+    let image = aout(RISCV64_MAGIC, RISCV64_ENTRY, &[0x13; 0x20], &[], 0, &syms);
+    std::fs::write(&input, image).unwrap();
+
+    let image = convert(&input, &[]);
+    let elf = Elf::parse(&image).unwrap();
+    let names = elf.section_headers.iter();
+    let mut names = names.filter_map(|s| elf.shdr_strtab.get_at(s.sh_name));
+    assert!(!names.any(|n| n == ".debug_line" || n == ".debug_info"));
+}
+
+// Compressed sections hold the same contents after an Elf64_Chdr with the
+// compression, the original size and alignment.
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+fn check_compressed(how: &str, ch_type: u32, decompress: fn(&[u8]) -> Vec<u8>) {
+    let dir = tempfile::tempdir().unwrap();
+    let input = kernel(dir.path());
+    let plain = convert(&input, &[]);
+    let image = convert(&input, &["--force", "--compress-debug-sections", how]);
+
+    let plain_elf = Elf::parse(&plain).unwrap();
+    let elf = Elf::parse(&image).unwrap();
+    let mut count = 0;
+    for sh in &elf.section_headers {
+        let name = elf.shdr_strtab.get_at(sh.sh_name).unwrap();
+        if !name.starts_with(".debug_") {
+            continue;
+        }
+        count += 1;
+        let compressed = goblin::elf::section_header::SHF_COMPRESSED as u64;
+        assert_ne!(sh.sh_flags & compressed, 0, "{name}");
+        assert_eq!(sh.sh_addralign, 8, "{name}");
+        let d = section(&elf, &image, name);
+        let field = |at: usize, n: usize| {
+            let mut b = [0u8; 8];
+            b[..n].copy_from_slice(&d[at..at + n]);
+            u64::from_le_bytes(b)
+        };
+        let original = section(&plain_elf, &plain, name);
+        assert_eq!(field(0, 4), ch_type as u64, "{name}");
+        assert_eq!(field(8, 8), original.len() as u64, "{name}");
+        assert_eq!(field(16, 8), 1, "{name}");
+        assert_eq!(decompress(&d[24..]), original, "{name}");
+    }
+    assert_eq!(count, 3);
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn zlib_compressed_sections() {
+    use goblin::elf::compression_header::ELFCOMPRESS_ZLIB;
+
+    check_compressed("zlib", ELFCOMPRESS_ZLIB, |d| {
+        let mut out = vec![];
+        std::io::Read::read_to_end(&mut flate2::read::ZlibDecoder::new(d), &mut out).unwrap();
+        out
+    });
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn zstd_compressed_sections() {
+    // ELFCOMPRESS_ZSTD, which goblin does not know yet
+    check_compressed("zstd", 2, |d| zstd::decode_all(d).unwrap());
+}
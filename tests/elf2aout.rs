@@ -0,0 +1,76 @@
+mod common;
+
+use common::*;
+
+const DATA: u64 = RISCV64_ENTRY + 0x1000;
+
+// symbols in address order, as elf2aout writes them
+fn kernel(dir: &std::path::Path) -> (std::path::PathBuf, Vec<u8>) {
+    let input = dir.join("9rv");
+    let mut syms = sym(RISCV64_ENTRY, b'T', "main");
+    syms.extend(sym(RISCV64_ENTRY + 0x10, b't', "splhi"));
+    syms.extend(sym(DATA, b'D', "conf"));
+    syms.extend(sym(DATA + 0x10, b'b', "ticks"));
+    let image = aout(
+        RISCV64_MAGIC,
+        RISCV64_ENTRY,
+        &[0x13; 0x20],
+        &[0x5a; 0x10],
+        0x10,
+        &syms,
+    );
+    std::fs::write(&input, &image).unwrap();
+    convert(&input, &[]);
+    (dir.join("9rv.elf"), image)
+}
+
+#[test]
+fn round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let (elf, image) = kernel(dir.path());
+    let back = dir.path().join("9rv.back");
+
+    let out = run(&[
+        "elf2aout",
+        elf.to_str().unwrap(),
+        "-o",
+        back.to_str().unwrap(),
+    ]);
+    assert!(out.status.success(), "{out:?}");
+    assert_eq!(std::fs::read(&back).unwrap(), image);
+}
+
+#[test]
+fn strip() {
+    let dir = tempfile::tempdir().unwrap();
+    let (elf, image) = kernel(dir.path());
+
+    // the default name is taken by the original
+    let out = run(&["elf2aout", "--strip", elf.to_str().unwrap()]);
+    assert!(!out.status.success());
+    let out = run(&["elf2aout", "--strip", "--force", elf.to_str().unwrap()]);
+    assert!(out.status.success(), "{out:?}");
+
+    let a = std::fs::read(dir.path().join("9rv")).unwrap();
+    // no symbol table, the rest as before
+    assert_eq!(a[16..20], [0; 4]);
+    assert_eq!(a[..16], image[..16]);
+    assert_eq!(a[20..], image[20..a.len()]);
+}
+
+#[test]
+fn entry_within_text() {
+    let dir = tempfile::tempdir().unwrap();
+    let (elf, _) = kernel(dir.path());
+    let input = dir.path().join("9rv");
+    let entry = format!("{:#x}", RISCV64_ENTRY + 4);
+    convert(&input, &["--force", "--entry", &entry]);
+
+    let out = run(&["elf2aout", "-o", "-", elf.to_str().unwrap()]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(
+        stderr.contains("entry 0x80200004 is not at the start of the text"),
+        "{stderr}"
+    );
+}
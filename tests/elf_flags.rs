@@ -0,0 +1,39 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+
+fn riscv_kernel(dir: &std::path::Path) -> std::path::PathBuf {
+    let input = dir.join("9rv");
+    let mut syms = sym(RISCV64_ENTRY, b'T', "_start");
+    syms.extend(sym(RISCV64_ENTRY + 0x10, b'T', "etext"));
+    let image = aout(
+        RISCV64_MAGIC,
+        RISCV64_ENTRY,
+        &[0x13; 0x20],
+        &[0xaa; 0x10],
+        0,
+        &syms,
+    );
+    std::fs::write(&input, image).unwrap();
+    input
+}
+
+#[test]
+fn riscv64_is_rvc_with_double_float_abi() {
+    let dir = tempfile::tempdir().unwrap();
+    let image = convert(&riscv_kernel(dir.path()), &[]);
+    let elf = Elf::parse(&image).unwrap();
+    assert_eq!(elf.header.e_flags, 0x5);
+}
+
+#[test]
+fn elf_flags_override() {
+    let dir = tempfile::tempdir().unwrap();
+    let image = convert(&riscv_kernel(dir.path()), &["--elf-flags", "0x1"]);
+    let elf = Elf::parse(&image).unwrap();
+    assert_eq!(elf.header.e_flags, 0x1);
+
+    let out = run(&["convert", "-", "--elf-flags", "0x100000000"]);
+    assert_eq!(out.status.code(), Some(2), "{out:?}");
+}
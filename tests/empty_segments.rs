@@ -0,0 +1,56 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+use goblin::elf::program_header::PT_LOAD;
+
+fn data_only() -> Vec<u8> {
+    let syms = sym(RISCV64_ENTRY, b'D', "payload");
+    aout(RISCV64_MAGIC, RISCV64_ENTRY, &[], &[0xaa; 0x40], 0, &syms)
+}
+
+fn load_segments(elf: &Elf) -> usize {
+    let loads = elf.program_headers.iter().filter(|p| p.p_type == PT_LOAD);
+    loads.count()
+}
+
+fn has_section(elf: &Elf, name: &str) -> bool {
+    let names = elf.section_headers.iter();
+    names
+        .filter_map(|s| elf.shdr_strtab.get_at(s.sh_name))
+        .any(|n| n == name)
+}
+
+#[test]
+fn empty_text_is_omitted() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("data-only");
+    std::fs::write(&input, data_only()).unwrap();
+
+    let image = convert(&input, &["--empty-segments", "omit"]);
+    let elf = Elf::parse(&image).unwrap();
+
+    assert_eq!(load_segments(&elf), 1);
+    assert!(!has_section(&elf, ".text"));
+    assert!(has_section(&elf, ".data"));
+}
+
+#[test]
+fn empty_text_is_kept() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("data-only");
+    std::fs::write(&input, data_only()).unwrap();
+
+    let image = convert(&input, &["--empty-segments", "keep"]);
+    let elf = Elf::parse(&image).unwrap();
+
+    assert_eq!(load_segments(&elf), 2);
+    let text = elf
+        .section_headers
+        .iter()
+        .find(|s| elf.shdr_strtab.get_at(s.sh_name) == Some(".text"))
+        .expect("no .text");
+    assert_eq!(text.sh_size, 0);
+    let empty = elf.program_headers.iter().find(|p| p.p_type == PT_LOAD);
+    assert_eq!(empty.unwrap().p_filesz, 0);
+}
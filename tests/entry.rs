@@ -0,0 +1,33 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+use goblin::elf::program_header::PT_LOAD;
+
+#[test]
+fn entry_overrides_only_e_entry() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9rv");
+    let mut syms = sym(RISCV64_ENTRY, b'T', "_start");
+    syms.extend(sym(RISCV64_ENTRY + 0x10, b'T', "etext"));
+    let image = aout(
+        RISCV64_MAGIC,
+        RISCV64_ENTRY,
+        &[0x13; 0x20],
+        &[0xaa; 0x10],
+        0,
+        &syms,
+    );
+    std::fs::write(&input, image).unwrap();
+
+    let trampoline = RISCV64_ENTRY + 0x10;
+    let image = convert(&input, &["--entry", &format!("{trampoline:#x}")]);
+    let elf = Elf::parse(&image).unwrap();
+    assert_eq!(elf.header.e_entry, trampoline);
+    let text = elf
+        .program_headers
+        .iter()
+        .find(|p| p.p_type == PT_LOAD)
+        .unwrap();
+    assert_eq!(text.p_vaddr, RISCV64_ENTRY);
+}
@@ -0,0 +1,66 @@
+#![cfg(unix)]
+mod common;
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::Command;
+
+use common::*;
+
+// Runs convert --gdb with a gdb-multiarch that records its arguments, one
+// per line.
+fn gdb_args(dir: &Path, input: &Path, extra: &[&str]) -> Vec<String> {
+    let gdb = dir.join("gdb-multiarch");
+    let log = dir.join("args");
+    let script = format!("#!/bin/sh\nprintf '%s\\n' \"$@\" > {}\n", log.display());
+    std::fs::write(&gdb, script).unwrap();
+    std::fs::set_permissions(&gdb, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let path = format!("{}:{}", dir.display(), std::env::var("PATH").unwrap());
+    let out = Command::new(env!("CARGO_BIN_EXE_p9aout2elf"))
+        .args(["convert", input.to_str().unwrap(), "--gdb", "--force"])
+        .args(extra)
+        .env("PATH", path)
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{out:?}");
+    let args = std::fs::read_to_string(log).unwrap();
+    args.lines().map(str::to_string).collect()
+}
+
+#[test]
+fn remote_target() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9rv");
+    let syms = sym(RISCV64_ENTRY, b'T', "main");
+    let image = aout(RISCV64_MAGIC, RISCV64_ENTRY, &[0x13; 0x20], &[], 0, &syms);
+    std::fs::write(&input, image).unwrap();
+    let elf = format!("{}.elf", input.display());
+
+    // only the file for the kernel profile
+    let args = gdb_args(dir.path(), &input, &[]);
+    assert_eq!(args, ["-ex", "set architecture riscv:rv64", &elf]);
+
+    // connected to qemu's stub for the named profile
+    let args = gdb_args(dir.path(), &input, &["--profile", "riscv64-sbi"]);
+    assert_eq!(
+        args,
+        [
+            "-ex",
+            "set architecture riscv:rv64",
+            "-ex",
+            "target remote :1234",
+            &elf
+        ]
+    );
+
+    let args = gdb_args(
+        dir.path(),
+        &input,
+        &["--profile", "riscv64-sbi", "--gdb-remote", "board:3333"],
+    );
+    assert_eq!(args[3], "target remote board:3333");
+
+    let out = run(&["convert", input.to_str().unwrap(), "--gdb-remote", ":1"]);
+    assert_eq!(out.status.code(), Some(2), "{out:?}");
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+use goblin::elf::header::EM_386;
+use goblin::elf::program_header::PT_LOAD;
+
+// 9front pc kernels are linked at KZERO 0xf0000000 and loaded at 1 MiB.
+const ENTRY: u64 = 0xf010_0020;
+
+#[test]
+fn kernel_is_elf32_at_kzero() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9pc");
+    let mut syms = sym32(ENTRY as u32, b'T', "_main");
+    syms.extend(sym32(ENTRY as u32 + 0x10, b'T', "_end_text"));
+    syms.extend(sym32(0xf010_1000, b'D', "conf"));
+    let image = aout(I386_MAGIC, ENTRY, &[0x90; 0x20], &[0xaa; 0x10], 0, &syms);
+    std::fs::write(&input, image).unwrap();
+
+    let image = convert(&input, &[]);
+    let elf = Elf::parse(&image).unwrap();
+
+    assert!(!elf.is_64);
+    assert_eq!(elf.header.e_machine, EM_386);
+    assert_eq!(elf.header.e_entry, ENTRY);
+    let text = elf
+        .program_headers
+        .iter()
+        .find(|p| p.p_type == PT_LOAD)
+        .unwrap();
+    assert_eq!(text.p_vaddr, ENTRY);
+    assert_eq!(text.p_paddr, 0x0010_0020);
+    assert_eq!(&image[text.p_offset as usize..][..0x20], &[0x90; 0x20]);
+
+    // only text symbols are converted, all but the last one
+    let main = elf
+        .syms
+        .iter()
+        .find(|s| elf.strtab.get_at(s.st_name) == Some("_main"));
+    assert_eq!(main.unwrap().st_value, ENTRY);
+
+    // data is on the page after the end of text, which includes the header
+    let data = elf
+        .section_headers
+        .iter()
+        .find(|s| elf.shdr_strtab.get_at(s.sh_name) == Some(".data"))
+        .unwrap();
+    assert_eq!(data.sh_addr, 0xf010_1000);
+    let data_segment = elf
+        .program_headers
+        .iter()
+        .filter(|p| p.p_type == PT_LOAD)
+        .nth(1)
+        .unwrap();
+    assert_eq!(data_segment.p_vaddr, 0xf010_1000);
+    assert_eq!(&image[data.sh_offset as usize..][..0x10], &[0xaa; 0x10]);
+    let conf = elf
+        .syms
+        .iter()
+        .find(|s| elf.strtab.get_at(s.st_name) == Some("conf"))
+        .unwrap();
+    assert_eq!(conf.st_value, 0xf010_1000);
+    assert_eq!(
+        conf.st_shndx,
+        elf.section_headers.iter().position(|s| s == data).unwrap()
+    );
+
+    let elf = format!("{}.elf", input.display());
+    let out = run(&["verify", input.to_str().unwrap(), &elf]);
+    assert!(out.status.success(), "{out:?}");
+}
@@ -0,0 +1,24 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+
+#[test]
+fn last_text_symbol_extends_to_end_of_text() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9rv");
+    let mut syms = sym(RISCV64_ENTRY, b'T', "_start");
+    syms.extend(sym(RISCV64_ENTRY + 0x10, b'T', "idlehands"));
+    let image = aout(RISCV64_MAGIC, RISCV64_ENTRY, &[0x13; 0x40], &[], 0, &syms);
+    std::fs::write(&input, image).unwrap();
+
+    let image = convert(&input, &[]);
+    let elf = Elf::parse(&image).unwrap();
+    let last = elf
+        .syms
+        .iter()
+        .find(|s| elf.strtab.get_at(s.st_name) == Some("idlehands"))
+        .expect("last text symbol dropped");
+    assert_eq!(last.st_value, RISCV64_ENTRY + 0x10);
+    assert_eq!(last.st_size, 0x30);
+}
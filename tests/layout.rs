@@ -0,0 +1,106 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+use goblin::elf::program_header::PT_LOAD;
+use goblin::elf::section_header::{SHF_ALLOC, SHT_NOBITS};
+use p9aout2elf::{ConvertOptions, aout_to_elf, aout_to_image};
+
+// Kernels of 32 and 64-bit, little and big-endian targets, with text and
+// data of sizes that leave the data unaligned in the a.out.
+fn kernels() -> Vec<(&'static str, Vec<u8>)> {
+    let text = [0x13; 0x1234];
+    let data = [0xaa; 0x56];
+    let mut res = vec![];
+    for (name, magic, entry) in [
+        ("riscv64", RISCV64_MAGIC, RISCV64_ENTRY),
+        ("amd64", AMD64_MAGIC, 0xffff_ffff_8011_0028),
+        ("i386", I386_MAGIC, 0xf010_0020),
+        ("mips", MIPS_MAGIC, 0x8002_0000),
+    ] {
+        let mut syms = vec![];
+        for (v, t, n) in [(entry, b'T', "main"), (entry + 0x100, b'T', "idle")] {
+            syms.extend(match magic & 0x8000 {
+                0 => sym32(v as u32, t, n),
+                _ => sym(v, t, n),
+            });
+        }
+        res.push((name, aout(magic, entry, &text, &data, 0x789, &syms)));
+    }
+    res
+}
+
+#[test]
+fn sections_within_congruent_segments() {
+    for (name, d) in kernels() {
+        let opts = ConvertOptions::default();
+        let image = aout_to_image(&d, &opts).unwrap();
+        let layout = image.layout().unwrap();
+        let out = aout_to_elf(&d, &opts).unwrap();
+        assert_eq!(out.len() as u64, layout.size, "{name}");
+        let elf = Elf::parse(&out).unwrap();
+
+        let loads: Vec<_> = elf
+            .program_headers
+            .iter()
+            .filter(|p| p.p_type == PT_LOAD)
+            .collect();
+        assert!(!loads.is_empty(), "{name}");
+        for p in &loads {
+            // loaders map file pages to memory pages
+            assert_eq!(p.p_offset % p.p_align, p.p_vaddr % p.p_align, "{name}");
+            assert!(p.p_filesz <= p.p_memsz, "{name}");
+        }
+
+        for sh in &elf.section_headers[1..] {
+            let sname = elf.shdr_strtab.get_at(sh.sh_name).unwrap();
+            // where the model placed it
+            let i = image.sections.iter().position(|s| s.name == sname);
+            if let Some(i) = i {
+                assert_eq!(sh.sh_offset, layout.sections[i].offset, "{name} {sname}");
+                assert_eq!(sh.sh_size, image.sections[i].size(), "{name} {sname}");
+            }
+            if sh.sh_addralign > 1 {
+                assert_eq!(sh.sh_offset % sh.sh_addralign, 0, "{name} {sname}");
+            }
+            if sh.sh_flags & SHF_ALLOC as u64 == 0 {
+                continue;
+            }
+            // file offset and address in the same relation as the segment's
+            let p = loads
+                .iter()
+                .find(|p| p.p_vaddr <= sh.sh_addr && sh.sh_addr < p.p_vaddr + p.p_memsz)
+                .unwrap_or_else(|| panic!("{name}: {sname} in no LOAD segment"));
+            assert_eq!(
+                sh.sh_offset - p.p_offset,
+                sh.sh_addr - p.p_vaddr,
+                "{name} {sname}"
+            );
+            if sh.sh_type != SHT_NOBITS {
+                assert!(
+                    sh.sh_addr + sh.sh_size <= p.p_vaddr + p.p_filesz,
+                    "{name} {sname}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn verifier_accepts_the_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    for (name, d) in kernels() {
+        let input = dir.path().join(name);
+        std::fs::write(&input, &d).unwrap();
+        let out = aout_to_elf(&d, &ConvertOptions::default()).unwrap();
+        let elf = dir.path().join(format!("{name}.elf"));
+        std::fs::write(&elf, out).unwrap();
+
+        let out = run(&["verify", input.to_str().unwrap(), elf.to_str().unwrap()]);
+        assert!(out.status.success(), "{name}: {out:?}");
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        assert!(stdout.contains("ok   text, 0x1234 bytes"), "{stdout}");
+        assert!(stdout.contains("ok   data, 0x56 bytes"), "{stdout}");
+        assert!(!stdout.contains("FAIL"), "{stdout}");
+    }
+}
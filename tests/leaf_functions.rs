@@ -0,0 +1,40 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+use goblin::elf::sym::{STB_GLOBAL, STB_LOCAL, STT_FUNC};
+
+#[test]
+fn leaf_functions_are_converted() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9rv");
+    let mut syms = sym(RISCV64_ENTRY, b'T', "_start");
+    syms.extend(sym(RISCV64_ENTRY + 0x10, b'L', "splhi"));
+    syms.extend(sym(RISCV64_ENTRY + 0x18, b'l', "spin"));
+    syms.extend(sym(RISCV64_ENTRY + 0x20, b'T', "main"));
+    let image = aout(RISCV64_MAGIC, RISCV64_ENTRY, &[0x13; 0x40], &[], 0, &syms);
+    std::fs::write(&input, image).unwrap();
+
+    let image = convert(&input, &[]);
+    let elf = Elf::parse(&image).unwrap();
+    let symbol = |name| {
+        elf.syms
+            .iter()
+            .find(|s| elf.strtab.get_at(s.st_name) == Some(name))
+            .unwrap_or_else(|| panic!("no {name}"))
+    };
+
+    let start = symbol("_start");
+    assert_eq!(start.st_size, 0x10);
+    for (name, value, bind) in [
+        ("splhi", RISCV64_ENTRY + 0x10, STB_GLOBAL),
+        ("spin", RISCV64_ENTRY + 0x18, STB_LOCAL),
+    ] {
+        let s = symbol(name);
+        assert_eq!(s.st_type(), STT_FUNC, "{name}");
+        assert_eq!(s.st_bind(), bind, "{name}");
+        assert_eq!(s.st_value, value, "{name}");
+        assert_eq!(s.st_size, 0x8, "{name}");
+        assert_eq!(s.st_shndx, start.st_shndx, "{name}");
+    }
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+use goblin::elf::header::EM_68K;
+
+const ENTRY: u64 = 0x2020;
+
+#[test]
+fn binary_is_big_endian_elf32() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("2.out");
+    let mut syms = sym32(ENTRY as u32, b'T', "_main");
+    syms.extend(sym32(ENTRY as u32 + 0x10, b'T', "etext"));
+    let image = aout(
+        M68020_MAGIC,
+        ENTRY,
+        &[0x4e, 0x71].repeat(0x10),
+        &[0xaa; 0x10],
+        0,
+        &syms,
+    );
+    std::fs::write(&input, image).unwrap();
+
+    let elf_image = convert(&input, &[]);
+    let elf = Elf::parse(&elf_image).unwrap();
+
+    assert!(!elf.is_64);
+    assert!(!elf.little_endian);
+    assert_eq!(elf.header.e_machine, EM_68K);
+    assert_eq!(elf.header.e_entry, ENTRY);
+    let main = elf
+        .syms
+        .iter()
+        .find(|s| elf.strtab.get_at(s.st_name) == Some("_main"));
+    assert_eq!(main.unwrap().st_value, ENTRY);
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+use goblin::elf::header::EM_RISCV;
+
+fn mangled_kernel(dir: &std::path::Path) -> std::path::PathBuf {
+    let input = dir.join("dump");
+    let mut syms = sym(RISCV64_ENTRY, b'T', "_start");
+    syms.extend(sym(RISCV64_ENTRY + 0x10, b'T', "etext"));
+    let mut image = aout(
+        RISCV64_MAGIC,
+        RISCV64_ENTRY,
+        &[0x13; 0x20],
+        &[0xaa; 0x10],
+        0,
+        &syms,
+    );
+    image[..4].copy_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+    std::fs::write(&input, image).unwrap();
+    input
+}
+
+#[test]
+fn machine_overrides_mangled_magic() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = mangled_kernel(dir.path());
+
+    let out = run(&["convert", input.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(4), "{out:?}");
+
+    let image = convert(&input, &["--machine", "riscv64"]);
+    let elf = Elf::parse(&image).unwrap();
+    assert_eq!(elf.header.e_machine, EM_RISCV);
+    assert_eq!(elf.header.e_entry, RISCV64_ENTRY);
+}
+
+#[test]
+fn machine_by_elf_name() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = mangled_kernel(dir.path());
+
+    // as goblin and Display print it, and as a number
+    for m in ["RISCV", "RiscV (0xf3)", "0xf3"] {
+        let image = convert(&input, &["--machine", m, "--force"]);
+        let elf = Elf::parse(&image).unwrap();
+        assert_eq!(elf.header.e_machine, EM_RISCV);
+    }
+
+    // ELF machines of several architectures have to be told apart
+    let out = run(&["convert", input.to_str().unwrap(), "--machine", "8"]);
+    assert_eq!(out.status.code(), Some(2), "{out:?}");
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(
+        stderr.contains("8 is one of mips, mips4000, spim, spim4000"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn parse_with_machine() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = mangled_kernel(dir.path());
+
+    let out = run(&["parse", input.to_str().unwrap(), "--machine", "rv64"]);
+    assert!(out.status.success(), "{out:?}");
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Architecture: riscv64"), "{stdout}");
+    assert!(stdout.contains("2 symbols read"), "{stdout}");
+}
+
+#[test]
+fn verify_with_machine() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = mangled_kernel(dir.path());
+
+    let out = run(&[
+        "convert",
+        input.to_str().unwrap(),
+        "--machine",
+        "riscv64",
+        "--verify",
+    ]);
+    assert!(out.status.success(), "{out:?}");
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("ok   text, 0x20 bytes"), "{stdout}");
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use common::*;
+use p9aout2elf::MachineArch;
+use p9aout2elf::magic::{HDR_MAGIC, Magic, magic};
+
+#[test]
+fn known_magics_decode() {
+    let m = Magic::decode(AMD64_MAGIC).unwrap();
+    assert_eq!((m.arch_id, m.hdr, m.dynamic), (26, true, false));
+    assert_eq!(m.arch_name(), Some("amd64"));
+    assert_eq!(m.value(), AMD64_MAGIC);
+    assert_eq!(magic(HDR_MAGIC, 26), AMD64_MAGIC);
+
+    let m = Magic::decode(I386_MAGIC).unwrap();
+    assert_eq!((m.arch_id, m.hdr), (11, false));
+    assert_eq!(m.to_string(), "_MAGIC(0, 11), intel 386");
+
+    assert_eq!(Magic::decode(0x7f45_4c46), None);
+}
+
+#[test]
+fn architectures_follow_the_formula() {
+    for (magic, arch) in [
+        (AMD64_MAGIC, MachineArch::Amd64),
+        (RISCV64_MAGIC, MachineArch::Riscv64),
+        (ARM64_MAGIC, MachineArch::Arm64),
+        (I386_MAGIC, MachineArch::I386),
+        (ARM_MAGIC, MachineArch::Arm),
+        (MIPS_MAGIC, MachineArch::Mips),
+        (SPARC_MAGIC, MachineArch::Sparc),
+        (POWER64_MAGIC, MachineArch::Power64),
+    ] {
+        // as read from the header
+        assert_eq!(MachineArch::from_magic(magic.to_be()), arch);
+    }
+}
+
+#[test]
+fn parse_explains_unsupported_magic() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("960.out");
+    // J_MAGIC, the retired intel 960
+    let image = aout(magic(0, 12), 0x1000, &[0; 0x10], &[], 0, &[]);
+    std::fs::write(&input, image).unwrap();
+
+    let out = run(&["parse", input.to_str().unwrap()]);
+    assert!(out.status.success(), "{out:?}");
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("_MAGIC(0, 12), intel 960"), "{stdout}");
+    assert!(stdout.contains("Unsupported architecture"), "{stdout}");
+}
@@ -0,0 +1,25 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn symbols_beyond_the_limit() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9rv");
+    let syms: Vec<u8> = (0..20000u64)
+        .flat_map(|i| sym(RISCV64_ENTRY + 4 * i, b'T', &format!("f{i}")))
+        .collect();
+    let image = aout(RISCV64_MAGIC, RISCV64_ENTRY, &[0x13; 0x20], &[], 0, &syms);
+    std::fs::write(&input, image).unwrap();
+
+    // an error instead of an abort from the allocator
+    let out = run(&["--max-memory", "4", "convert", input.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(5), "{out:?}");
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("symbols needs"), "{stderr}");
+    assert!(stderr.contains("left under --max-memory"), "{stderr}");
+    assert!(!dir.path().join("9rv.elf").exists());
+
+    let out = run(&["--max-memory", "64", "convert", input.to_str().unwrap()]);
+    assert!(out.status.success(), "{out:?}");
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+use goblin::elf::header::{EI_DATA, ELFDATA2LSB, ELFDATA2MSB, EM_MIPS};
+use goblin::elf::program_header::PT_LOAD;
+
+// Kernels are linked in KSEG0, e.g. 0x80020000 for the Indy.
+const ENTRY: u64 = 0x8002_0000;
+
+#[test]
+fn kernel_is_big_endian_elf32() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9indy");
+    let mut syms = sym32(ENTRY as u32, b'T', "_start");
+    syms.extend(sym32(ENTRY as u32 + 0x10, b'T', "etext"));
+    let text = [0x3c, 0x1d, 0x80, 0x02].repeat(8);
+    let image = aout(MIPS_MAGIC, ENTRY, &text, &[0xaa; 0x10], 0, &syms);
+    std::fs::write(&input, image).unwrap();
+
+    let image = convert(&input, &[]);
+    assert_eq!(image[EI_DATA], ELFDATA2MSB);
+    let elf = Elf::parse(&image).unwrap();
+
+    assert!(!elf.is_64);
+    assert!(!elf.little_endian);
+    assert_eq!(elf.header.e_machine, EM_MIPS);
+    assert_eq!(elf.header.e_entry, ENTRY);
+    let load = elf
+        .program_headers
+        .iter()
+        .find(|p| p.p_type == PT_LOAD)
+        .unwrap();
+    assert_eq!(load.p_vaddr, ENTRY);
+    assert_eq!(load.p_paddr, 0x2_0000);
+    // instructions are copied as they are
+    let off = load.p_offset as usize;
+    assert_eq!(image[off..off + text.len()], text);
+
+    let start = elf
+        .syms
+        .iter()
+        .find(|s| elf.strtab.get_at(s.st_name) == Some("_start"))
+        .unwrap();
+    assert_eq!(start.st_value, ENTRY);
+    let text_index = elf
+        .section_headers
+        .iter()
+        .position(|s| elf.shdr_strtab.get_at(s.sh_name) == Some(".text"));
+    assert_eq!(Some(start.st_shndx), text_index);
+}
+
+#[test]
+fn spim_kernel_is_little_endian_elf32() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9spim");
+    // the symbol table stays big endian, like the header
+    let mut syms = sym32(ENTRY as u32, b'T', "_start");
+    syms.extend(sym32(ENTRY as u32 + 0x10, b'T', "etext"));
+    let text = [0x02, 0x80, 0x1d, 0x3c].repeat(8);
+    let image = aout(SPIM_MAGIC, ENTRY, &text, &[0xaa; 0x10], 0, &syms);
+    std::fs::write(&input, image).unwrap();
+
+    let image = convert(&input, &[]);
+    assert_eq!(image[EI_DATA], ELFDATA2LSB);
+    let elf = Elf::parse(&image).unwrap();
+
+    assert!(!elf.is_64);
+    assert!(elf.little_endian);
+    assert_eq!(elf.header.e_machine, EM_MIPS);
+    assert_eq!(elf.header.e_entry, ENTRY);
+    let start = elf
+        .syms
+        .iter()
+        .find(|s| elf.strtab.get_at(s.st_name) == Some("_start"));
+    assert_eq!(start.unwrap().st_value, ENTRY);
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use common::*;
+
+fn kernel(dir: &std::path::Path) -> std::path::PathBuf {
+    let input = dir.join("9rv");
+    let data = RISCV64_ENTRY + 0x1000;
+    let mut syms = sym(RISCV64_ENTRY + 0x10, b't', "splhi");
+    syms.extend(sym(16, b'm', ".frame"));
+    syms.extend(sym(8, b'a', "s"));
+    syms.extend(sym(RISCV64_ENTRY, b'T', "main"));
+    syms.extend(sym(RISCV64_ENTRY + 0x18, b'L', "idle"));
+    syms.extend(sym(data, b'D', "conf"));
+    syms.extend(sym(data + 0x10, b'b', "ticks"));
+    syms.extend(sym(data, b'D', ".string"));
+    let image = aout(
+        RISCV64_MAGIC,
+        RISCV64_ENTRY,
+        &[0x13; 0x20],
+        &[0; 0x10],
+        0x10,
+        &syms,
+    );
+    std::fs::write(&input, image).unwrap();
+    input
+}
+
+fn nm(args: &[&str]) -> Vec<String> {
+    let mut a = vec!["nm"];
+    a.extend_from_slice(args);
+    let out = run(&a);
+    assert!(out.status.success(), "{out:?}");
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    stdout.lines().map(str::to_string).collect()
+}
+
+#[test]
+fn sorted_by_name() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = kernel(dir.path());
+
+    assert_eq!(
+        nm(&[input.to_str().unwrap()]),
+        [
+            "        80201000 D conf",
+            "        80200018 L idle",
+            "        80200000 T main",
+            "        80200010 t splhi",
+            "        80201010 b ticks",
+        ]
+    );
+}
+
+#[test]
+fn sorted_by_address_or_not_at_all() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = kernel(dir.path());
+    let input = input.to_str().unwrap();
+
+    let names = |lines: Vec<String>| -> Vec<String> {
+        let names = lines.iter().map(|l| l.rsplit(' ').next().unwrap());
+        names.map(str::to_string).collect()
+    };
+    let by_address = names(nm(&["-n", input]));
+    assert_eq!(by_address, ["main", "splhi", "idle", "conf", "ticks"]);
+    let unsorted = names(nm(&["-s", input]));
+    assert_eq!(unsorted, ["splhi", "main", "idle", "conf", "ticks"]);
+}
+
+#[test]
+fn converted_elf() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = kernel(dir.path());
+    convert(
+        &input,
+        &["--vbase", "0", "--define-sym", "magic=0x2badb002"],
+    );
+    let elf = dir.path().join("9rv.elf");
+
+    assert_eq!(
+        nm(&[elf.to_str().unwrap()]),
+        [
+            "        80201000 D conf",
+            "        80200018 T idle",
+            "        2badb002 A magic",
+            "        80200000 T main",
+            "        80200010 t splhi",
+            "        80201010 b ticks",
+        ]
+    );
+}
+
+#[test]
+fn values_of_64_bit_symbols() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9pc64");
+    let entry = 0xffff_ffff_8011_0000;
+    let mut syms = sym(entry, b'T', "_main");
+    syms.extend(sym(entry + 0x1000, b'D', "conf"));
+    let image = aout(AMD64_MAGIC, entry, &[0x90; 0x20], &[0; 0x10], 0, &syms);
+    std::fs::write(&input, image).unwrap();
+    let elf = dir.path().join("9pc64.elf");
+    convert(&input, &[]);
+
+    let expected = ["ffffffff80110000 T _main", "ffffffff80111000 D conf"];
+    assert_eq!(nm(&["-n", input.to_str().unwrap()]), expected);
+    assert_eq!(nm(&["-n", elf.to_str().unwrap()]), expected);
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+use goblin::elf::header::{EI_OSABI, ELFOSABI_FREEBSD, ELFOSABI_NONE, ELFOSABI_STANDALONE};
+
+fn riscv_kernel(dir: &std::path::Path) -> std::path::PathBuf {
+    let input = dir.join("9rv");
+    let mut syms = sym(RISCV64_ENTRY, b'T', "_start");
+    syms.extend(sym(RISCV64_ENTRY + 0x10, b'T', "etext"));
+    let image = aout(
+        RISCV64_MAGIC,
+        RISCV64_ENTRY,
+        &[0x13; 0x20],
+        &[0xaa; 0x10],
+        0,
+        &syms,
+    );
+    std::fs::write(&input, image).unwrap();
+    input
+}
+
+#[test]
+fn osabi_defaults_to_none() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = riscv_kernel(dir.path());
+
+    let image = convert(&input, &[]);
+    let elf = Elf::parse(&image).unwrap();
+    assert_eq!(elf.header.e_ident[EI_OSABI], ELFOSABI_NONE);
+}
+
+#[test]
+fn osabi_is_written() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = riscv_kernel(dir.path());
+
+    for (name, value) in [
+        ("standalone", ELFOSABI_STANDALONE),
+        ("freebsd", ELFOSABI_FREEBSD),
+    ] {
+        let image = convert(&input, &["--osabi", name, "--force"]);
+        let elf = Elf::parse(&image).unwrap();
+        assert_eq!(elf.header.e_ident[EI_OSABI], value, "{name}");
+    }
+}
+
+#[test]
+fn unknown_osabi_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = riscv_kernel(dir.path());
+
+    let out = run(&["convert", input.to_str().unwrap(), "--osabi", "plan9"]);
+    assert!(!out.status.success());
+}
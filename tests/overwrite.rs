@@ -0,0 +1,21 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn convert_keeps_existing_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("kernel");
+    let output = dir.path().join("kernel.elf");
+    let image = aout(RISCV64_MAGIC, RISCV64_ENTRY, &[0x13; 0x20], &[], 0, &[]);
+    std::fs::write(&input, image).unwrap();
+    std::fs::write(&output, b"precious").unwrap();
+
+    let out = run(&["convert", input.to_str().unwrap()]);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("use --force"));
+    assert_eq!(std::fs::read(&output).unwrap(), b"precious");
+
+    let elf = convert(&input, &["--force"]);
+    assert!(elf.starts_with(b"\x7fELF"));
+}
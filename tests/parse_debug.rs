@@ -0,0 +1,56 @@
+mod common;
+
+use common::*;
+
+fn parse_debug(input: &std::path::Path) -> String {
+    let out = run(&["parse", "--debug", input.to_str().unwrap()]);
+    assert!(out.status.success(), "{out:?}");
+    String::from_utf8(out.stdout).unwrap()
+}
+
+fn line<'a>(stdout: &'a str, prefix: &str) -> &'a str {
+    let line = stdout.lines().find(|l| l.starts_with(prefix));
+    line.unwrap_or_else(|| panic!("no {prefix} in {stdout}"))
+}
+
+#[test]
+fn previews() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9rv");
+    let syms = sym(RISCV64_ENTRY, b'T', "main");
+    let image = aout(
+        RISCV64_MAGIC,
+        RISCV64_ENTRY,
+        &[0x13; 0x20],
+        &[1, 2, 3],
+        0,
+        &syms,
+    );
+    std::fs::write(&input, &image).unwrap();
+
+    // up to 16 bytes, fewer for a shorter region
+    let stdout = parse_debug(&input);
+    let code = line(&stdout, "Code:");
+    assert!(code.ends_with(&format!(" {:02x?}", [0x13u8; 16])), "{code}");
+    assert_eq!(
+        line(&stdout, "Data:"),
+        "Data:    00000003 bytes @ 00000048 [01, 02, 03]"
+    );
+    let symbols = line(&stdout, "Symbols:");
+    assert!(
+        symbols.ends_with(&format!(" {:02x?}", &syms[..])),
+        "{symbols}"
+    );
+    assert!(!stdout.contains("(truncated)"), "{stdout}");
+
+    // cut off in the data, nothing is left of the symbols
+    std::fs::write(&input, &image[..0x49]).unwrap();
+    let stdout = parse_debug(&input);
+    assert_eq!(
+        line(&stdout, "Data:"),
+        "Data:    00000003 bytes @ 00000048 [01] (truncated)"
+    );
+    let symbols = line(&stdout, "Symbols:");
+    assert!(symbols.ends_with(" [] (truncated)"), "{symbols}");
+    assert!(stdout.contains("Symbol table truncated to 00000000 bytes"));
+}
@@ -0,0 +1,124 @@
+mod common;
+
+use common::*;
+use serde_json::{Value, json};
+
+fn parse_json(input: &std::path::Path) -> Value {
+    let out = run(&["parse", input.to_str().unwrap(), "--format", "json"]);
+    assert!(out.status.success(), "{out:?}");
+    serde_json::from_slice(&out.stdout).expect("not JSON")
+}
+
+#[test]
+fn header_regions_and_symbols() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9rv");
+    let mut syms = sym(1, b'f', "/");
+    syms.extend(sym(2, b'f', "main.c"));
+    let mut z = 1u64.to_be_bytes().to_vec();
+    z.extend_from_slice(&[b'z' | 0x80, 0, 0, 1, 0, 2, 0, 0]);
+    syms.extend(z);
+    syms.extend(sym(RISCV64_ENTRY, b'T', "main"));
+    syms.extend(sym(RISCV64_ENTRY + 0x1000, b'D', "conf"));
+    let image = aout(
+        RISCV64_MAGIC,
+        RISCV64_ENTRY,
+        &[0x13; 0x20],
+        &[0; 8],
+        0x40,
+        &syms,
+    );
+    std::fs::write(&input, image).unwrap();
+
+    let v = parse_json(&input);
+    assert_eq!(v["arch"], "riscv64");
+    assert_eq!(v["magic"], RISCV64_MAGIC);
+    assert_eq!(
+        v["header"],
+        json!({
+            "text_size": 0x20,
+            "data_size": 8,
+            "bss_size": 0x40,
+            "symbol_table_size": syms.len(),
+            "entry": RISCV64_ENTRY as u32,
+            "entry64": RISCV64_ENTRY,
+            "sp_size": 0,
+            "pc_size": 0,
+        })
+    );
+    let kinds: Vec<&str> = v["regions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["kind"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            "header", "pad", "text", "data", "symtab", "pc/sp", "pc/line"
+        ]
+    );
+    assert_eq!(
+        v["regions"][2],
+        json!({"kind": "text", "offset": 40, "len": 0x20})
+    );
+    assert_eq!(v["symbol_table_error"], Value::Null);
+
+    let symbols = v["symbols"].as_array().unwrap();
+    assert_eq!(symbols.len(), 5);
+    assert_eq!(
+        symbols[2],
+        json!({"value": 1, "type": "z", "name": "", "files": [1, 2], "path": "/main.c"})
+    );
+    assert_eq!(
+        symbols[3],
+        json!({"value": RISCV64_ENTRY, "type": "T", "name": "main"})
+    );
+    assert_eq!(symbols[4]["type"], "D");
+}
+
+#[test]
+fn corrupt_symbol_table() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9rv");
+    let mut syms = sym(RISCV64_ENTRY, b'T', "main");
+    // an unterminated name
+    syms.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, b'T' | 0x80, b'x']);
+    let image = aout(RISCV64_MAGIC, RISCV64_ENTRY, &[0x13; 0x20], &[], 0, &syms);
+    std::fs::write(&input, image).unwrap();
+
+    let v = parse_json(&input);
+    assert!(v["symbol_table_error"].is_string(), "{v}");
+    assert_eq!(v["symbols"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn elf_input_is_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9rv");
+    let syms = sym(RISCV64_ENTRY, b'T', "main");
+    let image = aout(RISCV64_MAGIC, RISCV64_ENTRY, &[0x13; 0x20], &[], 0, &syms);
+    std::fs::write(&input, image).unwrap();
+    convert(&input, &[]);
+
+    let elf = dir.path().join("9rv.elf");
+    let out = run(&["parse", elf.to_str().unwrap(), "--format", "json"]);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("a.out files only"));
+}
+
+#[test]
+fn symbols_of_64_bit_kernel() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9pc64");
+    let entry = 0xffff_ffff_8011_0000;
+    let syms = sym(entry, b'T', "_main");
+    let image = aout(AMD64_MAGIC, entry, &[0x90; 0x20], &[0; 8], 0, &syms);
+    std::fs::write(&input, image).unwrap();
+
+    let v = parse_json(&input);
+    assert_eq!(
+        v["symbols"][0],
+        json!({"value": 0xffff_ffff_8011_0000u64, "type": "T", "name": "_main"})
+    );
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+
+#[test]
+fn symbol_ranges_in_aout_and_elf() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9rv");
+    let mut syms = sym(RISCV64_ENTRY, b'T', "main");
+    syms.extend(sym(RISCV64_ENTRY + 0x10, b'T', "idle"));
+    let image = aout(RISCV64_MAGIC, RISCV64_ENTRY, &[0x13; 0x20], &[], 0, &syms);
+    std::fs::write(&input, &image).unwrap();
+    let elf = convert(&input, &[]);
+    let elf_file = dir.path().join("9rv.elf");
+
+    let expr = "text[idle+4..idle+8] = u32le(0x00100073)";
+    let out = run(&["patch", input.to_str().unwrap(), "--expr", expr]);
+    assert!(out.status.success(), "{out:?}");
+    let patched = std::fs::read(&input).unwrap();
+    // after the header and the 64-bit entry
+    let text = &patched[40..];
+    assert_eq!(text[0x14..0x18], [0x73, 0, 0x10, 0]);
+    assert_eq!(text[0x10..0x14], [0x13; 4]);
+
+    let expr = ".text[idle+4..idle+8] = u32le(0x00100073)";
+    let out = run(&["patch", elf_file.to_str().unwrap(), "--expr", expr]);
+    assert!(out.status.success(), "{out:?}");
+    let patched = std::fs::read(&elf_file).unwrap();
+    let e = Elf::parse(&elf).unwrap();
+    let sh = e
+        .section_headers
+        .iter()
+        .find(|s| e.shdr_strtab.get_at(s.sh_name) == Some(".text"))
+        .unwrap();
+    let at = sh.sh_offset as usize + 0x14;
+    assert_eq!(patched[at..at + 4], [0x73, 0, 0x10, 0]);
+
+    let expr = "text[nosuch..nosuch+4] = u32le(1)";
+    let out = run(&["patch", input.to_str().unwrap(), "--expr", expr]);
+    assert_eq!(out.status.code(), Some(1), "{out:?}");
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("no symbol nosuch in text"), "{stderr}");
+}
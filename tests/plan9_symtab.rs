@@ -0,0 +1,215 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+
+fn kernel(dir: &std::path::Path) -> (std::path::PathBuf, Vec<u8>) {
+    let input = dir.join("9rv");
+    let mut syms = sym(RISCV64_ENTRY, b'T', "main");
+    syms.extend(sym(RISCV64_ENTRY + 0x10, b'T', "idle"));
+    let image = aout(RISCV64_MAGIC, RISCV64_ENTRY, &[0x13; 0x20], &[], 0, &syms);
+    std::fs::write(&input, image).unwrap();
+    (input, syms)
+}
+
+#[test]
+fn symbol_table_is_a_section() {
+    let dir = tempfile::tempdir().unwrap();
+    let (input, syms) = kernel(dir.path());
+
+    let image = convert(&input, &[]);
+    let elf = Elf::parse(&image).unwrap();
+    let sh = elf
+        .section_headers
+        .iter()
+        .find(|s| elf.shdr_strtab.get_at(s.sh_name) == Some(".plan9.symtab"))
+        .expect("no .plan9.symtab");
+    assert_eq!(sh.sh_type, goblin::elf::section_header::SHT_PROGBITS);
+    assert_eq!(sh.sh_flags, 0);
+    assert_eq!(&image[sh.sh_offset as usize..][..sh.sh_size as usize], syms);
+    // only the text segment
+    let types: Vec<u32> = elf.program_headers.iter().map(|p| p.p_type).collect();
+    assert_eq!(types, [goblin::elf::program_header::PT_LOAD]);
+}
+
+fn contents<'a>(elf: &Elf, image: &'a [u8], name: &str) -> Option<&'a [u8]> {
+    let sh = elf
+        .section_headers
+        .iter()
+        .find(|s| elf.shdr_strtab.get_at(s.sh_name) == Some(name))?;
+    Some(&image[sh.sh_offset as usize..][..sh.sh_size as usize])
+}
+
+#[test]
+fn pc_tables_are_sections() {
+    let dir = tempfile::tempdir().unwrap();
+    let (input, _) = kernel(dir.path());
+    let (pcsp, pcline) = ([130, 6], [12, 130, 72]);
+    let mut image = std::fs::read(&input).unwrap();
+    image[24..28].copy_from_slice(&(pcsp.len() as u32).to_be_bytes());
+    image[28..32].copy_from_slice(&(pcline.len() as u32).to_be_bytes());
+    image.extend_from_slice(&pcsp);
+    image.extend_from_slice(&pcline);
+    std::fs::write(&input, image).unwrap();
+
+    let image = convert(&input, &[]);
+    let elf = Elf::parse(&image).unwrap();
+    assert_eq!(contents(&elf, &image, ".plan9.pcsp"), Some(&pcsp[..]));
+    assert_eq!(contents(&elf, &image, ".plan9.pcline"), Some(&pcline[..]));
+
+    let image = convert(&input, &["--drop-plan9-symtab", "--force"]);
+    let elf = Elf::parse(&image).unwrap();
+    assert_eq!(contents(&elf, &image, ".plan9.pcsp"), None);
+    assert_eq!(contents(&elf, &image, ".plan9.pcline"), None);
+}
+
+#[test]
+fn no_pc_tables_no_sections() {
+    let dir = tempfile::tempdir().unwrap();
+    let (input, _) = kernel(dir.path());
+
+    let image = convert(&input, &[]);
+    let elf = Elf::parse(&image).unwrap();
+    assert_eq!(contents(&elf, &image, ".plan9.pcsp"), None);
+    assert_eq!(contents(&elf, &image, ".plan9.pcline"), None);
+}
+
+#[test]
+fn drop_plan9_symtab() {
+    let dir = tempfile::tempdir().unwrap();
+    let (input, _) = kernel(dir.path());
+
+    let image = convert(&input, &["--drop-plan9-symtab"]);
+    let elf = Elf::parse(&image).unwrap();
+    let mut names = elf
+        .section_headers
+        .iter()
+        .filter_map(|s| elf.shdr_strtab.get_at(s.sh_name));
+    assert!(!names.any(|n| n == ".plan9.symtab"));
+    // the ELF symbols stay
+    assert!(
+        elf.syms
+            .iter()
+            .any(|s| elf.strtab.get_at(s.st_name) == Some("idle"))
+    );
+}
+
+// Records of the fixup tables in the byte order of the ELF: offset, width,
+// text or data, byte order of the field.
+fn fixups(elf: &Elf, image: &[u8], name: &str) -> Vec<(u32, u8, u8, u8)> {
+    let d = contents(elf, image, name).unwrap_or_default();
+    let offset = |r: &[u8]| match elf.little_endian {
+        true => u32::from_le_bytes([r[0], r[1], r[2], r[3]]),
+        false => u32::from_be_bytes([r[0], r[1], r[2], r[3]]),
+    };
+    d.chunks_exact(8)
+        .map(|r| (offset(r), r[4], r[5], r[6]))
+        .collect()
+}
+
+#[test]
+fn fixup_tables() {
+    let entry = 0x8002_0000u32;
+    for (magic, width) in [(MIPS_MAGIC, 4), (RISCV64_MAGIC, 8)] {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("9k");
+        let entries = [
+            (entry as u64, b'T', "main"),
+            (4, b'a', "x"),
+            (entry as u64 + 0x1000, b'D', "conf"),
+            (entry as u64 + 0x1010, b'b', "ticks"),
+        ];
+        let mut syms = vec![];
+        for (v, t, n) in entries {
+            syms.extend(match width {
+                4 => sym32(v as u32, t, n),
+                _ => sym(v, t, n),
+            });
+        }
+        let pcline = [12, 130, 72];
+        let mut image = aout(magic, entry as u64, &[0; 0x20], &[0; 0x10], 0x10, &syms);
+        image[28..32].copy_from_slice(&(pcline.len() as u32).to_be_bytes());
+        image.extend_from_slice(&pcline);
+        std::fs::write(&input, image).unwrap();
+
+        let image = convert(&input, &["--plan9-fixups"]);
+        let elf = Elf::parse(&image).unwrap();
+        let sh = |name: &str| {
+            let mut names = elf.section_headers.iter().map(|s| s.sh_name);
+            names
+                .position(|n| elf.shdr_strtab.get_at(n) == Some(name))
+                .unwrap()
+        };
+        let fixup_sh = &elf.section_headers[sh(".plan9.fixups.symtab")];
+        assert_eq!(fixup_sh.sh_link as usize, sh(".plan9.symtab"));
+        assert_eq!(fixup_sh.sh_entsize, 8);
+
+        // main, then conf and ticks, but not the automatic variable
+        let entry_size = |i: usize| width + 2 + entries[i].2.len();
+        let at = |i: usize| (0..i).map(entry_size).sum::<usize>() as u32;
+        let w = width as u8;
+        assert_eq!(
+            fixups(&elf, &image, ".plan9.fixups.symtab"),
+            [(at(0), w, 1, 2), (at(2), w, 2, 2), (at(3), w, 2, 2)],
+            "{magic:#x}"
+        );
+        let symtab = contents(&elf, &image, ".plan9.symtab").unwrap();
+        let value = &symtab[at(2) as usize..][..width];
+        assert_eq!(value[width - 4..], (entry + 0x1000).to_be_bytes());
+
+        assert_eq!(fixups(&elf, &image, ".plan9.fixups.pcline"), [(0, 0, 1, 2)]);
+        // none without the option
+        let image = convert(&input, &["--force"]);
+        let elf = Elf::parse(&image).unwrap();
+        assert!(fixups(&elf, &image, ".plan9.fixups.symtab").is_empty());
+    }
+}
+
+#[test]
+fn swapped_byte_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let (input, _) = kernel(dir.path());
+    // a file name as indices 1 and 0x102 into f symbols
+    let mut syms = sym(RISCV64_ENTRY, b'T', "main");
+    syms.extend(sym(1, b'z', ""));
+    syms.truncate(syms.len() - 1);
+    syms.extend_from_slice(&[0, 0, 1, 1, 2, 0, 0]);
+    syms.extend(sym(1, b'f', "/"));
+    let pcline = [12, 0, 0, 0, 1, 2, 130];
+    let mut image = aout(RISCV64_MAGIC, RISCV64_ENTRY, &[0x13; 0x20], &[], 0, &syms);
+    image[28..32].copy_from_slice(&(pcline.len() as u32).to_be_bytes());
+    image.extend_from_slice(&pcline);
+    std::fs::write(&input, image).unwrap();
+
+    let image = convert(&input, &["--plan9-byte-order", "little", "--plan9-fixups"]);
+    let elf = Elf::parse(&image).unwrap();
+    let mut swapped = RISCV64_ENTRY.to_le_bytes().to_vec();
+    swapped.extend_from_slice(b"\xd4main\0");
+    swapped.extend_from_slice(&1u64.to_le_bytes());
+    swapped.extend_from_slice(&[b'z' | 0x80, 0, 1, 0, 2, 1, 0, 0]);
+    swapped.extend_from_slice(&1u64.to_le_bytes());
+    swapped.extend_from_slice(b"\xe6/\0");
+    assert_eq!(contents(&elf, &image, ".plan9.symtab"), Some(&swapped[..]));
+    assert_eq!(
+        contents(&elf, &image, ".plan9.pcline"),
+        Some(&[12, 0, 2, 1, 0, 0, 130][..])
+    );
+    assert_eq!(fixups(&elf, &image, ".plan9.fixups.symtab"), [(0, 8, 1, 1)]);
+
+    let note = elf
+        .iter_note_sections(&image, Some(".note.plan9"))
+        .expect("no .note.plan9")
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        (note.name, note.n_type, note.desc),
+        ("Plan9", 1, &[1, 0, 0, 0][..])
+    );
+
+    // as in the a.out by default
+    let image = convert(&input, &["--force"]);
+    let elf = Elf::parse(&image).unwrap();
+    assert_eq!(contents(&elf, &image, ".plan9.symtab"), Some(&syms[..]));
+    assert_eq!(contents(&elf, &image, ".note.plan9"), None);
+}
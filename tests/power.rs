@@ -0,0 +1,49 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+use goblin::elf::header::{EM_PPC, EM_PPC64};
+
+const ENTRY: u64 = 0x8000_3100;
+
+#[test]
+fn power_is_big_endian_elf32() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9blast");
+    let mut syms = sym32(ENTRY as u32, b'T', "_start");
+    syms.extend(sym32(ENTRY as u32 + 0x10, b'T', "etext"));
+    let image = aout(POWER_MAGIC, ENTRY, &[0x60; 0x20], &[0xaa; 0x10], 0, &syms);
+    std::fs::write(&input, image).unwrap();
+
+    let elf_image = convert(&input, &[]);
+    let elf = Elf::parse(&elf_image).unwrap();
+
+    assert!(!elf.is_64);
+    assert!(!elf.little_endian);
+    assert_eq!(elf.header.e_machine, EM_PPC);
+    assert_eq!(elf.header.e_entry, ENTRY);
+}
+
+#[test]
+fn power64_is_big_endian_elf64() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9power64");
+    // expanded header, so symbol values have 8 bytes
+    let mut syms = sym(ENTRY, b'T', "_start");
+    syms.extend(sym(ENTRY + 0x10, b'T', "etext"));
+    let image = aout(POWER64_MAGIC, ENTRY, &[0x60; 0x20], &[0xaa; 0x10], 0, &syms);
+    std::fs::write(&input, image).unwrap();
+
+    let elf_image = convert(&input, &[]);
+    let elf = Elf::parse(&elf_image).unwrap();
+
+    assert!(elf.is_64);
+    assert!(!elf.little_endian);
+    assert_eq!(elf.header.e_machine, EM_PPC64);
+    assert_eq!(elf.header.e_entry, ENTRY);
+    let start = elf
+        .syms
+        .iter()
+        .find(|s| elf.strtab.get_at(s.st_name) == Some("_start"));
+    assert_eq!(start.unwrap().st_value, ENTRY);
+}
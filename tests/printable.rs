@@ -0,0 +1,49 @@
+use p9aout2elf::{parse_aout_symbols, printable};
+use proptest::prelude::*;
+
+#[test]
+fn control_characters_are_escaped() {
+    assert_eq!(printable("a\tb\nc"), "a\\tb\\nc");
+    assert_eq!(printable("\x1b[2J"), "\\u{1b}[2J");
+    assert_eq!(printable("\0\x7f"), "\\u{0}\\u{7f}");
+    // C1 controls and bidi overrides, which reorder what follows
+    assert_eq!(printable("\u{9b}"), "\\u{9b}");
+    assert_eq!(printable("abc\u{202e}fed"), "abc\\u{202e}fed");
+    assert_eq!(printable("\u{2066}x\u{2069}"), "\\u{2066}x\\u{2069}");
+}
+
+#[test]
+fn runes_stay() {
+    for s in [
+        "runtime·main",
+        "αβγ",
+        "日本",
+        "naïve",
+        "🙂",
+        "quote\"and\\slash",
+    ] {
+        assert_eq!(printable(s), s);
+    }
+}
+
+// Symbol names that are not UTF-8 are not shown as they are.
+#[test]
+fn invalid_utf8_names() {
+    let mut st = vec![0, 0, 0, 0, 0x80, 0x20, 0, 0, b'T' | 0x80];
+    st.extend_from_slice(b"ma\xffin\0");
+    let syms = parse_aout_symbols(&st, false);
+    assert_eq!(syms.len(), 1);
+    assert_eq!(printable(syms[0].name), "[noname]");
+}
+
+proptest! {
+    #[test]
+    fn output_has_no_controls(s in any::<String>()) {
+        let p = printable(&s);
+        prop_assert!(!p.chars().any(char::is_control), "{:?}", p);
+        // and is unchanged without any
+        if !s.chars().any(|c| c.is_control() || ('\u{202a}'..='\u{2069}').contains(&c)) {
+            prop_assert_eq!(p, s);
+        }
+    }
+}
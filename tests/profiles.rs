@@ -0,0 +1,118 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+
+// 9front riscv64 kernels are linked high, loaded at 0x80200000
+const ENTRY: u64 = 0xffff_ffc0_8020_0000;
+
+fn kernel(dir: &std::path::Path, magic: u32, entry: u64) -> std::path::PathBuf {
+    let input = dir.join("9k");
+    let syms = sym(entry, b'T', "main");
+    let image = aout(magic, entry, &[0x13; 0x20], &[0; 0x10], 0x10, &syms);
+    std::fs::write(&input, image).unwrap();
+    input
+}
+
+fn main_value(elf: &Elf) -> u64 {
+    let main = elf
+        .syms
+        .iter()
+        .find(|s| elf.strtab.get_at(s.st_name) == Some("main"));
+    main.unwrap().st_value
+}
+
+#[test]
+fn architecture_default() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = kernel(dir.path(), RISCV64_MAGIC, ENTRY);
+    let image = convert(&input, &[]);
+    let elf = Elf::parse(&image).unwrap();
+    assert_eq!(elf.header.e_entry, ENTRY);
+    assert_eq!(main_value(&elf), ENTRY);
+    // RVC, lp64d
+    assert_eq!(elf.header.e_flags, 0x5);
+}
+
+#[test]
+fn riscv64_sbi() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = kernel(dir.path(), RISCV64_MAGIC, ENTRY);
+    let image = convert(&input, &["--profile", "riscv64-sbi"]);
+    let elf = Elf::parse(&image).unwrap();
+    assert!(elf.is_64);
+    assert_eq!(elf.header.e_entry, 0x8020_0000);
+    assert_eq!(main_value(&elf), 0x8020_0000);
+    assert_eq!(elf.header.e_flags, 0x5);
+
+    // options given win over the profile
+    let image = convert(
+        &input,
+        &[
+            "--force",
+            "--profile",
+            "riscv64-sbi",
+            "--vbase",
+            "0x100000000",
+        ],
+    );
+    let elf = Elf::parse(&image).unwrap();
+    assert_eq!(elf.header.e_entry, 0x1_8020_0000);
+}
+
+#[test]
+fn amd64_qemu() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = kernel(dir.path(), AMD64_MAGIC, 0xffff_ffff_8011_0028);
+    let image = convert(&input, &["--profile", "amd64-qemu"]);
+    let elf = Elf::parse(&image).unwrap();
+    assert!(!elf.is_64);
+    assert_eq!(elf.header.e_entry, 0x8011_0028);
+    assert_eq!(main_value(&elf), 0x8011_0028);
+}
+
+#[test]
+fn profile_of_another_architecture() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = kernel(dir.path(), AMD64_MAGIC, 0xffff_ffff_8011_0028);
+    let out = run(&[
+        "convert",
+        input.to_str().unwrap(),
+        "--profile",
+        "riscv64-sbi",
+    ]);
+    assert_eq!(out.status.code(), Some(1), "{out:?}");
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(
+        stderr.contains("profile riscv64-sbi is for riscv64, not amd64"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn unknown_profile() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = kernel(dir.path(), RISCV64_MAGIC, ENTRY);
+    let out = run(&[
+        "convert",
+        input.to_str().unwrap(),
+        "--profile",
+        "riscv64-uboot",
+    ]);
+    assert_eq!(out.status.code(), Some(2), "{out:?}");
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(
+        stderr.contains("expected kernel, 9boot or one of riscv64-sbi, amd64-qemu"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn listed_as_capabilities() {
+    let out = run(&["--version-json"]);
+    let v: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(
+        v["profiles"],
+        serde_json::json!(["kernel", "9boot", "riscv64-sbi", "amd64-qemu"])
+    );
+}
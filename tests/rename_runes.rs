@@ -0,0 +1,58 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+
+fn kernel(dir: &std::path::Path, names: &[&str]) -> std::path::PathBuf {
+    let input = dir.join("9rv");
+    let syms: Vec<u8> = names
+        .iter()
+        .enumerate()
+        .flat_map(|(i, n)| sym(RISCV64_ENTRY + 0x10 * i as u64, b'T', n))
+        .collect();
+    let image = aout(RISCV64_MAGIC, RISCV64_ENTRY, &[0x13; 0x40], &[], 0, &syms);
+    std::fs::write(&input, image).unwrap();
+    input
+}
+
+fn names(elf: &Elf) -> Vec<String> {
+    let names = elf.syms.iter().filter_map(|s| elf.strtab.get_at(s.st_name));
+    names.filter(|n| !n.is_empty()).map(String::from).collect()
+}
+
+#[test]
+fn middle_dots_are_kept_by_default() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = kernel(dir.path(), &["main", "fmt·print"]);
+
+    let image = convert(&input, &[]);
+    assert_eq!(names(&Elf::parse(&image).unwrap()), ["main", "fmt·print"]);
+}
+
+#[test]
+fn middle_dots_are_replaced() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = kernel(dir.path(), &["main", "fmt·print"]);
+
+    for (with, name) in [(".", "fmt.print"), ("underscore", "fmt_print")] {
+        let image = convert(&input, &["--rename-runes", with, "--force"]);
+        assert_eq!(names(&Elf::parse(&image).unwrap()), ["main", name]);
+    }
+}
+
+#[test]
+fn collisions_are_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = kernel(dir.path(), &["fmt_print", "fmt·print"]);
+
+    let out = run(&["convert", input.to_str().unwrap(), "--rename-runes", "_"]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("fmt_print and fmt·print"), "{stderr}");
+
+    let image = convert(&input, &["--rename-runes", "_", "--best-effort"]);
+    assert_eq!(
+        names(&Elf::parse(&image).unwrap()),
+        ["fmt_print", "fmt_print"]
+    );
+}
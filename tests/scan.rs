@@ -0,0 +1,42 @@
+mod common;
+
+use common::*;
+
+const ENTRY: u64 = 0x8001_0000;
+
+// Images for power, power64 and power again, one after the other.
+fn blob(dir: &std::path::Path) -> std::path::PathBuf {
+    let input = dir.join("blob");
+    let mut d = vec![0xee; 0x10];
+    for magic in [POWER_MAGIC, POWER64_MAGIC, POWER_MAGIC] {
+        d.extend(aout(magic, ENTRY, &[0x60; 0x20], &[], 0, &[]));
+    }
+    std::fs::write(&input, d).unwrap();
+    input
+}
+
+#[test]
+fn names_by_arch() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = blob(dir.path());
+    let out = run(&["convert", input.to_str().unwrap(), "--scan", "--all"]);
+    assert!(out.status.success(), "{out:?}");
+    // power64 is no second power image
+    for n in ["blob.power.elf", "blob.power64.elf", "blob.power-1.elf"] {
+        assert!(dir.path().join(n).exists(), "{n}");
+    }
+    assert!(!dir.path().join("blob.power64-1.elf").exists());
+}
+
+#[test]
+fn failed_images() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = blob(dir.path());
+    std::fs::write(dir.path().join("blob.power64.elf"), b"").unwrap();
+    let out = run(&["convert", input.to_str().unwrap(), "--scan", "--all"]);
+    assert_eq!(out.status.code(), Some(1), "{out:?}");
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("1 of 3 images in"), "{stderr}");
+    // the others are converted still
+    assert!(dir.path().join("blob.power-1.elf").exists());
+}
@@ -0,0 +1,160 @@
+// The fields of schema version 1 of the JSON outputs. Tools may rely on
+// them, so they stay as long as the version does; new ones may be added.
+mod common;
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use common::*;
+use serde_json::Value;
+
+fn kernel() -> Vec<u8> {
+    let syms = sym(RISCV64_ENTRY, b'T', "main");
+    aout(
+        RISCV64_MAGIC,
+        RISCV64_ENTRY,
+        &[0x13; 0x20],
+        &[0; 8],
+        0,
+        &syms,
+    )
+}
+
+fn assert_fields(v: &Value, fields: &[&str]) {
+    assert_eq!(v["schema_version"], 1, "{v}");
+    for f in fields {
+        assert!(v.get(f).is_some(), "no {f} in {v}");
+    }
+}
+
+#[test]
+fn parse_v1() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9rv");
+    std::fs::write(&input, kernel()).unwrap();
+    let out = run(&["parse", input.to_str().unwrap(), "--format", "json"]);
+    assert!(out.status.success(), "{out:?}");
+
+    let v: Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_fields(
+        &v,
+        &[
+            "magic",
+            "magic_name",
+            "arch",
+            "header",
+            "regions",
+            "symbol_table_error",
+            "symbols",
+        ],
+    );
+    let symbol = &v["symbols"][0];
+    for f in ["value", "type", "name"] {
+        assert!(symbol.get(f).is_some(), "no {f} in {symbol}");
+    }
+}
+
+#[test]
+fn capabilities_v1() {
+    let out = run(&["--version-json"]);
+    assert!(out.status.success(), "{out:?}");
+    let v: Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_fields(
+        &v,
+        &[
+            "name",
+            "version",
+            "architectures",
+            "output_formats",
+            "output_classes",
+            "os_abis",
+            "compression",
+            "features",
+        ],
+    );
+}
+
+#[test]
+fn diagnostics_v1() {
+    let dir = tempfile::tempdir().unwrap();
+    let diagnostics = dir.path().join("diagnostics.json");
+    let missing = dir.path().join("missing");
+    let out = run(&[
+        "--diagnostics",
+        diagnostics.to_str().unwrap(),
+        "parse",
+        missing.to_str().unwrap(),
+    ]);
+    assert!(!out.status.success());
+
+    let v: Value = serde_json::from_slice(&std::fs::read(&diagnostics).unwrap()).unwrap();
+    assert_fields(
+        &v,
+        &[
+            "name", "version", "args", "success", "error", "errors", "warnings", "timings",
+        ],
+    );
+    assert_eq!(v["success"], false);
+}
+
+#[test]
+fn serve_v1() {
+    let data = BASE64.encode(kernel());
+    // linked above 4 GiB, as amd64 kernels are
+    let high = aout(
+        AMD64_MAGIC,
+        0xffff_ffff_8011_0020,
+        &[0x90; 0x20],
+        &[],
+        0,
+        &[],
+    );
+    let high = BASE64.encode(high);
+    let requests = [
+        format!(r#"{{"id": 1, "op": "parse", "data": "{data}"}}"#),
+        format!(r#"{{"id": 2, "op": "convert", "data": "{data}"}}"#),
+        r#"{"id": 3, "op": "convert", "data": "AAAA"}"#.to_string(),
+        "not JSON".to_string(),
+        format!(r#"{{"id": 5, "op": "parse", "data": "{high}"}}"#),
+        // ELF machine names as on the command line
+        format!(r#"{{"id": 6, "op": "convert", "data": "{data}", "machine": "RISCV"}}"#),
+        format!(r#"{{"id": 7, "op": "convert", "data": "{data}", "machine": "0x8"}}"#),
+    ];
+    let mut child = Command::new(env!("CARGO_BIN_EXE_p9aout2elf"))
+        .arg("serve")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    for r in &requests {
+        writeln!(stdin, "{r}").unwrap();
+    }
+    drop(stdin);
+    let out = child.wait_with_output().unwrap();
+    assert!(out.status.success(), "{out:?}");
+
+    let responses: Vec<Value> = out
+        .stdout
+        .split(|b| *b == b'\n')
+        .filter(|l| !l.is_empty())
+        .map(|l| serde_json::from_slice(l).unwrap())
+        .collect();
+    assert_eq!(responses.len(), requests.len());
+    assert_fields(
+        &responses[0],
+        &["id", "ok", "arch", "entry", "regions", "symbols"],
+    );
+    assert_fields(&responses[1], &["id", "ok", "data"]);
+    assert_fields(&responses[2], &["id", "ok", "error"]);
+    assert_fields(&responses[3], &["id", "ok", "error"]);
+    assert_eq!(responses[2]["ok"], false);
+    assert_eq!(responses[0]["entry"], RISCV64_ENTRY);
+    assert_eq!(responses[4]["entry"], 0xffff_ffff_8011_0020u64);
+    assert_eq!(responses[5]["ok"], true, "{}", responses[5]);
+    assert_eq!(responses[6]["ok"], false);
+    let e = responses[6]["error"].as_str().unwrap();
+    assert!(e.contains("0x8 is one of mips, mips4000"), "{e}");
+}
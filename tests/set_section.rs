@@ -0,0 +1,131 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+
+fn kernel(dir: &std::path::Path, extra: &[&str]) -> (std::path::PathBuf, Vec<u8>) {
+    let input = dir.join("9rv");
+    let syms = sym(RISCV64_ENTRY, b'T', "main");
+    let image = aout(
+        RISCV64_MAGIC,
+        RISCV64_ENTRY,
+        &[0x13; 0x20],
+        &[1; 8],
+        0,
+        &syms,
+    );
+    std::fs::write(&input, image).unwrap();
+    let elf = convert(&input, extra);
+    (dir.join("9rv.elf"), elf)
+}
+
+// Runs set-section on a changed copy of the ELF, expecting it to fail.
+fn set_section(dir: &std::path::Path, elf: &[u8], section: &str) -> String {
+    let file = dir.join("bad.elf");
+    let contents = dir.join("contents");
+    std::fs::write(&file, elf).unwrap();
+    std::fs::write(&contents, b"config").unwrap();
+    let out = run(&[
+        "set-section",
+        file.to_str().unwrap(),
+        section,
+        contents.to_str().unwrap(),
+    ]);
+    assert_eq!(out.status.code(), Some(1), "{out:?}");
+    // left as it was
+    assert_eq!(std::fs::read(&file).unwrap(), elf);
+    String::from_utf8_lossy(&out.stderr).to_string()
+}
+
+#[test]
+fn replace_strtab() {
+    let dir = tempfile::tempdir().unwrap();
+    let (file, _) = kernel(dir.path(), &[]);
+    let contents = dir.path().join("contents");
+    std::fs::write(&contents, b"\0main\0config\0").unwrap();
+    let out = run(&[
+        "set-section",
+        file.to_str().unwrap(),
+        ".strtab",
+        contents.to_str().unwrap(),
+    ]);
+    assert!(out.status.success(), "{out:?}");
+
+    let image = std::fs::read(&file).unwrap();
+    let elf = Elf::parse(&image).unwrap();
+    assert_eq!(elf.strtab.get_at(6), Some("config"));
+    assert_eq!(elf.syms.iter().count(), 2);
+}
+
+#[test]
+fn truncated_header() {
+    let dir = tempfile::tempdir().unwrap();
+    let (_, elf) = kernel(dir.path(), &[]);
+    // long enough for an ELF32 header, not an ELF64 one
+    let stderr = set_section(dir.path(), &elf[..0x38], ".strtab");
+    assert!(stderr.contains("ELF header truncated"), "{stderr}");
+
+    let (_, elf) = kernel(dir.path(), &["--force", "--class", "elf32"]);
+    let stderr = set_section(dir.path(), &elf[..0x30], ".strtab");
+    assert!(stderr.contains("ELF header truncated"), "{stderr}");
+}
+
+#[test]
+fn header_tables_beyond_64_bits() {
+    let dir = tempfile::tempdir().unwrap();
+    let (_, elf) = kernel(dir.path(), &[]);
+    for field in [0x20, 0x28] {
+        let mut bad = elf.clone();
+        bad[field..field + 8].copy_from_slice(&(u64::MAX - 8).to_le_bytes());
+        let stderr = set_section(dir.path(), &bad, ".strtab");
+        assert!(
+            stderr.contains("header tables exceed file size"),
+            "{stderr}"
+        );
+    }
+}
+
+#[test]
+fn header_entries_too_small() {
+    let dir = tempfile::tempdir().unwrap();
+    let (_, elf) = kernel(dir.path(), &[]);
+    let mut bad = elf.clone();
+    bad[0x3a..0x3c].copy_from_slice(&4u16.to_le_bytes());
+    let stderr = set_section(dir.path(), &bad, ".strtab");
+    assert!(
+        stderr.contains("section header size 4 is below 64"),
+        "{stderr}"
+    );
+
+    let mut bad = elf.clone();
+    bad[0x36..0x38].copy_from_slice(&0u16.to_le_bytes());
+    let stderr = set_section(dir.path(), &bad, ".strtab");
+    assert!(
+        stderr.contains("program header size 0 is below 56"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn section_beyond_64_bits() {
+    let dir = tempfile::tempdir().unwrap();
+    let (_, elf) = kernel(dir.path(), &[]);
+    let parsed = Elf::parse(&elf).unwrap();
+    let shoff = parsed.header.e_shoff as usize;
+    let index = parsed
+        .section_headers
+        .iter()
+        .position(|s| parsed.shdr_strtab.get_at(s.sh_name) == Some(".text"))
+        .unwrap();
+    // sh_offset, then sh_size
+    for field in [0x18, 0x20] {
+        let at = shoff + index * 0x40 + field;
+        let mut bad = elf.clone();
+        bad[at..at + 8].copy_from_slice(&(u64::MAX - 4).to_le_bytes());
+        let stderr = set_section(dir.path(), &bad, ".strtab");
+        assert!(
+            stderr.contains("section .text exceeds file size"),
+            "{stderr}"
+        );
+    }
+}
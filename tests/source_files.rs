@@ -0,0 +1,65 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+
+// z and Z names are a NUL and 16-bit indices of f symbols, up to a 0 index
+fn zsym(value: u64, sym_type: u8, indices: &[u16]) -> Vec<u8> {
+    let mut s = value.to_be_bytes().to_vec();
+    s.push(sym_type | 0x80);
+    s.push(0);
+    for i in indices.iter().chain(&[0]) {
+        s.extend_from_slice(&i.to_be_bytes());
+    }
+    s
+}
+
+fn kernel(dir: &std::path::Path) -> std::path::PathBuf {
+    let input = dir.join("9rv");
+    let mut syms = vec![];
+    for (i, c) in ["/", "sys", "src", "9", "port", "main.c", "u.h"]
+        .iter()
+        .enumerate()
+    {
+        syms.extend(sym(i as u64 + 1, b'f', c));
+    }
+    syms.extend(zsym(1, b'z', &[1, 2, 3, 4, 5, 6]));
+    syms.extend(zsym(2, b'z', &[1, 2, 3, 4, 5, 7]));
+    syms.extend(zsym(40, b'z', &[]));
+    // a name that looks like a C string would end early
+    syms.extend(zsym(1, b'Z', &[0x100]));
+    syms.extend(sym(RISCV64_ENTRY, b'T', "main"));
+    syms.extend(sym(RISCV64_ENTRY + 0x10, b'T', "schedinit"));
+    let image = aout(RISCV64_MAGIC, RISCV64_ENTRY, &[0x13; 0x20], &[], 0, &syms);
+    std::fs::write(&input, image).unwrap();
+    input
+}
+
+#[test]
+fn source_file_names_are_decoded() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = kernel(dir.path());
+
+    let out = run(&["parse", input.to_str().unwrap(), "--verbose"]);
+    assert!(out.status.success(), "{out:?}");
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("13 symbols read"), "{stdout}");
+    assert!(stdout.contains("1 /sys/src/9/port/main.c"), "{stdout}");
+    assert!(stdout.contains("2 /sys/src/9/port/u.h"), "{stdout}");
+}
+
+#[test]
+fn symbols_after_source_files_are_converted() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = kernel(dir.path());
+
+    let image = convert(&input, &[]);
+    let elf = Elf::parse(&image).unwrap();
+    let names: Vec<_> = elf
+        .syms
+        .iter()
+        .filter_map(|s| elf.strtab.get_at(s.st_name))
+        .filter(|n| !n.is_empty())
+        .collect();
+    assert_eq!(names, ["main", "schedinit"]);
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+use goblin::elf::header::{EM_SPARC, EM_SPARCV9};
+
+const ENTRY: u64 = 0xe000_4000;
+
+fn convert_sparc(magic: u32) -> Vec<u8> {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9ss");
+    let mut syms = sym32(ENTRY as u32, b'T', "_start");
+    syms.extend(sym32(ENTRY as u32 + 0x10, b'T', "etext"));
+    let image = aout(magic, ENTRY, &[0x01; 0x20], &[0xaa; 0x10], 0, &syms);
+    std::fs::write(&input, image).unwrap();
+    convert(&input, &[])
+}
+
+fn start_value(elf: &Elf) -> Option<u64> {
+    elf.syms
+        .iter()
+        .find(|s| elf.strtab.get_at(s.st_name) == Some("_start"))
+        .map(|s| s.st_value)
+}
+
+#[test]
+fn sparc_is_big_endian_elf32() {
+    let image = convert_sparc(SPARC_MAGIC);
+    let elf = Elf::parse(&image).unwrap();
+
+    assert!(!elf.is_64);
+    assert!(!elf.little_endian);
+    assert_eq!(elf.header.e_machine, EM_SPARC);
+    assert_eq!(elf.header.e_entry, ENTRY);
+    assert_eq!(start_value(&elf), Some(ENTRY));
+}
+
+#[test]
+fn sparc64_is_big_endian_elf64() {
+    let image = convert_sparc(SPARC64_MAGIC);
+    let elf = Elf::parse(&image).unwrap();
+
+    assert!(elf.is_64);
+    assert!(!elf.little_endian);
+    assert_eq!(elf.header.e_machine, EM_SPARCV9);
+    assert_eq!(elf.header.e_entry, ENTRY);
+    assert_eq!(start_value(&elf), Some(ENTRY));
+}
@@ -0,0 +1,34 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+
+#[test]
+fn repeated_names_are_stored_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9rv");
+    // static functions of the same name in several files
+    let mut syms = vec![];
+    for i in 0..4 {
+        syms.extend(sym(RISCV64_ENTRY + 0x10 * i, b't', "init"));
+    }
+    let image = aout(RISCV64_MAGIC, RISCV64_ENTRY, &[0x13; 0x40], &[], 0, &syms);
+    std::fs::write(&input, image).unwrap();
+
+    let image = convert(&input, &[]);
+    let elf = Elf::parse(&image).unwrap();
+    let init: Vec<_> = elf
+        .syms
+        .iter()
+        .filter(|s| elf.strtab.get_at(s.st_name) == Some("init"))
+        .collect();
+    assert_eq!(init.len(), 4);
+    assert!(init.iter().all(|s| s.st_name == init[0].st_name));
+
+    let strtab = elf
+        .section_headers
+        .iter()
+        .find(|s| elf.shdr_strtab.get_at(s.sh_name) == Some(".strtab"))
+        .unwrap();
+    assert_eq!(strtab.sh_size, b"\0init\0".len() as u64);
+}
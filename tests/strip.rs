@@ -0,0 +1,172 @@
+mod common;
+
+use common::*;
+use p9aout2elf::Error;
+use p9aout2elf::editor::SectionEditor;
+
+// What 9front's strip leaves of an image: the same header, text and data
+// with no symbols.
+fn golden(text: &[u8], data: &[u8]) -> Vec<u8> {
+    aout(RISCV64_MAGIC, RISCV64_ENTRY, text, data, 0x100, &[])
+}
+
+#[test]
+fn strip_matches_native() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("kernel");
+    let output = dir.path().join("kernel.stripped");
+    let (text, data) = ([0x13; 0x20], [0xaa; 0x10]);
+    let syms = sym(RISCV64_ENTRY, b'T', "_main");
+    let mut image = aout(RISCV64_MAGIC, RISCV64_ENTRY, &text, &data, 0x100, &syms);
+    image.extend_from_slice(b"trailer");
+    std::fs::write(&input, image).unwrap();
+
+    let out = run(&[
+        "strip",
+        input.to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+    ]);
+    assert!(out.status.success(), "{out:?}");
+    assert_eq!(std::fs::read(&output).unwrap(), golden(&text, &data));
+}
+
+#[test]
+fn strip_keeps_stripped_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("kernel");
+    let image = golden(&[0x13; 0x20], &[]);
+    std::fs::write(&input, &image).unwrap();
+
+    let out = run(&["strip", input.to_str().unwrap()]);
+    assert!(String::from_utf8_lossy(&out.stderr).contains("already stripped"));
+    assert_eq!(std::fs::read(&input).unwrap(), image);
+}
+
+#[test]
+fn stripped_converts_without_symbol_sections() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("kernel");
+    std::fs::write(&input, golden(&[0x13; 0x20], &[0xaa; 0x10])).unwrap();
+
+    let image = convert(&input, &[]);
+    let elf = goblin::elf::Elf::parse(&image).unwrap();
+    let names: Vec<_> = elf
+        .section_headers
+        .iter()
+        .filter_map(|s| elf.shdr_strtab.get_at(s.sh_name))
+        .collect();
+    assert!(!names.contains(&".symtab"), "{names:?}");
+    assert!(!names.contains(&".strtab"), "{names:?}");
+    assert_eq!(elf.program_headers.len(), 2);
+}
+
+fn section_names(image: &[u8]) -> Vec<String> {
+    let elf = goblin::elf::Elf::parse(image).unwrap();
+    let names = elf.section_headers.iter();
+    let names = names.filter_map(|s| elf.shdr_strtab.get_at(s.sh_name));
+    names.map(str::to_string).collect()
+}
+
+// a kernel with a symbol and pc tables, so that there is one of each of the
+// sections to strip
+fn kernel(dir: &std::path::Path) -> std::path::PathBuf {
+    let input = dir.join("kernel");
+    let mut syms = sym(1, b'f', "main.c");
+    let mut z = 1u64.to_be_bytes().to_vec();
+    z.extend_from_slice(&[b'z' | 0x80, 0, 0, 1, 0, 0]);
+    syms.extend(z);
+    syms.extend(sym(RISCV64_ENTRY, b'T', "_main"));
+    let mut image = aout(
+        RISCV64_MAGIC,
+        RISCV64_ENTRY,
+        &[0x13; 0x20],
+        &[0xaa; 0x10],
+        0x100,
+        &syms,
+    );
+    let (pcsp, pcline) = ([130, 6], [12, 130, 72]);
+    image[24..28].copy_from_slice(&(pcsp.len() as u32).to_be_bytes());
+    image[28..32].copy_from_slice(&(pcline.len() as u32).to_be_bytes());
+    image.extend_from_slice(&pcsp);
+    image.extend_from_slice(&pcline);
+    std::fs::write(&input, image).unwrap();
+    input
+}
+
+const STRIPPED_SECTIONS: [&str; 6] = [
+    "",
+    ".text",
+    ".data",
+    ".bss",
+    ".riscv.attributes",
+    ".shstrtab",
+];
+
+#[test]
+fn convert_strip() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = kernel(dir.path());
+
+    let names = section_names(&convert(&input, &[]));
+    assert!(names.iter().any(|n| n == ".debug_info"), "{names:?}");
+    assert!(names.iter().any(|n| n == ".plan9.pcline"), "{names:?}");
+
+    let names = section_names(&convert(&input, &["--strip", "--force"]));
+    assert_eq!(names, STRIPPED_SECTIONS);
+}
+
+#[test]
+fn strip_converted_elf() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = kernel(dir.path());
+    let elf = dir.path().join("kernel.elf");
+    let output = dir.path().join("kernel.elf.stripped");
+    let image = convert(&input, &[]);
+
+    let out = run(&[
+        "strip",
+        elf.to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+    ]);
+    assert!(out.status.success(), "{out:?}");
+    let stripped = std::fs::read(&output).unwrap();
+    assert!(stripped.len() < image.len());
+    assert_eq!(section_names(&stripped), STRIPPED_SECTIONS);
+    // text and data stay where they were
+    let before = goblin::elf::Elf::parse(&image).unwrap();
+    let after = goblin::elf::Elf::parse(&stripped).unwrap();
+    for (a, b) in before.program_headers.iter().zip(&after.program_headers) {
+        assert_eq!(a, b);
+        let range = a.p_offset as usize..(a.p_offset + a.p_filesz) as usize;
+        assert_eq!(image[range.clone()], stripped[range]);
+    }
+
+    let out = run(&["strip", output.to_str().unwrap()]);
+    assert!(String::from_utf8_lossy(&out.stderr).contains("already stripped"));
+}
+
+#[test]
+fn symbol_entry_size_zero() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = kernel(dir.path());
+    let mut image = convert(&input, &[]);
+
+    // sh_entsize of .symtab, the last field of its 64-bit header
+    let elf = goblin::elf::Elf::parse(&image).unwrap();
+    let index = elf
+        .section_headers
+        .iter()
+        .position(|s| elf.shdr_strtab.get_at(s.sh_name) == Some(".symtab"));
+    let at = elf.header.e_shoff as usize + 0x40 * index.unwrap() + 0x38;
+    drop(elf);
+    image[at..at + 8].fill(0);
+
+    // an error before the symbols are walked to renumber them
+    let mut e = SectionEditor::open(image).unwrap();
+    match e.remove(".debug_info") {
+        Err(Error::Invalid(m)) => assert!(m.contains(".symtab"), "{m}"),
+        r => panic!("{r:?}"),
+    }
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+
+fn name(len: usize, c: char) -> String {
+    std::iter::repeat_n(c, len).collect()
+}
+
+fn elf_symbol_names(elf: &Elf) -> Vec<String> {
+    let names = elf.syms.iter().filter_map(|s| elf.strtab.get_at(s.st_name));
+    names.filter(|n| !n.is_empty()).map(String::from).collect()
+}
+
+fn convert_syms(syms: &[u8], extra: &[&str]) -> std::process::Output {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("kernel");
+    let image = aout(RISCV64_MAGIC, RISCV64_ENTRY, &[0x13; 0x40], &[], 0, syms);
+    std::fs::write(&input, image).unwrap();
+    let mut args = vec!["convert", input.to_str().unwrap()];
+    args.extend_from_slice(extra);
+    run(&args)
+}
+
+#[test]
+fn long_names_are_kept_whole() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("kernel");
+    let names = [name(0x7f, 'a'), name(0x80, 'b'), name(0x100, 'c')];
+    let syms: Vec<u8> = names
+        .iter()
+        .enumerate()
+        .flat_map(|(i, n)| sym(RISCV64_ENTRY + 0x10 * i as u64, b'T', n))
+        .collect();
+    let image = aout(RISCV64_MAGIC, RISCV64_ENTRY, &[0x13; 0x40], &[], 0, &syms);
+    std::fs::write(&input, image).unwrap();
+
+    let image = convert(&input, &[]);
+    let elf = Elf::parse(&image).unwrap();
+    assert_eq!(elf_symbol_names(&elf), names);
+}
+
+// The terminator is the last byte of the table.
+#[test]
+fn name_at_the_end_of_the_table() {
+    let syms = sym(RISCV64_ENTRY, b'T', &name(0x80, 'x'));
+    let out = convert_syms(&syms, &[]);
+    assert!(out.status.success(), "{out:?}");
+}
+
+#[test]
+fn unterminated_name_is_refused() {
+    let mut syms = sym(RISCV64_ENTRY, b'T', &name(0x80, 'x'));
+    syms.pop();
+    let out = convert_syms(&syms, &[]);
+    let err = String::from_utf8_lossy(&out.stderr);
+    assert!(err.contains("unterminated name"), "{err}");
+}
@@ -0,0 +1,40 @@
+use p9aout2elf::timings::{self, CountingAlloc};
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+const MIB: usize = 1 << 20;
+
+// One test, as the counters are shared by all threads.
+#[test]
+fn counts_all_ways_of_allocating() {
+    timings::set_limit(64 * MIB);
+    timings::enable();
+    // the first stage allocates the list they are recorded in, which is
+    // never freed, so only take the snapshot after it
+    timings::stage("warm up", || ());
+    let before = timings::available().unwrap();
+
+    timings::stage("zeroed", || {
+        let v = vec![0u8; 4 * MIB];
+        assert!(timings::available().unwrap() <= before - 4 * MIB);
+        drop(v);
+    });
+    timings::stage("realloc", || {
+        let mut v: Vec<u8> = Vec::with_capacity(MIB);
+        v.reserve_exact(8 * MIB);
+        assert!(timings::available().unwrap() <= before - 8 * MIB);
+        v.shrink_to(MIB);
+        assert!(timings::available().unwrap() > before - 2 * MIB);
+    });
+    assert_eq!(timings::available().unwrap(), before);
+
+    let stages = timings::stages();
+    assert!(stages[1].2 >= 4 * MIB, "{stages:?}");
+    assert!(stages[2].2 >= 8 * MIB, "{stages:?}");
+    drop(stages);
+
+    // beyond the limit, allocations fail instead of being counted
+    assert!(Vec::<u8>::new().try_reserve_exact(128 * MIB).is_err());
+    assert_eq!(timings::available().unwrap(), before);
+}
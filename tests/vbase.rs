@@ -0,0 +1,117 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+
+#[test]
+fn vbase_overrides_profile_base() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9rv");
+    let mut syms = sym(RISCV64_ENTRY, b'T', "_start");
+    syms.extend(sym(RISCV64_ENTRY + 0x10, b'T', "etext"));
+    let image = aout(
+        RISCV64_MAGIC,
+        RISCV64_ENTRY,
+        &[0x13; 0x20],
+        &[0xaa; 0x10],
+        0,
+        &syms,
+    );
+    std::fs::write(&input, image).unwrap();
+
+    let image = convert(&input, &["--vbase", "0xffffffc000000000"]);
+    let elf = Elf::parse(&image).unwrap();
+    let entry = 0xffff_ffc0_0000_0000 + RISCV64_ENTRY;
+    assert_eq!(elf.header.e_entry, entry);
+    let start = elf
+        .syms
+        .iter()
+        .find(|s| elf.strtab.get_at(s.st_name) == Some("_start"));
+    assert_eq!(start.unwrap().st_value, entry);
+}
+
+#[test]
+fn zero_vbase_keeps_addresses_as_linked() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9pc64");
+    let entry = 0xffff_ffff_8011_0028;
+    let mut syms = sym(entry, b'T', "_main");
+    syms.extend(sym(entry + 0x10, b'T', "etext"));
+    let image = aout(AMD64_MAGIC, entry, &[0x90; 0x20], &[0xaa; 0x10], 0, &syms);
+    std::fs::write(&input, image).unwrap();
+
+    let image = convert(&input, &["--vbase", "0", "-f"]);
+    let elf = Elf::parse(&image).unwrap();
+    assert!(!elf.is_64);
+    assert_eq!(elf.header.e_entry, 0x8011_0028);
+}
+
+#[test]
+fn vbase_moves_elf32_symbols() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9pc");
+    let entry = 0xf010_0020;
+    let mut syms = sym32(entry, b'T', "_main");
+    syms.extend(sym32(entry + 0x10, b'T', "etext"));
+    syms.extend(sym32(0xf010_1000, b'D', "conf"));
+    let image = aout(
+        I386_MAGIC,
+        entry as u64,
+        &[0x90; 0x20],
+        &[0xaa; 0x10],
+        0,
+        &syms,
+    );
+    std::fs::write(&input, image).unwrap();
+
+    let image = convert(&input, &["--vbase", "0x1000"]);
+    let elf = Elf::parse(&image).unwrap();
+    assert!(!elf.is_64);
+    assert_eq!(elf.header.e_entry, 0xf010_1020);
+    let value = |name| {
+        let s = elf
+            .syms
+            .iter()
+            .find(|s| elf.strtab.get_at(s.st_name) == Some(name));
+        s.unwrap().st_value
+    };
+    assert_eq!(value("_main"), 0xf010_1020);
+    assert_eq!(value("conf"), 0xf010_2000);
+
+    // symbols have to stay within 32 bits
+    let out = run(&[
+        "convert",
+        "-f",
+        "--vbase",
+        "0x10000000",
+        input.to_str().unwrap(),
+    ]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("does not fit"), "{stderr}");
+}
+
+#[test]
+fn vbase_beyond_64_bits() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("9rv");
+    let image = aout(
+        RISCV64_MAGIC,
+        RISCV64_ENTRY,
+        &[0x13; 0x20],
+        &[0xaa; 0x10],
+        0x10,
+        &[],
+    );
+    std::fs::write(&input, image).unwrap();
+
+    let out = run(&[
+        "convert",
+        input.to_str().unwrap(),
+        "--vbase",
+        "0xffffffff80000000",
+    ]);
+    assert_eq!(out.status.code(), Some(5), "{out:?}");
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("exceeds 64 bits"), "{stderr}");
+}
@@ -0,0 +1,152 @@
+mod common;
+
+use common::*;
+use goblin::elf::Elf;
+use goblin::elf::section_header::SHT_SYMTAB;
+
+fn kernel(dir: &std::path::Path, extra: &[&str]) -> std::path::PathBuf {
+    let input = dir.join("9rv");
+    let mut syms = sym(RISCV64_ENTRY, b'T', "main");
+    syms.extend(sym(RISCV64_ENTRY + 0x10, b'T', "etext"));
+    syms.extend(sym(RISCV64_ENTRY + 0x1000, b'D', "conf"));
+    let image = aout(
+        RISCV64_MAGIC,
+        RISCV64_ENTRY,
+        &[0x13; 0x20],
+        &[0; 0x10],
+        0x10,
+        &syms,
+    );
+    std::fs::write(&input, image).unwrap();
+    convert(&input, extra);
+    dir.join("9rv.elf")
+}
+
+fn verify(elf: &std::path::Path) -> (bool, String) {
+    let out = run(&["verify", elf.to_str().unwrap()]);
+    let stdout = String::from_utf8_lossy(&out.stdout).to_string();
+    (out.status.success(), stdout)
+}
+
+#[test]
+fn converted_files_are_well_formed() {
+    for extra in [
+        &[][..],
+        &["--symbols-only"],
+        &["--strip"],
+        &["--class", "elf32"],
+    ] {
+        let dir = tempfile::tempdir().unwrap();
+        let elf = kernel(dir.path(), extra);
+        let (ok, stdout) = verify(&elf);
+        assert!(ok, "{extra:?}: {stdout}");
+        assert!(!stdout.contains("FAIL"), "{extra:?}: {stdout}");
+    }
+}
+
+#[test]
+fn entry_outside_the_text() {
+    let dir = tempfile::tempdir().unwrap();
+    let elf = kernel(dir.path(), &[]);
+    let mut image = std::fs::read(&elf).unwrap();
+    image[24..32].copy_from_slice(&0x1000u64.to_le_bytes());
+    std::fs::write(&elf, image).unwrap();
+
+    let (ok, stdout) = verify(&elf);
+    assert!(!ok);
+    assert!(stdout.contains("  FAIL entry 0x1000"), "{stdout}");
+    assert!(
+        stdout.contains("not in an executable LOAD segment"),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn symbol_in_missing_section() {
+    let dir = tempfile::tempdir().unwrap();
+    let elf = kernel(dir.path(), &[]);
+    let mut image = std::fs::read(&elf).unwrap();
+    let parsed = Elf::parse(&image).unwrap();
+    let symtab = parsed
+        .section_headers
+        .iter()
+        .find(|s| s.sh_type == SHT_SYMTAB)
+        .unwrap();
+    // st_shndx of the first symbol after the null one
+    let at = (symtab.sh_offset + symtab.sh_entsize + 6) as usize;
+    image[at..at + 2].copy_from_slice(&200u16.to_le_bytes());
+    std::fs::write(&elf, image).unwrap();
+
+    let (ok, stdout) = verify(&elf);
+    assert!(!ok);
+    assert!(stdout.contains("section 200 does not exist"), "{stdout}");
+}
+
+#[test]
+fn against_the_aout() {
+    let dir = tempfile::tempdir().unwrap();
+    let elf = kernel(dir.path(), &[]);
+    let input = dir.path().join("9rv");
+    let out = run(&["verify", input.to_str().unwrap(), elf.to_str().unwrap()]);
+    assert!(out.status.success(), "{out:?}");
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("against"), "{stdout}");
+    assert!(stdout.contains("Verify the structure of"), "{stdout}");
+}
+
+#[test]
+fn against_the_aout_at_other_bases() {
+    for (extra, base) in [
+        (&["--profile", "riscv64-sbi"][..], "0x0"),
+        (&["--vbase", "0x4000000000"], "0x4000000000"),
+    ] {
+        let dir = tempfile::tempdir().unwrap();
+        let elf = kernel(dir.path(), extra);
+        let input = dir.path().join("9rv");
+        let out = run(&["verify", input.to_str().unwrap(), elf.to_str().unwrap()]);
+        assert!(out.status.success(), "{extra:?}: {out:?}");
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        assert!(
+            stdout.contains(&format!("virtual base {base}\n")),
+            "{stdout}"
+        );
+        assert!(stdout.contains("3 symbols, 0 not in the a.out"), "{stdout}");
+    }
+}
+
+#[test]
+fn convert_verify() {
+    let dir = tempfile::tempdir().unwrap();
+    let elf = kernel(dir.path(), &[]);
+    let input = dir.path().join("9rv");
+    let out = run(&["convert", "--force", "--verify", input.to_str().unwrap()]);
+    assert!(out.status.success(), "{out:?}");
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("  ok   text, 0x20 bytes"), "{stdout}");
+    assert!(stdout.contains("  ok   entry 0x80200000"), "{stdout}");
+    assert!(!stdout.contains("FAIL"), "{stdout}");
+    assert!(elf.exists());
+}
+
+#[test]
+fn text_differs_from_the_aout() {
+    let dir = tempfile::tempdir().unwrap();
+    let elf = kernel(dir.path(), &[]);
+    let input = dir.path().join("9rv");
+    let mut image = std::fs::read(&elf).unwrap();
+    let parsed = Elf::parse(&image).unwrap();
+    let text = parsed
+        .section_headers
+        .iter()
+        .find(|s| parsed.shdr_strtab.get_at(s.sh_name) == Some(".text"))
+        .unwrap();
+    let at = text.sh_offset as usize + 5;
+    image[at] ^= 0xff;
+    std::fs::write(&elf, image).unwrap();
+
+    let out = run(&["verify", input.to_str().unwrap(), elf.to_str().unwrap()]);
+    assert!(!out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("  FAIL text, 0x20 bytes"), "{stdout}");
+    assert!(stdout.contains("first difference at 0x5"), "{stdout}");
+}